@@ -3,14 +3,22 @@ use clap::{
     crate_description, crate_name, crate_version, value_t, value_t_or_exit, App, AppSettings, Arg,
     SubCommand,
 };
-use hmt_escrow::state::{DataHash, DataUrl, Escrow, Factory};
+use hmt_escrow::state::{
+    DataHash, DataUrl, Escrow, Factory, FactoryEntry, HashAlgorithm, PayoutReceipt, UrlData,
+    VestingSchedule, WorkerReputation, STAKE_BASIS_POINTS, URL_LEN,
+};
 use hmt_escrow::{
     self,
     instruction::{
-        cancel as cancel_escrow, complete as complete_escrow, factory_initialize,
-        initialize as initialize_escrow, payout, setup as setup_escrow, store_amounts,
-        store_results,
+        batch_payout, cancel as cancel_escrow, claim, close as close_escrow,
+        complete as complete_escrow, dispute, extend_expiration, factory_approve_oracle,
+        factory_initialize, factory_set_admin, factory_set_paused, factory_set_policy,
+        factory_set_version, fund as fund_escrow, fund_native, initialize as initialize_escrow,
+        payout_ids, release_bonds, resolve_dispute, settle_fees, setup as setup_escrow,
+        slash_bonds, store_amounts, store_payouts_root, store_results, vested_payout, withdraw,
+        write_url_data, PayoutId,
     },
+    merkle::{leaf_hash, merkle_proof, merkle_root, MerkleHash},
     processor::Processor as EscrowProcessor,
 };
 use solana_clap_utils::{
@@ -30,11 +38,12 @@ use solana_sdk::{
     transaction::Transaction,
 };
 use spl_token::{
-    self, instruction::initialize_account, state::Account as TokenAccount, state::Mint as TokenMint,
+    self, instruction::initialize_account, native_mint, state::Account as TokenAccount,
+    state::Mint as TokenMint,
 };
 use std::fs::File;
 use std::io::BufReader;
-use std::{fmt::Display, process::exit, str, str::FromStr};
+use std::{fmt::Display, process::exit, str::FromStr};
 
 struct Config {
     rpc_client: RpcClient,
@@ -47,6 +56,12 @@ struct Config {
 type Error = Box<dyn std::error::Error>;
 type CommandResult = Result<Option<Transaction>, Error>;
 
+/// Maximum number of accounts requested by a single `getMultipleAccounts` call
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Maximum number of URL bytes written by a single `WriteUrlData` instruction
+const URL_DATA_CHUNK_LEN: usize = 800;
+
 macro_rules! unique_signers {
     ($vec:ident) => {
         $vec.sort_by_key(|l| l.pubkey());
@@ -69,10 +84,117 @@ fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(),
     }
 }
 
+/// Converts URL to `DataUrl`, URLs longer than `URL_LEN` are written to a new URL data account
+fn create_data_url(config: &Config, url: &str) -> Result<DataUrl, Error> {
+    if url.len() <= URL_LEN {
+        return Ok(DataUrl::from_str(url)?);
+    }
+
+    let url_data = Keypair::new();
+    println!("Creating URL data account {}", url_data.pubkey());
+
+    let url_data_len = UrlData::LEN + url.len();
+    let url_data_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(url_data_len)?;
+
+    for (index, chunk) in url.as_bytes().chunks(URL_DATA_CHUNK_LEN).enumerate() {
+        let mut instructions: Vec<Instruction> = vec![];
+        let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+        let mut rent_free_balance = 0;
+        if index == 0 {
+            // Account for the URL data
+            instructions.push(system_instruction::create_account(
+                &config.fee_payer.pubkey(),
+                &url_data.pubkey(),
+                url_data_balance,
+                url_data_len as u64,
+                &hmt_escrow::id(),
+            ));
+            signers.push(&url_data);
+            rent_free_balance = url_data_balance;
+        }
+        instructions.push(write_url_data(
+            &hmt_escrow::id(),
+            &url_data.pubkey(),
+            &config.owner.pubkey(),
+            (index * URL_DATA_CHUNK_LEN) as u32,
+            chunk,
+        )?);
+
+        let mut transaction =
+            Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
+
+        let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+        check_fee_payer_balance(
+            config,
+            rent_free_balance + fee_calculator.calculate_fee(&transaction.message()),
+        )?;
+        unique_signers!(signers);
+        transaction.sign(&signers, recent_blockhash);
+        config
+            .rpc_client
+            .send_and_confirm_transaction_with_spinner_and_commitment(
+                &transaction,
+                config.commitment_config,
+            )?;
+    }
+
+    Ok(DataUrl::new_from_account(&url_data.pubkey()))
+}
+
+/// Reads URL, URLs stored in the URL data account are fetched from it
+fn read_data_url(config: &Config, url: &DataUrl) -> Result<String, Error> {
+    match url.account() {
+        Some(account) => {
+            let account_data = config
+                .rpc_client
+                .get_account_data(&account)
+                .or(Err("Cannot read URL data"))?;
+            let url = UrlData::unpack_url(account_data.as_slice())
+                .map_err(|_| format!("{} is not a valid URL data address", account))?;
+            Ok(String::from_utf8_lossy(url).into_owned())
+        }
+        None => Ok(url.to_string()),
+    }
+}
+
+/// Wrap account used to unwrap SOL of the native mint escrow
+fn wrap_account(escrow: &Pubkey, escrow_info: &Escrow) -> Option<Pubkey> {
+    if escrow_info.token_mint == native_mint::id() {
+        Some(EscrowProcessor::find_wrap_address(&hmt_escrow::id(), escrow).0)
+    } else {
+        None
+    }
+}
+
+/// Bond vault and oracle token accounts used to return the bonds of the bonded escrow
+fn bond_accounts(escrow: &Pubkey, escrow_info: &Escrow) -> Option<(Pubkey, Pubkey, Pubkey)> {
+    match (
+        escrow_info.bonded,
+        escrow_info.reputation_oracle_token_account,
+        escrow_info.recording_oracle_token_account,
+    ) {
+        (true, COption::Some(reputation_oracle_token), COption::Some(recording_oracle_token)) => {
+            Some((
+                EscrowProcessor::find_bond_vault_address(&hmt_escrow::id(), escrow).0,
+                reputation_oracle_token,
+                recording_oracle_token,
+            ))
+        }
+        _ => None,
+    }
+}
+
 fn command_create_factory(config: &Config, version: u8) -> CommandResult {
     let factory_account = Keypair::new();
     println!("Creating Factory account: {}", factory_account.pubkey());
 
+    let admin: Pubkey = config.owner.pubkey();
+    if config.verbose {
+        println!("Factory administrator: {}", admin);
+    }
+
     let factory_account_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(Factory::LEN)?;
@@ -87,10 +209,19 @@ fn command_create_factory(config: &Config, version: u8) -> CommandResult {
             &hmt_escrow::id(),
         ),
         // Initialize Factory account
-        factory_initialize(&hmt_escrow::id(), &factory_account.pubkey(), version)?,
+        factory_initialize(
+            &hmt_escrow::id(),
+            &factory_account.pubkey(),
+            &admin,
+            version,
+        )?,
     ];
 
-    let mut signers = vec![config.fee_payer.as_ref(), &factory_account];
+    let mut signers = vec![
+        config.fee_payer.as_ref(),
+        config.owner.as_ref(),
+        &factory_account,
+    ];
 
     let mut transaction =
         Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
@@ -105,15 +236,163 @@ fn command_create_factory(config: &Config, version: u8) -> CommandResult {
     Ok(Some(transaction))
 }
 
+fn command_set_factory_admin(
+    config: &Config,
+    factory: &Pubkey,
+    new_admin: &Pubkey,
+) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[factory_set_admin(
+            &hmt_escrow::id(),
+            factory,
+            &config.owner.pubkey(),
+            new_admin,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_set_factory_version(config: &Config, factory: &Pubkey, version: u8) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[factory_set_version(
+            &hmt_escrow::id(),
+            factory,
+            &config.owner.pubkey(),
+            version,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+/// Converts a fractional percentage to basis points
+fn percent_to_basis_points(percent: f64) -> Result<u16, Error> {
+    if !(0.0..=100.0).contains(&percent) {
+        return Err("Stake out of 0 to 100 percent bounds".into());
+    }
+    Ok((percent * STAKE_BASIS_POINTS as f64 / 100.0).round() as u16)
+}
+
+/// Converts basis points to a fractional percentage
+fn basis_points_to_percent(basis_points: u16) -> f64 {
+    basis_points as f64 * 100.0 / STAKE_BASIS_POINTS as f64
+}
+
+fn command_set_factory_policy(
+    config: &Config,
+    factory: &Pubkey,
+    mint: &Option<Pubkey>,
+    max_duration: u64,
+    max_oracle_stake: f64,
+) -> CommandResult {
+    // Validate parameters
+    let max_oracle_stake = percent_to_basis_points(max_oracle_stake)?;
+    if max_oracle_stake == 0 {
+        return Err("Invalid stake value".into());
+    }
+
+    let token_mint: COption<Pubkey> = match mint {
+        Some(value) => COption::Some(*value),
+        None => COption::None,
+    };
+
+    let mut transaction = Transaction::new_with_payer(
+        &[factory_set_policy(
+            &hmt_escrow::id(),
+            factory,
+            &config.owner.pubkey(),
+            token_mint,
+            max_duration,
+            max_oracle_stake,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_set_factory_paused(config: &Config, factory: &Pubkey, paused: bool) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[factory_set_paused(
+            &hmt_escrow::id(),
+            factory,
+            &config.owner.pubkey(),
+            paused,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_approve_oracle(
+    config: &Config,
+    factory: &Pubkey,
+    oracle: &Pubkey,
+    min_scored_amount: u64,
+) -> CommandResult {
+    let (oracle_approval, _) =
+        EscrowProcessor::find_oracle_approval_address(&hmt_escrow::id(), factory, oracle);
+    if config.verbose {
+        println!("Oracle approval: {}", oracle_approval);
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[factory_approve_oracle(
+            &hmt_escrow::id(),
+            factory,
+            &config.owner.pubkey(),
+            &oracle_approval,
+            oracle,
+            min_scored_amount,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
 fn command_create(
     config: &Config,
     factory: &Pubkey,
-    mint: &Pubkey,
+    mint: &Option<Pubkey>,
     launcher: &Option<Pubkey>,
     canceler: &Option<Pubkey>,
     canceler_token: &Option<Pubkey>,
     duration: u64,
 ) -> CommandResult {
+    // Escrow without the mint holds native SOL
+    let mint = &mint.unwrap_or_else(native_mint::id);
+    let native = *mint == native_mint::id();
+
     let escrow_token_account = Keypair::new();
     println!(
         "Creating escrow token account {}",
@@ -122,13 +401,30 @@ fn command_create(
 
     let escrow_account = Keypair::new();
 
+    // Read factory state to find the next registry index
+    let account_data = config
+        .rpc_client
+        .get_account_data(factory)
+        .or(Err("Cannot read factory data"))?;
+    let factory_info: Factory = Factory::unpack(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid factory address", factory))?;
+    let (factory_entry, _) = EscrowProcessor::find_factory_entry_address(
+        &hmt_escrow::id(),
+        factory,
+        factory_info.escrow_count,
+    );
+
     let token_account_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(TokenAccount::LEN)?;
     let escrow_account_balance = config
         .rpc_client
         .get_minimum_balance_for_rent_exemption(Escrow::LEN)?;
-    let mut total_rent_free_balances = token_account_balance + escrow_account_balance;
+    let factory_entry_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(FactoryEntry::LEN)?;
+    let mut total_rent_free_balances =
+        token_account_balance + escrow_account_balance + factory_entry_balance;
 
     // Calculate withdraw authority used for minting pool tokens
     let (authority, _) =
@@ -136,6 +432,10 @@ fn command_create(
 
     if config.verbose {
         println!("Escrow authority {}", authority);
+        println!(
+            "Factory entry {} (index {})",
+            factory_entry, factory_info.escrow_count
+        );
     }
 
     let mut instructions: Vec<Instruction> = vec![
@@ -177,6 +477,8 @@ fn command_create(
     let canceler_token_account = Keypair::new();
     let canceler_token: Pubkey = match canceler_token {
         Some(value) => *value,
+        // Native SOL is returned to the canceler wallet
+        None if native => canceler,
         None => {
             println!(
                 "Creating canceler token account {}",
@@ -209,6 +511,14 @@ fn command_create(
         }
     };
 
+    // Authority reserve pays for the rent of the account unwrapping SOL
+    let escrow_authority = if native {
+        total_rent_free_balances += token_account_balance;
+        Some(&authority)
+    } else {
+        None
+    };
+
     println!("Creating escrow {}", escrow_account.pubkey());
     instructions.extend(vec![
         // Initialize escrow account
@@ -221,7 +531,10 @@ fn command_create(
             &launcher,
             &canceler,
             &canceler_token,
+            &factory_entry,
+            &config.fee_payer.pubkey(),
             duration,
+            escrow_authority,
         )?,
     ]);
 
@@ -238,6 +551,89 @@ fn command_create(
     Ok(Some(transaction))
 }
 
+fn command_list(
+    config: &Config,
+    factory: &Pubkey,
+    offset: u64,
+    limit: Option<u64>,
+) -> CommandResult {
+    let account_data = config
+        .rpc_client
+        .get_account_data(factory)
+        .or(Err("Cannot read factory data"))?;
+    let factory_info: Factory = Factory::unpack(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid factory address", factory))?;
+
+    println!("Factory version: {}", factory_info.version);
+    println!("Administrator: {}", factory_info.admin);
+    println!(
+        "Allowed token mint: {}",
+        format_coption_key(&factory_info.token_mint)
+    );
+    if factory_info.max_duration == 0 {
+        println!("Max duration: unlimited");
+    } else {
+        println!("Max duration: {} seconds", factory_info.max_duration);
+    }
+    println!(
+        "Max oracle stake: {}%",
+        basis_points_to_percent(factory_info.max_oracle_stake)
+    );
+    println!("Paused: {}", factory_info.paused);
+    println!("Escrows: {}", factory_info.escrow_count);
+
+    let end = match limit {
+        Some(limit) => offset.saturating_add(limit).min(factory_info.escrow_count),
+        None => factory_info.escrow_count,
+    };
+    if offset >= end {
+        return Ok(None);
+    }
+
+    let indices: Vec<u64> = (offset..end).collect();
+    for chunk in indices.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        let entries: Vec<Pubkey> = chunk
+            .iter()
+            .map(|index| {
+                EscrowProcessor::find_factory_entry_address(&hmt_escrow::id(), factory, *index).0
+            })
+            .collect();
+        let accounts = config.rpc_client.get_multiple_accounts(&entries)?;
+        for (index, account) in chunk.iter().zip(accounts) {
+            match account.and_then(|account| FactoryEntry::unpack(account.data.as_slice()).ok()) {
+                Some(entry) => println!("{}: {}", index, entry.escrow),
+                None => println!("{}: not found", index),
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn command_worker_reputation(config: &Config, factory: &Pubkey, worker: &Pubkey) -> CommandResult {
+    let (worker_reputation, _) =
+        EscrowProcessor::find_worker_reputation_address(&hmt_escrow::id(), factory, worker);
+    let account_data = config
+        .rpc_client
+        .get_account_data(&worker_reputation)
+        .or(Err("Worker has no reputation in the factory"))?;
+    let worker_reputation: WorkerReputation = WorkerReputation::unpack(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid reputation address", worker_reputation))?;
+
+    println!("Worker: {}", worker_reputation.worker);
+    println!("Jobs completed: {}", worker_reputation.jobs_completed);
+    println!(
+        "Total earned: {} base token units",
+        worker_reputation.total_earned
+    );
+    println!(
+        "Score: {}%",
+        basis_points_to_percent(worker_reputation.score)
+    );
+
+    Ok(None)
+}
+
 fn format_coption_key<'a>(optional: &'a COption<Pubkey>) -> Box<dyn std::fmt::Display + 'a> {
     match optional {
         COption::Some(key) => Box::new(key),
@@ -260,6 +656,14 @@ fn command_info(config: &Config, escrow: &Pubkey) -> CommandResult {
     println!("Escrow information");
     println!("==================");
     println!("State: {:?}", escrow.state);
+    if escrow.created_at != 0 {
+        println!(
+            "Created: {}",
+            NaiveDateTime::from_timestamp(escrow.created_at, 0)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        );
+    }
     println!(
         "Expires: {}",
         NaiveDateTime::from_timestamp(escrow.expires, 0)
@@ -271,6 +675,13 @@ fn command_info(config: &Config, escrow: &Pubkey) -> CommandResult {
     println!("Launcher: {}", escrow.launcher);
     println!("Canceler: {}", escrow.canceler);
     println!("Canceler token account: {}", escrow.canceler_token_account);
+    for handler in escrow
+        .trusted_handlers
+        .iter()
+        .filter(|handler| **handler != Pubkey::default())
+    {
+        println!("Trusted handler: {}", handler);
+    }
     println!();
     println!("Reputation oracle");
     println!("=================");
@@ -279,7 +690,30 @@ fn command_info(config: &Config, escrow: &Pubkey) -> CommandResult {
         "Token account: {}",
         format_coption_key(&escrow.reputation_oracle_token_account)
     );
-    println!("Fee: {}%", escrow.reputation_oracle_stake);
+    println!(
+        "Fee: {}%",
+        basis_points_to_percent(escrow.reputation_oracle_stake)
+    );
+    println!(
+        "Accrued fee: {}",
+        spl_token::amount_to_ui_amount(escrow.reputation_oracle_fee_remainder, mint_info.decimals)
+            / STAKE_BASIS_POINTS as f64
+    );
+    if escrow.defer_fees {
+        println!(
+            "Unsettled fee: {}",
+            spl_token::amount_to_ui_amount(
+                escrow.reputation_oracle_fee_accrued,
+                mint_info.decimals
+            )
+        );
+    }
+    if escrow.bonded {
+        println!(
+            "Bond: {}",
+            spl_token::amount_to_ui_amount(escrow.reputation_oracle_bond, mint_info.decimals)
+        );
+    }
     println!();
     println!("Recording oracle");
     println!("================");
@@ -288,29 +722,67 @@ fn command_info(config: &Config, escrow: &Pubkey) -> CommandResult {
         "Token account: {}",
         format_coption_key(&escrow.recording_oracle_token_account)
     );
-    println!("Fee: {}%", escrow.recording_oracle_stake);
+    println!(
+        "Fee: {}%",
+        basis_points_to_percent(escrow.recording_oracle_stake)
+    );
+    println!(
+        "Accrued fee: {}",
+        spl_token::amount_to_ui_amount(escrow.recording_oracle_fee_remainder, mint_info.decimals)
+            / STAKE_BASIS_POINTS as f64
+    );
+    if escrow.defer_fees {
+        println!(
+            "Unsettled fee: {}",
+            spl_token::amount_to_ui_amount(escrow.recording_oracle_fee_accrued, mint_info.decimals)
+        );
+    }
+    if escrow.bonded {
+        println!(
+            "Bond: {}",
+            spl_token::amount_to_ui_amount(escrow.recording_oracle_bond, mint_info.decimals)
+        );
+    }
     println!();
     println!("Data");
     println!("====");
     println!(
         "Job manifest URL: {}",
-        str::from_utf8(escrow.manifest_url.as_ref()).unwrap_or("")
-    );
-    println!(
-        "Job manifest hash: {}",
-        hex::encode(escrow.manifest_hash.as_ref())
+        read_data_url(config, &escrow.manifest_url)?
     );
+    println!("Job manifest hash: {}", escrow.manifest_hash.to_string());
     println!(
         "Final results URL: {}",
-        str::from_utf8(escrow.final_results_url.as_ref()).unwrap_or("")
+        read_data_url(config, &escrow.final_results_url)?
     );
     println!(
         "Final results hash: {}",
-        hex::encode(escrow.final_results_hash.as_ref())
+        escrow.final_results_hash.to_string()
     );
+    if escrow.dispute_window != 0 {
+        println!("Dispute window: {} seconds", escrow.dispute_window);
+        if escrow.results_stored_at != 0 {
+            println!(
+                "Dispute window ends: {}",
+                NaiveDateTime::from_timestamp(escrow.dispute_window_end(), 0)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            );
+        }
+    }
+    if escrow.results_rejected {
+        println!("Results rejected, oracle bonds can be slashed");
+    }
+    if escrow.payouts_root != MerkleHash::default() {
+        println!("Payouts root: {}", hex::encode(escrow.payouts_root));
+    }
     println!();
     println!("Amounts and recipients");
     println!("======================");
+    println!(
+        "Funded: {}",
+        spl_token::amount_to_ui_amount(escrow.funded_amount, mint_info.decimals),
+    );
     println!(
         "Amount: {} ({} sent)",
         spl_token::amount_to_ui_amount(escrow.total_amount, mint_info.decimals),
@@ -320,6 +792,12 @@ fn command_info(config: &Config, escrow: &Pubkey) -> CommandResult {
         "Recipients: {} ({} sent)",
         escrow.total_recipients, escrow.sent_recipients,
     );
+    if escrow.vesting_amount != 0 {
+        println!(
+            "Vesting: {}",
+            spl_token::amount_to_ui_amount(escrow.vesting_amount, mint_info.decimals),
+        );
+    }
 
     Ok(None)
 }
@@ -331,28 +809,29 @@ fn command_setup(
     escrow: &Pubkey,
     reputation_oracle: &Option<Pubkey>,
     reputation_oracle_token: &Option<Pubkey>,
-    reputation_oracle_stake: u8,
+    reputation_oracle_stake: f64,
     recording_oracle: &Option<Pubkey>,
     recording_oracle_token: &Option<Pubkey>,
-    recording_oracle_stake: u8,
+    recording_oracle_stake: f64,
     manifest_url: &str,
     manifest_hash: &Option<String>,
+    defer_fees: bool,
+    dispute_window: u64,
+    reputation_oracle_bond: f64,
+    reputation_oracle_signer: &Option<Box<dyn Signer>>,
+    recording_oracle_bond: f64,
+    recording_oracle_signer: &Option<Box<dyn Signer>>,
 ) -> CommandResult {
     // Validate parameters
-    if reputation_oracle_stake > 100
-        || recording_oracle_stake > 100
-        || reputation_oracle_stake.saturating_add(recording_oracle_stake) > 100
-    {
+    let reputation_oracle_stake = percent_to_basis_points(reputation_oracle_stake)?;
+    let recording_oracle_stake = percent_to_basis_points(recording_oracle_stake)?;
+    if reputation_oracle_stake.saturating_add(recording_oracle_stake) > STAKE_BASIS_POINTS {
         return Err("Invalid stake values".into());
     }
 
-    let manifest_url: DataUrl = DataUrl::from_str(manifest_url).or(Err("URL too long"))?;
     let manifest_hash: DataHash = match manifest_hash {
         None => Default::default(),
-        Some(value) => {
-            let bytes = hex::decode(value).or(Err("Hash decoding error"))?;
-            DataHash::new_from_slice(&bytes).or(Err("Wrong hash size"))?
-        }
+        Some(value) => parse_hash(value)?,
     };
 
     let mut instructions: Vec<Instruction> = vec![];
@@ -367,9 +846,51 @@ fn command_setup(
     let account_data = config.rpc_client.get_account_data(escrow)?;
     let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())?;
 
-    // Unwrap optionals
-    let reputation_oracle: Pubkey = reputation_oracle.unwrap_or(config.owner.pubkey());
-    let recording_oracle: Pubkey = recording_oracle.unwrap_or(config.owner.pubkey());
+    let manifest_url = create_data_url(config, manifest_url)?;
+
+    // Unwrap optionals, oracles posting bonds must sign the setup
+    let reputation_oracle: Pubkey = match reputation_oracle_signer {
+        Some(signer) => signer.pubkey(),
+        None => reputation_oracle.unwrap_or(config.owner.pubkey()),
+    };
+    let recording_oracle: Pubkey = match recording_oracle_signer {
+        Some(signer) => signer.pubkey(),
+        None => recording_oracle.unwrap_or(config.owner.pubkey()),
+    };
+
+    // Convert bonds to the escrow mint amounts
+    let (reputation_oracle_bond, recording_oracle_bond) =
+        if reputation_oracle_bond != 0.0 || recording_oracle_bond != 0.0 {
+            let account_data = config
+                .rpc_client
+                .get_account_data(&escrow_info.token_mint)
+                .or(Err("Cannot read escrow mint data"))?;
+            let mint_info: TokenMint = TokenMint::unpack_from_slice(account_data.as_slice())
+                .map_err(|_| format!("{} is not a valid mint address", escrow_info.token_mint))?;
+            (
+                spl_token::ui_amount_to_amount(reputation_oracle_bond, mint_info.decimals),
+                spl_token::ui_amount_to_amount(recording_oracle_bond, mint_info.decimals),
+            )
+        } else {
+            (0, 0)
+        };
+    if reputation_oracle_bond != 0 {
+        match reputation_oracle_signer {
+            Some(signer) => signers.push(signer.as_ref()),
+            None if reputation_oracle == config.owner.pubkey() => {}
+            None => {
+                return Err("Reputation oracle bond requires --reputation-oracle-keypair".into())
+            }
+        }
+    }
+    if recording_oracle_bond != 0 {
+        match recording_oracle_signer {
+            Some(signer) => signers.push(signer.as_ref()),
+            None if recording_oracle == config.owner.pubkey() => {}
+            None => return Err("Recording oracle bond requires --recording-oracle-keypair".into()),
+        }
+    }
+    let bond_vault = EscrowProcessor::find_bond_vault_address(&hmt_escrow::id(), escrow).0;
     let reputation_oracle_token_account = Keypair::new();
     let reputation_oracle_token: Pubkey = match reputation_oracle_token {
         Some(value) => *value,
@@ -453,6 +974,16 @@ fn command_setup(
             recording_oracle_stake,
             &manifest_url,
             &manifest_hash,
+            defer_fees,
+            dispute_window,
+            reputation_oracle_bond,
+            recording_oracle_bond,
+            &escrow_info.factory,
+            if reputation_oracle_bond != 0 || recording_oracle_bond != 0 {
+                Some((&bond_vault, &escrow_info.token_mint))
+            } else {
+                None
+            },
         )?,
     ]);
 
@@ -477,13 +1008,9 @@ fn command_store_results(
     results_hash: &Option<String>,
 ) -> CommandResult {
     // Validate parameters
-    let results_url: DataUrl = DataUrl::from_str(results_url).or(Err("URL too long"))?;
     let results_hash: DataHash = match results_hash {
         None => Default::default(),
-        Some(value) => {
-            let bytes = hex::decode(value).or(Err("Hash decoding error"))?;
-            DataHash::new_from_slice(&bytes).or(Err("Wrong hash size"))?
-        }
+        Some(value) => parse_hash(value)?,
     };
 
     // Read escrow state to sure that it's initialized
@@ -491,9 +1018,11 @@ fn command_store_results(
         .rpc_client
         .get_account_data(escrow)
         .or(Err("Cannot read escrow data"))?;
-    Escrow::unpack_from_slice(account_data.as_slice())
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
         .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
 
+    let results_url = create_data_url(config, results_url)?;
+
     let mut transaction = Transaction::new_with_payer(
         &[
             // Store results instruction
@@ -503,6 +1032,7 @@ fn command_store_results(
                 &config.owner.pubkey(),
                 &results_url,
                 &results_hash,
+                &escrow_info.factory,
             )?,
         ],
         Some(&config.fee_payer.pubkey()),
@@ -522,9 +1052,8 @@ struct PayoutRecord {
     amount: f64,
 }
 
-/// Creates transaction for payout from the escrow account
-fn command_payout(config: &Config, escrow: &Pubkey, file_name: &str) -> CommandResult {
-    // Read CSV file and validate its contents
+/// Reads CSV file with recipients and amounts
+fn read_payout_records(file_name: &str) -> Result<Vec<PayoutRecord>, Error> {
     let file = File::open(file_name).map_err(|_| format!("Cannot find file {}", file_name))?;
     let file_reader = BufReader::new(file);
     let mut csv_reader = csv::ReaderBuilder::new()
@@ -548,7 +1077,35 @@ fn command_payout(config: &Config, escrow: &Pubkey, file_name: &str) -> CommandR
     if recipients.is_empty() {
         return Err("Cannot find anyone to sent tokens to".into());
     }
+    Ok(recipients)
+}
+
+/// Calculates payouts tree leaves, leaf index is the record position in the file
+fn payout_leaves(recipients: &[PayoutRecord], decimals: u8) -> Vec<MerkleHash> {
+    recipients
+        .iter()
+        .enumerate()
+        .map(|(index, record)| {
+            leaf_hash(
+                index as u64,
+                &record.recipient,
+                spl_token::ui_amount_to_amount(record.amount, decimals),
+            )
+        })
+        .collect()
+}
+
+/// Creates transaction for payout from the escrow account
+fn command_payout(config: &Config, escrow: &Pubkey, file_name: &str) -> CommandResult {
+    // Read CSV file and validate its contents
+    let recipients = read_payout_records(file_name)?;
     let total_amount: f64 = recipients.iter().map(|x| x.amount).sum();
+    let mut unique_recipients: Vec<Pubkey> = recipients.iter().map(|x| x.recipient).collect();
+    unique_recipients.sort();
+    unique_recipients.dedup();
+    if unique_recipients.len() != recipients.len() {
+        return Err("Every recipient must appear in the file only once".into());
+    }
 
     // Read escrow state
     let account_data = config
@@ -583,82 +1140,105 @@ fn command_payout(config: &Config, escrow: &Pubkey, file_name: &str) -> CommandR
             &config.owner.pubkey(),
             total_amount,
             recipients.len() as u64,
+            &escrow_info.factory,
         )?,
     ];
 
-    // Check escrow token account balance
-    let account_data = config
-        .rpc_client
-        .get_account_data(&escrow_info.token_account)
-        .or(Err("Cannot read escrow token account data"))?;
-    let token_account_info: TokenAccount = TokenAccount::unpack_from_slice(account_data.as_slice())
-        .map_err(|_| {
-            format!(
-                "{} is not a valid token account address",
-                escrow_info.token_account
-            )
-        })?;
-
-    if total_amount > token_account_info.amount {
+    // Check escrow funded amount
+    if total_amount > escrow_info.funded_amount {
         return Err(format!(
-            "{} tokens needed on escrow account, only {} found",
+            "{} tokens needed on escrow account, only {} funded",
             spl_token::amount_to_ui_amount(total_amount, mint_info.decimals),
-            spl_token::amount_to_ui_amount(token_account_info.amount, mint_info.decimals)
+            spl_token::amount_to_ui_amount(escrow_info.funded_amount, mint_info.decimals)
         )
         .into());
     }
 
     let authority =
         EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+
+    // Payout IDs depend on the whole file, so the same file can't be paid out twice
+    let payouts_all: Vec<(Pubkey, u64)> = recipients
+        .iter()
+        .map(|record| {
+            (
+                record.recipient,
+                spl_token::ui_amount_to_amount(record.amount, mint_info.decimals),
+            )
+        })
+        .collect();
+    let payout_ids = payout_ids(&payouts_all);
+
     let mut instructions_ui_amount: f64 = 0.0;
-    instructions.extend(
-        recipients
+    let mut payouts_count: usize = 0;
+    for (batch_index, batch) in recipients.chunks(u8::MAX as usize).enumerate() {
+        let batch_start = batch_index * u8::MAX as usize;
+        let batch_end = batch_start + batch.len();
+        let payouts = &payouts_all[batch_start..batch_end];
+        let payout_receipts: Vec<(PayoutId, Pubkey)> = payout_ids[batch_start..batch_end]
             .iter()
-            .filter_map(|record| {
-                let instruction = payout(
+            .map(|payout_id| {
+                let (payout_receipt, _) = EscrowProcessor::find_payout_receipt_address(
                     &hmt_escrow::id(),
-                    &escrow,
-                    &config.owner.pubkey(),
-                    &escrow_info.token_account,
-                    &authority,
-                    &record.recipient,
-                    &reputation_oracle_token_account,
-                    &recording_oracle_token_account,
-                    &spl_token::id(),
-                    spl_token::ui_amount_to_amount(record.amount, mint_info.decimals),
-                )
-                .ok();
-
-                if instruction != None {
-                    println!("{}: {}", record.recipient, record.amount);
-                    instructions_ui_amount += record.amount;
-                }
-
-                instruction
+                    escrow,
+                    payout_id,
+                );
+                (*payout_id, payout_receipt)
             })
-            .collect::<Vec<Instruction>>(),
-    );
+            .collect();
+        instructions.push(batch_payout(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &escrow_info.token_account,
+            &authority,
+            &reputation_oracle_token_account,
+            &recording_oracle_token_account,
+            &spl_token::id(),
+            &escrow_info.factory,
+            payouts,
+            &payout_receipts,
+            wrap_account(escrow, &escrow_info).as_ref(),
+        )?);
+
+        for record in batch {
+            println!("{}: {}", record.recipient, record.amount);
+            instructions_ui_amount += record.amount;
+        }
+        payouts_count += batch.len();
+    }
 
-    if !instructions.is_empty() {
+    if payouts_count != 0 {
         let total_fees = escrow_info.reputation_oracle_stake + escrow_info.recording_oracle_stake;
         if total_fees != 0 {
             println!(
                 "Sending {} to {} recipients",
-                instructions_ui_amount,
-                instructions.len()
+                instructions_ui_amount, payouts_count
             );
             println!(
                 "{}% ({}) will be used to pay oracle fees",
-                total_fees,
-                total_fees as f64 * instructions_ui_amount / 100.0
+                basis_points_to_percent(total_fees),
+                total_fees as f64 * instructions_ui_amount / STAKE_BASIS_POINTS as f64
             );
         }
 
         let mut transaction =
             Transaction::new_with_payer(&instructions, Some(&config.fee_payer.pubkey()));
 
+        // Payout receipts are paid by the trusted handler
+        let payout_receipts_balance = if config.owner.pubkey() == config.fee_payer.pubkey() {
+            config
+                .rpc_client
+                .get_minimum_balance_for_rent_exemption(PayoutReceipt::LEN)?
+                * payouts_count as u64
+        } else {
+            0
+        };
         let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
-        check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+        check_fee_payer_balance(
+            config,
+            payout_receipts_balance + fee_calculator.calculate_fee(&transaction.message()),
+        )?;
         let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
         unique_signers!(signers);
         transaction.sign(&signers, recent_blockhash);
@@ -675,6 +1255,27 @@ fn command_cancel(config: &Config, escrow: &Pubkey) -> CommandResult {
     let authority =
         EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
 
+    // Vesting schedules are stopped right away, unvested tokens go to the canceler
+    let vesting_schedules = if escrow_info.vesting_amount != 0 {
+        let vesting_schedules: Vec<Pubkey> = config
+            .rpc_client
+            .get_program_accounts(&hmt_escrow::id())?
+            .into_iter()
+            .filter(|(_, account)| {
+                VestingSchedule::unpack(&account.data)
+                    .map(|vesting_schedule| vesting_schedule.escrow == *escrow)
+                    .unwrap_or(false)
+            })
+            .map(|(address, _)| address)
+            .collect();
+        if config.verbose {
+            println!("Vesting schedules stopped: {}", vesting_schedules.len());
+        }
+        Some(vesting_schedules)
+    } else {
+        None
+    };
+
     let mut transaction = Transaction::new_with_payer(
         &[cancel_escrow(
             &hmt_escrow::id(),
@@ -684,6 +1285,8 @@ fn command_cancel(config: &Config, escrow: &Pubkey) -> CommandResult {
             &authority,
             &escrow_info.canceler_token_account,
             &spl_token::id(),
+            wrap_account(escrow, &escrow_info).as_ref(),
+            vesting_schedules.as_deref(),
         )?],
         Some(&config.fee_payer.pubkey()),
     );
@@ -696,16 +1299,204 @@ fn command_cancel(config: &Config, escrow: &Pubkey) -> CommandResult {
     Ok(Some(transaction))
 }
 
-fn command_complete(config: &Config, escrow: &Pubkey) -> CommandResult {
+/// Pays a single recipient with tokens which vest linearly after the cliff
+fn command_vested_payout(
+    config: &Config,
+    escrow: &Pubkey,
+    recipient: &Pubkey,
+    amount: f64,
+    start: i64,
+    cliff: i64,
+    end: i64,
+) -> CommandResult {
+    // Read escrow state
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    // Check oracle accounts
+    let reputation_oracle_token_account = escrow_info
+        .reputation_oracle_token_account
+        .ok_or::<Error>("Reputation oracle token account not defined".into())?;
+    let recording_oracle_token_account = escrow_info
+        .recording_oracle_token_account
+        .ok_or::<Error>("Recording oracle token account not defined".into())?;
+
+    if start > cliff || cliff > end || end > escrow_info.expires {
+        return Err("Vesting must start before the cliff and end before the escrow expires".into());
+    }
+
+    // Check token mint to convert amount to u64
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_info.token_mint)
+        .or(Err("Cannot read escrow mint data"))?;
+    let mint_info: TokenMint = TokenMint::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid mint address", escrow_info.token_mint))?;
+    let amount = spl_token::ui_amount_to_amount(amount, mint_info.decimals);
+
+    let authority =
+        EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+    let (vesting_schedule, _) =
+        EscrowProcessor::find_vesting_schedule_address(&hmt_escrow::id(), escrow, recipient);
+
     let mut transaction = Transaction::new_with_payer(
-        &[complete_escrow(
+        &[vested_payout(
             &hmt_escrow::id(),
-            &escrow,
+            escrow,
             &config.owner.pubkey(),
+            &escrow_info.token_account,
+            &authority,
+            recipient,
+            &reputation_oracle_token_account,
+            &recording_oracle_token_account,
+            &spl_token::id(),
+            amount,
+            &escrow_info.factory,
+            &vesting_schedule,
+            start,
+            cliff,
+            end,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    // Vesting schedule is paid by the reputation oracle
+    let vesting_schedule_balance = if config.owner.pubkey() == config.fee_payer.pubkey() {
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(VestingSchedule::LEN)?
+    } else {
+        0
+    };
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        vesting_schedule_balance + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+/// Withdraws vested tokens to the recipient, unvested tokens of a cancelled escrow
+/// are released back to the escrow
+fn command_withdraw(config: &Config, escrow: &Pubkey, recipient: &Pubkey) -> CommandResult {
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    let (vesting_schedule, _) =
+        EscrowProcessor::find_vesting_schedule_address(&hmt_escrow::id(), escrow, recipient);
+    let account_data = config
+        .rpc_client
+        .get_account_data(&vesting_schedule)
+        .or(Err("Recipient has no vesting payout in the escrow"))?;
+    let vesting_schedule_info = VestingSchedule::unpack(account_data.as_slice()).map_err(|_| {
+        format!(
+            "{} is not a valid vesting schedule address",
+            vesting_schedule
+        )
+    })?;
+    println!(
+        "Vesting {} base token units to {} from {} until {}, {} withdrawn",
+        vesting_schedule_info.total,
+        recipient,
+        NaiveDateTime::from_timestamp(vesting_schedule_info.cliff, 0).format("%Y-%m-%d %H:%M:%S"),
+        NaiveDateTime::from_timestamp(vesting_schedule_info.end, 0).format("%Y-%m-%d %H:%M:%S"),
+        vesting_schedule_info.withdrawn,
+    );
+
+    let authority =
+        EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw(
+            &hmt_escrow::id(),
+            escrow,
+            &vesting_schedule,
+            &escrow_info.token_account,
+            &authority,
+            recipient,
+            &spl_token::id(),
+            wrap_account(escrow, &escrow_info).as_ref(),
         )?],
         Some(&config.fee_payer.pubkey()),
     );
 
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+/// Stores final amounts and the root of the payouts tree, so the recipients can claim their payouts
+fn command_store_payouts_root(config: &Config, escrow: &Pubkey, file_name: &str) -> CommandResult {
+    // Read CSV file and validate its contents
+    let recipients = read_payout_records(file_name)?;
+    let total_amount: f64 = recipients.iter().map(|x| x.amount).sum();
+
+    // Read escrow state
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    // Check token mint to convert amount to u64
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_info.token_mint)
+        .or(Err("Cannot read escrow mint data"))?;
+    let mint_info: TokenMint = TokenMint::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid mint address", escrow_info.token_mint))?;
+    let total_amount = spl_token::ui_amount_to_amount(total_amount, mint_info.decimals);
+
+    // Check escrow funded amount
+    if total_amount > escrow_info.funded_amount {
+        return Err(format!(
+            "{} tokens needed on escrow account, only {} funded",
+            spl_token::amount_to_ui_amount(total_amount, mint_info.decimals),
+            spl_token::amount_to_ui_amount(escrow_info.funded_amount, mint_info.decimals)
+        )
+        .into());
+    }
+
+    let payouts_root = merkle_root(&payout_leaves(&recipients, mint_info.decimals))
+        .ok_or("Cannot calculate payouts root")?;
+    println!("Payouts root: {}", hex::encode(payouts_root));
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            store_amounts(
+                &hmt_escrow::id(),
+                escrow,
+                &config.owner.pubkey(),
+                total_amount,
+                recipients.len() as u64,
+                &escrow_info.factory,
+            )?,
+            store_payouts_root(
+                &hmt_escrow::id(),
+                escrow,
+                &config.owner.pubkey(),
+                &escrow_info.factory,
+                &payouts_root,
+            )?,
+        ],
+        Some(&config.fee_payer.pubkey()),
+    );
+
     let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
     check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
     let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
@@ -714,140 +1505,1051 @@ fn command_complete(config: &Config, escrow: &Pubkey) -> CommandResult {
     Ok(Some(transaction))
 }
 
-/// Return an error if a hex cannot be parsed.
-pub fn is_hex<T>(string: T) -> Result<(), String>
-where
-    T: AsRef<str> + Display,
-{
-    match hex::decode(string.as_ref()) {
-        Ok(_) => Ok(()),
-        Err(err) => Err(format!("{}", err)),
+/// Claims the recipient's payout from the stored payouts tree
+fn command_claim(
+    config: &Config,
+    escrow: &Pubkey,
+    file_name: &str,
+    recipient: &Pubkey,
+) -> CommandResult {
+    let recipients = read_payout_records(file_name)?;
+    let index = recipients
+        .iter()
+        .position(|record| record.recipient == *recipient)
+        .ok_or_else(|| format!("Cannot find {} in {}", recipient, file_name))?;
+
+    // Read escrow state
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    // Check oracle accounts
+    let reputation_oracle_token_account = escrow_info
+        .reputation_oracle_token_account
+        .ok_or::<Error>("Reputation oracle token account not defined".into())?;
+    let recording_oracle_token_account = escrow_info
+        .recording_oracle_token_account
+        .ok_or::<Error>("Recording oracle token account not defined".into())?;
+
+    // Check token mint to convert amount to u64
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_info.token_mint)
+        .or(Err("Cannot read escrow mint data"))?;
+    let mint_info: TokenMint = TokenMint::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid mint address", escrow_info.token_mint))?;
+
+    let leaves = payout_leaves(&recipients, mint_info.decimals);
+    if merkle_root(&leaves) != Some(escrow_info.payouts_root) {
+        return Err("File doesn't match the payouts root stored in the escrow".into());
     }
+    let proof = merkle_proof(&leaves, index).ok_or("Cannot build payout proof")?;
+
+    let authority =
+        EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+    let (claim_receipt, _) =
+        EscrowProcessor::find_claim_receipt_address(&hmt_escrow::id(), escrow, index as u64);
+    let amount = spl_token::ui_amount_to_amount(recipients[index].amount, mint_info.decimals);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[claim(
+            &hmt_escrow::id(),
+            escrow,
+            &config.owner.pubkey(),
+            &escrow_info.token_account,
+            &authority,
+            recipient,
+            &reputation_oracle_token_account,
+            &recording_oracle_token_account,
+            &spl_token::id(),
+            &escrow_info.factory,
+            &claim_receipt,
+            index as u64,
+            amount,
+            &proof,
+            wrap_account(escrow, &escrow_info).as_ref(),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let claim_receipt_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(PayoutReceipt::LEN)?;
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        claim_receipt_balance + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
 }
 
-fn main() {
-    let matches = App::new(crate_name!())
-        .about(crate_description!())
-        .version(crate_version!())
-        .setting(AppSettings::SubcommandRequiredElseHelp)
-        .arg({
-            let arg = Arg::with_name("config_file")
-                .short("C")
-                .long("config")
-                .value_name("PATH")
-                .takes_value(true)
-                .global(true)
-                .help("Configuration file to use");
-            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
-                arg.default_value(&config_file)
-            } else {
-                arg
+/// Deposits tokens from the launcher's token account to the escrow, native SOL
+/// escrows are funded from the launcher wallet
+fn command_fund(
+    config: &Config,
+    escrow: &Pubkey,
+    source: &Option<Pubkey>,
+    amount: f64,
+) -> CommandResult {
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    // Check token mint to convert amount to u64
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_info.token_mint)
+        .or(Err("Cannot read escrow mint data"))?;
+    let mint_info: TokenMint = TokenMint::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid mint address", escrow_info.token_mint))?;
+
+    let amount = spl_token::ui_amount_to_amount(amount, mint_info.decimals);
+    let mut wrapped_balance = 0;
+    let instruction = match (wrap_account(escrow, &escrow_info), source) {
+        (Some(wrap_account), _) => {
+            let authority =
+                EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+            if config.owner.pubkey() == config.fee_payer.pubkey() {
+                wrapped_balance = amount;
             }
-        })
-        .arg(
-            Arg::with_name("verbose")
-                .long("verbose")
-                .short("v")
-                .takes_value(false)
-                .global(true)
-                .help("Show additional information"),
-        )
-        .arg(
-            Arg::with_name("json_rpc_url")
-                .long("url")
+            fund_native(
+                &hmt_escrow::id(),
+                &escrow,
+                &config.owner.pubkey(),
+                &escrow_info.token_account,
+                &authority,
+                &spl_token::id(),
+                &wrap_account,
+                amount,
+            )?
+        }
+        (None, Some(source)) => fund_escrow(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            source,
+            &escrow_info.token_account,
+            &spl_token::id(),
+            amount,
+        )?,
+        (None, None) => return Err("Source token account is required".into()),
+    };
+
+    let mut transaction =
+        Transaction::new_with_payer(&[instruction], Some(&config.fee_payer.pubkey()));
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(
+        config,
+        wrapped_balance + fee_calculator.calculate_fee(&transaction.message()),
+    )?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+/// Moves escrow expiration time later, must be signed by the launcher or canceler
+fn command_extend(config: &Config, escrow: &Pubkey, additional_seconds: u64) -> CommandResult {
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    if config.verbose {
+        println!(
+            "Escrow will expire at {}",
+            NaiveDateTime::from_timestamp(escrow_info.expires + additional_seconds as i64, 0)
+                .format("%Y-%m-%d %H:%M:%S")
+        );
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[extend_expiration(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &escrow_info.factory,
+            additional_seconds,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_close(config: &Config, escrow: &Pubkey, receiver: &Option<Pubkey>) -> CommandResult {
+    let account_data = config.rpc_client.get_account_data(escrow)?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())?;
+
+    let authority =
+        EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+    let receiver: Pubkey = receiver.unwrap_or(config.fee_payer.pubkey());
+    let bond_accounts = bond_accounts(escrow, &escrow_info);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_escrow(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &escrow_info.token_account,
+            &authority,
+            &escrow_info.canceler_token_account,
+            &receiver,
+            &spl_token::id(),
+            wrap_account(escrow, &escrow_info).as_ref(),
+            bond_accounts
+                .as_ref()
+                .map(|(vault, reputation_token, recording_token)| {
+                    (vault, reputation_token, recording_token)
+                }),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_complete(config: &Config, escrow: &Pubkey) -> CommandResult {
+    let account_data = config.rpc_client.get_account_data(escrow)?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())?;
+    let bond_accounts = bond_accounts(escrow, &escrow_info);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[complete_escrow(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            bond_accounts
+                .as_ref()
+                .map(|(vault, reputation_token, recording_token)| {
+                    (vault, reputation_token, recording_token)
+                }),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_dispute(config: &Config, escrow: &Pubkey) -> CommandResult {
+    let mut transaction = Transaction::new_with_payer(
+        &[dispute(&hmt_escrow::id(), &escrow, &config.owner.pubkey())?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_resolve_dispute(
+    config: &Config,
+    escrow: &Pubkey,
+    accept_results: bool,
+) -> CommandResult {
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[resolve_dispute(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &escrow_info.factory,
+            accept_results,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_slash_bonds(
+    config: &Config,
+    escrow: &Pubkey,
+    reputation_oracle_amount: Option<f64>,
+    recording_oracle_amount: Option<f64>,
+    receiver: &Option<Pubkey>,
+) -> CommandResult {
+    // Read escrow state
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+    if !escrow_info.results_rejected {
+        return Err("Bonds can only be slashed after the results are rejected".into());
+    }
+
+    // Check token mint to convert amounts
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_info.token_mint)
+        .or(Err("Cannot read escrow mint data"))?;
+    let mint_info: TokenMint = TokenMint::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid mint address", escrow_info.token_mint))?;
+
+    // Whole bonds are slashed by default
+    let reputation_oracle_amount = reputation_oracle_amount
+        .map(|amount| spl_token::ui_amount_to_amount(amount, mint_info.decimals))
+        .unwrap_or(escrow_info.reputation_oracle_bond);
+    let recording_oracle_amount = recording_oracle_amount
+        .map(|amount| spl_token::ui_amount_to_amount(amount, mint_info.decimals))
+        .unwrap_or(escrow_info.recording_oracle_bond);
+    let receiver: Pubkey = receiver.unwrap_or(escrow_info.canceler_token_account);
+
+    let bond_vault = EscrowProcessor::find_bond_vault_address(&hmt_escrow::id(), escrow).0;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[slash_bonds(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &bond_vault,
+            &receiver,
+            &spl_token::id(),
+            reputation_oracle_amount,
+            recording_oracle_amount,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_release_bonds(config: &Config, escrow: &Pubkey) -> CommandResult {
+    // Read escrow state
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+    let (bond_vault, reputation_oracle_token, recording_oracle_token) =
+        bond_accounts(escrow, &escrow_info).ok_or("Escrow has no oracle bonds")?;
+    if escrow_info.reputation_oracle_bond == 0 && escrow_info.recording_oracle_bond == 0 {
+        return Err("Oracle bonds are already released".into());
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[release_bonds(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &bond_vault,
+            &reputation_oracle_token,
+            &recording_oracle_token,
+            &spl_token::id(),
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+fn command_settle_fees(config: &Config, escrow: &Pubkey) -> CommandResult {
+    // Read escrow state
+    let account_data = config
+        .rpc_client
+        .get_account_data(escrow)
+        .or(Err("Cannot read escrow data"))?;
+    let escrow_info: Escrow = Escrow::unpack_from_slice(account_data.as_slice())
+        .map_err(|_| format!("{} is not a valid escrow address", escrow))?;
+    if !escrow_info.defer_fees {
+        return Err("Escrow fees are not deferred".into());
+    }
+
+    // Check oracle accounts
+    let reputation_oracle_token_account = escrow_info
+        .reputation_oracle_token_account
+        .ok_or::<Error>("Reputation oracle token account not defined".into())?;
+    let recording_oracle_token_account = escrow_info
+        .recording_oracle_token_account
+        .ok_or::<Error>("Recording oracle token account not defined".into())?;
+
+    let authority =
+        EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow, escrow_info.bump_seed)?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[settle_fees(
+            &hmt_escrow::id(),
+            &escrow,
+            &config.owner.pubkey(),
+            &escrow_info.token_account,
+            &authority,
+            &reputation_oracle_token_account,
+            &recording_oracle_token_account,
+            &spl_token::id(),
+            &escrow_info.factory,
+        )?],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(&transaction.message()))?;
+    let mut signers = vec![config.fee_payer.as_ref(), config.owner.as_ref()];
+    unique_signers!(signers);
+    transaction.sign(&signers, recent_blockhash);
+    Ok(Some(transaction))
+}
+
+/// Parses a hash in `[ALGORITHM:]HEX` format, SHA-256 is the default algorithm
+pub fn parse_hash(string: &str) -> Result<DataHash, String> {
+    let (algorithm, digest) = match string.find(':') {
+        Some(position) => (
+            HashAlgorithm::from_str(&string[..position])
+                .map_err(|_| format!("Unknown hash algorithm {}", &string[..position]))?,
+            &string[position + 1..],
+        ),
+        None => (HashAlgorithm::Sha256, string),
+    };
+    if algorithm == HashAlgorithm::Sha1 {
+        return Err("SHA-1 hashes are not accepted anymore".to_string());
+    }
+    let bytes = hex::decode(digest).map_err(|err| format!("{}", err))?;
+    DataHash::new(algorithm, &bytes).map_err(|_| {
+        format!(
+            "{} hash must be {} bytes long",
+            algorithm,
+            algorithm.digest_len()
+        )
+    })
+}
+
+/// Return an error if a hash cannot be parsed.
+pub fn is_hash<T>(string: T) -> Result<(), String>
+where
+    T: AsRef<str> + Display,
+{
+    parse_hash(string.as_ref()).map(|_| ())
+}
+
+fn main() {
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg({
+            let arg = Arg::with_name("config_file")
+                .short("C")
+                .long("config")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .help("Configuration file to use");
+            if let Some(ref config_file) = *solana_cli_config::CONFIG_FILE {
+                arg.default_value(&config_file)
+            } else {
+                arg
+            }
+        })
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .takes_value(false)
+                .global(true)
+                .help("Show additional information"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
                 .value_name("URL")
                 .takes_value(true)
                 .validator(is_url)
                 .help("JSON RPC URL for the cluster.  Default from the configuration file."),
         )
-        .arg(
-            Arg::with_name("owner")
-                .long("owner")
-                .value_name("KEYPAIR")
-                .validator(is_keypair)
-                .takes_value(true)
-                .help(
-                    "Specify the stake pool or stake account owner. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
+        .arg(
+            Arg::with_name("owner")
+                .long("owner")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the stake pool or stake account owner. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .arg(
+            Arg::with_name("fee_payer")
+                .long("fee-payer")
+                .value_name("KEYPAIR")
+                .validator(is_keypair)
+                .takes_value(true)
+                .help(
+                    "Specify the fee-payer account. \
+                     This may be a keypair file, the ASK keyword. \
+                     Defaults to the client keypair.",
+                ),
+        )
+        .subcommand(SubCommand::with_name("create-factory").about("Create a new Factory account administered by --owner")
+            .arg(
+                Arg::with_name("version")
+                    .index(1)
+                    .validator(is_parsable::<u8>)
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory's version"),
+            ))
+        .subcommand(SubCommand::with_name("set-factory-admin").about("Transfers factory administration, must be signed by the current administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("new_admin")
+                    .validator(is_pubkey)
+                    .index(2)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("New factory administrator"),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-factory-version").about("Bumps factory version, must be signed by the administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("version")
+                    .index(2)
+                    .validator(is_parsable::<u8>)
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory's new version, greater than the current one"),
+            )
+        )
+        .subcommand(SubCommand::with_name("set-factory-policy").about("Sets policy enforced on escrows created under the factory, must be signed by the administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("mint")
+                    .long("mint")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Token mint escrows are restricted to [default: any mint]"),
+            )
+            .arg(
+                Arg::with_name("max_duration")
+                    .long("max-duration")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .help("Maximum escrow duration in seconds, 0 for unlimited [default: 0]"),
+            )
+            .arg(
+                Arg::with_name("max_oracle_stake")
+                    .long("max-oracle-stake")
+                    .validator(is_parsable::<f64>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .help("Maximum sum of the oracle fees, from 0.01 to 100 percent [default: 100]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("pause-factory").about("Pauses setup, results and payouts of all the factory escrows, must be signed by the administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+        )
+        .subcommand(SubCommand::with_name("unpause-factory").about("Resumes paused factory, must be signed by the administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+        )
+        .subcommand(SubCommand::with_name("approve-oracle").about("Approves the reputation oracle to score the factory workers, must be signed by the administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("oracle")
+                    .validator(is_pubkey)
+                    .index(2)
+                    .value_name("ORACLE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reputation oracle address"),
+            )
+            .arg(
+                Arg::with_name("min_scored_amount")
+                    .long("min-scored-amount")
+                    .validator(is_parsable::<u64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Minimum payout the oracle can score, in the smallest token units"),
+            )
+        )
+        .subcommand(SubCommand::with_name("revoke-oracle").about("Revokes the reputation oracle approval, must be signed by the administrator")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("oracle")
+                    .validator(is_pubkey)
+                    .index(2)
+                    .value_name("ORACLE_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reputation oracle address"),
+            )
         )
-        .arg(
-            Arg::with_name("fee_payer")
-                .long("fee-payer")
-                .value_name("KEYPAIR")
-                .validator(is_keypair)
-                .takes_value(true)
-                .help(
-                    "Specify the fee-payer account. \
-                     This may be a keypair file, the ASK keyword. \
-                     Defaults to the client keypair.",
-                ),
+        .subcommand(SubCommand::with_name("create").about("Create a new escrow")
+            .arg(
+                Arg::with_name("factory")
+                    .long("factory")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address new Escrow will belongs to"),
+            )
+            .arg(
+                Arg::with_name("mint")
+                    .long("mint")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .required_unless("native")
+                    .help("Mint address for the token managed by this escrow"),
+            )
+            .arg(
+                Arg::with_name("native")
+                    .long("native")
+                    .takes_value(false)
+                    .conflicts_with("mint")
+                    .help("Create escrow paying in SOL, the canceler receives funds to its wallet unless --canceler-receiver is set"),
+            )
+            .arg(
+                Arg::with_name("launcher")
+                    .long("launcher")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account which can manage the escrow [default: --owner]"),
+            )
+            .arg(
+                Arg::with_name("canceler")
+                    .long("canceler")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Account which is able to cancel this escrow [default: --owner]"),
+            )
+            .arg(
+                Arg::with_name("canceler_token")
+                    .long("canceler-receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Token account which can receive tokens specified by the --mint parameter [default: new token account owned by the --canceler]"),
+            )
+            .arg(
+                Arg::with_name("duration")
+                    .long("duration")
+                    .short("d")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Escrow duration in seconds, once this time passes escrow contract is no longer operational"),
+            )
+        )
+        .subcommand(SubCommand::with_name("list").about("Lists escrows registered in the factory")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("offset")
+                    .long("offset")
+                    .validator(is_parsable::<u64>)
+                    .value_name("INDEX")
+                    .takes_value(true)
+                    .help("Index of the first escrow to list [default: 0]"),
+            )
+            .arg(
+                Arg::with_name("limit")
+                    .long("limit")
+                    .validator(is_parsable::<u64>)
+                    .value_name("NUMBER")
+                    .takes_value(true)
+                    .help("Maximum number of escrows to list [default: all]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("info").about("Shows information about the escrow account")
+            .arg(
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Escrow address"),
+            )
+        )
+        .subcommand(SubCommand::with_name("worker-reputation").about("Shows reputation of the worker earned in the factory escrows")
+            .arg(
+                Arg::with_name("factory")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("FACTORY_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Factory address"),
+            )
+            .arg(
+                Arg::with_name("worker")
+                    .validator(is_pubkey)
+                    .index(2)
+                    .value_name("WORKER_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Worker wallet address"),
+            )
+        )
+        .subcommand(SubCommand::with_name("setup").about("Configures and launches escrow")
+            .arg(
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Escrow address"),
+            )
+            .arg(
+                Arg::with_name("reputation_oracle")
+                    .long("reputation-oracle")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Escrow reputation oracle address [default: --owner]"),
+            )
+            .arg(
+                Arg::with_name("reputation_oracle_token")
+                    .long("reputation-oracle-token")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Reputation oracle token address [default: new token account owned by the --reputation-oracle]"),
+            )
+            .arg(
+                Arg::with_name("reputation_oracle_stake")
+                    .long("reputation-oracle-stake")
+                    .validator(is_parsable::<f64>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Reputation oracle fee in payouts, from 0 to 100 percent"),
+            )
+            .arg(
+                Arg::with_name("recording_oracle")
+                    .long("recording-oracle")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Escrow recording oracle address [default: --owner]"),
+            )
+            .arg(
+                Arg::with_name("recording_oracle_token")
+                    .long("recording-oracle-token")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Recording oracle token address [default: new token account owned by the --recording-oracle]"),
+            )
+            .arg(
+                Arg::with_name("recording_oracle_stake")
+                    .long("recording-oracle-stake")
+                    .validator(is_parsable::<f64>)
+                    .value_name("PERCENT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Recording oracle fee in payouts, from 0 to 100 percent"),
+            )
+            .arg(
+                Arg::with_name("manifest_url")
+                    .long("manifest-url")
+                    .validator(is_url)
+                    .value_name("URL")
+                    .takes_value(true)
+                    .help("Job manifest URL [default: empty string]"),
+            )
+            .arg(
+                Arg::with_name("manifest_hash")
+                    .long("manifest-hash")
+                    .validator(is_hash)
+                    .value_name("[ALGORITHM:]HEX")
+                    .takes_value(true)
+                    .help("Manifest hash in hex format, ALGORITHM is sha256 or keccak256 [default: empty hash, sha256 if omitted]"),
+            )
+            .arg(
+                Arg::with_name("defer_fees")
+                    .long("defer-fees")
+                    .takes_value(false)
+                    .help("Accrue oracle fees in the escrow until they are settled with the settle-fees command"),
+            )
+            .arg(
+                Arg::with_name("dispute_window")
+                    .long("dispute-window")
+                    .validator(is_parsable::<u64>)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Seconds after storing the results during which the launcher can dispute them and payouts are stopped"),
+            )
+            .arg(
+                Arg::with_name("reputation_oracle_bond")
+                    .long("reputation-oracle-bond")
+                    .validator(is_parsable::<f64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Tokens the reputation oracle locks until the escrow is complete, taken from its token account"),
+            )
+            .arg(
+                Arg::with_name("reputation_oracle_keypair")
+                    .long("reputation-oracle-keypair")
+                    .validator(is_keypair)
+                    .value_name("KEYPAIR")
+                    .takes_value(true)
+                    .conflicts_with("reputation_oracle")
+                    .help("Reputation oracle keypair signing its bond [default: --owner]"),
+            )
+            .arg(
+                Arg::with_name("recording_oracle_bond")
+                    .long("recording-oracle-bond")
+                    .validator(is_parsable::<f64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help("Tokens the recording oracle locks until the escrow is complete, taken from its token account"),
+            )
+            .arg(
+                Arg::with_name("recording_oracle_keypair")
+                    .long("recording-oracle-keypair")
+                    .validator(is_keypair)
+                    .value_name("KEYPAIR")
+                    .takes_value(true)
+                    .conflicts_with("recording_oracle")
+                    .help("Recording oracle keypair signing its bond [default: --owner]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("fund").about("Deposits tokens to the escrow, must be signed by the launcher")
+            .arg(
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Escrow address"),
+            )
+            .arg(
+                Arg::with_name("amount")
+                    .validator(is_parsable::<f64>)
+                    .index(2)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Amount of tokens to deposit"),
+            )
+            .arg(
+                Arg::with_name("source")
+                    .long("source")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Launcher's token account to take tokens from, native SOL escrows are funded from the --owner wallet"),
+            )
+        )
+        .subcommand(SubCommand::with_name("extend").about("Extends escrow expiration time, must be signed by the launcher or canceler")
+            .arg(
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Escrow address"),
+            )
+            .arg(
+                Arg::with_name("additional_seconds")
+                    .validator(is_parsable::<u64>)
+                    .index(2)
+                    .value_name("SECONDS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Number of seconds to add to the escrow expiration time"),
+            )
         )
-        .subcommand(SubCommand::with_name("create-factory").about("Create a new Factory account")
+        .subcommand(SubCommand::with_name("store-results").about("Stores results in the escrow")
             .arg(
-                Arg::with_name("version")
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
                     .index(1)
-                    .validator(is_parsable::<u8>)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Factory's version"),
-            ))
-        .subcommand(SubCommand::with_name("create").about("Create a new escrow")
+                    .help("Escrow address"),
+            )
             .arg(
-                Arg::with_name("factory")
-                    .long("factory")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("results_url")
+                    .long("results-url")
+                    .validator(is_url)
+                    .value_name("URL")
                     .takes_value(true)
-                    .required(true)
-                    .help("Factory address new Escrow will belongs to"),
+                    .help("Final results URL [default: empty string]"),
             )
             .arg(
-                Arg::with_name("mint")
-                    .long("mint")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("results_hash")
+                    .long("results-hash")
+                    .validator(is_hash)
+                    .value_name("[ALGORITHM:]HEX")
                     .takes_value(true)
-                    .required(true)
-                    .help("Mint address for the token managed by this escrow"),
+                    .help("Results hash in hex format, ALGORITHM is sha256 or keccak256 [default: empty hash, sha256 if omitted]"),
             )
+        )
+        .subcommand(SubCommand::with_name("payout").about("Pays tokens from the escrow account")
             .arg(
-                Arg::with_name("launcher")
-                    .long("launcher")
+                Arg::with_name("escrow")
                     .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
-                    .help("Account which can manage the escrow [default: --owner]"),
+                    .required(true)
+                    .help("Escrow address"),
             )
             .arg(
-                Arg::with_name("canceler")
-                    .long("canceler")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("file_name")
+                    .validator(is_parsable::<String>)
+                    .index(2)
+                    .value_name("FILE")
                     .takes_value(true)
-                    .help("Account which is able to cancel this escrow [default: --owner]"),
+                    .required(true)
+                    .help("CSV file with recipients and amounts, <address>,<amount> on each line"),
             )
+        )
+        .subcommand(SubCommand::with_name("store-payouts-root").about("Stores final amounts and the Merkle root of the payouts, so the recipients can claim them")
             .arg(
-                Arg::with_name("canceler_token")
-                    .long("canceler-receiver")
+                Arg::with_name("escrow")
                     .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
-                    .help("Token account which can receive tokens specified by the --mint parameter [default: new token account owned by the --canceler]"),
+                    .required(true)
+                    .help("Escrow address"),
             )
             .arg(
-                Arg::with_name("duration")
-                    .long("duration")
-                    .short("d")
-                    .validator(is_parsable::<u64>)
-                    .value_name("SECONDS")
+                Arg::with_name("file_name")
+                    .validator(is_parsable::<String>)
+                    .index(2)
+                    .value_name("FILE")
                     .takes_value(true)
                     .required(true)
-                    .help("Escrow duration in seconds, once this time passes escrow contract is no longer operational"),
+                    .help("CSV file with recipients and amounts, <address>,<amount> on each line"),
             )
         )
-        .subcommand(SubCommand::with_name("info").about("Shows information about the escrow account")
+        .subcommand(SubCommand::with_name("claim").about("Claims a payout from the stored Merkle root of the payouts")
             .arg(
                 Arg::with_name("escrow")
                     .validator(is_pubkey)
@@ -857,8 +2559,26 @@ fn main() {
                     .required(true)
                     .help("Escrow address"),
             )
+            .arg(
+                Arg::with_name("file_name")
+                    .validator(is_parsable::<String>)
+                    .index(2)
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .required(true)
+                    .help("CSV file the payouts root was calculated from"),
+            )
+            .arg(
+                Arg::with_name("recipient")
+                    .validator(is_pubkey)
+                    .index(3)
+                    .value_name("RECIPIENT_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Recipient token account to claim the payout for"),
+            )
         )
-        .subcommand(SubCommand::with_name("setup").about("Configures and launches escrow")
+        .subcommand(SubCommand::with_name("vested-payout").about("Pays a recipient with tokens vesting linearly from the start to the end, nothing vests before the cliff")
             .arg(
                 Arg::with_name("escrow")
                     .validator(is_pubkey)
@@ -869,73 +2589,82 @@ fn main() {
                     .help("Escrow address"),
             )
             .arg(
-                Arg::with_name("reputation_oracle")
-                    .long("reputation-oracle")
+                Arg::with_name("recipient")
                     .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                    .index(2)
+                    .value_name("RECIPIENT_ADDRESS")
                     .takes_value(true)
-                    .help("Escrow reputation oracle address [default: --owner]"),
+                    .required(true)
+                    .help("Recipient token account, native SOL escrows can pay a wallet"),
             )
             .arg(
-                Arg::with_name("reputation_oracle_token")
-                    .long("reputation-oracle-token")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("amount")
+                    .validator(is_parsable::<f64>)
+                    .index(3)
+                    .value_name("AMOUNT")
                     .takes_value(true)
-                    .help("Reputation oracle token address [default: new token account owned by the --reputation-oracle]"),
+                    .required(true)
+                    .help("Payout amount including the oracle fees"),
             )
             .arg(
-                Arg::with_name("reputation_oracle_stake")
-                    .long("reputation-oracle-stake")
-                    .validator(is_parsable::<u8>)
-                    .value_name("PERCENT")
+                Arg::with_name("start")
+                    .long("start")
+                    .validator(is_parsable::<i64>)
+                    .value_name("TIMESTAMP")
                     .takes_value(true)
                     .required(true)
-                    .help("Reputation oracle fee in payouts, from 0 to 100 percent"),
+                    .help("Unix timestamp the vesting starts at"),
             )
             .arg(
-                Arg::with_name("recording_oracle")
-                    .long("recording-oracle")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("cliff")
+                    .long("cliff")
+                    .validator(is_parsable::<i64>)
+                    .value_name("TIMESTAMP")
                     .takes_value(true)
-                    .help("Escrow recording oracle address [default: --owner]"),
+                    .help("Unix timestamp nothing can be withdrawn before [default: --start]"),
             )
             .arg(
-                Arg::with_name("recording_oracle_token")
-                    .long("recording-oracle-token")
-                    .validator(is_pubkey)
-                    .value_name("ADDRESS")
+                Arg::with_name("end")
+                    .long("end")
+                    .validator(is_parsable::<i64>)
+                    .value_name("TIMESTAMP")
                     .takes_value(true)
-                    .help("Recording oracle token address [default: new token account owned by the --recording-oracle]"),
+                    .required(true)
+                    .help("Unix timestamp the payout is fully vested at, must not be after the escrow expiration"),
             )
+        )
+        .subcommand(SubCommand::with_name("withdraw").about("Withdraws vested tokens to the recipient, can be signed by anyone")
             .arg(
-                Arg::with_name("recording_oracle_stake")
-                    .long("recording-oracle-stake")
-                    .validator(is_parsable::<u8>)
-                    .value_name("PERCENT")
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("Recording oracle fee in payouts, from 0 to 100 percent"),
+                    .help("Escrow address"),
             )
             .arg(
-                Arg::with_name("manifest_url")
-                    .long("manifest-url")
-                    .validator(is_url)
-                    .value_name("URL")
+                Arg::with_name("recipient")
+                    .validator(is_pubkey)
+                    .index(2)
+                    .value_name("RECIPIENT_ADDRESS")
                     .takes_value(true)
-                    .help("Job manifest URL [default: empty string]"),
+                    .required(true)
+                    .help("Recipient of the vested payout"),
             )
+        )
+        .subcommand(SubCommand::with_name("cancel").about("Cancels escrow, all remaining funds are returned to the canceler's token account")
             .arg(
-                Arg::with_name("manifest_hash")
-                    .long("manifest-hash")
-                    .validator(is_hex)
-                    .value_name("HEX")
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
-                    .help("20-byte manifest SHA1 hash in hex format [default: 0-byte hash]"),
+                    .required(true)
+                    .help("Escrow address"),
             )
         )
-        .subcommand(SubCommand::with_name("store-results").about("Stores results in the escrow")
+        .subcommand(SubCommand::with_name("close").about("Closes complete or cancelled escrow and its token account, reclaiming the rent")
             .arg(
                 Arg::with_name("escrow")
                     .validator(is_pubkey)
@@ -946,23 +2675,26 @@ fn main() {
                     .help("Escrow address"),
             )
             .arg(
-                Arg::with_name("results_url")
-                    .long("results-url")
-                    .validator(is_url)
-                    .value_name("URL")
+                Arg::with_name("receiver")
+                    .long("receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
                     .takes_value(true)
-                    .help("Final results URL [default: empty string]"),
+                    .help("Account to receive the reclaimed rent [default: --fee-payer]"),
             )
+        )
+        .subcommand(SubCommand::with_name("complete").about("Completes escrow")
             .arg(
-                Arg::with_name("results_hash")
-                    .long("results-hash")
-                    .validator(is_hex)
-                    .value_name("HEX")
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
-                    .help("20-byte results SHA1 hash in hex format [default: 0-byte hash]"),
+                    .required(true)
+                    .help("Escrow address"),
             )
         )
-        .subcommand(SubCommand::with_name("payout").about("Pays tokens from the escrow account")
+        .subcommand(SubCommand::with_name("settle-fees").about("Sends deferred oracle fees, must be signed by an oracle, the launcher or a trusted handler")
             .arg(
                 Arg::with_name("escrow")
                     .validator(is_pubkey)
@@ -972,17 +2704,19 @@ fn main() {
                     .required(true)
                     .help("Escrow address"),
             )
+        )
+        .subcommand(SubCommand::with_name("dispute").about("Disputes the final results during the dispute window, must be signed by the launcher")
             .arg(
-                Arg::with_name("file_name")
-                    .validator(is_parsable::<String>)
-                    .index(2)
-                    .value_name("FILE")
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
                     .takes_value(true)
                     .required(true)
-                    .help("CSV file with recipients and amounts, <address>,<amount> on each line"),
+                    .help("Escrow address"),
             )
         )
-        .subcommand(SubCommand::with_name("cancel").about("Cancels escrow, all remaining funds are returned to the canceler's token account")
+        .subcommand(SubCommand::with_name("resolve-dispute").about("Resolves the escrow dispute, must be signed by the factory admin")
             .arg(
                 Arg::with_name("escrow")
                     .validator(is_pubkey)
@@ -992,8 +2726,57 @@ fn main() {
                     .required(true)
                     .help("Escrow address"),
             )
+            .arg(
+                Arg::with_name("accept")
+                    .long("accept")
+                    .takes_value(false)
+                    .required_unless("reject")
+                    .conflicts_with("reject")
+                    .help("Accept the disputed results, payouts can be sent right away"),
+            )
+            .arg(
+                Arg::with_name("reject")
+                    .long("reject")
+                    .takes_value(false)
+                    .help("Drop the disputed results, payouts wait for the new results"),
+            )
         )
-        .subcommand(SubCommand::with_name("complete").about("Completes escrow")
+        .subcommand(SubCommand::with_name("slash-bonds").about("Slashes oracle bonds after the results are rejected, must be signed by the launcher")
+            .arg(
+                Arg::with_name("escrow")
+                    .validator(is_pubkey)
+                    .index(1)
+                    .value_name("ESCROW_ADDRESS")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Escrow address"),
+            )
+            .arg(
+                Arg::with_name("reputation_oracle_amount")
+                    .long("reputation-oracle-amount")
+                    .validator(is_parsable::<f64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount taken from the reputation oracle bond [default: whole bond]"),
+            )
+            .arg(
+                Arg::with_name("recording_oracle_amount")
+                    .long("recording-oracle-amount")
+                    .validator(is_parsable::<f64>)
+                    .value_name("AMOUNT")
+                    .takes_value(true)
+                    .help("Amount taken from the recording oracle bond [default: whole bond]"),
+            )
+            .arg(
+                Arg::with_name("receiver")
+                    .long("receiver")
+                    .validator(is_pubkey)
+                    .value_name("ADDRESS")
+                    .takes_value(true)
+                    .help("Token account to receive the slashed tokens [default: canceler token account]"),
+            )
+        )
+        .subcommand(SubCommand::with_name("release-bonds").about("Returns oracle bonds of a cancelled or expired escrow, must be signed by an oracle")
             .arg(
                 Arg::with_name("escrow")
                     .validator(is_pubkey)
@@ -1054,9 +2837,49 @@ fn main() {
             let version = value_t_or_exit!(arg_matches, "version", u8);
             command_create_factory(&config, version)
         }
+        ("set-factory-admin", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let new_admin: Pubkey = pubkey_of(arg_matches, "new_admin").unwrap();
+            command_set_factory_admin(&config, &factory, &new_admin)
+        }
+        ("set-factory-version", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let version = value_t_or_exit!(arg_matches, "version", u8);
+            command_set_factory_version(&config, &factory, version)
+        }
+        ("set-factory-policy", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let mint: Option<Pubkey> = pubkey_of(arg_matches, "mint");
+            let max_duration: u64 = value_of(arg_matches, "max_duration").unwrap_or(0);
+            let max_oracle_stake: f64 = value_of(arg_matches, "max_oracle_stake").unwrap_or(100.0);
+            command_set_factory_policy(&config, &factory, &mint, max_duration, max_oracle_stake)
+        }
+        ("pause-factory", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            command_set_factory_paused(&config, &factory, true)
+        }
+        ("unpause-factory", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            command_set_factory_paused(&config, &factory, false)
+        }
+        ("approve-oracle", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let oracle: Pubkey = pubkey_of(arg_matches, "oracle").unwrap();
+            let min_scored_amount: u64 = value_of(arg_matches, "min_scored_amount").unwrap();
+            if min_scored_amount == 0 {
+                Err("Minimum scored amount must be positive, use revoke-oracle to revoke".into())
+            } else {
+                command_approve_oracle(&config, &factory, &oracle, min_scored_amount)
+            }
+        }
+        ("revoke-oracle", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let oracle: Pubkey = pubkey_of(arg_matches, "oracle").unwrap();
+            command_approve_oracle(&config, &factory, &oracle, 0)
+        }
         ("create", Some(arg_matches)) => {
             let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
-            let mint: Pubkey = pubkey_of(arg_matches, "mint").unwrap();
+            let mint: Option<Pubkey> = pubkey_of(arg_matches, "mint");
             let launcher: Option<Pubkey> = pubkey_of(arg_matches, "launcher");
             let canceler: Option<Pubkey> = pubkey_of(arg_matches, "canceler");
             let canceler_token: Option<Pubkey> = pubkey_of(arg_matches, "canceler_token");
@@ -1071,24 +2894,52 @@ fn main() {
                 duration,
             )
         }
+        ("list", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let offset: u64 = value_of(arg_matches, "offset").unwrap_or(0);
+            let limit: Option<u64> = value_of(arg_matches, "limit");
+            command_list(&config, &factory, offset, limit)
+        }
         ("info", Some(arg_matches)) => {
             let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
             command_info(&config, &escrow)
         }
+        ("worker-reputation", Some(arg_matches)) => {
+            let factory: Pubkey = pubkey_of(arg_matches, "factory").unwrap();
+            let worker: Pubkey = pubkey_of(arg_matches, "worker").unwrap();
+            command_worker_reputation(&config, &factory, &worker)
+        }
         ("setup", Some(arg_matches)) => {
             let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
             let reputation_oracle: Option<Pubkey> = pubkey_of(arg_matches, "reputation_oracle");
             let reputation_oracle_token: Option<Pubkey> =
                 pubkey_of(arg_matches, "reputation_oracle_token");
             let reputation_oracle_stake =
-                value_t_or_exit!(arg_matches, "reputation_oracle_stake", u8);
+                value_t_or_exit!(arg_matches, "reputation_oracle_stake", f64);
             let recording_oracle: Option<Pubkey> = pubkey_of(arg_matches, "recording_oracle");
             let recording_oracle_token: Option<Pubkey> =
                 pubkey_of(arg_matches, "recording_oracle_token");
             let recording_oracle_stake =
-                value_t_or_exit!(arg_matches, "recording_oracle_stake", u8);
+                value_t_or_exit!(arg_matches, "recording_oracle_stake", f64);
             let manifest_url: String = value_of(arg_matches, "manifest_url").unwrap_or_default();
             let manifest_hash: Option<String> = value_of(arg_matches, "manifest_hash");
+            let defer_fees = arg_matches.is_present("defer_fees");
+            let dispute_window = value_t_or_exit!(arg_matches, "dispute_window", u64);
+            let reputation_oracle_bond =
+                value_t_or_exit!(arg_matches, "reputation_oracle_bond", f64);
+            let recording_oracle_bond = value_t_or_exit!(arg_matches, "recording_oracle_bond", f64);
+            let mut oracle_signer = |name: &str| {
+                arg_matches.value_of(name).map(|path| {
+                    signer_from_path(arg_matches, path, name, &mut wallet_manager).unwrap_or_else(
+                        |e| {
+                            eprintln!("error: {}", e);
+                            exit(1);
+                        },
+                    )
+                })
+            };
+            let reputation_oracle_signer = oracle_signer("reputation_oracle_keypair");
+            let recording_oracle_signer = oracle_signer("recording_oracle_keypair");
             command_setup(
                 &config,
                 &escrow,
@@ -1100,8 +2951,25 @@ fn main() {
                 recording_oracle_stake,
                 &manifest_url,
                 &manifest_hash,
+                defer_fees,
+                dispute_window,
+                reputation_oracle_bond,
+                &reputation_oracle_signer,
+                recording_oracle_bond,
+                &recording_oracle_signer,
             )
         }
+        ("fund", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let source: Option<Pubkey> = pubkey_of(arg_matches, "source");
+            command_fund(&config, &escrow, &source, amount)
+        }
+        ("extend", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let additional_seconds = value_t_or_exit!(arg_matches, "additional_seconds", u64);
+            command_extend(&config, &escrow, additional_seconds)
+        }
         ("store-results", Some(arg_matches)) => {
             let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
             let results_url: String = value_of(arg_matches, "results_url").unwrap_or_default();
@@ -1113,14 +2981,76 @@ fn main() {
             let file_name = value_t_or_exit!(arg_matches, "file_name", String);
             command_payout(&config, &escrow, &file_name)
         }
+        ("store-payouts-root", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let file_name = value_t_or_exit!(arg_matches, "file_name", String);
+            command_store_payouts_root(&config, &escrow, &file_name)
+        }
+        ("claim", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let file_name = value_t_or_exit!(arg_matches, "file_name", String);
+            let recipient: Pubkey = pubkey_of(arg_matches, "recipient").unwrap();
+            command_claim(&config, &escrow, &file_name, &recipient)
+        }
+        ("vested-payout", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let recipient: Pubkey = pubkey_of(arg_matches, "recipient").unwrap();
+            let amount = value_t_or_exit!(arg_matches, "amount", f64);
+            let start = value_t_or_exit!(arg_matches, "start", i64);
+            let cliff = value_t!(arg_matches, "cliff", i64).unwrap_or(start);
+            let end = value_t_or_exit!(arg_matches, "end", i64);
+            command_vested_payout(&config, &escrow, &recipient, amount, start, cliff, end)
+        }
+        ("withdraw", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let recipient: Pubkey = pubkey_of(arg_matches, "recipient").unwrap();
+            command_withdraw(&config, &escrow, &recipient)
+        }
         ("cancel", Some(arg_matches)) => {
             let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
             command_cancel(&config, &escrow)
         }
+        ("close", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let receiver: Option<Pubkey> = pubkey_of(arg_matches, "receiver");
+            command_close(&config, &escrow, &receiver)
+        }
         ("complete", Some(arg_matches)) => {
             let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
             command_complete(&config, &escrow)
         }
+        ("settle-fees", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            command_settle_fees(&config, &escrow)
+        }
+        ("dispute", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            command_dispute(&config, &escrow)
+        }
+        ("resolve-dispute", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let accept_results = arg_matches.is_present("accept");
+            command_resolve_dispute(&config, &escrow, accept_results)
+        }
+        ("slash-bonds", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            let reputation_oracle_amount: Option<f64> =
+                value_of(arg_matches, "reputation_oracle_amount");
+            let recording_oracle_amount: Option<f64> =
+                value_of(arg_matches, "recording_oracle_amount");
+            let receiver: Option<Pubkey> = pubkey_of(arg_matches, "receiver");
+            command_slash_bonds(
+                &config,
+                &escrow,
+                reputation_oracle_amount,
+                recording_oracle_amount,
+                &receiver,
+            )
+        }
+        ("release-bonds", Some(arg_matches)) => {
+            let escrow: Pubkey = pubkey_of(arg_matches, "escrow").unwrap();
+            command_release_bonds(&config, &escrow)
+        }
         _ => unreachable!(),
     }
     .and_then(|transaction| {
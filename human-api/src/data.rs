@@ -24,6 +24,8 @@ pub struct InitJobArgs {
     pub repOraclePub: String,
     /// Manifest URL
     pub manifestUrl: String,
+    /// Manifest hash algorithm, sha256 or keccak256 [default: sha256]
+    pub hashAlgorithm: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -39,6 +41,8 @@ pub struct StoreResultsArgs {
     pub repOraclePub: String,
     /// Result URL
     pub resultsUrl: String,
+    /// Results hash algorithm, sha256 or keccak256 [default: sha256]
+    pub hashAlgorithm: Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -3,7 +3,8 @@ use crate::*;
 use crate::data::*;
 use crate::responses::*;
 use hmt_escrow::instruction::factory_initialize;
-use hmt_escrow::state::Factory;
+use hmt_escrow::processor::Processor as EscrowProcessor;
+use hmt_escrow::state::{Factory, FactoryEntry};
 use rocket::State;
 use rocket_contrib::json::Json;
 use solana_program::instruction::Instruction;
@@ -14,44 +15,80 @@ use solana_sdk::{
     transaction::Transaction,
 };
 
-///  Returns addresses of all jobs deployed in the factory
-#[get("/factory?<address>")]
-pub fn get_factory(address: String, config: State<Config>) -> Result<OkResponse, ErrorResponse> {
-    let human_protocol_program = Pubkey::from_str(&config.human_protocol_program).map_err(|e| {
-        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+/// Maximum number of accounts requested by a single `getMultipleAccounts` call
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+///  Returns addresses of jobs deployed in the factory, optionally paged by `offset` and `limit`
+#[get("/factory?<address>&<offset>&<limit>")]
+pub fn get_factory(
+    address: String,
+    offset: Option<u64>,
+    limit: Option<u64>,
+    config: State<Config>,
+) -> Result<OkResponse, ErrorResponse> {
+    let factory_pub_key = Pubkey::from_str(&address).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
             error: e.to_string(),
         }))
     })?;
 
-    let memcp = Memcmp {
-        offset: config.data_offset_to_begin_match,
-        bytes: MemcmpEncodedBytes::Binary(address),
-        encoding: None,
-    };
-    let filters = RpcFilterType::Memcmp(memcp);
-    let configs = RpcProgramAccountsConfig {
-        filters: Some(vec![filters]),
-        account_config: RpcAccountInfoConfig {
-            encoding: Some(UiAccountEncoding::Base64),
-            commitment: Some(CommitmentConfig::default()),
-            ..RpcAccountInfoConfig::default()
-        },
-    };
-    let accounts_with_config = config
+    let account_data = config
         .rpc_client
-        .get_program_accounts_with_config(&human_protocol_program, configs)
+        .get_account_data(&factory_pub_key)
         .map_err(|e| {
-            ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
-                parameter_name: "address".to_string(),
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
                 error: e.to_string(),
             }))
         })?;
+    let factory_info = Factory::unpack(account_data.as_slice()).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+
+    let offset = offset.unwrap_or(0);
+    let end = match limit {
+        Some(limit) => offset.saturating_add(limit).min(factory_info.escrow_count),
+        None => factory_info.escrow_count,
+    };
+
+    let mut jobs = vec![];
+    if offset < end {
+        let indices: Vec<u64> = (offset..end).collect();
+        for chunk in indices.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let entries: Vec<Pubkey> = chunk
+                .iter()
+                .map(|index| {
+                    EscrowProcessor::find_factory_entry_address(
+                        &hmt_escrow::id(),
+                        &factory_pub_key,
+                        *index,
+                    )
+                    .0
+                })
+                .collect();
+            let accounts = config
+                .rpc_client
+                .get_multiple_accounts(&entries)
+                .map_err(|e| {
+                    ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                        error: e.to_string(),
+                    }))
+                })?;
+            jobs.extend(accounts.iter().filter_map(|account| {
+                account
+                    .as_ref()
+                    .and_then(|account| FactoryEntry::unpack(account.data.as_slice()).ok())
+                    .map(|entry| entry.escrow.to_string())
+            }));
+        }
+    }
 
     Ok(OkResponse::FactoryJobsResponse(Json(FactoryJobs {
-        jobs: accounts_with_config
-            .iter()
-            .map(|account_data| account_data.0.to_string())
-            .collect::<Vec<_>>(),
+        count: factory_info.escrow_count,
+        jobs,
     })))
 }
 
@@ -87,6 +124,7 @@ pub fn new_factory(
         factory_initialize(
             &hmt_escrow::id(),
             &factory_acc.pubkey(),
+            &payer.pubkey(),
             config.factory_version,
         )
         .map_err(|e| {
@@ -1,17 +1,25 @@
 use crate::responses::*;
 use crate::*;
-use hmt_escrow::state::Escrow;
+use hmt_escrow::{
+    processor::Processor as EscrowProcessor,
+    state::{DataHash, DataUrl, Escrow, HashAlgorithm, UrlData},
+};
 use rocket_contrib::json::Json;
-use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use solana_program::{
+    instruction::Instruction, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
 use solana_sdk::{
     native_token::*,
     signature::{Keypair, Signer},
     system_instruction,
 };
 use spl_token::{
-    instruction::initialize_account, instruction::initialize_mint, state::Account as TokenAccount,
-    state::Mint as TokenMint,
+    instruction::initialize_account, instruction::initialize_mint, native_mint,
+    state::Account as TokenAccount, state::Mint as TokenMint,
 };
+use std::str::FromStr;
 
 pub fn check_fee_payer_balance(
     config: &Config,
@@ -140,3 +148,82 @@ pub fn create_escrow_account(
 
     Ok(instruction)
 }
+
+/// Reads URL, URLs stored in the URL data account are fetched from it
+pub fn read_data_url(config: &Config, url: &DataUrl) -> Result<String, ErrorResponse> {
+    let account = match url.account() {
+        Some(account) => account,
+        None => return Ok(url.to_string()),
+    };
+
+    let account_data = config.rpc_client.get_account_data(&account).map_err(|e| {
+        ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })?;
+    let url = UrlData::unpack_url(account_data.as_slice()).map_err(|e| {
+        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })?;
+
+    Ok(String::from_utf8_lossy(url).into_owned())
+}
+
+/// Hashes the data with the requested algorithm, SHA-256 is used by default
+pub fn hash_data(algorithm: &Option<String>, data: &[u8]) -> Result<DataHash, ErrorResponse> {
+    let algorithm = match algorithm {
+        None => HashAlgorithm::Sha256,
+        Some(value) => HashAlgorithm::from_str(value).map_err(|e| {
+            ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+                parameter_name: "hashAlgorithm".to_string(),
+                error: e.to_string(),
+            }))
+        })?,
+    };
+    let digest = match algorithm {
+        HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        HashAlgorithm::Keccak256 => Keccak256::digest(data).to_vec(),
+        _ => {
+            return Err(ErrorResponse::InvalidParameterResponse(Json(
+                InvalidParameter {
+                    parameter_name: "hashAlgorithm".to_string(),
+                    error: format!("{} hashes are not accepted", algorithm),
+                },
+            )))
+        }
+    };
+
+    DataHash::new(algorithm, &digest).map_err(|e| {
+        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })
+}
+
+/// Wrap account used to unwrap SOL of the native mint escrow
+pub fn wrap_account(escrow: &Pubkey, escrow_info: &Escrow) -> Option<Pubkey> {
+    if escrow_info.token_mint == native_mint::id() {
+        Some(EscrowProcessor::find_wrap_address(&hmt_escrow::id(), escrow).0)
+    } else {
+        None
+    }
+}
+
+/// Bond vault and oracle token accounts used to return the bonds of the bonded escrow
+pub fn bond_accounts(escrow: &Pubkey, escrow_info: &Escrow) -> Option<(Pubkey, Pubkey, Pubkey)> {
+    match (
+        escrow_info.bonded,
+        escrow_info.reputation_oracle_token_account,
+        escrow_info.recording_oracle_token_account,
+    ) {
+        (true, COption::Some(reputation_oracle_token), COption::Some(recording_oracle_token)) => {
+            Some((
+                EscrowProcessor::find_bond_vault_address(&hmt_escrow::id(), escrow).0,
+                reputation_oracle_token,
+                recording_oracle_token,
+            ))
+        }
+        _ => None,
+    }
+}
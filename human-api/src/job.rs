@@ -3,19 +3,26 @@ use crate::helpers::*;
 use crate::responses::*;
 use crate::Config;
 use hmt_escrow::{
+    instruction::add_trusted_handlers as add_trusted_handlers_instruction,
+    instruction::batch_payout,
     instruction::cancel as cancel_escrow,
+    instruction::close as close_escrow,
     instruction::complete as complete_escrow,
+    instruction::extend_expiration,
+    instruction::fund as fund_escrow,
     instruction::initialize as initialize_escrow,
-    instruction::payout,
+    instruction::payout_ids,
     instruction::setup as setup_escrow,
     instruction::store_amounts,
-    instruction::store_results,
+    instruction::store_intermediate_results,
+    instruction::PayoutId,
     processor::Processor as EscrowProcessor,
-    state::{DataHash, DataUrl, Escrow},
+    state::{
+        DataUrl, Escrow, Factory, FactoryEntry, PayoutReceipt, ResultsLog, STAKE_BASIS_POINTS,
+    },
 };
 use rocket::State;
 use rocket_contrib::json::Json;
-use sha1::{Digest, Sha1};
 use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
 use solana_sdk::{
     signature::{Keypair, Signer},
@@ -39,6 +46,22 @@ pub fn new_job(
         }))
     })?;
 
+    // Read factory state to find the next registry index
+    let factory_account_data = config
+        .rpc_client
+        .get_account_data(&factory_pub_key)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    let factory_info = Factory::unpack(factory_account_data.as_slice()).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "factoryAddress".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+
     let mut instructions = vec![];
     let mut signers = vec![];
     let mut total_rent_free_balances = 0;
@@ -113,6 +136,20 @@ pub fn new_job(
             }))
         })?;
 
+    let (factory_entry, _) = EscrowProcessor::find_factory_entry_address(
+        &hmt_escrow::id(),
+        &factory_pub_key,
+        factory_info.escrow_count,
+    );
+    total_rent_free_balances += config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(FactoryEntry::LEN)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
     // Initialize Escrow
     instructions.push(
         initialize_escrow(
@@ -124,7 +161,10 @@ pub fn new_job(
             &payer.pubkey(),
             &payer.pubkey(),
             &escrow_token_account.pubkey(),
+            &factory_entry,
+            &payer.pubkey(),
             config.escrow_duration,
+            None,
         )
         .map_err(|e| {
             ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -158,13 +198,7 @@ pub fn new_job(
         }))
     })?;
 
-    let mut hasher = Sha1::new();
-    hasher.update(str_manifest_data);
-    let manifest_hash = DataHash::new_from_slice(&hasher.finalize()).map_err(|e| {
-        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
-            error: e.to_string(),
-        }))
-    })?;
+    let manifest_hash = hash_data(&job_init_args.hashAlgorithm, str_manifest_data.as_bytes())?;
 
     let reputation_oracle_account_pub_key =
         Pubkey::from_str(&job_init_args.repOraclePub).map_err(|e| {
@@ -224,12 +258,18 @@ pub fn new_job(
             &payer.pubkey(),
             &reputation_oracle_account_pub_key,
             &reputation_oracle_token_account.pubkey(),
-            (manifest_data.oracle_stake * 100.0) as u8,
+            (manifest_data.oracle_stake * STAKE_BASIS_POINTS as f64).round() as u16,
             &recording_oracle_account_pub_key,
             &reputation_oracle_token_account.pubkey(),
-            (manifest_data.oracle_stake * 100.0) as u8,
+            (manifest_data.oracle_stake * STAKE_BASIS_POINTS as f64).round() as u16,
             &manifest_url,
             &manifest_hash,
+            false,
+            0,
+            0,
+            0,
+            &factory_pub_key,
+            None,
         )
         .map_err(|e| {
             ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -362,7 +402,7 @@ pub fn get_job_manifest_url(
     })?;
 
     Ok(OkResponse::DataResponse(Json(Response {
-        data: escrow_info.manifest_url.to_string(),
+        data: read_data_url(&config, &escrow_info.manifest_url)?,
     })))
 }
 
@@ -510,6 +550,12 @@ pub fn abort_job(
                 &authority,
                 &escrow_info.canceler_token_account,
                 &spl_token::id(),
+                wrap_account(&escrow_pub_key, &escrow_info).as_ref(),
+                if escrow_info.vesting_amount != 0 {
+                    Some(&[])
+                } else {
+                    None
+                },
             )
             .map_err(|e| {
                 ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -597,6 +643,12 @@ pub fn cancel_job(
             &authority,
             &escrow_info.canceler_token_account,
             &spl_token::id(),
+            wrap_account(&escrow_pub_key, &escrow_info).as_ref(),
+            if escrow_info.vesting_amount != 0 {
+                Some(&[])
+            } else {
+                None
+            },
         )
         .map_err(|e| {
             ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -637,11 +689,13 @@ pub fn cancel_job(
     })))
 }
 
-/// Complete a given job
+/// Fund a given job from the gas payer's token account
 #[allow(non_snake_case)]
-#[get("/complete?<address>&<gasPayerPrivate>")]
-pub fn complete_job(
+#[get("/fund?<address>&<amount>&<tokenAccount>&<gasPayerPrivate>")]
+pub fn fund_job(
     address: String,
+    amount: f64,
+    tokenAccount: String,
     gasPayerPrivate: String,
     config: State<Config>,
 ) -> Result<OkResponse, ErrorResponse> {
@@ -651,16 +705,312 @@ pub fn complete_job(
             error: e.to_string(),
         }))
     })?;
+    let token_account_pub_key = Pubkey::from_str(&tokenAccount).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "tokenAccount".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
     let payer = Keypair::from_base58_string(&gasPayerPrivate);
 
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_pub_key)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    let escrow_info = Escrow::unpack_from_slice(account_data.as_slice()).map_err(|e| {
+        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })?;
+
+    // Check token mint to convert amount to u64
+    let token_mint_account_data = config
+        .rpc_client
+        .get_account_data(&escrow_info.token_mint)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    let mint_info: TokenMint = TokenMint::unpack_from_slice(token_mint_account_data.as_slice())
+        .map_err(|e| {
+            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
     let mut transaction = Transaction::new_with_payer(
-        &[
-            complete_escrow(&hmt_escrow::id(), &escrow_pub_key, &payer.pubkey()).map_err(|e| {
+        &[fund_escrow(
+            &hmt_escrow::id(),
+            &escrow_pub_key,
+            &payer.pubkey(),
+            &token_account_pub_key,
+            &escrow_info.token_account,
+            &spl_token::id(),
+            spl_token::ui_amount_to_amount(amount, mint_info.decimals),
+        )
+        .map_err(|e| {
+            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?],
+        Some(&payer.pubkey()),
+    );
+    let (recent_blockhash, fee_calculator) =
+        config.rpc_client.get_recent_blockhash().map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    check_fee_payer_balance(
+        &config,
+        &payer.pubkey(),
+        fee_calculator.calculate_fee(&transaction.message()),
+    )
+    .map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "gasPayerPrivate".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    transaction.sign(&vec![&payer], recent_blockhash);
+    config
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
+    Ok(OkResponse::BoolResponse(Json(BoolResponse {
+        success: true,
+    })))
+}
+
+/// Extend job expiration by the given number of seconds
+#[allow(non_snake_case)]
+#[get("/extend?<address>&<seconds>&<gasPayerPrivate>")]
+pub fn extend_job(
+    address: String,
+    seconds: u64,
+    gasPayerPrivate: String,
+    config: State<Config>,
+) -> Result<OkResponse, ErrorResponse> {
+    let escrow_pub_key = Pubkey::from_str(&address).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    let payer = Keypair::from_base58_string(&gasPayerPrivate);
+
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_pub_key)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    let escrow_info = Escrow::unpack_from_slice(account_data.as_slice()).map_err(|e| {
+        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[extend_expiration(
+            &hmt_escrow::id(),
+            &escrow_pub_key,
+            &payer.pubkey(),
+            &escrow_info.factory,
+            seconds,
+        )
+        .map_err(|e| {
+            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?],
+        Some(&payer.pubkey()),
+    );
+    let (recent_blockhash, fee_calculator) =
+        config.rpc_client.get_recent_blockhash().map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    check_fee_payer_balance(
+        &config,
+        &payer.pubkey(),
+        fee_calculator.calculate_fee(&transaction.message()),
+    )
+    .map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "gasPayerPrivate".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    transaction.sign(&vec![&payer], recent_blockhash);
+    config
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
+    Ok(OkResponse::BoolResponse(Json(BoolResponse {
+        success: true,
+    })))
+}
+
+/// Close a complete or cancelled job, rent is reclaimed by the gas payer
+#[allow(non_snake_case)]
+#[get("/close?<address>&<gasPayerPrivate>")]
+pub fn close_job(
+    address: String,
+    gasPayerPrivate: String,
+    config: State<Config>,
+) -> Result<OkResponse, ErrorResponse> {
+    let escrow_pub_key = Pubkey::from_str(&address).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    let payer = Keypair::from_base58_string(&gasPayerPrivate);
+
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_pub_key)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    let escrow_info = Escrow::unpack_from_slice(account_data.as_slice()).map_err(|e| {
+        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })?;
+
+    let authority =
+        EscrowProcessor::authority_id(&hmt_escrow::id(), &escrow_pub_key, escrow_info.bump_seed)
+            .map_err(|e| {
                 ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
                     error: e.to_string(),
                 }))
-            })?,
-        ],
+            })?;
+    let bond_accounts = bond_accounts(&escrow_pub_key, &escrow_info);
+    let mut transaction = Transaction::new_with_payer(
+        &[close_escrow(
+            &hmt_escrow::id(),
+            &escrow_pub_key,
+            &payer.pubkey(),
+            &escrow_info.token_account,
+            &authority,
+            &escrow_info.canceler_token_account,
+            &payer.pubkey(),
+            &spl_token::id(),
+            wrap_account(&escrow_pub_key, &escrow_info).as_ref(),
+            bond_accounts
+                .as_ref()
+                .map(|(vault, reputation_token, recording_token)| {
+                    (vault, reputation_token, recording_token)
+                }),
+        )
+        .map_err(|e| {
+            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?],
+        Some(&payer.pubkey()),
+    );
+    let (recent_blockhash, fee_calculator) =
+        config.rpc_client.get_recent_blockhash().map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    check_fee_payer_balance(
+        &config,
+        &payer.pubkey(),
+        fee_calculator.calculate_fee(&transaction.message()),
+    )
+    .map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "gasPayerPrivate".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    transaction.sign(&vec![&payer], recent_blockhash);
+    config
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
+    Ok(OkResponse::BoolResponse(Json(BoolResponse {
+        success: true,
+    })))
+}
+
+/// Complete a given job
+#[allow(non_snake_case)]
+#[get("/complete?<address>&<gasPayerPrivate>")]
+pub fn complete_job(
+    address: String,
+    gasPayerPrivate: String,
+    config: State<Config>,
+) -> Result<OkResponse, ErrorResponse> {
+    let escrow_pub_key = Pubkey::from_str(&address).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    let payer = Keypair::from_base58_string(&gasPayerPrivate);
+
+    let account_data = config
+        .rpc_client
+        .get_account_data(&escrow_pub_key)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    let escrow_info = Escrow::unpack_from_slice(account_data.as_slice()).map_err(|e| {
+        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+            error: e.to_string(),
+        }))
+    })?;
+    let bond_accounts = bond_accounts(&escrow_pub_key, &escrow_info);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[complete_escrow(
+            &hmt_escrow::id(),
+            &escrow_pub_key,
+            &payer.pubkey(),
+            bond_accounts
+                .as_ref()
+                .map(|(vault, reputation_token, recording_token)| {
+                    (vault, reputation_token, recording_token)
+                }),
+        )
+        .map_err(|e| {
+            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?],
         Some(&payer.pubkey()),
     );
 
@@ -696,7 +1046,8 @@ pub fn complete_job(
     })))
 }
 
-/// Store job results
+/// Store job intermediate results, earlier results are kept in the escrow results log
+/// holding up to `MAX_INTERMEDIATE_RESULTS` records
 #[post(
     "/storeIntermediateResults",
     format = "json",
@@ -732,13 +1083,10 @@ pub fn store_job_intermediate_results(
         }))
     })?;
 
-    let mut hasher = Sha1::new();
-    hasher.update(str_results_data);
-    let results_hash = DataHash::new_from_slice(&hasher.finalize()).map_err(|e| {
-        ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
-            error: e.to_string(),
-        }))
-    })?;
+    let results_hash = hash_data(
+        &store_results_args.hashAlgorithm,
+        str_results_data.as_bytes(),
+    )?;
 
     let results_url = DataUrl::from_str(&store_results_args.resultsUrl).map_err(|e| {
         ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -755,21 +1103,39 @@ pub fn store_job_intermediate_results(
                 error: e.to_string(),
             }))
         })?;
-    Escrow::unpack_from_slice(account_data.as_slice()).map_err(|e| {
+    let escrow_info = Escrow::unpack_from_slice(account_data.as_slice()).map_err(|e| {
         ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
             error: e.to_string(),
         }))
     })?;
 
+    // Results log is created with the first record
+    let (results_log, _) =
+        EscrowProcessor::find_results_log_address(&hmt_escrow::id(), &escrow_pub_key);
+    let results_log_balance = if config.rpc_client.get_account_data(&results_log).is_ok() {
+        0
+    } else {
+        config
+            .rpc_client
+            .get_minimum_balance_for_rent_exemption(ResultsLog::ACCOUNT_LEN)
+            .map_err(|e| {
+                ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                    error: e.to_string(),
+                }))
+            })?
+    };
+
     let mut transaction = Transaction::new_with_payer(
         &[
-            // Store results instruction
-            store_results(
+            // Store intermediate results instruction
+            store_intermediate_results(
                 &hmt_escrow::id(),
                 &escrow_pub_key,
                 &payer.pubkey(),
                 &results_url,
                 &results_hash,
+                &escrow_info.factory,
+                &results_log,
             )
             .map_err(|e| {
                 ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -789,7 +1155,7 @@ pub fn store_job_intermediate_results(
     check_fee_payer_balance(
         &config,
         &payer.pubkey(),
-        fee_calculator.calculate_fee(&transaction.message()),
+        results_log_balance + fee_calculator.calculate_fee(&transaction.message()),
     )
     .map_err(|e| {
         ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
@@ -906,6 +1272,7 @@ pub fn bulk_payout(
             &payer.pubkey(),
             total_amount,
             recipients.len() as u64,
+            &escrow_info.factory,
         )
         .map_err(|e| {
             ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
@@ -928,27 +1295,12 @@ pub fn bulk_payout(
         })),
     )?;
 
-    // Check escrow token account balance
-    let account_data = config
-        .rpc_client
-        .get_account_data(&escrow_info.token_account)
-        .map_err(|e| {
-            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
-                error: e.to_string(),
-            }))
-        })?;
-    let token_account_info: TokenAccount = TokenAccount::unpack_from_slice(account_data.as_slice())
-        .map_err(|e| {
-            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
-                error: e.to_string(),
-            }))
-        })?;
-
-    if total_amount > token_account_info.amount {
+    // Check escrow funded amount
+    if total_amount > escrow_info.funded_amount {
         return Err(ErrorResponse::InvalidParameterResponse(Json(
             InvalidParameter {
                 parameter_name: "address".to_string(),
-                error: "Escrow token account doesn't have enough tokens to do payout".to_string(),
+                error: "Escrow isn't funded enough to do payout".to_string(),
             },
         )));
     }
@@ -960,20 +1312,45 @@ pub fn bulk_payout(
                 }))
             })?;
 
-    let payout_instructions: Vec<Instruction> = recipients
+    // Payout IDs depend on the whole payouts list, so it can't be paid out twice
+    let payouts: Vec<(Pubkey, u64)> = recipients
         .iter()
-        .filter_map(|record| {
-            payout(
+        .map(|record| {
+            (
+                record.recipient,
+                spl_token::ui_amount_to_amount(record.amount, mint_info.decimals),
+            )
+        })
+        .collect();
+    let payout_receipts: Vec<(PayoutId, Pubkey)> = payout_ids(&payouts)
+        .into_iter()
+        .map(|payout_id| {
+            let (payout_receipt, _) = EscrowProcessor::find_payout_receipt_address(
+                &hmt_escrow::id(),
+                &escrow_pub_key,
+                &payout_id,
+            );
+            (payout_id, payout_receipt)
+        })
+        .collect();
+
+    let payout_instructions: Vec<Instruction> = payouts
+        .chunks(u8::MAX as usize)
+        .zip(payout_receipts.chunks(u8::MAX as usize))
+        .filter_map(|(payouts, payout_receipts)| {
+            batch_payout(
                 &hmt_escrow::id(),
                 &escrow_pub_key,
                 &payer.pubkey(),
                 &escrow_info.token_account,
                 &authority,
-                &record.recipient,
                 &reputation_oracle_token_account,
                 &recording_oracle_token_account,
                 &spl_token::id(),
-                spl_token::ui_amount_to_amount(record.amount, mint_info.decimals),
+                &escrow_info.factory,
+                payouts,
+                payout_receipts,
+                wrap_account(&escrow_pub_key, &escrow_info).as_ref(),
             )
             .ok()
         })
@@ -987,6 +1364,15 @@ pub fn bulk_payout(
 
     instructions.extend(payout_instructions);
 
+    let payout_receipt_balance = config
+        .rpc_client
+        .get_minimum_balance_for_rent_exemption(PayoutReceipt::LEN)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
     let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer.pubkey()));
     let (recent_blockhash, fee_calculator) =
         config.rpc_client.get_recent_blockhash().map_err(|e| {
@@ -997,7 +1383,8 @@ pub fn bulk_payout(
     check_fee_payer_balance(
         &config,
         &payer.pubkey(),
-        fee_calculator.calculate_fee(&transaction.message()),
+        payout_receipt_balance * payout_receipts.len() as u64
+            + fee_calculator.calculate_fee(&transaction.message()),
     )
     .map_err(|e| {
         ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
@@ -1025,18 +1412,125 @@ pub fn bulk_payout(
 #[post(
     "/addTrustedHandlers",
     format = "json",
-    data = "<_trusted_handlers_args>"
+    data = "<trusted_handlers_args>"
 )]
 pub fn add_trusted_handlers(
-    _trusted_handlers_args: Json<TrustedHandlersArgs>,
+    trusted_handlers_args: Json<TrustedHandlersArgs>,
+    config: State<Config>,
 ) -> Result<OkResponse, ErrorResponse> {
-    unimplemented!();
+    let payer = Keypair::from_base58_string(&trusted_handlers_args.gasPayerPrivate);
+    let escrow_pub_key = Pubkey::from_str(&trusted_handlers_args.address).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    let handlers = trusted_handlers_args
+        .handlers
+        .iter()
+        .map(|handler| Pubkey::from_str(handler))
+        .collect::<Result<Vec<Pubkey>, _>>()
+        .map_err(|e| {
+            ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+                parameter_name: "handlers".to_string(),
+                error: e.to_string(),
+            }))
+        })?;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[add_trusted_handlers_instruction(
+            &hmt_escrow::id(),
+            &escrow_pub_key,
+            &payer.pubkey(),
+            &handlers,
+        )
+        .map_err(|e| {
+            ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+                parameter_name: "handlers".to_string(),
+                error: e.to_string(),
+            }))
+        })?],
+        Some(&payer.pubkey()),
+    );
+    let (recent_blockhash, fee_calculator) =
+        config.rpc_client.get_recent_blockhash().map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+    check_fee_payer_balance(
+        &config,
+        &payer.pubkey(),
+        fee_calculator.calculate_fee(&transaction.message()),
+    )
+    .map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "gasPayerPrivate".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+    transaction.sign(&vec![&payer], recent_blockhash);
+    config
+        .rpc_client
+        .send_and_confirm_transaction(&transaction)
+        .map_err(|e| {
+            ErrorResponse::BadGatewayErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
+    Ok(OkResponse::BoolResponse(Json(BoolResponse {
+        success: true,
+    })))
 }
 
 /// Retrieve the intermediate results stored by the Recording Oracle
-#[get("/intermediateResults?<_address>")]
-pub fn get_intermediate_results(_address: String) -> Result<OkResponse, ErrorResponse> {
-    unimplemented!();
+#[get("/intermediateResults?<address>")]
+pub fn get_intermediate_results(
+    address: String,
+    config: State<Config>,
+) -> Result<OkResponse, ErrorResponse> {
+    let escrow_pub_key = Pubkey::from_str(&address).map_err(|e| {
+        ErrorResponse::InvalidParameterResponse(Json(InvalidParameter {
+            parameter_name: "address".to_string(),
+            error: e.to_string(),
+        }))
+    })?;
+
+    // No results log until the first intermediate results are stored
+    let (results_log, _) =
+        EscrowProcessor::find_results_log_address(&hmt_escrow::id(), &escrow_pub_key);
+    let account_data = match config.rpc_client.get_account(&results_log) {
+        Ok(account) if account.owner == hmt_escrow::id() => account.data,
+        _ => {
+            return Ok(OkResponse::IntermediateResultsResponse(Json(
+                IntermediateResults { results: vec![] },
+            )))
+        }
+    };
+    let results = ResultsLog::unpack_from_slice(account_data.as_slice())
+        .and_then(|log| log.unpack_results(account_data.as_slice()))
+        .map_err(|e| {
+            ErrorResponse::ServerErrorResponse(Json(ErrorMessage {
+                error: e.to_string(),
+            }))
+        })?;
+
+    let results = results
+        .iter()
+        .map(|result| {
+            Ok(ResultsRecord {
+                url: read_data_url(&config, &result.url)?,
+                hash: result.hash.to_string(),
+                timestamp: result.timestamp,
+                submitter: result.submitter.to_string(),
+            })
+        })
+        .collect::<Result<Vec<ResultsRecord>, ErrorResponse>>()?;
+
+    Ok(OkResponse::IntermediateResultsResponse(Json(
+        IntermediateResults { results },
+    )))
 }
 
 /// Retrieve the final results
@@ -1067,6 +1561,6 @@ pub fn get_final_results(
     })?;
 
     Ok(OkResponse::DataResponse(Json(Response {
-        data: escrow_info.final_results_url.to_string(),
+        data: read_data_url(&config, &escrow_info.final_results_url)?,
     })))
 }
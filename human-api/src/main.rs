@@ -16,13 +16,7 @@ pub use crate::job::*;
 pub use crate::manifest::*;
 
 use rocket::fairing::AdHoc;
-use solana_account_decoder::UiAccountEncoding;
 use solana_client::rpc_client::RpcClient;
-use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
-use solana_client::rpc_filter::Memcmp;
-use solana_client::rpc_filter::MemcmpEncodedBytes;
-use solana_client::rpc_filter::RpcFilterType;
-use solana_sdk::commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
 use std::str::FromStr;
 use rocket::{request::Request, response, response::Responder };
@@ -33,7 +27,6 @@ pub struct Config {
     pub token_decimals: u8,
     pub rpc_client: RpcClient,
     pub human_protocol_program: String,
-    pub data_offset_to_begin_match: usize,
     pub escrow_duration: u64,
 }
 
@@ -67,10 +60,6 @@ pub fn rocket() -> rocket::Rocket {
             let factory_version = rocket.config().get_int("factory_version").unwrap_or(1) as u8;
             let human_protocol_program =
                 String::from(rocket.config().get_str("human_protocol_program").unwrap());
-            let data_offset_to_begin_match = rocket
-                .config()
-                .get_int("data_offset_to_begin_match")
-                .unwrap() as usize;
             let escrow_duration = rocket.config().get_int("escrow_duration").unwrap() as u64;
             let token_decimals = rocket.config().get_int("token_decimals").unwrap() as u8;
             let config = Config {
@@ -78,7 +67,6 @@ pub fn rocket() -> rocket::Rocket {
                 token_decimals,
                 rpc_client,
                 human_protocol_program,
-                data_offset_to_begin_match,
                 escrow_duration,
             };
 
@@ -96,9 +84,14 @@ pub fn rocket() -> rocket::Rocket {
                 get_job_balance,
                 abort_job,
                 cancel_job,
+                close_job,
+                fund_job,
+                extend_job,
                 complete_job,
                 store_job_intermediate_results,
+                get_intermediate_results,
                 bulk_payout,
+                add_trusted_handlers,
                 get_final_results
             ],
         )
@@ -114,6 +107,7 @@ fn main() {
 #[cfg(test)]
 mod test {
     use super::*;
+    use hmt_escrow::state::{Factory, FactoryEntry, STAKE_BASIS_POINTS};
     use rocket::http::Status;
     use rocket::local::Client;
     use serde_json::{json, Value};
@@ -121,14 +115,13 @@ mod test {
     use solana_account_decoder::UiAccountEncoding;
     use solana_client::mock_sender::Mocks;
     use solana_client::rpc_request::RpcRequest;
-    use solana_client::rpc_response::RpcKeyedAccount;
-    use solana_sdk::{account::Account, pubkey::Pubkey};
+    use solana_client::rpc_response::{Response, RpcResponseContext};
+    use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey};
 
     pub const TEST_ENDPOINT: &str = "TestUrl";
     pub const FACTORY_VERSION: u8 = 1;
     pub const TOKEN_DECIMALS: u8 = 9;
     pub const HUMAN_PROTOCOL_PROGRAM: &str = "rK6j1hcHDTWerdrAS2w3BFifjHkPrRrnGYC7GRNwqKF";
-    pub const OFFSET: usize = 348;
     pub const DURATION: u64 = 3400;
 
     #[derive(Default)]
@@ -145,18 +138,66 @@ mod test {
             ));
         }
 
-        pub fn mock_get_program_addresses_with_filter(&mut self) -> Pubkey {
-            let p_k = Pubkey::new_unique();
-            let account = Account::new(10, 10, &Pubkey::new_unique());
-            let ui_account =
-                UiAccount::encode(&p_k, account, UiAccountEncoding::Base64, None, None);
-            let response_data = vec![RpcKeyedAccount {
-                pubkey: p_k.to_string(),
-                account: ui_account,
-            }];
-            self.mocks
-                .insert(RpcRequest::GetProgramAccounts, json!(response_data));
-            p_k
+        pub fn mock_get_factory_entries(&mut self, factory_pub_key: &Pubkey) -> Pubkey {
+            let escrow_pub_key = Pubkey::new_unique();
+
+            let mut factory_account = Account::new(10, Factory::LEN, &hmt_escrow::id());
+            Factory::pack(
+                Factory {
+                    version: FACTORY_VERSION,
+                    escrow_count: 1,
+                    admin: Pubkey::new_unique(),
+                    max_oracle_stake: STAKE_BASIS_POINTS,
+                    ..Default::default()
+                },
+                &mut factory_account.data,
+            )
+            .unwrap();
+            let ui_factory_account = UiAccount::encode(
+                factory_pub_key,
+                factory_account,
+                UiAccountEncoding::Base64,
+                None,
+                None,
+            );
+            self.mocks.insert(
+                RpcRequest::GetAccountInfo,
+                json!(Response {
+                    context: RpcResponseContext { slot: 1 },
+                    value: Some(ui_factory_account),
+                }),
+            );
+
+            let (factory_entry_pub_key, _) =
+                hmt_escrow::processor::Processor::find_factory_entry_address(
+                    &hmt_escrow::id(),
+                    factory_pub_key,
+                    0,
+                );
+            let mut factory_entry_account = Account::new(10, FactoryEntry::LEN, &hmt_escrow::id());
+            FactoryEntry::pack(
+                FactoryEntry {
+                    escrow: escrow_pub_key,
+                },
+                &mut factory_entry_account.data,
+            )
+            .unwrap();
+            let ui_factory_entry_account = UiAccount::encode(
+                &factory_entry_pub_key,
+                factory_entry_account,
+                UiAccountEncoding::Base64,
+                None,
+                None,
+            );
+            self.mocks.insert(
+                RpcRequest::GetMultipleAccounts,
+                json!(Response {
+                    context: RpcResponseContext { slot: 1 },
+                    value: vec![Some(ui_factory_entry_account)],
+                }),
+            );
+
+            escrow_pub_key
         }
     }
 
@@ -168,7 +209,6 @@ mod test {
                     token_decimals: TOKEN_DECIMALS,
                     rpc_client: mocked_client.solana_client.unwrap(),
                     human_protocol_program: String::from(HUMAN_PROTOCOL_PROGRAM),
-                    data_offset_to_begin_match: OFFSET,
                     escrow_duration: DURATION,
                 };
 
@@ -183,17 +223,19 @@ mod test {
     #[test]
     fn test_get_factory_addresses() {
         let mut rpc_client = MockedRpcClient::default();
-        let response_pub_key = rpc_client.mock_get_program_addresses_with_filter();
+        let factory_pub_key = Pubkey::new_unique();
+        let response_pub_key = rpc_client.mock_get_factory_entries(&factory_pub_key);
         rpc_client.create_rpc_client();
 
         let client = Client::new(test_rocket(rpc_client)).expect("valid rocket instance");
         let mut response = client
-            .get(format!("/factory?address={}", HUMAN_PROTOCOL_PROGRAM))
+            .get(format!("/factory?address={}", factory_pub_key))
             .dispatch();
         assert_eq!(response.status(), Status::Ok);
 
         let response_body: Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
         let expected_response = Value::Array(vec![Value::String(response_pub_key.to_string())]);
         assert_eq!(expected_response, response_body["jobs"]);
+        assert_eq!(Value::from(1), response_body["count"]);
     }
 }
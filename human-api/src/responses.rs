@@ -26,8 +26,28 @@ pub struct BoolResponse {
     pub success: bool,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResultsRecord {
+    /// Results URL
+    pub url: String,
+    /// Results hash in hex format
+    pub hash: String,
+    /// Time the results were stored
+    pub timestamp: i64,
+    /// Account which stored the results
+    pub submitter: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct IntermediateResults {
+    /// Intermediate results in the order they were stored
+    pub results: Vec<ResultsRecord>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FactoryJobs {
+    /// Total number of jobs deployed in the factory
+    pub count: u64,
     /// Response data
     pub jobs: Vec<String>,
 }
@@ -44,6 +64,8 @@ pub enum OkResponse {
     BoolResponse(Json<BoolResponse>),
     #[response(status = 200, content_type = "json")]
     FactoryJobsResponse(Json<FactoryJobs>),
+    #[response(status = 200, content_type = "json")]
+    IntermediateResultsResponse(Json<IntermediateResults>),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
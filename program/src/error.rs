@@ -46,6 +46,152 @@ pub enum EscrowError {
     /// Factory isn't initialized
     #[error("Factory isn't initialized")]
     FactoryNotInitialized,
+
+    /// Escrow duration exceeds the factory maximum
+    #[error("Duration out of bounds")]
+    DurationOutOfBounds,
+
+    /// Factory is paused by the administrator
+    #[error("Factory paused")]
+    FactoryPaused,
+
+    /// Merkle proof doesn't lead to the stored payouts root
+    #[error("Invalid Merkle proof")]
+    InvalidMerkleProof,
+
+    /// Payout was already claimed
+    #[error("Already claimed")]
+    AlreadyClaimed,
+
+    /// Payout with this ID was already sent
+    #[error("Duplicate payout")]
+    DuplicatePayout,
+
+    /// Escrow hasn't expired yet
+    #[error("Escrow not expired")]
+    EscrowNotExpired,
+
+    /// No room left for the trusted handlers
+    #[error("Too many trusted handlers")]
+    TooManyTrustedHandlers,
+
+    /// Only launcher and its trusted handlers can set up escrow
+    #[error("Unauthorized setup")]
+    UnauthorizedSetup,
+
+    /// Only recording oracle, launcher and its trusted handlers can store results
+    #[error("Unauthorized results storing")]
+    UnauthorizedStoreResults,
+
+    /// Only reputation oracle can store final amounts
+    #[error("Unauthorized final amounts storing")]
+    UnauthorizedStoreAmounts,
+
+    /// Only reputation oracle can send payouts or store the payouts root
+    #[error("Unauthorized payout")]
+    UnauthorizedPayout,
+
+    /// Only canceler, launcher and its trusted handlers can cancel escrow
+    #[error("Unauthorized cancel")]
+    UnauthorizedCancel,
+
+    /// Only reputation oracle, launcher and its trusted handlers can complete escrow
+    #[error("Unauthorized complete")]
+    UnauthorizedComplete,
+
+    /// Only canceler, launcher and its trusted handlers can close escrow
+    #[error("Unauthorized close")]
+    UnauthorizedClose,
+
+    /// Only launcher can fund escrow
+    #[error("Unauthorized fund")]
+    UnauthorizedFund,
+
+    /// Only launcher and canceler can extend escrow expiration
+    #[error("Unauthorized expiration extension")]
+    UnauthorizedExtend,
+
+    /// Only launcher and canceler can change trusted handlers
+    #[error("Unauthorized trusted handlers update")]
+    UnauthorizedHandlersUpdate,
+
+    /// No room left in the intermediate results log, it holds up to
+    /// `MAX_INTERMEDIATE_RESULTS` records
+    #[error("Too many intermediate results")]
+    TooManyIntermediateResults,
+
+    /// Hash algorithm isn't accepted for the new hashes
+    #[error("Unsupported hash algorithm")]
+    UnsupportedHashAlgorithm,
+
+    /// Escrow already uses the latest account layout
+    #[error("Escrow already migrated")]
+    AlreadyMigrated,
+
+    /// Oracle fee calculation overflow
+    #[error("Fee overflow")]
+    FeeOverflow,
+
+    /// Only oracles, launcher and its trusted handlers can settle fees
+    #[error("Unauthorized fee settlement")]
+    UnauthorizedSettleFees,
+
+    /// Deferred oracle fees must be settled first
+    #[error("Fees not settled")]
+    FeesNotSettled,
+
+    /// Payouts wait for the final results and the end of their dispute window
+    #[error("Dispute window open")]
+    DisputeWindowOpen,
+
+    /// Results can only be disputed during the dispute window after storing them
+    #[error("Dispute window closed")]
+    DisputeWindowClosed,
+
+    /// Only launcher can dispute the final results
+    #[error("Unauthorized dispute")]
+    UnauthorizedDispute,
+
+    /// Only launcher can slash the oracle bonds
+    #[error("Unauthorized slash")]
+    UnauthorizedSlash,
+
+    /// Oracle bonds can only be slashed after the final results are rejected
+    #[error("Slash not allowed")]
+    SlashNotAllowed,
+
+    /// Worker score is above the maximum score
+    #[error("Score out of bounds")]
+    ScoreOutOfBounds,
+
+    /// Vesting schedule must start before the cliff and end after it, before
+    /// the escrow expires
+    #[error("Invalid vesting schedule")]
+    InvalidVestingSchedule,
+
+    /// Vesting payouts must be withdrawn first
+    #[error("Vesting in progress")]
+    VestingInProgress,
+
+    /// Legacy escrow must be migrated to the latest layout first
+    #[error("Migration required")]
+    MigrationRequired,
+
+    /// Only oracles can release their bonds
+    #[error("Unauthorized release")]
+    UnauthorizedRelease,
+
+    /// Worker scores are only accepted from the oracles approved by the factory
+    #[error("Oracle not approved")]
+    OracleNotApproved,
+
+    /// Scored payout is below the minimum amount of the oracle approval
+    #[error("Scored payout too small")]
+    ScoredPayoutTooSmall,
+
+    /// Recipient already has a vesting schedule in the escrow
+    #[error("Vesting schedule exists")]
+    VestingScheduleExists,
 }
 
 const BASE_ERROR_CODE: u32 = 0x100;
@@ -0,0 +1,1223 @@
+//! Log events of the escrow state transitions
+//!
+//! Every successful instruction logs an event encoded as base64 binary, so
+//! that indexers can follow the escrows from the transaction logs. The binary
+//! starts with the encoding version followed by the event tag. Events of the
+//! failed transactions are logged as well and must be skipped by the indexers.
+
+use crate::merkle::MerkleHash;
+use crate::state::DataHash;
+use solana_program::{
+    msg, program_error::ProgramError, program_option::COption, program_pack::Pack, pubkey::Pubkey,
+};
+use std::convert::TryInto;
+
+/// Version of the event encoding
+pub const EVENT_VERSION: u8 = 1;
+
+/// Prefix of the log lines carrying the events
+pub const EVENT_LOG_PREFIX: &str = "hmt-escrow event: ";
+
+/// Prefix the runtime adds to the program log lines
+const PROGRAM_LOG_PREFIX: &str = "Program log: ";
+
+/// Escrow events
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowEvent {
+    /// Factory initialized
+    FactoryInitialized {
+        /// Factory account
+        factory: Pubkey,
+        /// Factory version
+        version: u8,
+    },
+
+    /// Factory administration transferred
+    FactoryAdminSet {
+        /// Factory account
+        factory: Pubkey,
+        /// New factory administrator
+        admin: Pubkey,
+    },
+
+    /// Factory version bumped
+    FactoryVersionSet {
+        /// Factory account
+        factory: Pubkey,
+        /// New factory version
+        version: u8,
+    },
+
+    /// Factory policy changed
+    FactoryPolicySet {
+        /// Factory account
+        factory: Pubkey,
+        /// Token mint escrows of the factory are restricted to, any mint if not set
+        token_mint: COption<Pubkey>,
+        /// Maximum escrow duration in seconds, unlimited if zero
+        max_duration: u64,
+        /// Maximum sum of the oracle stakes in basis points
+        max_oracle_stake: u16,
+    },
+
+    /// Factory paused or resumed
+    FactoryPaused {
+        /// Factory account
+        factory: Pubkey,
+        /// True if paused, false if resumed
+        paused: bool,
+    },
+
+    /// Reputation oracle approved by the factory or the approval revoked
+    OracleApproved {
+        /// Factory account
+        factory: Pubkey,
+        /// Reputation oracle
+        oracle: Pubkey,
+        /// Minimum payout amount the oracle can score, zero if revoked
+        min_scored_amount: u64,
+    },
+
+    /// Escrow created
+    EscrowCreated {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Factory account the escrow belongs to
+        factory: Pubkey,
+        /// Token mint of the escrow
+        token_mint: Pubkey,
+        /// Launcher account
+        launcher: Pubkey,
+        /// Canceler account
+        canceler: Pubkey,
+        /// Expiration timestamp
+        expires: i64,
+    },
+
+    /// Escrow set up and moved into the pending state
+    EscrowSetup {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Reputation oracle account
+        reputation_oracle: Pubkey,
+        /// Reputation oracle fee in basis points
+        reputation_oracle_stake: u16,
+        /// Recording oracle account
+        recording_oracle: Pubkey,
+        /// Recording oracle fee in basis points
+        recording_oracle_stake: u16,
+        /// Oracle fees are accrued until settled
+        defer_fees: bool,
+    },
+
+    /// Escrow funded
+    Funded {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Deposited amount
+        amount: u64,
+        /// Total amount deposited so far
+        funded_amount: u64,
+    },
+
+    /// Escrow expiration extended
+    ExpirationExtended {
+        /// Escrow account
+        escrow: Pubkey,
+        /// New expiration timestamp
+        expires: i64,
+    },
+
+    /// Trusted handlers added
+    TrustedHandlersAdded {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Added handlers
+        handlers: Vec<Pubkey>,
+    },
+
+    /// Trusted handlers removed
+    TrustedHandlersRemoved {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Removed handlers
+        handlers: Vec<Pubkey>,
+    },
+
+    /// Final results stored
+    ResultsStored {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Final results hash
+        results_hash: DataHash,
+    },
+
+    /// Intermediate results stored
+    IntermediateResultsStored {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Intermediate results hash
+        results_hash: DataHash,
+    },
+
+    /// Final amounts stored
+    AmountsStored {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Total amount of the payouts
+        total_amount: u64,
+        /// Total number of the recipients
+        total_recipients: u64,
+    },
+
+    /// Merkle root of the claimable payouts stored
+    PayoutsRootStored {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Merkle root of the payouts
+        payouts_root: MerkleHash,
+        /// Total amount of the payouts
+        total_amount: u64,
+        /// Total number of the recipients
+        total_recipients: u64,
+    },
+
+    /// Payout sent, either directly or claimed
+    PayoutMade {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Recipient token account
+        recipient: Pubkey,
+        /// Amount received by the recipient
+        amount: u64,
+        /// Reputation oracle fee, accrued if the fees are deferred
+        reputation_oracle_fee: u64,
+        /// Recording oracle fee, accrued if the fees are deferred
+        recording_oracle_fee: u64,
+    },
+
+    /// Deferred oracle fees settled
+    FeesSettled {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Amount sent to the reputation oracle
+        reputation_oracle_fee: u64,
+        /// Amount sent to the recording oracle
+        recording_oracle_fee: u64,
+    },
+
+    /// Escrow cancelled or reclaimed after expiration
+    Cancelled {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Amount returned to the canceler
+        amount: u64,
+    },
+
+    /// Escrow completed
+    Completed {
+        /// Escrow account
+        escrow: Pubkey,
+    },
+
+    /// Escrow and its token account closed
+    Closed {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Receiver of the reclaimed lamports
+        receiver: Pubkey,
+    },
+
+    /// URL data written
+    UrlDataWritten {
+        /// URL data account
+        url_data: Pubkey,
+        /// Offset of the written bytes
+        offset: u32,
+        /// Number of the written bytes
+        len: u32,
+    },
+
+    /// Escrow migrated to the latest layout
+    Migrated {
+        /// Escrow account
+        escrow: Pubkey,
+        /// New layout version
+        version: u8,
+    },
+
+    /// Final results disputed by the launcher
+    Disputed {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Hash of the disputed results
+        results_hash: DataHash,
+    },
+
+    /// Dispute resolved by the factory administrator
+    DisputeResolved {
+        /// Escrow account
+        escrow: Pubkey,
+        /// True if the results are accepted, false if they are dropped
+        accept_results: bool,
+    },
+
+    /// Oracle bonds locked in the bond vault
+    BondsLocked {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Reputation oracle bond
+        reputation_oracle_bond: u64,
+        /// Recording oracle bond
+        recording_oracle_bond: u64,
+    },
+
+    /// Oracle bonds slashed by the launcher
+    BondsSlashed {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Receiver token account of the slashed tokens
+        receiver: Pubkey,
+        /// Amount taken from the reputation oracle bond
+        reputation_oracle_amount: u64,
+        /// Amount taken from the recording oracle bond
+        recording_oracle_amount: u64,
+    },
+
+    /// Oracle bonds returned to the oracles
+    BondsReleased {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Amount returned to the reputation oracle
+        reputation_oracle_amount: u64,
+        /// Amount returned to the recording oracle
+        recording_oracle_amount: u64,
+    },
+
+    /// Worker reputation updated by the scored payout
+    WorkerReputationUpdated {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Worker wallet
+        worker: Pubkey,
+        /// Number of the escrows the worker received scored payouts from
+        jobs_completed: u64,
+        /// Total amount of tokens received
+        total_earned: u64,
+        /// Average score of the jobs in basis points
+        score: u16,
+    },
+
+    /// Payout vesting schedule created
+    VestingCreated {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Payment recipient
+        recipient: Pubkey,
+        /// Vested amount of tokens, excluding oracle fees
+        amount: u64,
+        /// Reputation oracle fee
+        reputation_oracle_fee: u64,
+        /// Recording oracle fee
+        recording_oracle_fee: u64,
+        /// Vesting start timestamp
+        start: i64,
+        /// Vesting cliff timestamp
+        cliff: i64,
+        /// Vesting end timestamp
+        end: i64,
+    },
+
+    /// Vested tokens withdrawn
+    VestingWithdrawn {
+        /// Escrow account
+        escrow: Pubkey,
+        /// Payment recipient
+        recipient: Pubkey,
+        /// Amount of tokens sent to the recipient
+        amount: u64,
+        /// Unvested amount of tokens released back to the cancelled escrow
+        released: u64,
+    },
+}
+
+impl EscrowEvent {
+    /// Logs the event
+    pub fn emit(&self) {
+        msg!("{}{}", EVENT_LOG_PREFIX, base64::encode(self.pack()));
+    }
+
+    /// Decodes the event from the log line, returns `None` if the line isn't an event
+    pub fn decode_log(log: &str) -> Option<Result<Self, ProgramError>> {
+        let log = log.strip_prefix(PROGRAM_LOG_PREFIX).unwrap_or(log);
+        let data = log.strip_prefix(EVENT_LOG_PREFIX)?;
+        Some(
+            base64::decode(data)
+                .map_err(|_| ProgramError::InvalidArgument)
+                .and_then(|data| Self::unpack(&data)),
+        )
+    }
+
+    /// Decodes all the events logged by the program in the transaction logs.
+    /// Lines logged while another program is running are skipped, so other
+    /// programs can't pass off their logs as escrow events.
+    pub fn decode_logs(program_id: &Pubkey, logs: &[String]) -> Result<Vec<Self>, ProgramError> {
+        let program = program_id.to_string();
+        let mut invoked: Vec<&str> = vec![];
+        let mut events = vec![];
+        for log in logs {
+            let mut words = log.split(' ');
+            match (words.next(), words.next(), words.next()) {
+                (Some("Program"), Some(id), Some("invoke")) => invoked.push(id),
+                (Some("Program"), Some(_), Some("success"))
+                | (Some("Program"), Some(_), Some("failed:")) => {
+                    invoked.pop();
+                }
+                _ if invoked.last() == Some(&program.as_str()) => {
+                    if let Some(event) = Self::decode_log(log) {
+                        events.push(event?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(events)
+    }
+
+    /// Unpacks a byte buffer into an [EscrowEvent](enum.EscrowEvent.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&version, rest) = input.split_first().ok_or(ProgramError::InvalidArgument)?;
+        if version != EVENT_VERSION {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (&tag, rest) = rest.split_first().ok_or(ProgramError::InvalidArgument)?;
+        let (escrow, rest) = Self::unpack_pubkey(rest)?;
+        Ok(match tag {
+            1 => {
+                let (version, _rest) = Self::unpack_u8(rest)?;
+                Self::FactoryInitialized {
+                    factory: escrow,
+                    version,
+                }
+            }
+            2 => {
+                let (admin, _rest) = Self::unpack_pubkey(rest)?;
+                Self::FactoryAdminSet {
+                    factory: escrow,
+                    admin,
+                }
+            }
+            3 => {
+                let (version, _rest) = Self::unpack_u8(rest)?;
+                Self::FactoryVersionSet {
+                    factory: escrow,
+                    version,
+                }
+            }
+            4 => {
+                let (token_mint, rest) = Self::unpack_pubkey_option(rest)?;
+                let (max_duration, rest) = Self::unpack_u64(rest)?;
+                let (max_oracle_stake, _rest) = Self::unpack_u16(rest)?;
+                Self::FactoryPolicySet {
+                    factory: escrow,
+                    token_mint,
+                    max_duration,
+                    max_oracle_stake,
+                }
+            }
+            5 => {
+                let (paused, _rest) = Self::unpack_bool(rest)?;
+                Self::FactoryPaused {
+                    factory: escrow,
+                    paused,
+                }
+            }
+            6 => {
+                let (factory, rest) = Self::unpack_pubkey(rest)?;
+                let (token_mint, rest) = Self::unpack_pubkey(rest)?;
+                let (launcher, rest) = Self::unpack_pubkey(rest)?;
+                let (canceler, rest) = Self::unpack_pubkey(rest)?;
+                let (expires, _rest) = Self::unpack_u64(rest)?;
+                Self::EscrowCreated {
+                    escrow,
+                    factory,
+                    token_mint,
+                    launcher,
+                    canceler,
+                    expires: expires as i64,
+                }
+            }
+            7 => {
+                let (reputation_oracle, rest) = Self::unpack_pubkey(rest)?;
+                let (reputation_oracle_stake, rest) = Self::unpack_u16(rest)?;
+                let (recording_oracle, rest) = Self::unpack_pubkey(rest)?;
+                let (recording_oracle_stake, rest) = Self::unpack_u16(rest)?;
+                let (defer_fees, _rest) = Self::unpack_bool(rest)?;
+                Self::EscrowSetup {
+                    escrow,
+                    reputation_oracle,
+                    reputation_oracle_stake,
+                    recording_oracle,
+                    recording_oracle_stake,
+                    defer_fees,
+                }
+            }
+            8 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (funded_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::Funded {
+                    escrow,
+                    amount,
+                    funded_amount,
+                }
+            }
+            9 => {
+                let (expires, _rest) = Self::unpack_u64(rest)?;
+                Self::ExpirationExtended {
+                    escrow,
+                    expires: expires as i64,
+                }
+            }
+            10 => {
+                let (handlers, _rest) = Self::unpack_pubkeys(rest)?;
+                Self::TrustedHandlersAdded { escrow, handlers }
+            }
+            11 => {
+                let (handlers, _rest) = Self::unpack_pubkeys(rest)?;
+                Self::TrustedHandlersRemoved { escrow, handlers }
+            }
+            12 => {
+                let (results_hash, _rest) = Self::unpack_hash(rest)?;
+                Self::ResultsStored {
+                    escrow,
+                    results_hash,
+                }
+            }
+            13 => {
+                let (results_hash, _rest) = Self::unpack_hash(rest)?;
+                Self::IntermediateResultsStored {
+                    escrow,
+                    results_hash,
+                }
+            }
+            14 => {
+                let (total_amount, rest) = Self::unpack_u64(rest)?;
+                let (total_recipients, _rest) = Self::unpack_u64(rest)?;
+                Self::AmountsStored {
+                    escrow,
+                    total_amount,
+                    total_recipients,
+                }
+            }
+            15 => {
+                let (payouts_root, rest) = Self::unpack_bytes(rest, 32)?;
+                let (total_amount, rest) = Self::unpack_u64(rest)?;
+                let (total_recipients, _rest) = Self::unpack_u64(rest)?;
+                Self::PayoutsRootStored {
+                    escrow,
+                    payouts_root: payouts_root
+                        .try_into()
+                        .or(Err(ProgramError::InvalidArgument))?,
+                    total_amount,
+                    total_recipients,
+                }
+            }
+            16 => {
+                let (recipient, rest) = Self::unpack_pubkey(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (reputation_oracle_fee, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_fee, _rest) = Self::unpack_u64(rest)?;
+                Self::PayoutMade {
+                    escrow,
+                    recipient,
+                    amount,
+                    reputation_oracle_fee,
+                    recording_oracle_fee,
+                }
+            }
+            17 => {
+                let (reputation_oracle_fee, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_fee, _rest) = Self::unpack_u64(rest)?;
+                Self::FeesSettled {
+                    escrow,
+                    reputation_oracle_fee,
+                    recording_oracle_fee,
+                }
+            }
+            18 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::Cancelled { escrow, amount }
+            }
+            19 => Self::Completed { escrow },
+            20 => {
+                let (receiver, _rest) = Self::unpack_pubkey(rest)?;
+                Self::Closed { escrow, receiver }
+            }
+            21 => {
+                let (offset, rest) = Self::unpack_u32(rest)?;
+                let (len, _rest) = Self::unpack_u32(rest)?;
+                Self::UrlDataWritten {
+                    url_data: escrow,
+                    offset,
+                    len,
+                }
+            }
+            22 => {
+                let (version, _rest) = Self::unpack_u8(rest)?;
+                Self::Migrated { escrow, version }
+            }
+            23 => {
+                let (results_hash, _rest) = Self::unpack_hash(rest)?;
+                Self::Disputed {
+                    escrow,
+                    results_hash,
+                }
+            }
+            24 => {
+                let (accept_results, _rest) = Self::unpack_bool(rest)?;
+                Self::DisputeResolved {
+                    escrow,
+                    accept_results,
+                }
+            }
+            25 => {
+                let (reputation_oracle_bond, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_bond, _rest) = Self::unpack_u64(rest)?;
+                Self::BondsLocked {
+                    escrow,
+                    reputation_oracle_bond,
+                    recording_oracle_bond,
+                }
+            }
+            26 => {
+                let (receiver, rest) = Self::unpack_pubkey(rest)?;
+                let (reputation_oracle_amount, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::BondsSlashed {
+                    escrow,
+                    receiver,
+                    reputation_oracle_amount,
+                    recording_oracle_amount,
+                }
+            }
+            27 => {
+                let (reputation_oracle_amount, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::BondsReleased {
+                    escrow,
+                    reputation_oracle_amount,
+                    recording_oracle_amount,
+                }
+            }
+            28 => {
+                let (worker, rest) = Self::unpack_pubkey(rest)?;
+                let (jobs_completed, rest) = Self::unpack_u64(rest)?;
+                let (total_earned, rest) = Self::unpack_u64(rest)?;
+                let (score, _rest) = Self::unpack_u16(rest)?;
+                Self::WorkerReputationUpdated {
+                    escrow,
+                    worker,
+                    jobs_completed,
+                    total_earned,
+                    score,
+                }
+            }
+            29 => {
+                let (recipient, rest) = Self::unpack_pubkey(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (reputation_oracle_fee, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_fee, rest) = Self::unpack_u64(rest)?;
+                let (start, rest) = Self::unpack_u64(rest)?;
+                let (cliff, rest) = Self::unpack_u64(rest)?;
+                let (end, _rest) = Self::unpack_u64(rest)?;
+                Self::VestingCreated {
+                    escrow,
+                    recipient,
+                    amount,
+                    reputation_oracle_fee,
+                    recording_oracle_fee,
+                    start: start as i64,
+                    cliff: cliff as i64,
+                    end: end as i64,
+                }
+            }
+            30 => {
+                let (recipient, rest) = Self::unpack_pubkey(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (released, _rest) = Self::unpack_u64(rest)?;
+                Self::VestingWithdrawn {
+                    escrow,
+                    recipient,
+                    amount,
+                    released,
+                }
+            }
+            31 => {
+                let (oracle, rest) = Self::unpack_pubkey(rest)?;
+                let (min_scored_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::OracleApproved {
+                    factory: escrow,
+                    oracle,
+                    min_scored_amount,
+                }
+            }
+            _ => return Err(ProgramError::InvalidArgument),
+        })
+    }
+
+    /// Packs an [EscrowEvent](enum.EscrowEvent.html) into a byte buffer.
+    /// Every event starts with the account it's about, the escrow, the factory
+    /// or the URL data account.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = vec![EVENT_VERSION];
+        match self {
+            Self::FactoryInitialized { factory, version } => {
+                buf.push(1);
+                buf.extend(factory.as_ref());
+                buf.push(*version);
+            }
+            Self::FactoryAdminSet { factory, admin } => {
+                buf.push(2);
+                buf.extend(factory.as_ref());
+                buf.extend(admin.as_ref());
+            }
+            Self::FactoryVersionSet { factory, version } => {
+                buf.push(3);
+                buf.extend(factory.as_ref());
+                buf.push(*version);
+            }
+            Self::FactoryPolicySet {
+                factory,
+                token_mint,
+                max_duration,
+                max_oracle_stake,
+            } => {
+                buf.push(4);
+                buf.extend(factory.as_ref());
+                Self::pack_pubkey_option(token_mint, &mut buf);
+                buf.extend(&max_duration.to_le_bytes());
+                buf.extend(&max_oracle_stake.to_le_bytes());
+            }
+            Self::FactoryPaused { factory, paused } => {
+                buf.push(5);
+                buf.extend(factory.as_ref());
+                buf.push(*paused as u8);
+            }
+            Self::EscrowCreated {
+                escrow,
+                factory,
+                token_mint,
+                launcher,
+                canceler,
+                expires,
+            } => {
+                buf.push(6);
+                buf.extend(escrow.as_ref());
+                buf.extend(factory.as_ref());
+                buf.extend(token_mint.as_ref());
+                buf.extend(launcher.as_ref());
+                buf.extend(canceler.as_ref());
+                buf.extend(&expires.to_le_bytes());
+            }
+            Self::EscrowSetup {
+                escrow,
+                reputation_oracle,
+                reputation_oracle_stake,
+                recording_oracle,
+                recording_oracle_stake,
+                defer_fees,
+            } => {
+                buf.push(7);
+                buf.extend(escrow.as_ref());
+                buf.extend(reputation_oracle.as_ref());
+                buf.extend(&reputation_oracle_stake.to_le_bytes());
+                buf.extend(recording_oracle.as_ref());
+                buf.extend(&recording_oracle_stake.to_le_bytes());
+                buf.push(*defer_fees as u8);
+            }
+            Self::Funded {
+                escrow,
+                amount,
+                funded_amount,
+            } => {
+                buf.push(8);
+                buf.extend(escrow.as_ref());
+                buf.extend(&amount.to_le_bytes());
+                buf.extend(&funded_amount.to_le_bytes());
+            }
+            Self::ExpirationExtended { escrow, expires } => {
+                buf.push(9);
+                buf.extend(escrow.as_ref());
+                buf.extend(&expires.to_le_bytes());
+            }
+            Self::TrustedHandlersAdded { escrow, handlers } => {
+                buf.push(10);
+                buf.extend(escrow.as_ref());
+                Self::pack_pubkeys(handlers, &mut buf);
+            }
+            Self::TrustedHandlersRemoved { escrow, handlers } => {
+                buf.push(11);
+                buf.extend(escrow.as_ref());
+                Self::pack_pubkeys(handlers, &mut buf);
+            }
+            Self::ResultsStored {
+                escrow,
+                results_hash,
+            } => {
+                buf.push(12);
+                buf.extend(escrow.as_ref());
+                Self::pack_hash(results_hash, &mut buf);
+            }
+            Self::IntermediateResultsStored {
+                escrow,
+                results_hash,
+            } => {
+                buf.push(13);
+                buf.extend(escrow.as_ref());
+                Self::pack_hash(results_hash, &mut buf);
+            }
+            Self::AmountsStored {
+                escrow,
+                total_amount,
+                total_recipients,
+            } => {
+                buf.push(14);
+                buf.extend(escrow.as_ref());
+                buf.extend(&total_amount.to_le_bytes());
+                buf.extend(&total_recipients.to_le_bytes());
+            }
+            Self::PayoutsRootStored {
+                escrow,
+                payouts_root,
+                total_amount,
+                total_recipients,
+            } => {
+                buf.push(15);
+                buf.extend(escrow.as_ref());
+                buf.extend(payouts_root);
+                buf.extend(&total_amount.to_le_bytes());
+                buf.extend(&total_recipients.to_le_bytes());
+            }
+            Self::PayoutMade {
+                escrow,
+                recipient,
+                amount,
+                reputation_oracle_fee,
+                recording_oracle_fee,
+            } => {
+                buf.push(16);
+                buf.extend(escrow.as_ref());
+                buf.extend(recipient.as_ref());
+                buf.extend(&amount.to_le_bytes());
+                buf.extend(&reputation_oracle_fee.to_le_bytes());
+                buf.extend(&recording_oracle_fee.to_le_bytes());
+            }
+            Self::FeesSettled {
+                escrow,
+                reputation_oracle_fee,
+                recording_oracle_fee,
+            } => {
+                buf.push(17);
+                buf.extend(escrow.as_ref());
+                buf.extend(&reputation_oracle_fee.to_le_bytes());
+                buf.extend(&recording_oracle_fee.to_le_bytes());
+            }
+            Self::Cancelled { escrow, amount } => {
+                buf.push(18);
+                buf.extend(escrow.as_ref());
+                buf.extend(&amount.to_le_bytes());
+            }
+            Self::Completed { escrow } => {
+                buf.push(19);
+                buf.extend(escrow.as_ref());
+            }
+            Self::Closed { escrow, receiver } => {
+                buf.push(20);
+                buf.extend(escrow.as_ref());
+                buf.extend(receiver.as_ref());
+            }
+            Self::UrlDataWritten {
+                url_data,
+                offset,
+                len,
+            } => {
+                buf.push(21);
+                buf.extend(url_data.as_ref());
+                buf.extend(&offset.to_le_bytes());
+                buf.extend(&len.to_le_bytes());
+            }
+            Self::Migrated { escrow, version } => {
+                buf.push(22);
+                buf.extend(escrow.as_ref());
+                buf.push(*version);
+            }
+            Self::Disputed {
+                escrow,
+                results_hash,
+            } => {
+                buf.push(23);
+                buf.extend(escrow.as_ref());
+                Self::pack_hash(results_hash, &mut buf);
+            }
+            Self::DisputeResolved {
+                escrow,
+                accept_results,
+            } => {
+                buf.push(24);
+                buf.extend(escrow.as_ref());
+                buf.push(*accept_results as u8);
+            }
+            Self::BondsLocked {
+                escrow,
+                reputation_oracle_bond,
+                recording_oracle_bond,
+            } => {
+                buf.push(25);
+                buf.extend(escrow.as_ref());
+                buf.extend(&reputation_oracle_bond.to_le_bytes());
+                buf.extend(&recording_oracle_bond.to_le_bytes());
+            }
+            Self::BondsSlashed {
+                escrow,
+                receiver,
+                reputation_oracle_amount,
+                recording_oracle_amount,
+            } => {
+                buf.push(26);
+                buf.extend(escrow.as_ref());
+                buf.extend(receiver.as_ref());
+                buf.extend(&reputation_oracle_amount.to_le_bytes());
+                buf.extend(&recording_oracle_amount.to_le_bytes());
+            }
+            Self::BondsReleased {
+                escrow,
+                reputation_oracle_amount,
+                recording_oracle_amount,
+            } => {
+                buf.push(27);
+                buf.extend(escrow.as_ref());
+                buf.extend(&reputation_oracle_amount.to_le_bytes());
+                buf.extend(&recording_oracle_amount.to_le_bytes());
+            }
+            Self::WorkerReputationUpdated {
+                escrow,
+                worker,
+                jobs_completed,
+                total_earned,
+                score,
+            } => {
+                buf.push(28);
+                buf.extend(escrow.as_ref());
+                buf.extend(worker.as_ref());
+                buf.extend(&jobs_completed.to_le_bytes());
+                buf.extend(&total_earned.to_le_bytes());
+                buf.extend(&score.to_le_bytes());
+            }
+            Self::VestingCreated {
+                escrow,
+                recipient,
+                amount,
+                reputation_oracle_fee,
+                recording_oracle_fee,
+                start,
+                cliff,
+                end,
+            } => {
+                buf.push(29);
+                buf.extend(escrow.as_ref());
+                buf.extend(recipient.as_ref());
+                buf.extend(&amount.to_le_bytes());
+                buf.extend(&reputation_oracle_fee.to_le_bytes());
+                buf.extend(&recording_oracle_fee.to_le_bytes());
+                buf.extend(&start.to_le_bytes());
+                buf.extend(&cliff.to_le_bytes());
+                buf.extend(&end.to_le_bytes());
+            }
+            Self::VestingWithdrawn {
+                escrow,
+                recipient,
+                amount,
+                released,
+            } => {
+                buf.push(30);
+                buf.extend(escrow.as_ref());
+                buf.extend(recipient.as_ref());
+                buf.extend(&amount.to_le_bytes());
+                buf.extend(&released.to_le_bytes());
+            }
+            Self::OracleApproved {
+                factory,
+                oracle,
+                min_scored_amount,
+            } => {
+                buf.push(31);
+                buf.extend(factory.as_ref());
+                buf.extend(oracle.as_ref());
+                buf.extend(&min_scored_amount.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
+        let (&value, rest) = input.split_first().ok_or(ProgramError::InvalidArgument)?;
+        Ok((value, rest))
+    }
+
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        match input.split_first() {
+            Option::Some((&0, rest)) => Ok((false, rest)),
+            Option::Some((&1, rest)) => Ok((true, rest)),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+
+    fn unpack_bytes(input: &[u8], len: usize) -> Result<(&[u8], &[u8]), ProgramError> {
+        if input.len() >= len {
+            Ok(input.split_at(len))
+        } else {
+            Err(ProgramError::InvalidArgument)
+        }
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        let (bytes, rest) = Self::unpack_bytes(input, 2)?;
+        Ok((
+            u16::from_le_bytes(bytes.try_into().or(Err(ProgramError::InvalidArgument))?),
+            rest,
+        ))
+    }
+
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        let (bytes, rest) = Self::unpack_bytes(input, 4)?;
+        Ok((
+            u32::from_le_bytes(bytes.try_into().or(Err(ProgramError::InvalidArgument))?),
+            rest,
+        ))
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        let (bytes, rest) = Self::unpack_bytes(input, 8)?;
+        Ok((
+            u64::from_le_bytes(bytes.try_into().or(Err(ProgramError::InvalidArgument))?),
+            rest,
+        ))
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        let (bytes, rest) = Self::unpack_bytes(input, 32)?;
+        Ok((Pubkey::new(bytes), rest))
+    }
+
+    fn unpack_pubkey_option(input: &[u8]) -> Result<(COption<Pubkey>, &[u8]), ProgramError> {
+        match input.split_first() {
+            Option::Some((&0, rest)) => Ok((COption::None, rest)),
+            Option::Some((&1, rest)) => {
+                let (key, rest) = Self::unpack_pubkey(rest)?;
+                Ok((COption::Some(key), rest))
+            }
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+
+    fn pack_pubkey_option(value: &COption<Pubkey>, buf: &mut Vec<u8>) {
+        match *value {
+            COption::Some(ref key) => {
+                buf.push(1);
+                buf.extend(key.as_ref());
+            }
+            COption::None => buf.push(0),
+        }
+    }
+
+    fn unpack_pubkeys(input: &[u8]) -> Result<(Vec<Pubkey>, &[u8]), ProgramError> {
+        let (count, mut rest) = Self::unpack_u8(input)?;
+        let mut keys = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (key, next) = Self::unpack_pubkey(rest)?;
+            keys.push(key);
+            rest = next;
+        }
+        Ok((keys, rest))
+    }
+
+    fn pack_pubkeys(keys: &[Pubkey], buf: &mut Vec<u8>) {
+        buf.push(keys.len() as u8);
+        for key in keys {
+            buf.extend(key.as_ref());
+        }
+    }
+
+    fn unpack_hash(input: &[u8]) -> Result<(DataHash, &[u8]), ProgramError> {
+        let (bytes, rest) = Self::unpack_bytes(input, DataHash::LEN)?;
+        Ok((DataHash::unpack_from_slice(bytes)?, rest))
+    }
+
+    fn pack_hash(hash: &DataHash, buf: &mut Vec<u8>) {
+        let mut bytes = [0; DataHash::LEN];
+        hash.pack_into_slice(&mut bytes);
+        buf.extend(&bytes);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::HashAlgorithm;
+
+    #[test]
+    fn test_event_packing() {
+        let escrow = Pubkey::new_unique();
+        let events = vec![
+            EscrowEvent::FactoryPolicySet {
+                factory: Pubkey::new_unique(),
+                token_mint: COption::Some(Pubkey::new_unique()),
+                max_duration: 86400,
+                max_oracle_stake: 5000,
+            },
+            EscrowEvent::FactoryPolicySet {
+                factory: Pubkey::new_unique(),
+                token_mint: COption::None,
+                max_duration: 86400,
+                max_oracle_stake: 5000,
+            },
+            EscrowEvent::OracleApproved {
+                factory: Pubkey::new_unique(),
+                oracle: Pubkey::new_unique(),
+                min_scored_amount: 1000000,
+            },
+            EscrowEvent::EscrowCreated {
+                escrow,
+                factory: Pubkey::new_unique(),
+                token_mint: Pubkey::new_unique(),
+                launcher: Pubkey::new_unique(),
+                canceler: Pubkey::new_unique(),
+                expires: 1612137600,
+            },
+            EscrowEvent::TrustedHandlersAdded {
+                escrow,
+                handlers: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            },
+            EscrowEvent::ResultsStored {
+                escrow,
+                results_hash: DataHash::new(HashAlgorithm::Keccak256, &[7; 32]).unwrap(),
+            },
+            EscrowEvent::PayoutMade {
+                escrow,
+                recipient: Pubkey::new_unique(),
+                amount: 900,
+                reputation_oracle_fee: 40,
+                recording_oracle_fee: 60,
+            },
+            EscrowEvent::Disputed {
+                escrow,
+                results_hash: DataHash::new(HashAlgorithm::Sha256, &[8; 32]).unwrap(),
+            },
+            EscrowEvent::DisputeResolved {
+                escrow,
+                accept_results: true,
+            },
+            EscrowEvent::BondsLocked {
+                escrow,
+                reputation_oracle_bond: 500,
+                recording_oracle_bond: 700,
+            },
+            EscrowEvent::BondsSlashed {
+                escrow,
+                receiver: Pubkey::new_unique(),
+                reputation_oracle_amount: 0,
+                recording_oracle_amount: 700,
+            },
+            EscrowEvent::BondsReleased {
+                escrow,
+                reputation_oracle_amount: 500,
+                recording_oracle_amount: 0,
+            },
+            EscrowEvent::WorkerReputationUpdated {
+                escrow,
+                worker: Pubkey::new_unique(),
+                jobs_completed: 3,
+                total_earned: 2700,
+                score: 9500,
+            },
+            EscrowEvent::VestingCreated {
+                escrow,
+                recipient: Pubkey::new_unique(),
+                amount: 900,
+                reputation_oracle_fee: 40,
+                recording_oracle_fee: 60,
+                start: 1612137600,
+                cliff: 1612224000,
+                end: 1614556800,
+            },
+            EscrowEvent::VestingWithdrawn {
+                escrow,
+                recipient: Pubkey::new_unique(),
+                amount: 300,
+                released: 600,
+            },
+            EscrowEvent::Completed { escrow },
+        ];
+        for event in events {
+            let packed = event.pack();
+            assert_eq!(packed[0], EVENT_VERSION);
+            assert_eq!(EscrowEvent::unpack(&packed).unwrap(), event);
+            assert!(EscrowEvent::unpack(&packed[..packed.len() - 1]).is_err());
+        }
+
+        let packed = EscrowEvent::Completed { escrow }.pack();
+        let mut expect = vec![EVENT_VERSION, 19];
+        expect.extend(escrow.as_ref());
+        assert_eq!(packed, expect);
+
+        // Unknown version or tag
+        let mut unknown = packed.clone();
+        unknown[0] = EVENT_VERSION + 1;
+        assert!(EscrowEvent::unpack(&unknown).is_err());
+        let mut unknown = packed;
+        unknown[1] = 0;
+        assert!(EscrowEvent::unpack(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_event_logs() {
+        let program_id = crate::id();
+        let other_program_id = Pubkey::new_unique();
+        let escrow = Pubkey::new_unique();
+        let event = EscrowEvent::Cancelled {
+            escrow,
+            amount: 1000,
+        };
+        let line = format!(
+            "{}{}{}",
+            PROGRAM_LOG_PREFIX,
+            EVENT_LOG_PREFIX,
+            base64::encode(event.pack())
+        );
+
+        assert_eq!(EscrowEvent::decode_log(&line), Some(Ok(event.clone())));
+        assert_eq!(
+            EscrowEvent::decode_log("Program log: Instruction: Cancel"),
+            None
+        );
+        assert!(
+            EscrowEvent::decode_log("Program log: hmt-escrow event: !!!")
+                .unwrap()
+                .is_err()
+        );
+
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Instruction: Cancel".to_string(),
+            format!("Program {} invoke [2]", other_program_id),
+            line.clone(),
+            format!("Program {} success", other_program_id),
+            line,
+            format!("Program {} success", program_id),
+        ];
+        assert_eq!(
+            EscrowEvent::decode_logs(&program_id, &logs).unwrap(),
+            vec![event]
+        );
+    }
+}
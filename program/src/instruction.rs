@@ -1,14 +1,22 @@
 //! Instruction types
 #![allow(clippy::too_many_arguments)]
 
-use crate::state::{DataHash, DataUrl, URL_LEN};
+use crate::merkle::MerkleHash;
+use crate::state::{DataHash, DataUrl, HashAlgorithm, MAX_TRUSTED_HANDLERS, URL_ACCOUNT_LEN};
+use num_enum::TryFromPrimitive;
 use solana_program::{
+    clock::UnixTimestamp,
+    hash::{hash, hashv},
     instruction::{AccountMeta, Instruction},
     program_error::ProgramError,
+    program_option::COption,
     pubkey::Pubkey,
-    sysvar,
+    system_program, sysvar,
 };
 use std::{convert::TryInto, mem::size_of};
+
+/// Payout ID, a payout with the given ID can be sent only once
+pub type PayoutId = [u8; 32];
 /// Instructions supported by the escrow program
 #[repr(C)]
 #[derive(Clone, Debug, PartialEq)]
@@ -16,8 +24,11 @@ pub enum EscrowInstruction {
     /// Initialize a new Factory.
     ///
     /// This instruction receives new account and initialize new Factory
+    /// with no token mint restriction, unlimited duration and oracle stakes
+    /// limited to 100 percents. Must be signed by the factory administrator.
     ///
     /// 0. [w] Account for the Factory
+    /// 1. [s] Factory administrator
     FactoryInitialize {
         /// Factory's version
         version: u8,
@@ -26,19 +37,34 @@ pub enum EscrowInstruction {
     /// Initializes a new escrow.
     ///
     /// This instructions receives new uninitialized account and initializes
-    /// new escrow on it. No signers is required, this instruction should called
-    /// right after escrow account creation.
+    /// new escrow on it. Only the payer is required to sign, this instruction
+    /// should be called right after escrow account creation.
+    ///
+    /// The escrow is registered in the factory under the next free index,
+    /// the factory entry account is created by this instruction.
+    ///
+    /// Escrow with the native mint holds wrapped SOL, its canceler may receive
+    /// funds to a system account. The payer funds the escrow authority reserve
+    /// paying for the rent of the wrap account used to unwrap SOL.
     ///
     /// Accounts expected by this instruction:
     ///
     /// 0. [w] Account for the new escrow
-    /// 1. [] Factory account this Escrow belongs to
+    /// 1. [w] Factory account this Escrow belongs to
     /// 2. [] Clock sysvar
     /// 3. [] Mint account for token managed by this escrow
     /// 4. [] Token account where escrow funds will be stored
     /// 5. [] Escrow launcher account
     /// 6. [] Escrow canceler account
     /// 7. [] Canceler's token account to receive escrow funds
+    /// 8. [w] Factory entry account, derived from the factory and its escrow count
+    /// 9. [ws] Payer for the factory entry account
+    /// 10. [] Rent sysvar
+    /// 11. [] System program
+    ///
+    /// For the native mint escrow:
+    ///
+    /// 12. [w] Escrow signing authority
     Initialize {
         /// Escrow duration in seconds, escrow can only be canceled after its duration expires
         duration: u64,
@@ -46,42 +72,71 @@ pub enum EscrowInstruction {
 
     /// Setup initialized escrow and moves it into pending state.
     ///
-    /// This instruction must be signed by one of the trusted handlers
-    /// which are either launcher or canceler.
+    /// This instruction must be signed by the launcher or one of its
+    /// additional trusted handlers.
     ///
     /// Accounts expected by this instruction:
     ///
     /// 0. [w] Escrow account
-    /// 1. [s] Trusted handler signing this transaction
+    /// 1. [s] Launcher or trusted handler signing this transaction
     /// 2. [] Clock sysvar
     /// 3. [] Signer account for the reputation oracle for this escrow
     /// 4. [] Reputation oracle's token account to receive fees
     /// 5. [] Signer account for the recording oracle for this escrow
     /// 6. [] Recording oracle's token account to receive fees
+    /// 7. [] Factory account this escrow belongs to
+    ///
+    /// If the oracles post bonds, with the launcher or trusted handler writable
+    /// to pay the bond vault rent. Oracle posting a bond signs the transaction
+    /// and its token account is writable, the bond is taken from it:
+    ///
+    /// 8. [w] Bond vault, program address derived from the escrow
+    /// 9. [] Token mint
+    /// 10. [] Rent sysvar
+    /// 11. [] System program
+    /// 12. [] Token contract program
     Setup {
-        /// Reputation oracle fee in percents
-        reputation_oracle_stake: u8,
+        /// Reputation oracle fee in basis points
+        reputation_oracle_stake: u16,
 
-        /// Recording oracle fee in percents
-        recording_oracle_stake: u8,
+        /// Recording oracle fee in basis points
+        recording_oracle_stake: u16,
 
         /// Manifest URL
         manifest_url: DataUrl,
 
         /// Manifest hash
         manifest_hash: DataHash,
+
+        /// Accrue oracle fees in the escrow until `SettleFees` instead of
+        /// sending them with every payout
+        defer_fees: bool,
+
+        /// Seconds after `StoreResults` during which the launcher can dispute
+        /// the results and payouts are stopped, no dispute window if zero.
+        /// Must end before the escrow expires
+        dispute_window: u64,
+
+        /// Tokens the reputation oracle locks in the bond vault until the
+        /// escrow is complete
+        reputation_oracle_bond: u64,
+
+        /// Tokens the recording oracle locks in the bond vault until the
+        /// escrow is complete
+        recording_oracle_bond: u64,
     },
 
     /// Store job's final url and results hash
     ///
-    /// When the job is over save final results URL and hash.
-    /// Must be signed by one of the trusted handlers.
+    /// When the job is over save final results URL and hash. Must be signed by
+    /// the recording oracle or the launcher.
     ///
     /// Accounts expected by this instruction:
     ///
     /// 0. [w] Escrow account
-    /// 1. [s] Trusted handler signing this transaction
+    /// 1. [s] Recording oracle or launcher signing this transaction
     /// 2. [] Clock sysvar
+    /// 3. [] Factory account this escrow belongs to
     StoreResults {
         /// Final results URL
         final_results_url: DataUrl,
@@ -92,13 +147,14 @@ pub enum EscrowInstruction {
     /// Store job's total amount and total recipients
     /// 
     /// When the job is over save total amount of tokens and number of recipients.
-    /// Must be signed by one of the trusted handlers.
+    /// Must be signed by the reputation oracle.
     /// 
     /// Accounts expected by this instruction:
     /// 
     /// 0. [w] Escrow account
-    /// 1. [s] Trusted handler signing this transaction
+    /// 1. [s] Reputation oracle signing this transaction
     /// 2. [] Clock sysvar
+    /// 3. [] Factory account this escrow belongs to
     StoreFinalAmounts {
         /// Total amount to pay
         total_amount: u64,
@@ -109,13 +165,17 @@ pub enum EscrowInstruction {
     /// Do a single payout
     ///
     /// After results are stored send this message multiple times to send tokens
-    /// to participants as well as oracle's fees. Must be signed by one of the trusted
-    /// handlers.
+    /// to participants as well as oracle's fees. Fee fractions too small to be
+    /// sent are accrued in the escrow and rounded up with the last payout. With
+    /// deferred fees the oracle fees stay in the escrow until `SettleFees`. Must
+    /// be signed by the reputation oracle. The payout with the worker score also
+    /// updates the reputation of the worker owning the payment recipient.
     ///
     /// Accounts expected by this instruction:
     ///
     /// 0. [w] Escrow account
-    /// 1. [s] Trusted handler signing this transaction
+    /// 1. [s] Reputation oracle signing this transaction, writable if it pays for the receipt
+    ///    or the worker reputation account
     /// 2. [] Clock sysvar
     /// 3. [w] Escrow token sending account
     /// 4. [] Escrow signing authority (token sending account's owner)
@@ -123,38 +183,672 @@ pub enum EscrowInstruction {
     /// 6. [w] Reputation oracle's token account to receive fees
     /// 7. [w] Recording oracle's token account to receive fees
     /// 8. [] Token contract program
+    /// 9. [] Factory account this escrow belongs to
+    ///
+    /// With the payout ID:
+    ///
+    /// 10. [w] Payout receipt, program address derived from the escrow and the payout ID
+    /// 11. [] Rent sysvar
+    /// 12. [] System program
+    ///
+    /// For the native mint escrow, following the M accounts above, with the
+    /// escrow signing authority writable. Payouts to system accounts are
+    /// unwrapped, only one payout per transaction can be unwrapped:
+    ///
+    /// M. [w] Wrap account, program address derived from the escrow
+    /// M+1. [] Native mint
+    /// M+2. [] Rent sysvar
+    /// M+3. [] System program
+    ///
+    /// With the worker score, following the N accounts above. Scores are only
+    /// accepted from the reputation oracle approved by the factory, and the
+    /// first scored payout of the worker counts the escrow as a completed job:
+    ///
+    /// N. [w] Worker reputation, program address derived from the factory and the worker wallet
+    /// N+1. [w] Worker job receipt, program address derived from the escrow and the worker wallet
+    /// N+2. [] Oracle approval, program address derived from the factory and the reputation oracle
+    /// N+3. [] Rent sysvar
+    /// N+4. [] System program
     Payout {
         /// Amount of tokens to pay
         amount: u64,
+
+        /// Optional payout ID, the payout is rejected if the ID was already paid
+        payout_id: Option<PayoutId>,
+
+        /// Optional score of the worker's job in basis points
+        worker_score: Option<u16>,
     },
     /// Cancel escrow
     ///
     /// Before escrow is finalized it is possible to cancel it and send all funds to
-    /// the canceler token account. Must be signed by one of the trusted
-    /// handlers.
+    /// the canceler token account, except the deferred fees left to settle and
+    /// the vesting payouts not withdrawn yet. Vesting payouts stop vesting at the
+    /// first cancellation, so the escrow can also be cancelled once paid while
+    /// they vest. Unvested tokens of the vesting schedules passed to the
+    /// instruction are sent to the canceler as well, the ones released by
+    /// `Withdraw` are sent by cancelling the escrow again. Must be signed by the
+    /// canceler or the launcher. Allowed even if the factory is paused.
     ///
     /// Accounts expected by this instruction:
     ///
     /// 0. [w] Escrow account
-    /// 1. [s] Trusted handler signing this transaction
+    /// 1. [s] Canceler or launcher signing this transaction
     /// 2. [w] Escrow token sending account
     /// 3. [] Escrow signing authority (token sending account's owner)
     /// 4. [w] Canceler token account to receive funds
     /// 5. [] Token contract program
+    ///
+    /// For the native mint escrow, with the escrow signing authority writable.
+    /// Funds are unwrapped if the canceler receives them to a system account:
+    ///
+    /// 6. [w] Wrap account, program address derived from the escrow
+    /// 7. [] Native mint
+    /// 8. [] Rent sysvar
+    /// 9. [] System program
+    ///
+    /// For the escrow with vesting payouts not withdrawn yet, following the N
+    /// accounts above:
+    ///
+    /// N. [] Clock sysvar
+    /// N+1. [w] Vesting schedules of the escrow to stop, any number of them
     Cancel,
 
     /// Complete escrow
     ///
     /// When payouts are complete it is possible to mark this escrow complete which
-    /// simply changes its status. Must be signed by one of the trusted
-    /// handlers.
+    /// simply changes its status. Escrows with deferred fees must settle them
+    /// first, vesting payouts must be withdrawn. Must be signed by the reputation
+    /// oracle, the launcher or one of its trusted handlers.
     ///
     /// Accounts expected by this instruction:
     ///
     /// 0. [w] Escrow account
-    /// 1. [s] Trusted handler signing this transaction
+    /// 1. [s] Reputation oracle, launcher or trusted handler signing this transaction
     /// 2. [] Clock sysvar
+    ///
+    /// If the oracles posted bonds, they are returned to the oracle token accounts:
+    ///
+    /// 3. [w] Bond vault, program address derived from the escrow
+    /// 4. [w] Reputation oracle token account
+    /// 5. [w] Recording oracle token account
+    /// 6. [] Token contract program
     Complete,
+
+    /// Transfer factory administration
+    ///
+    /// Must be signed by the current factory administrator.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Factory account
+    /// 1. [s] Current factory administrator
+    /// 2. [] New factory administrator
+    FactorySetAdmin,
+
+    /// Bump factory version
+    ///
+    /// Must be signed by the factory administrator, the new version
+    /// has to be greater than the current one.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Factory account
+    /// 1. [s] Factory administrator
+    FactorySetVersion {
+        /// Factory's new version
+        version: u8,
+    },
+
+    /// Set factory-wide policy enforced on escrows created under the factory
+    ///
+    /// Must be signed by the factory administrator.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Factory account
+    /// 1. [s] Factory administrator
+    FactorySetPolicy {
+        /// Token mint escrows are restricted to, any mint if not set
+        token_mint: COption<Pubkey>,
+
+        /// Maximum escrow duration in seconds, unlimited if zero
+        max_duration: u64,
+
+        /// Maximum sum of the oracle stakes in basis points
+        max_oracle_stake: u16,
+    },
+
+    /// Pause or resume all escrows created under the factory
+    ///
+    /// While paused `Setup`, `StoreResults`, `StoreFinalAmounts` and `Payout`
+    /// are rejected, `Cancel` remains allowed. Must be signed by the factory
+    /// administrator.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Factory account
+    /// 1. [s] Factory administrator
+    FactorySetPaused {
+        /// Pause if true, resume otherwise
+        paused: bool,
+    },
+
+    /// Close escrow
+    ///
+    /// Once escrow is complete or cancelled it is possible to close its accounts
+    /// and reclaim the rent. Any tokens left are sent to the canceler token account,
+    /// escrow token account is closed and escrow data is zeroed. All lamports of
+    /// both accounts are sent to the receiver. Deferred fees must be settled and
+    /// vesting payouts withdrawn first.
+    /// Must be signed by the canceler, the launcher or one of its trusted handlers.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Canceler, launcher or trusted handler signing this transaction
+    /// 2. [w] Escrow token account
+    /// 3. [] Escrow signing authority (token account's owner)
+    /// 4. [w] Canceler token account to receive remaining tokens
+    /// 5. [w] Receiver of the reclaimed lamports
+    /// 6. [] Token contract program
+    ///
+    /// For the native mint escrow, with the escrow signing authority writable.
+    /// The authority reserve is sent to the receiver as well:
+    ///
+    /// 7. [w] Wrap account, program address derived from the escrow
+    /// 8. [] Native mint
+    /// 9. [] Rent sysvar
+    /// 10. [] System program
+    ///
+    /// If the oracles posted bonds, following the native mint accounts if any.
+    /// Remaining bonds are returned to the oracles and the bond vault rent is
+    /// sent to the receiver:
+    ///
+    /// 7. [w] Bond vault, program address derived from the escrow
+    /// 8. [w] Reputation oracle token account
+    /// 9. [w] Recording oracle token account
+    Close,
+
+    /// Fund escrow
+    ///
+    /// Transfers tokens from the launcher's token account to the escrow token
+    /// account and records the deposited amount. Must be signed by the launcher.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Launcher, owner of the source token account
+    /// 2. [] Clock sysvar
+    /// 3. [w] Launcher's token account to take tokens from
+    /// 4. [w] Escrow token account
+    /// 5. [] Token contract program
+    Fund {
+        /// Amount of tokens to deposit
+        amount: u64,
+    },
+
+    /// Do a batch of payouts
+    ///
+    /// Sends tokens to several participants in a single instruction. Oracle fees are
+    /// calculated for every payout the same way as in `Payout` and sent to the oracles
+    /// once for the whole batch. Must be signed by the reputation oracle.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Reputation oracle signing this transaction, writable if it pays for the receipts
+    /// 2. [] Clock sysvar
+    /// 3. [w] Escrow token sending account
+    /// 4. [] Escrow signing authority (token sending account's owner)
+    /// 5. [w] Reputation oracle's token account to receive fees
+    /// 6. [w] Recording oracle's token account to receive fees
+    /// 7. [] Token contract program
+    /// 8. [] Factory account this escrow belongs to
+    /// 9. ..9+N [w] Payment recipients, one for each amount
+    ///
+    /// With the payout IDs:
+    ///
+    /// 9+N. [] Rent sysvar
+    /// 10+N. [] System program
+    /// 11+N. ..11+2N [w] Payout receipts, one for each payout ID
+    ///
+    /// For the native mint escrow, following the M accounts above, with the
+    /// escrow signing authority writable. Payouts to system accounts are
+    /// unwrapped together:
+    ///
+    /// M. [w] Wrap account, program address derived from the escrow
+    /// M+1. [] Native mint
+    /// M+2. [] Rent sysvar
+    /// M+3. [] System program
+    BatchPayout {
+        /// Amounts of tokens to pay, in the order of the recipient accounts
+        amounts: Vec<u64>,
+
+        /// Payout IDs, either empty or one for each amount
+        payout_ids: Vec<PayoutId>,
+    },
+
+    /// Store Merkle root of the claimable payouts
+    ///
+    /// Switches escrow to the claim mode: instead of sending `Payout` for every
+    /// participant the reputation oracle stores the root of the tree with
+    /// `(index, recipient token account, amount)` leaves, and the participants
+    /// claim their payouts with the `Claim` instruction. The root can be stored
+    /// only once. Must be signed by the reputation oracle.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Reputation oracle signing this transaction
+    /// 2. [] Clock sysvar
+    /// 3. [] Factory account this escrow belongs to
+    StorePayoutsRoot {
+        /// Merkle root of the payouts tree
+        payouts_root: MerkleHash,
+    },
+
+    /// Claim a payout from the stored payouts tree
+    ///
+    /// Sends tokens to the recipient of the proven leaf as well as oracle's fees,
+    /// and creates the claim receipt so the leaf can't be claimed again.
+    /// Can be sent by anyone, the signer pays for the receipt account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [ws] Claimant paying for the receipt account
+    /// 2. [] Clock sysvar
+    /// 3. [w] Escrow token sending account
+    /// 4. [] Escrow signing authority (token sending account's owner)
+    /// 5. [w] Payment recipient from the leaf
+    /// 6. [w] Reputation oracle's token account to receive fees
+    /// 7. [w] Recording oracle's token account to receive fees
+    /// 8. [] Token contract program
+    /// 9. [] Factory account this escrow belongs to
+    /// 10. [w] Claim receipt, program address derived from the escrow and the leaf index
+    /// 11. [] Rent sysvar
+    /// 12. [] System program
+    ///
+    /// For the native mint escrow, with the escrow signing authority writable.
+    /// Payout to a system account is unwrapped:
+    ///
+    /// 13. [w] Wrap account, program address derived from the escrow
+    /// 14. [] Native mint
+    /// 15. [] Rent sysvar
+    /// 16. [] System program
+    Claim {
+        /// Leaf index
+        index: u64,
+
+        /// Amount of tokens to pay, including oracle fees
+        amount: u64,
+
+        /// Sibling hashes from the leaf up to the root
+        proof: Vec<MerkleHash>,
+    },
+
+    /// Reclaim funds of the expired escrow
+    ///
+    /// Once escrow is expired all remaining funds, except the deferred fees left
+    /// to settle and the vesting payouts not withdrawn yet, can be sent to the
+    /// canceler token account and escrow is cancelled. Vesting payouts end before
+    /// the escrow expires, so they are fully vested by then and nothing of them is
+    /// returned, the tokens stay in the escrow until the recipients call
+    /// `Withdraw`. Can be sent by anyone, so the funds are not lost together with
+    /// the trusted handler keys. Allowed even if the factory is paused.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [] Clock sysvar
+    /// 2. [w] Escrow token sending account
+    /// 3. [] Escrow signing authority (token sending account's owner)
+    /// 4. [w] Canceler token account to receive funds
+    /// 5. [] Token contract program
+    ///
+    /// For the native mint escrow, with the escrow signing authority writable.
+    /// Funds are unwrapped if the canceler receives them to a system account:
+    ///
+    /// 6. [w] Wrap account, program address derived from the escrow
+    /// 7. [] Native mint
+    /// 8. [] Rent sysvar
+    /// 9. [] System program
+    Reclaim,
+
+    /// Extend escrow expiration
+    ///
+    /// Moves escrow expiration time later. The time from the escrow creation
+    /// until the new expiration can't exceed the factory maximum duration, escrows
+    /// created before the creation time was stored can only be extended when the
+    /// duration is unlimited. Must be signed by the launcher or the canceler before
+    /// the escrow expires.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Launcher or canceler signing this transaction
+    /// 2. [] Clock sysvar
+    /// 3. [] Factory account this escrow belongs to
+    ExtendExpiration {
+        /// Number of seconds to add to the escrow expiration time
+        additional_seconds: u64,
+    },
+
+    /// Add trusted handlers
+    ///
+    /// Additional trusted handlers act on behalf of the launcher: they are allowed
+    /// to set up, complete and close the escrow and to settle the deferred fees.
+    /// Handlers already in the list are skipped. Must be signed by the launcher or
+    /// the canceler.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Launcher or canceler signing this transaction
+    /// 2. [] Clock sysvar
+    AddTrustedHandlers {
+        /// Handlers to add
+        handlers: Vec<Pubkey>,
+    },
+
+    /// Remove trusted handlers
+    ///
+    /// Removes handlers added with `AddTrustedHandlers`, handlers not in the list
+    /// are skipped. Must be signed by the launcher or the canceler.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Launcher or canceler signing this transaction
+    /// 2. [] Clock sysvar
+    RemoveTrustedHandlers {
+        /// Handlers to remove
+        handlers: Vec<Pubkey>,
+    },
+
+    /// Store job's intermediate results
+    ///
+    /// Appends results URL and hash, the current time and the signer to the
+    /// escrow results log, unlike `StoreResults` earlier records are kept.
+    /// The log account is created with the first record, the signer pays for it.
+    /// The log holds up to `MAX_INTERMEDIATE_RESULTS` records, once it is full
+    /// the instruction is rejected and only `StoreResults` can be used.
+    /// Must be signed by the recording oracle or the launcher.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [] Escrow account
+    /// 1. [ws] Recording oracle or launcher signing this transaction
+    /// 2. [] Clock sysvar
+    /// 3. [] Factory account this escrow belongs to
+    /// 4. [w] Results log, program address derived from the escrow
+    /// 5. [] Rent sysvar
+    /// 6. [] System program
+    StoreIntermediateResults {
+        /// Intermediate results URL
+        results_url: DataUrl,
+
+        /// Intermediate results hash
+        results_hash: DataHash,
+    },
+
+    /// Write URL data
+    ///
+    /// Writes part of the URL longer than `URL_LEN` to the URL data account, so
+    /// that it can be referenced by `Setup`, `StoreResults` and
+    /// `StoreIntermediateResults`. The account must be created with the size of
+    /// the header plus the URL length, the first write sets its authority and
+    /// the account type. Accounts of other types are rejected.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] URL data account
+    /// 1. [s] URL data authority
+    WriteUrlData {
+        /// Offset of the written bytes in the URL
+        offset: u32,
+
+        /// URL bytes to write
+        data: Vec<u8>,
+    },
+
+    /// Migrate escrow
+    ///
+    /// Rewrites the escrow in the latest account layout, the escrow data is kept
+    /// as is. Escrows of the older versioned layouts are rewritten in place and
+    /// the instruction can be signed by anyone.
+    ///
+    /// Legacy escrows, created before the layout got the version byte, can only
+    /// be read until migrated. Their accounts can't be resized, so the escrow is
+    /// copied into the new account of the latest layout size, created by the
+    /// launcher and owned by the program. The escrow token account is handed
+    /// over to the authority of the new escrow and the legacy account is closed.
+    /// Tokens sent and left in the token account are counted as funded. Legacy
+    /// escrows predate the factory registry, the new escrow is registered in its
+    /// factory under the next free index. Must be signed by the launcher, paying
+    /// for the factory entry account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    ///
+    /// Legacy escrow migration only:
+    ///
+    /// 1. [w] New escrow account, uninitialized
+    /// 2. [ws] Launcher
+    /// 3. [w] Escrow token account
+    /// 4. [] Legacy escrow authority
+    /// 5. [w] Receiver of the legacy escrow account lamports
+    /// 6. [] Token program
+    /// 7. [w] Factory account this escrow belongs to
+    /// 8. [w] Factory entry account, derived from the factory and its escrow count
+    /// 9. [] Rent sysvar
+    /// 10. [] System program
+    Migrate,
+
+    /// Settle deferred oracle fees
+    ///
+    /// Sends the oracle fees accrued by the payouts of the escrow with deferred
+    /// fees to the oracle token accounts. Fees can be settled after expiration
+    /// or cancellation, since the oracles have already earned them. Must be
+    /// signed by the reputation oracle, the recording oracle, the launcher or
+    /// one of its trusted handlers.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Oracle, launcher or trusted handler signing this transaction
+    /// 2. [w] Escrow token sending account
+    /// 3. [] Escrow signing authority (token sending account's owner)
+    /// 4. [w] Reputation oracle's token account to receive fees
+    /// 5. [w] Recording oracle's token account to receive fees
+    /// 6. [] Token contract program
+    /// 7. [] Factory account this escrow belongs to
+    SettleFees,
+
+    /// Fund native SOL escrow
+    ///
+    /// Wraps SOL of the launcher in the temporary wrap account, moves it to the
+    /// escrow token account and records the deposited amount. The wrap account
+    /// is closed and its rent is returned to the launcher, so it can be used only
+    /// once per transaction. Only for the native mint escrow, must be signed by
+    /// the launcher.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [ws] Launcher paying the SOL
+    /// 2. [] Clock sysvar
+    /// 3. [w] Escrow token account
+    /// 4. [] Escrow signing authority (token account's owner)
+    /// 5. [] Token contract program
+    /// 6. [w] Wrap account, program address derived from the escrow
+    /// 7. [] Native mint
+    /// 8. [] Rent sysvar
+    /// 9. [] System program
+    FundNative {
+        /// Amount of lamports to deposit
+        amount: u64,
+    },
+
+    /// Dispute final results
+    ///
+    /// Moves the escrow into disputed state, payouts, claims and new results
+    /// are rejected until the dispute is resolved. Results can only be disputed
+    /// during the dispute window after `StoreResults`. Must be signed by the
+    /// launcher.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Launcher signing this transaction
+    /// 2. [] Clock sysvar
+    Dispute,
+
+    /// Resolve dispute
+    ///
+    /// Returns the disputed escrow into pending or partial state. Accepted
+    /// results can be paid out right away, rejected results are dropped and
+    /// payouts wait for the new results and their dispute window. Must be
+    /// signed by the administrator of the factory escrow belongs to.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Factory administrator signing this transaction
+    /// 2. [] Clock sysvar
+    /// 3. [] Factory account this escrow belongs to
+    ResolveDispute {
+        /// Accept the disputed results or drop them
+        accept_results: bool,
+    },
+
+    /// Slash oracle bonds
+    ///
+    /// Sends the given parts of the oracle bonds to the receiver token account.
+    /// Allowed once after the factory administrator rejects the disputed
+    /// results with `ResolveDispute`. Must be signed by the launcher.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Launcher signing this transaction
+    /// 2. [] Clock sysvar
+    /// 3. [w] Bond vault, program address derived from the escrow
+    /// 4. [w] Receiver token account of the slashed tokens
+    /// 5. [] Token contract program
+    SlashBonds {
+        /// Amount taken from the reputation oracle bond
+        reputation_oracle_amount: u64,
+
+        /// Amount taken from the recording oracle bond
+        recording_oracle_amount: u64,
+    },
+
+    /// Do a single vesting payout
+    ///
+    /// Counts the payout as sent and sends the oracle fees like `Payout`, but the
+    /// recipient's tokens stay in the escrow and vest linearly from the start to
+    /// the end of the schedule. Nothing is vested before the cliff. Vesting must
+    /// end before the escrow expires, every recipient can have only one vesting
+    /// payout and the further ones are rejected with `VestingScheduleExists`.
+    /// Must be signed by the reputation oracle.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [ws] Reputation oracle signing this transaction and paying for the schedule
+    /// 2. [] Clock sysvar
+    /// 3. [w] Escrow token sending account
+    /// 4. [] Escrow signing authority (token sending account's owner)
+    /// 5. [] Payment recipient, a system account is paid in SOL by the native mint escrow
+    /// 6. [w] Reputation oracle's token account to receive fees
+    /// 7. [w] Recording oracle's token account to receive fees
+    /// 8. [] Token contract program
+    /// 9. [] Factory account this escrow belongs to
+    /// 10. [w] Vesting schedule, program address derived from the escrow and the recipient
+    /// 11. [] Rent sysvar
+    /// 12. [] System program
+    VestedPayout {
+        /// Amount of tokens to pay, including oracle fees
+        amount: u64,
+
+        /// Vesting start timestamp
+        start: UnixTimestamp,
+
+        /// Nothing is vested before the cliff timestamp
+        cliff: UnixTimestamp,
+
+        /// Whole payout is vested at the end timestamp
+        end: UnixTimestamp,
+    },
+
+    /// Withdraw vested tokens
+    ///
+    /// Sends the vested tokens not withdrawn yet to the recipient of the vesting
+    /// schedule. Payouts of the cancelled escrow stop vesting at the cancellation
+    /// and the unvested tokens are released back to the escrow, so that `Cancel`
+    /// can return them to the canceler. Can be sent by anyone, tokens only go to
+    /// the schedule recipient. Allowed even if the factory is paused or the
+    /// results are disputed.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [] Clock sysvar
+    /// 2. [w] Vesting schedule, program address derived from the escrow and the recipient
+    /// 3. [w] Escrow token sending account
+    /// 4. [] Escrow signing authority (token sending account's owner)
+    /// 5. [w] Payment recipient of the schedule
+    /// 6. [] Token contract program
+    ///
+    /// For the native mint escrow, with the escrow signing authority writable.
+    /// Payout to a system account is unwrapped:
+    ///
+    /// 7. [w] Wrap account, program address derived from the escrow
+    /// 8. [] Native mint
+    /// 9. [] Rent sysvar
+    /// 10. [] System program
+    Withdraw,
+
+    /// Release oracle bonds
+    ///
+    /// Returns the remaining oracle bonds of the cancelled or expired escrow to
+    /// the oracle token accounts, so that the oracles don't depend on `Close`.
+    /// Not allowed while the rejected results let the launcher slash the bonds.
+    /// Must be signed by the reputation oracle or the recording oracle.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [w] Escrow account
+    /// 1. [s] Reputation oracle or recording oracle signing this transaction
+    /// 2. [] Clock sysvar
+    /// 3. [w] Bond vault, program address derived from the escrow
+    /// 4. [w] Reputation oracle token account
+    /// 5. [w] Recording oracle token account
+    /// 6. [] Token contract program
+    ReleaseBonds,
+
+    /// Approve the reputation oracle to score the workers of the factory
+    ///
+    /// Creates or updates the oracle approval, payouts below the minimum amount
+    /// can't be scored. Zero minimum amount revokes the approval and closes the
+    /// account. Must be signed by the factory administrator, who pays for the
+    /// approval account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    /// 0. [] Factory account
+    /// 1. [ws] Factory administrator
+    /// 2. [w] Oracle approval, program address derived from the factory and the oracle
+    /// 3. [] Reputation oracle
+    /// 4. [] Rent sysvar
+    /// 5. [] System program
+    FactoryApproveOracle {
+        /// Minimum payout amount the oracle can score, zero to revoke the approval
+        min_scored_amount: u64,
+    },
 }
 
 impl EscrowInstruction {
@@ -173,15 +867,23 @@ impl EscrowInstruction {
                 Self::Initialize { duration }
             }
             3 => {
-                let (reputation_oracle_stake, rest) = Self::unpack_u8(rest)?;
-                let (recording_oracle_stake, rest) = Self::unpack_u8(rest)?;
+                let (reputation_oracle_stake, rest) = Self::unpack_u16(rest)?;
+                let (recording_oracle_stake, rest) = Self::unpack_u16(rest)?;
                 let (manifest_url, rest) = Self::unpack_url(rest)?;
-                let (manifest_hash, _rest) = Self::unpack_hash(rest)?;
+                let (manifest_hash, rest) = Self::unpack_hash(rest)?;
+                let (defer_fees, rest) = Self::unpack_bool(rest)?;
+                let (dispute_window, rest) = Self::unpack_u64(rest)?;
+                let (reputation_oracle_bond, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_bond, _rest) = Self::unpack_u64(rest)?;
                 Self::Setup {
                     reputation_oracle_stake,
                     recording_oracle_stake,
                     manifest_url,
                     manifest_hash,
+                    defer_fees,
+                    dispute_window,
+                    reputation_oracle_bond,
+                    recording_oracle_bond,
                 }
             }
             4 => {
@@ -201,11 +903,157 @@ impl EscrowInstruction {
                 }
             }
             6 => {
-                let (amount, _rest) = Self::unpack_u64(rest)?;
-                Self::Payout { amount }
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (payout_id, rest) = if rest.len() >= 32 {
+                    let (payout_id, rest) = Self::unpack_bytes32(rest)?;
+                    (Some(payout_id), rest)
+                } else {
+                    (None, rest)
+                };
+                let worker_score = if rest.is_empty() {
+                    None
+                } else {
+                    let (worker_score, _rest) = Self::unpack_u16(rest)?;
+                    Some(worker_score)
+                };
+                Self::Payout {
+                    amount,
+                    payout_id,
+                    worker_score,
+                }
             }
             7 => Self::Cancel,
             8 => Self::Complete,
+            9 => Self::FactorySetAdmin,
+            10 => {
+                let (version, _rest) = Self::unpack_u8(rest)?;
+                Self::FactorySetVersion { version }
+            }
+            11 => {
+                let (token_mint, rest) = Self::unpack_pubkey_option(rest)?;
+                let (max_duration, rest) = Self::unpack_u64(rest)?;
+                let (max_oracle_stake, _rest) = Self::unpack_u16(rest)?;
+                Self::FactorySetPolicy {
+                    token_mint,
+                    max_duration,
+                    max_oracle_stake,
+                }
+            }
+            12 => {
+                let (paused, _rest) = Self::unpack_bool(rest)?;
+                Self::FactorySetPaused { paused }
+            }
+            13 => Self::Close,
+            14 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::Fund { amount }
+            }
+            15 => {
+                let (count, mut rest) = Self::unpack_u8(rest)?;
+                let mut amounts = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (amount, next) = Self::unpack_u64(rest)?;
+                    amounts.push(amount);
+                    rest = next;
+                }
+                let mut payout_ids = vec![];
+                if !rest.is_empty() {
+                    for _ in 0..count {
+                        let (payout_id, next) = Self::unpack_bytes32(rest)?;
+                        payout_ids.push(payout_id);
+                        rest = next;
+                    }
+                }
+                Self::BatchPayout {
+                    amounts,
+                    payout_ids,
+                }
+            }
+            16 => {
+                let (payouts_root, _rest) = Self::unpack_bytes32(rest)?;
+                Self::StorePayoutsRoot { payouts_root }
+            }
+            17 => {
+                let (index, rest) = Self::unpack_u64(rest)?;
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (count, mut rest) = Self::unpack_u8(rest)?;
+                let mut proof = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (hash, next) = Self::unpack_bytes32(rest)?;
+                    proof.push(hash);
+                    rest = next;
+                }
+                Self::Claim {
+                    index,
+                    amount,
+                    proof,
+                }
+            }
+            18 => Self::Reclaim,
+            19 => {
+                let (additional_seconds, _rest) = Self::unpack_u64(rest)?;
+                Self::ExtendExpiration { additional_seconds }
+            }
+            20 => {
+                let (handlers, _rest) = Self::unpack_pubkeys(rest)?;
+                Self::AddTrustedHandlers { handlers }
+            }
+            21 => {
+                let (handlers, _rest) = Self::unpack_pubkeys(rest)?;
+                Self::RemoveTrustedHandlers { handlers }
+            }
+            22 => {
+                let (results_url, rest) = Self::unpack_url(rest)?;
+                let (results_hash, _rest) = Self::unpack_hash(rest)?;
+                Self::StoreIntermediateResults {
+                    results_url,
+                    results_hash,
+                }
+            }
+            23 => {
+                let (offset, rest) = Self::unpack_u32(rest)?;
+                Self::WriteUrlData {
+                    offset,
+                    data: rest.to_vec(),
+                }
+            }
+            24 => Self::Migrate,
+            25 => Self::SettleFees,
+            26 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FundNative { amount }
+            }
+            27 => Self::Dispute,
+            28 => {
+                let (accept_results, _rest) = Self::unpack_bool(rest)?;
+                Self::ResolveDispute { accept_results }
+            }
+            29 => {
+                let (reputation_oracle_amount, rest) = Self::unpack_u64(rest)?;
+                let (recording_oracle_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::SlashBonds {
+                    reputation_oracle_amount,
+                    recording_oracle_amount,
+                }
+            }
+            30 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (start, rest) = Self::unpack_u64(rest)?;
+                let (cliff, rest) = Self::unpack_u64(rest)?;
+                let (end, _rest) = Self::unpack_u64(rest)?;
+                Self::VestedPayout {
+                    amount,
+                    start: start as UnixTimestamp,
+                    cliff: cliff as UnixTimestamp,
+                    end: end as UnixTimestamp,
+                }
+            }
+            31 => Self::Withdraw,
+            32 => Self::ReleaseBonds,
+            33 => {
+                let (min_scored_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FactoryApproveOracle { min_scored_amount }
+            }
             _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
@@ -227,20 +1075,28 @@ impl EscrowInstruction {
                 recording_oracle_stake,
                 manifest_url,
                 manifest_hash,
+                defer_fees,
+                dispute_window,
+                reputation_oracle_bond,
+                recording_oracle_bond,
             } => {
                 buf.push(3);
-                buf.push(reputation_oracle_stake);
-                buf.push(recording_oracle_stake);
-                buf.extend(manifest_url.as_ref());
-                buf.extend(manifest_hash.as_ref());
+                buf.extend(&reputation_oracle_stake.to_le_bytes());
+                buf.extend(&recording_oracle_stake.to_le_bytes());
+                Self::pack_url(&manifest_url, &mut buf);
+                Self::pack_hash(&manifest_hash, &mut buf);
+                buf.push(defer_fees as u8);
+                buf.extend(&dispute_window.to_le_bytes());
+                buf.extend(&reputation_oracle_bond.to_le_bytes());
+                buf.extend(&recording_oracle_bond.to_le_bytes());
             }
             Self::StoreResults {
                 final_results_url,
                 final_results_hash,
             } => {
                 buf.push(4);
-                buf.extend(final_results_url.as_ref());
-                buf.extend(final_results_hash.as_ref());
+                Self::pack_url(&final_results_url, &mut buf);
+                Self::pack_hash(&final_results_hash, &mut buf);
             }
             Self::StoreFinalAmounts {
                 total_amount,
@@ -250,12 +1106,139 @@ impl EscrowInstruction {
                 buf.extend(&total_amount.to_le_bytes());
                 buf.extend(&total_recipients.to_le_bytes());
             }
-            Self::Payout { amount } => {
+            Self::Payout {
+                amount,
+                ref payout_id,
+                worker_score,
+            } => {
                 buf.push(6);
                 buf.extend(&amount.to_le_bytes());
+                if let Some(payout_id) = payout_id {
+                    buf.extend(payout_id);
+                }
+                if let Some(worker_score) = worker_score {
+                    buf.extend(&worker_score.to_le_bytes());
+                }
             }
             Self::Cancel => buf.push(7),
             Self::Complete => buf.push(8),
+            Self::FactorySetAdmin => buf.push(9),
+            Self::FactorySetVersion { version } => {
+                buf.push(10);
+                buf.push(version);
+            }
+            Self::FactorySetPolicy {
+                token_mint,
+                max_duration,
+                max_oracle_stake,
+            } => {
+                buf.push(11);
+                Self::pack_pubkey_option(&token_mint, &mut buf);
+                buf.extend(&max_duration.to_le_bytes());
+                buf.extend(&max_oracle_stake.to_le_bytes());
+            }
+            Self::FactorySetPaused { paused } => {
+                buf.push(12);
+                buf.push(paused as u8);
+            }
+            Self::Close => buf.push(13),
+            Self::Fund { amount } => {
+                buf.push(14);
+                buf.extend(&amount.to_le_bytes());
+            }
+            Self::BatchPayout {
+                ref amounts,
+                ref payout_ids,
+            } => {
+                buf.push(15);
+                buf.push(amounts.len() as u8);
+                for amount in amounts {
+                    buf.extend(&amount.to_le_bytes());
+                }
+                for payout_id in payout_ids {
+                    buf.extend(payout_id);
+                }
+            }
+            Self::StorePayoutsRoot { payouts_root } => {
+                buf.push(16);
+                buf.extend(&payouts_root);
+            }
+            Self::Claim {
+                index,
+                amount,
+                ref proof,
+            } => {
+                buf.push(17);
+                buf.extend(&index.to_le_bytes());
+                buf.extend(&amount.to_le_bytes());
+                buf.push(proof.len() as u8);
+                for hash in proof {
+                    buf.extend(hash);
+                }
+            }
+            Self::Reclaim => buf.push(18),
+            Self::ExtendExpiration { additional_seconds } => {
+                buf.push(19);
+                buf.extend(&additional_seconds.to_le_bytes());
+            }
+            Self::AddTrustedHandlers { ref handlers } => {
+                buf.push(20);
+                Self::pack_pubkeys(handlers, &mut buf);
+            }
+            Self::RemoveTrustedHandlers { ref handlers } => {
+                buf.push(21);
+                Self::pack_pubkeys(handlers, &mut buf);
+            }
+            Self::StoreIntermediateResults {
+                results_url,
+                results_hash,
+            } => {
+                buf.push(22);
+                Self::pack_url(&results_url, &mut buf);
+                Self::pack_hash(&results_hash, &mut buf);
+            }
+            Self::WriteUrlData { offset, ref data } => {
+                buf.push(23);
+                buf.extend(&offset.to_le_bytes());
+                buf.extend(data);
+            }
+            Self::Migrate => buf.push(24),
+            Self::SettleFees => buf.push(25),
+            Self::FundNative { amount } => {
+                buf.push(26);
+                buf.extend(&amount.to_le_bytes());
+            }
+            Self::Dispute => buf.push(27),
+            Self::ResolveDispute { accept_results } => {
+                buf.push(28);
+                buf.push(accept_results as u8);
+            }
+            Self::SlashBonds {
+                reputation_oracle_amount,
+                recording_oracle_amount,
+            } => {
+                buf.push(29);
+                buf.extend(&reputation_oracle_amount.to_le_bytes());
+                buf.extend(&recording_oracle_amount.to_le_bytes());
+            }
+            Self::VestedPayout {
+                amount,
+                start,
+                cliff,
+                end,
+            } => {
+                buf.push(30);
+                buf.extend(&amount.to_le_bytes());
+                buf.extend(&start.to_le_bytes());
+                buf.extend(&cliff.to_le_bytes());
+                buf.extend(&end.to_le_bytes());
+            }
+            Self::Withdraw => buf.push(31),
+            Self::ReleaseBonds => buf.push(32),
+            Self::FactoryApproveOracle { min_scored_amount } => {
+                buf.push(33);
+                buf.extend(&min_scored_amount.to_le_bytes());
+            }
         }
         buf
     }
@@ -267,11 +1250,11 @@ impl EscrowInstruction {
         }
     }
 
-    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
-        if input.len() >= 8 {
-            let (bytes, rest) = input.split_at(8);
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() >= 2 {
+            let (bytes, rest) = input.split_at(2);
             Ok((
-                u64::from_le_bytes(
+                u16::from_le_bytes(
                     bytes
                         .try_into()
                         .or(Err(ProgramError::InvalidInstructionData))?,
@@ -283,11 +1266,27 @@ impl EscrowInstruction {
         }
     }
 
-    fn unpack_hash(input: &[u8]) -> Result<(DataHash, &[u8]), ProgramError> {
-        if input.len() >= 20 {
-            let (bytes, rest) = input.split_at(20);
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (bytes, rest) = input.split_at(4);
+            Ok((
+                u32::from_le_bytes(
+                    bytes
+                        .try_into()
+                        .or(Err(ProgramError::InvalidInstructionData))?,
+                ),
+                rest,
+            ))
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() >= 8 {
+            let (bytes, rest) = input.split_at(8);
             Ok((
-                DataHash::new_from_array(
+                u64::from_le_bytes(
                     bytes
                         .try_into()
                         .or(Err(ProgramError::InvalidInstructionData))?,
@@ -299,27 +1298,130 @@ impl EscrowInstruction {
         }
     }
 
+    fn unpack_bool(input: &[u8]) -> Result<(bool, &[u8]), ProgramError> {
+        match input.split_first() {
+            Option::Some((&0, rest)) => Ok((false, rest)),
+            Option::Some((&1, rest)) => Ok((true, rest)),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    fn unpack_pubkey_option(input: &[u8]) -> Result<(COption<Pubkey>, &[u8]), ProgramError> {
+        match input.split_first() {
+            Option::Some((&0, rest)) => Ok((COption::None, rest)),
+            Option::Some((&1, rest)) if rest.len() >= 32 => {
+                let (key, rest) = rest.split_at(32);
+                let pk = Pubkey::new(key);
+                Ok((COption::Some(pk), rest))
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    fn pack_pubkey_option(value: &COption<Pubkey>, buf: &mut Vec<u8>) {
+        match *value {
+            COption::Some(ref key) => {
+                buf.push(1);
+                buf.extend_from_slice(&key.to_bytes());
+            }
+            COption::None => buf.push(0),
+        }
+    }
+
+    fn unpack_pubkeys(input: &[u8]) -> Result<(Vec<Pubkey>, &[u8]), ProgramError> {
+        let (count, mut rest) = Self::unpack_u8(input)?;
+        let mut keys = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let (key, next) = Self::unpack_bytes32(rest)?;
+            keys.push(Pubkey::new_from_array(key));
+            rest = next;
+        }
+        Ok((keys, rest))
+    }
+
+    fn pack_pubkeys(keys: &[Pubkey], buf: &mut Vec<u8>) {
+        buf.push(keys.len() as u8);
+        for key in keys {
+            buf.extend_from_slice(&key.to_bytes());
+        }
+    }
+
+    fn unpack_bytes32(input: &[u8]) -> Result<([u8; 32], &[u8]), ProgramError> {
+        if input.len() >= 32 {
+            let (bytes, rest) = input.split_at(32);
+            Ok((
+                bytes
+                    .try_into()
+                    .or(Err(ProgramError::InvalidInstructionData))?,
+                rest,
+            ))
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn unpack_hash(input: &[u8]) -> Result<(DataHash, &[u8]), ProgramError> {
+        let (algorithm, rest) = Self::unpack_u8(input)?;
+        let algorithm = HashAlgorithm::try_from_primitive(algorithm)
+            .or(Err(ProgramError::InvalidInstructionData))?;
+        let (digest_len, rest) = Self::unpack_u8(rest)?;
+        if rest.len() >= digest_len as usize {
+            let (digest, rest) = rest.split_at(digest_len as usize);
+            Ok((DataHash::new(algorithm, digest)?, rest))
+        } else {
+            Err(ProgramError::InvalidInstructionData)
+        }
+    }
+
+    fn pack_hash(hash: &DataHash, buf: &mut Vec<u8>) {
+        buf.push(hash.algorithm() as u8);
+        buf.push(hash.as_ref().len() as u8);
+        buf.extend(hash.as_ref());
+    }
+
     fn unpack_url(input: &[u8]) -> Result<(DataUrl, &[u8]), ProgramError> {
-        if input.len() >= URL_LEN {
-            let (bytes, rest) = input.split_at(URL_LEN);
-            let mut bytes_copy = [0u8; URL_LEN];
-            bytes_copy.copy_from_slice(bytes);
-            Ok((DataUrl::new_from_array(bytes_copy), rest))
+        let (length, rest) = Self::unpack_u16(input)?;
+        if length == URL_ACCOUNT_LEN {
+            let (key, rest) = Self::unpack_bytes32(rest)?;
+            Ok((
+                DataUrl::new_from_account(&Pubkey::new_from_array(key)),
+                rest,
+            ))
+        } else if rest.len() >= length as usize {
+            let (bytes, rest) = rest.split_at(length as usize);
+            Ok((DataUrl::new_from_slice(bytes)?, rest))
         } else {
             Err(ProgramError::InvalidInstructionData)
         }
     }
+
+    fn pack_url(url: &DataUrl, buf: &mut Vec<u8>) {
+        match url.account() {
+            Some(account) => {
+                buf.extend(&URL_ACCOUNT_LEN.to_le_bytes());
+                buf.extend_from_slice(&account.to_bytes());
+            }
+            None => {
+                buf.extend(&(url.as_ref().len() as u16).to_le_bytes());
+                buf.extend(url.as_ref());
+            }
+        }
+    }
 }
 
 /// Creates `FactoryInitialize` instruction.
 pub fn factory_initialize(
     escrow_program_id: &Pubkey,
     factory: &Pubkey,
+    admin: &Pubkey,
     version: u8,
 ) -> Result<Instruction, ProgramError> {
     let data = EscrowInstruction::FactoryInitialize { version }.pack();
 
-    let accounts = vec![AccountMeta::new(*factory, false)];
+    let accounts = vec![
+        AccountMeta::new(*factory, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
 
     Ok(Instruction {
         program_id: *escrow_program_id,
@@ -338,20 +1440,30 @@ pub fn initialize(
     launcher: &Pubkey,
     canceler: &Pubkey,
     canceler_token_account: &Pubkey,
+    factory_entry: &Pubkey,
+    payer: &Pubkey,
     duration: u64,
+    escrow_authority: Option<&Pubkey>,
 ) -> Result<Instruction, ProgramError> {
     let data = EscrowInstruction::Initialize { duration }.pack();
 
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*escrow, false),
-        AccountMeta::new_readonly(*factory, false),
+        AccountMeta::new(*factory, false),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new_readonly(*token_mint, false),
         AccountMeta::new_readonly(*token_account, false),
         AccountMeta::new_readonly(*launcher, false),
         AccountMeta::new_readonly(*canceler, false),
         AccountMeta::new_readonly(*canceler_token_account, false),
+        AccountMeta::new(*factory_entry, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
+    if let Some(escrow_authority) = escrow_authority {
+        accounts.push(AccountMeta::new(*escrow_authority, false));
+    }
 
     Ok(Instruction {
         program_id: *escrow_program_id,
@@ -367,30 +1479,56 @@ pub fn setup(
     trusted_handler: &Pubkey,
     reputation_oracle: &Pubkey,
     reputation_oracle_token_account: &Pubkey,
-    reputation_oracle_stake: u8,
+    reputation_oracle_stake: u16,
     recording_oracle: &Pubkey,
     recording_oracle_token_account: &Pubkey,
-    recording_oracle_stake: u8,
+    recording_oracle_stake: u16,
     manifest_url: &DataUrl,
     manifest_hash: &DataHash,
+    defer_fees: bool,
+    dispute_window: u64,
+    reputation_oracle_bond: u64,
+    recording_oracle_bond: u64,
+    factory: &Pubkey,
+    bond_vault: Option<(&Pubkey, &Pubkey)>,
 ) -> Result<Instruction, ProgramError> {
+    let bonded = reputation_oracle_bond != 0 || recording_oracle_bond != 0;
+    if bonded != bond_vault.is_some() {
+        return Err(ProgramError::InvalidArgument);
+    }
     let data = EscrowInstruction::Setup {
         reputation_oracle_stake,
         recording_oracle_stake,
         manifest_url: *manifest_url,
         manifest_hash: *manifest_hash,
+        defer_fees,
+        dispute_window,
+        reputation_oracle_bond,
+        recording_oracle_bond,
     }
     .pack();
 
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*escrow, false),
-        AccountMeta::new_readonly(*trusted_handler, true),
+        if bonded {
+            AccountMeta::new(*trusted_handler, true)
+        } else {
+            AccountMeta::new_readonly(*trusted_handler, true)
+        },
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(*reputation_oracle, false),
-        AccountMeta::new_readonly(*reputation_oracle_token_account, false),
-        AccountMeta::new_readonly(*recording_oracle, false),
-        AccountMeta::new_readonly(*recording_oracle_token_account, false),
+        oracle_meta(reputation_oracle, reputation_oracle_bond),
+        oracle_token_account_meta(reputation_oracle_token_account, reputation_oracle_bond),
+        oracle_meta(recording_oracle, recording_oracle_bond),
+        oracle_token_account_meta(recording_oracle_token_account, recording_oracle_bond),
+        AccountMeta::new_readonly(*factory, false),
     ];
+    if let Some((bond_vault, token_mint)) = bond_vault {
+        accounts.push(AccountMeta::new(*bond_vault, false));
+        accounts.push(AccountMeta::new_readonly(*token_mint, false));
+        accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+        accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    }
 
     Ok(Instruction {
         program_id: *escrow_program_id,
@@ -406,6 +1544,7 @@ pub fn store_results(
     trusted_handler: &Pubkey,
     final_results_url: &DataUrl,
     final_results_hash: &DataHash,
+    factory: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
     let data = EscrowInstruction::StoreResults {
         final_results_url: *final_results_url,
@@ -417,6 +1556,7 @@ pub fn store_results(
         AccountMeta::new(*escrow, false),
         AccountMeta::new_readonly(*trusted_handler, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*factory, false),
     ];
 
     Ok(Instruction {
@@ -433,6 +1573,7 @@ pub fn store_amounts(
     trusted_handler: &Pubkey,
     total_amount: u64,
     total_recipients: u64,
+    factory: &Pubkey,
 ) -> Result<Instruction, ProgramError> {
     let data = EscrowInstruction::StoreFinalAmounts {
         total_amount,
@@ -443,6 +1584,7 @@ pub fn store_amounts(
         AccountMeta::new(*escrow, false),
         AccountMeta::new_readonly(*trusted_handler, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*factory, false),
     ];
 
     Ok(Instruction {
@@ -464,20 +1606,49 @@ pub fn payout(
     recording_oracle_token_account: &Pubkey,
     token_program_id: &Pubkey,
     amount: u64,
+    factory: &Pubkey,
+    payout_receipt: Option<(&PayoutId, &Pubkey)>,
+    wrap_account: Option<&Pubkey>,
+    worker_reputation: Option<(u16, &Pubkey, &Pubkey, &Pubkey)>,
 ) -> Result<Instruction, ProgramError> {
-    let data = EscrowInstruction::Payout { amount }.pack();
+    let data = EscrowInstruction::Payout {
+        amount,
+        payout_id: payout_receipt.map(|(payout_id, _)| *payout_id),
+        worker_score: worker_reputation.map(|(worker_score, _, _, _)| worker_score),
+    }
+    .pack();
 
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*escrow, false),
-        AccountMeta::new_readonly(*trusted_handler, true),
+        if payout_receipt.is_some() || worker_reputation.is_some() {
+            AccountMeta::new(*trusted_handler, true)
+        } else {
+            AccountMeta::new_readonly(*trusted_handler, true)
+        },
         AccountMeta::new_readonly(sysvar::clock::id(), false),
         AccountMeta::new(*escrow_token_account, false),
-        AccountMeta::new_readonly(*escrow_authority, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
         AccountMeta::new(*recipient_token_account, false),
         AccountMeta::new(*reputation_oracle_token_account, false),
         AccountMeta::new(*recording_oracle_token_account, false),
         AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*factory, false),
     ];
+    if let Some((_, payout_receipt)) = payout_receipt {
+        accounts.push(AccountMeta::new(*payout_receipt, false));
+        accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+
+    push_native_accounts(&mut accounts, wrap_account);
+
+    if let Some((_, worker_reputation, worker_job, oracle_approval)) = worker_reputation {
+        accounts.push(AccountMeta::new(*worker_reputation, false));
+        accounts.push(AccountMeta::new(*worker_job, false));
+        accounts.push(AccountMeta::new_readonly(*oracle_approval, false));
+        accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
 
     Ok(Instruction {
         program_id: *escrow_program_id,
@@ -486,6 +1657,36 @@ pub fn payout(
     })
 }
 
+/// Derives stable IDs for the list of payouts.
+///
+/// IDs depend on the whole list but not on its order, so sending the same
+/// payouts file again produces the same IDs and can't pay anyone twice.
+/// Every row gets its own ID, a recipient listed several times is paid
+/// for every row.
+pub fn payout_ids(payouts: &[(Pubkey, u64)]) -> Vec<PayoutId> {
+    let mut order: Vec<usize> = (0..payouts.len()).collect();
+    order.sort_by_key(|&row| payouts[row]);
+    let mut data = Vec::with_capacity(payouts.len() * 40);
+    for &row in order.iter() {
+        let (recipient, amount) = &payouts[row];
+        data.extend(recipient.as_ref());
+        data.extend(&amount.to_le_bytes());
+    }
+    let digest = hash(&data);
+
+    // Rows are numbered in the sorted list to keep IDs independent of the order
+    let mut ids = vec![PayoutId::default(); payouts.len()];
+    for (index, &row) in order.iter().enumerate() {
+        ids[row] = hashv(&[
+            digest.as_ref(),
+            &(index as u64).to_le_bytes(),
+            payouts[row].0.as_ref(),
+        ])
+        .to_bytes();
+    }
+    ids
+}
+
 /// Creates `Cancel` instruction
 pub fn cancel(
     escrow_program_id: &Pubkey,
@@ -495,18 +1696,29 @@ pub fn cancel(
     escrow_authority: &Pubkey,
     canceler_token_account: &Pubkey,
     token_program_id: &Pubkey,
+    wrap_account: Option<&Pubkey>,
+    vesting_schedules: Option<&[Pubkey]>,
 ) -> Result<Instruction, ProgramError> {
     let data = EscrowInstruction::Cancel.pack();
 
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*escrow, false),
         AccountMeta::new_readonly(*trusted_handler, true),
         AccountMeta::new(*escrow_token_account, false),
-        AccountMeta::new_readonly(*escrow_authority, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
         AccountMeta::new(*canceler_token_account, false),
         AccountMeta::new_readonly(*token_program_id, false),
     ];
 
+    push_native_accounts(&mut accounts, wrap_account);
+
+    if let Some(vesting_schedules) = vesting_schedules {
+        accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+        for vesting_schedule in vesting_schedules {
+            accounts.push(AccountMeta::new(*vesting_schedule, false));
+        }
+    }
+
     Ok(Instruction {
         program_id: *escrow_program_id,
         accounts,
@@ -519,15 +1731,21 @@ pub fn complete(
     escrow_program_id: &Pubkey,
     escrow: &Pubkey,
     trusted_handler: &Pubkey,
+    bond_accounts: Option<(&Pubkey, &Pubkey, &Pubkey)>,
 ) -> Result<Instruction, ProgramError> {
     let data = EscrowInstruction::Complete.pack();
 
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(*escrow, false),
         AccountMeta::new_readonly(*trusted_handler, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
     ];
 
+    if bond_accounts.is_some() {
+        push_bond_accounts(&mut accounts, bond_accounts);
+        accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+    }
+
     Ok(Instruction {
         program_id: *escrow_program_id,
         accounts,
@@ -535,80 +1753,1307 @@ pub fn complete(
     })
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+/// Creates `FactorySetAdmin` instruction
+pub fn factory_set_admin(
+    escrow_program_id: &Pubkey,
+    factory: &Pubkey,
+    admin: &Pubkey,
+    new_admin: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FactorySetAdmin.pack();
 
-    #[test]
-    fn test_instruction_packing() {
-        let check = EscrowInstruction::Initialize {
-            duration: 2592000, // 0x0000000000278D00
-        };
-        let packed = check.pack();
-        let expect: Vec<u8> = vec![2, 0x00, 0x8D, 0x27, 0x00, 0x00, 0x00, 0x00, 0x00];
-        assert_eq!(packed, expect);
-        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
-        assert_eq!(unpacked, check);
+    let accounts = vec![
+        AccountMeta::new(*factory, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(*new_admin, false),
+    ];
 
-        let check = EscrowInstruction::Setup {
-            reputation_oracle_stake: 5,
-            recording_oracle_stake: 10,
-            manifest_url: DataUrl::new_from_array([10; URL_LEN]),
-            manifest_hash: DataHash::new_from_array([11; 20]),
-        };
-        let packed = check.pack();
-        let mut expect: Vec<u8> = vec![3, 5, 10];
-        expect.extend(&[10; URL_LEN]);
-        expect.extend(&[11; 20]);
-        assert_eq!(packed, expect);
-        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
-        assert_eq!(unpacked, check);
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
 
-        let check = EscrowInstruction::StoreResults {
-            final_results_url: DataUrl::new_from_array([21; URL_LEN]),
-            final_results_hash: DataHash::new_from_array([22; 20]),
-        };
-        let packed = check.pack();
-        let mut expect: Vec<u8> = vec![4];
-        expect.extend(&[21; URL_LEN]);
-        expect.extend(&[22; 20]);
-        assert_eq!(packed, expect);
-        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
-        assert_eq!(unpacked, check);
+/// Creates `FactorySetVersion` instruction
+pub fn factory_set_version(
+    escrow_program_id: &Pubkey,
+    factory: &Pubkey,
+    admin: &Pubkey,
+    version: u8,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FactorySetVersion { version }.pack();
 
-        let check = EscrowInstruction::StoreFinalAmounts {
-            total_amount: 1000000,  // 0x00000000000F4240
-            total_recipients: 1000, // 0x00000000000003E8
-        };
-        let packed = check.pack();
-        let mut expect: Vec<u8> = vec![5];
-        expect.extend(&[0x40, 0x42, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00]);
-        expect.extend(&[0xE8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
-        assert_eq!(packed, expect);
-        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
-        assert_eq!(unpacked, check);
+    let accounts = vec![
+        AccountMeta::new(*factory, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
 
-        let check = EscrowInstruction::Payout {
-            amount: 1000000000000, // 0x000000E8D4A51000
-        };
-        let packed = check.pack();
-        let expect: Vec<u8> = vec![6, 0x00, 0x10, 0xA5, 0xD4, 0xE8, 0x00, 0x00, 0x00];
-        assert_eq!(packed, expect);
-        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
-        assert_eq!(unpacked, check);
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
 
-        let check = EscrowInstruction::Cancel;
-        let packed = check.pack();
-        let expect: Vec<u8> = vec![7];
-        assert_eq!(packed, expect);
-        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
-        assert_eq!(unpacked, check);
+/// Creates `FactorySetPolicy` instruction
+pub fn factory_set_policy(
+    escrow_program_id: &Pubkey,
+    factory: &Pubkey,
+    admin: &Pubkey,
+    token_mint: COption<Pubkey>,
+    max_duration: u64,
+    max_oracle_stake: u16,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FactorySetPolicy {
+        token_mint,
+        max_duration,
+        max_oracle_stake,
+    }
+    .pack();
 
-        let check = EscrowInstruction::Complete;
-        let packed = check.pack();
+    let accounts = vec![
+        AccountMeta::new(*factory, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `FactorySetPaused` instruction
+pub fn factory_set_paused(
+    escrow_program_id: &Pubkey,
+    factory: &Pubkey,
+    admin: &Pubkey,
+    paused: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FactorySetPaused { paused }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*factory, false),
+        AccountMeta::new_readonly(*admin, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Close` instruction
+pub fn close(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    canceler_token_account: &Pubkey,
+    receiver: &Pubkey,
+    token_program_id: &Pubkey,
+    wrap_account: Option<&Pubkey>,
+    bond_accounts: Option<(&Pubkey, &Pubkey, &Pubkey)>,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Close.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*trusted_handler, true),
+        AccountMeta::new(*escrow_token_account, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
+        AccountMeta::new(*canceler_token_account, false),
+        AccountMeta::new(*receiver, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    push_native_accounts(&mut accounts, wrap_account);
+    push_bond_accounts(&mut accounts, bond_accounts);
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Fund` instruction
+pub fn fund(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    launcher: &Pubkey,
+    launcher_token_account: &Pubkey,
+    escrow_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Fund { amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*launcher, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*launcher_token_account, false),
+        AccountMeta::new(*escrow_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `BatchPayout` instruction
+pub fn batch_payout(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    reputation_oracle_token_account: &Pubkey,
+    recording_oracle_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    factory: &Pubkey,
+    payouts: &[(Pubkey, u64)],
+    payout_receipts: &[(PayoutId, Pubkey)],
+    wrap_account: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    if payouts.is_empty()
+        || payouts.len() > u8::MAX as usize
+        || !(payout_receipts.is_empty() || payout_receipts.len() == payouts.len())
+    {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = EscrowInstruction::BatchPayout {
+        amounts: payouts.iter().map(|(_, amount)| *amount).collect(),
+        payout_ids: payout_receipts
+            .iter()
+            .map(|(payout_id, _)| *payout_id)
+            .collect(),
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*escrow, false),
+        if payout_receipts.is_empty() {
+            AccountMeta::new_readonly(*trusted_handler, true)
+        } else {
+            AccountMeta::new(*trusted_handler, true)
+        },
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*escrow_token_account, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
+        AccountMeta::new(*reputation_oracle_token_account, false),
+        AccountMeta::new(*recording_oracle_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*factory, false),
+    ];
+    accounts.extend(
+        payouts
+            .iter()
+            .map(|(recipient, _)| AccountMeta::new(*recipient, false)),
+    );
+    if !payout_receipts.is_empty() {
+        accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+        accounts.extend(
+            payout_receipts
+                .iter()
+                .map(|(_, payout_receipt)| AccountMeta::new(*payout_receipt, false)),
+        );
+    }
+
+    push_native_accounts(&mut accounts, wrap_account);
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `StorePayoutsRoot` instruction
+pub fn store_payouts_root(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    factory: &Pubkey,
+    payouts_root: &MerkleHash,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::StorePayoutsRoot {
+        payouts_root: *payouts_root,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*trusted_handler, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*factory, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Claim` instruction
+pub fn claim(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    claimant: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    recipient_token_account: &Pubkey,
+    reputation_oracle_token_account: &Pubkey,
+    recording_oracle_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    factory: &Pubkey,
+    claim_receipt: &Pubkey,
+    index: u64,
+    amount: u64,
+    proof: &[MerkleHash],
+    wrap_account: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    if proof.len() > u8::MAX as usize {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = EscrowInstruction::Claim {
+        index,
+        amount,
+        proof: proof.to_vec(),
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*claimant, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*escrow_token_account, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
+        AccountMeta::new(*recipient_token_account, false),
+        AccountMeta::new(*reputation_oracle_token_account, false),
+        AccountMeta::new(*recording_oracle_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*factory, false),
+        AccountMeta::new(*claim_receipt, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    push_native_accounts(&mut accounts, wrap_account);
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Reclaim` instruction
+pub fn reclaim(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    canceler_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    wrap_account: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Reclaim.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*escrow_token_account, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
+        AccountMeta::new(*canceler_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    push_native_accounts(&mut accounts, wrap_account);
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `ExtendExpiration` instruction
+pub fn extend_expiration(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    factory: &Pubkey,
+    additional_seconds: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::ExtendExpiration { additional_seconds }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*trusted_handler, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*factory, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `AddTrustedHandlers` instruction
+pub fn add_trusted_handlers(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    handlers: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    if handlers.len() > MAX_TRUSTED_HANDLERS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = EscrowInstruction::AddTrustedHandlers {
+        handlers: handlers.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*trusted_handler, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `RemoveTrustedHandlers` instruction
+pub fn remove_trusted_handlers(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    handlers: &[Pubkey],
+) -> Result<Instruction, ProgramError> {
+    if handlers.len() > MAX_TRUSTED_HANDLERS {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let data = EscrowInstruction::RemoveTrustedHandlers {
+        handlers: handlers.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*trusted_handler, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `StoreIntermediateResults` instruction
+pub fn store_intermediate_results(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    results_url: &DataUrl,
+    results_hash: &DataHash,
+    factory: &Pubkey,
+    results_log: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::StoreIntermediateResults {
+        results_url: *results_url,
+        results_hash: *results_hash,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*escrow, false),
+        AccountMeta::new(*trusted_handler, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*factory, false),
+        AccountMeta::new(*results_log, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `WriteUrlData` instruction
+pub fn write_url_data(
+    escrow_program_id: &Pubkey,
+    url_data: &Pubkey,
+    authority: &Pubkey,
+    offset: u32,
+    data: &[u8],
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::WriteUrlData {
+        offset,
+        data: data.to_vec(),
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*url_data, false),
+        AccountMeta::new_readonly(*authority, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Migrate` instruction
+pub fn migrate(escrow_program_id: &Pubkey, escrow: &Pubkey) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Migrate.pack();
+
+    let accounts = vec![AccountMeta::new(*escrow, false)];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Migrate` instruction copying the legacy escrow into the new account
+pub fn migrate_legacy(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    new_escrow: &Pubkey,
+    launcher: &Pubkey,
+    token_account: &Pubkey,
+    authority: &Pubkey,
+    receiver: &Pubkey,
+    factory: &Pubkey,
+    factory_entry: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Migrate.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*new_escrow, false),
+        AccountMeta::new(*launcher, true),
+        AccountMeta::new(*token_account, false),
+        AccountMeta::new_readonly(*authority, false),
+        AccountMeta::new(*receiver, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(*factory, false),
+        AccountMeta::new(*factory_entry, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `SettleFees` instruction
+pub fn settle_fees(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    reputation_oracle_token_account: &Pubkey,
+    recording_oracle_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    factory: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SettleFees.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*trusted_handler, true),
+        AccountMeta::new(*escrow_token_account, false),
+        AccountMeta::new_readonly(*escrow_authority, false),
+        AccountMeta::new(*reputation_oracle_token_account, false),
+        AccountMeta::new(*recording_oracle_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*factory, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `FundNative` instruction
+pub fn fund_native(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    launcher: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    token_program_id: &Pubkey,
+    wrap_account: &Pubkey,
+    amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FundNative { amount }.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*launcher, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*escrow_token_account, false),
+        AccountMeta::new_readonly(*escrow_authority, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    push_native_accounts(&mut accounts, Some(wrap_account));
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Dispute` instruction
+pub fn dispute(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    launcher: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Dispute.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*launcher, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `ResolveDispute` instruction
+pub fn resolve_dispute(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    admin: &Pubkey,
+    factory: &Pubkey,
+    accept_results: bool,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::ResolveDispute { accept_results }.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*admin, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(*factory, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `SlashBonds` instruction
+pub fn slash_bonds(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    launcher: &Pubkey,
+    bond_vault: &Pubkey,
+    receiver_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    reputation_oracle_amount: u64,
+    recording_oracle_amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::SlashBonds {
+        reputation_oracle_amount,
+        recording_oracle_amount,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*launcher, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*bond_vault, false),
+        AccountMeta::new(*receiver_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `VestedPayout` instruction
+pub fn vested_payout(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    trusted_handler: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    recipient: &Pubkey,
+    reputation_oracle_token_account: &Pubkey,
+    recording_oracle_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+    amount: u64,
+    factory: &Pubkey,
+    vesting_schedule: &Pubkey,
+    start: UnixTimestamp,
+    cliff: UnixTimestamp,
+    end: UnixTimestamp,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::VestedPayout {
+        amount,
+        start,
+        cliff,
+        end,
+    }
+    .pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new(*trusted_handler, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*escrow_token_account, false),
+        AccountMeta::new_readonly(*escrow_authority, false),
+        AccountMeta::new_readonly(*recipient, false),
+        AccountMeta::new(*reputation_oracle_token_account, false),
+        AccountMeta::new(*recording_oracle_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*factory, false),
+        AccountMeta::new(*vesting_schedule, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `Withdraw` instruction
+pub fn withdraw(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    vesting_schedule: &Pubkey,
+    escrow_token_account: &Pubkey,
+    escrow_authority: &Pubkey,
+    recipient: &Pubkey,
+    token_program_id: &Pubkey,
+    wrap_account: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::Withdraw.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*vesting_schedule, false),
+        AccountMeta::new(*escrow_token_account, false),
+        escrow_authority_meta(escrow_authority, wrap_account),
+        AccountMeta::new(*recipient, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    push_native_accounts(&mut accounts, wrap_account);
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `ReleaseBonds` instruction
+pub fn release_bonds(
+    escrow_program_id: &Pubkey,
+    escrow: &Pubkey,
+    oracle: &Pubkey,
+    bond_vault: &Pubkey,
+    reputation_oracle_token_account: &Pubkey,
+    recording_oracle_token_account: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::ReleaseBonds.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*escrow, false),
+        AccountMeta::new_readonly(*oracle, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new(*bond_vault, false),
+        AccountMeta::new(*reputation_oracle_token_account, false),
+        AccountMeta::new(*recording_oracle_token_account, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates `FactoryApproveOracle` instruction
+pub fn factory_approve_oracle(
+    escrow_program_id: &Pubkey,
+    factory: &Pubkey,
+    admin: &Pubkey,
+    oracle_approval: &Pubkey,
+    oracle: &Pubkey,
+    min_scored_amount: u64,
+) -> Result<Instruction, ProgramError> {
+    let data = EscrowInstruction::FactoryApproveOracle { min_scored_amount }.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*factory, false),
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*oracle_approval, false),
+        AccountMeta::new_readonly(*oracle, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *escrow_program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Oracle signs the setup when it posts a bond
+fn oracle_meta(oracle: &Pubkey, bond: u64) -> AccountMeta {
+    AccountMeta::new_readonly(*oracle, bond != 0)
+}
+
+/// Oracle token account is writable when the bond is taken from it
+fn oracle_token_account_meta(token_account: &Pubkey, bond: u64) -> AccountMeta {
+    if bond != 0 {
+        AccountMeta::new(*token_account, false)
+    } else {
+        AccountMeta::new_readonly(*token_account, false)
+    }
+}
+
+/// Escrow authority is writable when it unwraps SOL of the native mint escrow
+fn escrow_authority_meta(escrow_authority: &Pubkey, wrap_account: Option<&Pubkey>) -> AccountMeta {
+    if wrap_account.is_some() {
+        AccountMeta::new(*escrow_authority, false)
+    } else {
+        AccountMeta::new_readonly(*escrow_authority, false)
+    }
+}
+
+/// Appends the accounts used to wrap and unwrap SOL of the native mint escrow
+fn push_native_accounts(accounts: &mut Vec<AccountMeta>, wrap_account: Option<&Pubkey>) {
+    if let Some(wrap_account) = wrap_account {
+        accounts.push(AccountMeta::new(*wrap_account, false));
+        accounts.push(AccountMeta::new_readonly(
+            spl_token::native_mint::id(),
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+}
+
+/// Appends the bond vault and the oracle token accounts the bonds are returned to
+fn push_bond_accounts(
+    accounts: &mut Vec<AccountMeta>,
+    bond_accounts: Option<(&Pubkey, &Pubkey, &Pubkey)>,
+) {
+    if let Some((bond_vault, reputation_oracle_token_account, recording_oracle_token_account)) =
+        bond_accounts
+    {
+        accounts.push(AccountMeta::new(*bond_vault, false));
+        accounts.push(AccountMeta::new(*reputation_oracle_token_account, false));
+        accounts.push(AccountMeta::new(*recording_oracle_token_account, false));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::state::URL_LEN;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_payout_ids() {
+        let first = Pubkey::new_unique();
+        let second = Pubkey::new_unique();
+        let ids = payout_ids(&[(first, 1000), (second, 2000)]);
+        assert_ne!(ids[0], ids[1]);
+
+        // Same payouts in another order
+        assert_eq!(
+            payout_ids(&[(second, 2000), (first, 1000)]),
+            vec![ids[1], ids[0]]
+        );
+
+        // Another list of payouts
+        assert_ne!(payout_ids(&[(first, 1000), (second, 3000)])[0], ids[0]);
+
+        // Recipient repeated in the list gets an ID for every row
+        let ids = payout_ids(&[(first, 1000), (second, 2000), (first, 1000)]);
+        assert_ne!(ids[0], ids[2]);
+        assert_ne!(ids[0], ids[1]);
+        assert_ne!(ids[1], ids[2]);
+        let mut reordered = payout_ids(&[(first, 1000), (first, 1000), (second, 2000)]);
+        reordered.sort();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(reordered, sorted);
+    }
+
+    #[test]
+    fn test_instruction_packing() {
+        let check = EscrowInstruction::Initialize {
+            duration: 2592000, // 0x0000000000278D00
+        };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![2, 0x00, 0x8D, 0x27, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Setup {
+            reputation_oracle_stake: 550, // 0x0226
+            recording_oracle_stake: 1025, // 0x0401
+            manifest_url: DataUrl::new_from_array([10; URL_LEN]),
+            manifest_hash: DataHash::new(HashAlgorithm::Sha256, &[11; 32]).unwrap(),
+            defer_fees: true,
+            dispute_window: 86400,        // 0x0000000000015180
+            reputation_oracle_bond: 1000, // 0x00000000000003E8
+            recording_oracle_bond: 0,
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![3, 0x26, 0x02, 0x01, 0x04, 0x00, 0x01];
+        expect.extend(&[10; URL_LEN]);
+        expect.extend(&[0x12, 32]);
+        expect.extend(&[11; 32]);
+        expect.push(1);
+        expect.extend(&[0x80, 0x51, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0xE8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0; 8]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::StoreResults {
+            final_results_url: DataUrl::new_from_array([21; URL_LEN]),
+            final_results_hash: DataHash::new(HashAlgorithm::Keccak256, &[22; 32]).unwrap(),
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![4, 0x00, 0x01];
+        expect.extend(&[21; URL_LEN]);
+        expect.extend(&[0x1b, 32]);
+        expect.extend(&[22; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::StoreResults {
+            final_results_url: DataUrl::from_str("http://result.com").unwrap(),
+            final_results_hash: DataHash::new(HashAlgorithm::Keccak256, &[22; 32]).unwrap(),
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![4, 17, 0];
+        expect.extend(b"http://result.com");
+        expect.extend(&[0x1b, 32]);
+        expect.extend(&[22; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::StoreResults {
+            final_results_url: DataUrl::new_from_account(&Pubkey::new_from_array([21; 32])),
+            final_results_hash: DataHash::new(HashAlgorithm::Keccak256, &[22; 32]).unwrap(),
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![4, 0xFF, 0xFF];
+        expect.extend(&[21; 32]);
+        expect.extend(&[0x1b, 32]);
+        expect.extend(&[22; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        // URL length exceeding URL_LEN
+        let mut expect: Vec<u8> = vec![4, 0x01, 0x01];
+        expect.extend(&[21; URL_LEN + 1]);
+        expect.extend(&[0x1b, 32]);
+        expect.extend(&[22; 32]);
+        assert!(EscrowInstruction::unpack(&expect).is_err());
+
+        let check = EscrowInstruction::StoreFinalAmounts {
+            total_amount: 1000000,  // 0x00000000000F4240
+            total_recipients: 1000, // 0x00000000000003E8
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![5];
+        expect.extend(&[0x40, 0x42, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0xE8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Payout {
+            amount: 1000000000000, // 0x000000E8D4A51000
+            payout_id: None,
+            worker_score: None,
+        };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![6, 0x00, 0x10, 0xA5, 0xD4, 0xE8, 0x00, 0x00, 0x00];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Payout {
+            amount: 1000000000000, // 0x000000E8D4A51000
+            payout_id: Some([1; 32]),
+            worker_score: None,
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![6, 0x00, 0x10, 0xA5, 0xD4, 0xE8, 0x00, 0x00, 0x00];
+        expect.extend(&[1; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Payout {
+            amount: 1000000000000, // 0x000000E8D4A51000
+            payout_id: None,
+            worker_score: Some(9500), // 0x251C
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![6, 0x00, 0x10, 0xA5, 0xD4, 0xE8, 0x00, 0x00, 0x00];
+        expect.extend(&[0x1C, 0x25]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Payout {
+            amount: 1000000000000, // 0x000000E8D4A51000
+            payout_id: Some([1; 32]),
+            worker_score: Some(9500), // 0x251C
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![6, 0x00, 0x10, 0xA5, 0xD4, 0xE8, 0x00, 0x00, 0x00];
+        expect.extend(&[1; 32]);
+        expect.extend(&[0x1C, 0x25]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Cancel;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![7];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Complete;
+        let packed = check.pack();
         let expect: Vec<u8> = vec![8];
         assert_eq!(packed, expect);
         let unpacked = EscrowInstruction::unpack(&expect).unwrap();
         assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FactorySetAdmin;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![9];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FactorySetVersion { version: 2 };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![10, 2];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FactorySetPolicy {
+            token_mint: COption::Some(Pubkey::new_from_array([1; 32])),
+            max_duration: 2592000,  // 0x0000000000278D00
+            max_oracle_stake: 2000, // 0x07D0
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![11, 1];
+        expect.extend(&[1; 32]);
+        expect.extend(&[0x00, 0x8D, 0x27, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0xD0, 0x07]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FactorySetPolicy {
+            token_mint: COption::None,
+            max_duration: 0,
+            max_oracle_stake: 10000, // 0x2710
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![11, 0];
+        expect.extend(&[0; 8]);
+        expect.extend(&[0x10, 0x27]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FactorySetPaused { paused: true };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![12, 1];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Close;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![13];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Fund { amount: 1000 };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![14, 0xE8, 0x03, 0, 0, 0, 0, 0, 0];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::BatchPayout {
+            amounts: vec![1000, 2],
+            payout_ids: vec![],
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![
+            15, 2, 0xE8, 0x03, 0, 0, 0, 0, 0, 0, 0x02, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        // Amounts count exceeding the data size
+        assert!(EscrowInstruction::unpack(&expect[..10]).is_err());
+
+        let check = EscrowInstruction::BatchPayout {
+            amounts: vec![1000, 2],
+            payout_ids: vec![[1; 32], [2; 32]],
+        };
+        let packed = check.pack();
+        expect.extend(&[1; 32]);
+        expect.extend(&[2; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        // Payout IDs count less than amounts count
+        assert!(EscrowInstruction::unpack(&expect[..50]).is_err());
+
+        let check = EscrowInstruction::StorePayoutsRoot {
+            payouts_root: [1; 32],
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![16];
+        expect.extend(&[1; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Claim {
+            index: 3,
+            amount: 1000,
+            proof: vec![[1; 32], [2; 32]],
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![17, 3, 0, 0, 0, 0, 0, 0, 0, 0xE8, 0x03, 0, 0, 0, 0, 0, 0, 2];
+        expect.extend(&[1; 32]);
+        expect.extend(&[2; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Reclaim;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![18];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::ExtendExpiration {
+            additional_seconds: 3600,
+        };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![19, 0x10, 0x0E, 0, 0, 0, 0, 0, 0];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::AddTrustedHandlers {
+            handlers: vec![
+                Pubkey::new_from_array([1; 32]),
+                Pubkey::new_from_array([2; 32]),
+            ],
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![20, 2];
+        expect.extend(&[1; 32]);
+        expect.extend(&[2; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::RemoveTrustedHandlers {
+            handlers: vec![Pubkey::new_from_array([3; 32])],
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![21, 1];
+        expect.extend(&[3; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::StoreIntermediateResults {
+            results_url: DataUrl::new_from_array([23; URL_LEN]),
+            results_hash: DataHash::new(HashAlgorithm::Sha256, &[24; 32]).unwrap(),
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![22, 0x00, 0x01];
+        expect.extend(&[23; URL_LEN]);
+        expect.extend(&[0x12, 32]);
+        expect.extend(&[24; 32]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::WriteUrlData {
+            offset: 1000,
+            data: b"https://".to_vec(),
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![23, 0xE8, 0x03, 0, 0];
+        expect.extend(b"https://");
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Migrate;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![24];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::SettleFees;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![25];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FundNative {
+            amount: 1_000_000_000, // 0x000000003B9ACA00
+        };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![26, 0x00, 0xCA, 0x9A, 0x3B, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Dispute;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![27];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::ResolveDispute {
+            accept_results: true,
+        };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![28, 1];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::SlashBonds {
+            reputation_oracle_amount: 256, // 0x0000000000000100
+            recording_oracle_amount: 1,
+        };
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![29, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::VestedPayout {
+            amount: 1000,      // 0x00000000000003E8
+            start: 1612137600, // 0x0000000060174480
+            cliff: 1612224000, // 0x0000000060189600
+            end: 1614556800,   // 0x00000000603C2E80
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![30];
+        expect.extend(&[0xE8, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0x80, 0x44, 0x17, 0x60, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0x00, 0x96, 0x18, 0x60, 0x00, 0x00, 0x00, 0x00]);
+        expect.extend(&[0x80, 0x2E, 0x3C, 0x60, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::Withdraw;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![31];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::ReleaseBonds;
+        let packed = check.pack();
+        let expect: Vec<u8> = vec![32];
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
+
+        let check = EscrowInstruction::FactoryApproveOracle {
+            min_scored_amount: 1000000,
+        };
+        let packed = check.pack();
+        let mut expect: Vec<u8> = vec![33];
+        expect.extend(&[0x40, 0x42, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(packed, expect);
+        let unpacked = EscrowInstruction::unpack(&expect).unwrap();
+        assert_eq!(unpacked, check);
     }
 }
@@ -3,7 +3,9 @@
 //! Human Protocol Escrow Contract
 
 pub mod error;
+pub mod event;
 pub mod instruction;
+pub mod merkle;
 pub mod processor;
 pub mod state;
 
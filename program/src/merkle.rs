@@ -0,0 +1,112 @@
+//! Merkle tree of claimable payouts
+
+use solana_program::{hash::hashv, pubkey::Pubkey};
+
+/// Size of the tree node hash
+pub const MERKLE_HASH_LEN: usize = 32;
+
+/// Tree node hash
+pub type MerkleHash = [u8; MERKLE_HASH_LEN];
+
+// Domain prefixes, so that a leaf can never be passed off as an inner node
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Calculates the hash of the `(index, recipient, amount)` leaf
+pub fn leaf_hash(index: u64, recipient: &Pubkey, amount: u64) -> MerkleHash {
+    hashv(&[
+        LEAF_PREFIX,
+        &index.to_le_bytes(),
+        recipient.as_ref(),
+        &amount.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Calculates the hash of the inner node, children are sorted so that proofs
+/// don't have to carry the node positions
+pub fn node_hash(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    if left <= right {
+        hashv(&[NODE_PREFIX, left, right]).to_bytes()
+    } else {
+        hashv(&[NODE_PREFIX, right, left]).to_bytes()
+    }
+}
+
+/// Checks if the leaf belongs to the tree with the given root
+pub fn verify_proof(proof: &[MerkleHash], root: &MerkleHash, leaf: MerkleHash) -> bool {
+    let computed = proof
+        .iter()
+        .fold(leaf, |node, sibling| node_hash(&node, sibling));
+    computed == *root
+}
+
+/// Calculates the tree root, a node without a sibling moves up unchanged
+pub fn merkle_root(leaves: &[MerkleHash]) -> Option<MerkleHash> {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level.first().copied()
+}
+
+/// Builds the proof of the leaf with the given index
+pub fn merkle_proof(leaves: &[MerkleHash], index: usize) -> Option<Vec<MerkleHash>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut proof = vec![];
+    let mut level = leaves.to_vec();
+    let mut index = index;
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        level = next_level(&level);
+        index /= 2;
+    }
+    Some(proof)
+}
+
+fn next_level(level: &[MerkleHash]) -> Vec<MerkleHash> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash(left, right),
+            [single] => *single,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merkle_proofs() {
+        let recipients: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let leaves: Vec<MerkleHash> = recipients
+            .iter()
+            .enumerate()
+            .map(|(index, recipient)| leaf_hash(index as u64, recipient, 1000 * index as u64))
+            .collect();
+        let root = merkle_root(&leaves).unwrap();
+
+        for (index, recipient) in recipients.iter().enumerate() {
+            let proof = merkle_proof(&leaves, index).unwrap();
+            let leaf = leaf_hash(index as u64, recipient, 1000 * index as u64);
+            assert!(verify_proof(&proof, &root, leaf));
+
+            // Wrong amount or index
+            let leaf = leaf_hash(index as u64, recipient, 1000 * index as u64 + 1);
+            assert!(!verify_proof(&proof, &root, leaf));
+            let leaf = leaf_hash(index as u64 + 1, recipient, 1000 * index as u64);
+            assert!(!verify_proof(&proof, &root, leaf));
+        }
+
+        assert_eq!(merkle_proof(&leaves, 5), None);
+        assert_eq!(merkle_root(&[]), None);
+        assert_eq!(merkle_root(&leaves[..1]), Some(leaves[0]));
+    }
+}
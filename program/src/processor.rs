@@ -1,13 +1,15 @@
 //! Program state processor
 
 use crate::error::EscrowError;
-use crate::instruction::EscrowInstruction;
+use crate::event::EscrowEvent;
+use crate::instruction::{EscrowInstruction, PayoutId};
+use crate::merkle::{self, MerkleHash};
 use crate::state::*;
 use num_traits::FromPrimitive;
-use solana_program::program::invoke_signed;
+use solana_program::program::{invoke, invoke_signed};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
-    clock::Clock,
+    clock::{Clock, UnixTimestamp},
     decode_error::DecodeError,
     entrypoint::ProgramResult,
     msg,
@@ -15,9 +17,55 @@ use solana_program::{
     program_option::COption,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
+    rent::Rent,
+    system_instruction, system_program,
     sysvar::Sysvar,
 };
 use spl_token::state::Account as TokenAccount;
+use std::convert::TryInto;
+
+/// Escrow participant roles, every instruction is allowed only for some of them
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Role {
+    /// Escrow launcher
+    Launcher,
+    /// Escrow canceler
+    Canceler,
+    /// Reputation oracle set up for the escrow
+    ReputationOracle,
+    /// Recording oracle set up for the escrow
+    RecordingOracle,
+    /// Additional handler acting on behalf of the launcher
+    TrustedHandler,
+}
+
+/// Accounts used to wrap and unwrap native SOL of the native mint escrow
+struct NativeAccounts<'a> {
+    /// Temporary native token account, program address derived from the escrow
+    wrap_account_info: AccountInfo<'a>,
+    /// Wrap account bump seed
+    wrap_bump_seed: u8,
+    /// Native mint
+    native_mint_info: AccountInfo<'a>,
+    /// Rent sysvar, required to initialize the wrap account
+    rent_info: AccountInfo<'a>,
+    /// Rent
+    rent: Rent,
+    /// System program
+    system_program_info: AccountInfo<'a>,
+}
+
+/// Bond vault and the oracle token accounts the bonds are returned to
+struct BondAccounts<'a> {
+    /// Token account holding the oracle bonds, program address derived from the escrow
+    bond_vault_info: AccountInfo<'a>,
+    /// Bond vault bump seed
+    bond_vault_bump_seed: u8,
+    /// Reputation oracle token account
+    reputation_oracle_token_account_info: AccountInfo<'a>,
+    /// Recording oracle token account
+    recording_oracle_token_account_info: AccountInfo<'a>,
+}
 
 /// Program state handler.
 pub struct Processor {}
@@ -44,41 +92,341 @@ impl Processor {
         Pubkey::find_program_address(&[&escrow_account_key.to_bytes()[..32]], escrow_program_id)
     }
 
-    /// Verifies if transaction is signed by the trusted handler
-    fn check_trusted_handler(escrow: &Escrow, trusted_handler_info: &AccountInfo) -> ProgramResult {
-        // Check if instruction is signed by the trusted handler
-        if !trusted_handler_info.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
+    /// Generates address and seed bump for the factory entry with the given index
+    pub fn find_factory_entry_address(
+        escrow_program_id: &Pubkey,
+        factory_account_key: &Pubkey,
+        index: u64,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                FACTORY_ENTRY_SEED,
+                &factory_account_key.to_bytes()[..32],
+                &index.to_le_bytes(),
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the claim receipt of the given payouts tree leaf
+    pub fn find_claim_receipt_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+        index: u64,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                CLAIM_RECEIPT_SEED,
+                &escrow_account_key.to_bytes()[..32],
+                &index.to_le_bytes(),
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the receipt of the payout with the given ID
+    pub fn find_payout_receipt_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+        payout_id: &PayoutId,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                PAYOUT_RECEIPT_SEED,
+                &escrow_account_key.to_bytes()[..32],
+                payout_id,
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the intermediate results log of the escrow
+    pub fn find_results_log_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[RESULTS_LOG_SEED, &escrow_account_key.to_bytes()[..32]],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the temporary native token account of the escrow
+    pub fn find_wrap_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[WRAP_SEED, &escrow_account_key.to_bytes()[..32]],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the oracle bond vault of the escrow
+    pub fn find_bond_vault_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[BOND_VAULT_SEED, &escrow_account_key.to_bytes()[..32]],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the reputation account of the worker
+    pub fn find_worker_reputation_address(
+        escrow_program_id: &Pubkey,
+        factory_account_key: &Pubkey,
+        worker: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                WORKER_REPUTATION_SEED,
+                &factory_account_key.to_bytes()[..32],
+                &worker.to_bytes()[..32],
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the receipt of the escrow job of the worker
+    pub fn find_worker_job_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+        worker: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                WORKER_JOB_SEED,
+                &escrow_account_key.to_bytes()[..32],
+                &worker.to_bytes()[..32],
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the approval of the reputation oracle
+    pub fn find_oracle_approval_address(
+        escrow_program_id: &Pubkey,
+        factory_account_key: &Pubkey,
+        oracle: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                ORACLE_APPROVAL_SEED,
+                &factory_account_key.to_bytes()[..32],
+                &oracle.to_bytes()[..32],
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Generates address and seed bump for the vesting schedule of the payout recipient
+    pub fn find_vesting_schedule_address(
+        escrow_program_id: &Pubkey,
+        escrow_account_key: &Pubkey,
+        recipient: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                VESTING_SCHEDULE_SEED,
+                &escrow_account_key.to_bytes()[..32],
+                &recipient.to_bytes()[..32],
+            ],
+            escrow_program_id,
+        )
+    }
+
+    /// Checks if the escrow holds wrapped SOL
+    fn is_native(escrow: &Escrow) -> bool {
+        escrow.token_mint == spl_token::native_mint::id()
+    }
+
+    /// Checks if the account is a wallet rather than a token account, native SOL
+    /// escrows unwrap payouts sent to such accounts
+    fn is_system_account(account_info: &AccountInfo) -> bool {
+        *account_info.owner == system_program::id()
+    }
+
+    /// Reads the accounts trailing the instruction accounts of the native mint escrow
+    fn next_native_accounts<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        program_id: &Pubkey,
+        escrow_info: &AccountInfo,
+        escrow: &Escrow,
+        account_info_iter: &mut I,
+    ) -> Result<Option<NativeAccounts<'b>>, ProgramError> {
+        if !Self::is_native(escrow) {
+            return Ok(None);
         }
 
-        // Check is signer is either launcher or canceler authority
-        if *trusted_handler_info.key == escrow.launcher {
-            return Ok(());
+        let wrap_account_info = next_account_info(account_info_iter)?;
+        let native_mint_info = next_account_info(account_info_iter)?;
+        let rent_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (wrap_key, wrap_bump_seed) = Self::find_wrap_address(program_id, escrow_info.key);
+        if *wrap_account_info.key != wrap_key
+            || *native_mint_info.key != escrow.token_mint
+            || *system_program_info.key != system_program::id()
+        {
+            return Err(ProgramError::InvalidInstructionData);
         }
-        if *trusted_handler_info.key == escrow.canceler {
-            return Ok(());
+
+        Ok(Some(NativeAccounts {
+            wrap_account_info: wrap_account_info.clone(),
+            wrap_bump_seed,
+            native_mint_info: native_mint_info.clone(),
+            rent_info: rent_info.clone(),
+            rent: Rent::from_account_info(rent_info)?,
+            system_program_info: system_program_info.clone(),
+        }))
+    }
+
+    /// Reads the bond accounts trailing the instruction accounts of the bonded escrow
+    fn next_bond_accounts<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        program_id: &Pubkey,
+        escrow_info: &AccountInfo,
+        escrow: &Escrow,
+        account_info_iter: &mut I,
+    ) -> Result<Option<BondAccounts<'b>>, ProgramError> {
+        if !escrow.bonded {
+            return Ok(None);
         }
 
-        // Check for reputation and recording oracles
-        if let COption::Some(pubkey) = escrow.reputation_oracle {
-            if *trusted_handler_info.key == pubkey {
-                return Ok(());
-            }
+        let bond_vault_info = next_account_info(account_info_iter)?;
+        let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
+
+        let (bond_vault_key, bond_vault_bump_seed) =
+            Self::find_bond_vault_address(program_id, escrow_info.key);
+        if *bond_vault_info.key != bond_vault_key
+            || COption::Some(*reputation_oracle_token_account_info.key)
+                != escrow.reputation_oracle_token_account
+            || COption::Some(*recording_oracle_token_account_info.key)
+                != escrow.recording_oracle_token_account
+        {
+            return Err(ProgramError::InvalidInstructionData);
         }
-        if let COption::Some(pubkey) = escrow.recording_oracle {
-            if *trusted_handler_info.key == pubkey {
-                return Ok(());
+
+        Ok(Some(BondAccounts {
+            bond_vault_info: bond_vault_info.clone(),
+            bond_vault_bump_seed,
+            reputation_oracle_token_account_info: reputation_oracle_token_account_info.clone(),
+            recording_oracle_token_account_info: recording_oracle_token_account_info.clone(),
+        }))
+    }
+
+    /// Checks if the key has the role in the escrow
+    fn has_role(escrow: &Escrow, key: &Pubkey, role: Role) -> bool {
+        match role {
+            Role::Launcher => *key == escrow.launcher,
+            Role::Canceler => *key == escrow.canceler,
+            Role::ReputationOracle => escrow.reputation_oracle == COption::Some(*key),
+            Role::RecordingOracle => escrow.recording_oracle == COption::Some(*key),
+            Role::TrustedHandler => {
+                *key != Pubkey::default() && escrow.trusted_handlers.contains(key)
             }
         }
+    }
+
+    /// Verifies if transaction is signed by one of the roles allowed to send the instruction
+    fn check_role(
+        escrow: &Escrow,
+        signer_info: &AccountInfo,
+        allowed_roles: &[Role],
+        denied: EscrowError,
+    ) -> ProgramResult {
+        if !signer_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if allowed_roles
+            .iter()
+            .any(|role| Self::has_role(escrow, signer_info.key, *role))
+        {
+            Ok(())
+        } else {
+            Err(denied.into())
+        }
+    }
+
+    /// Verifies if transaction is signed by the factory administrator
+    fn get_factory_with_admin_check(
+        factory_info: &AccountInfo,
+        admin_info: &AccountInfo,
+    ) -> Result<Factory, ProgramError> {
+        let factory = Factory::unpack_unchecked(&factory_info.data.borrow())?;
+
+        if !factory.is_initialized() {
+            return Err(EscrowError::FactoryNotInitialized.into());
+        }
+
+        // Check if instruction is signed by the factory administrator
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *admin_info.key != factory.admin {
+            return Err(EscrowError::UnauthorizedSigner.into());
+        }
+
+        Ok(factory)
+    }
+
+    /// Reads the factory escrow belongs to and verifies it is not paused
+    fn get_factory_with_pause_check(
+        escrow: &Escrow,
+        factory_info: &AccountInfo,
+    ) -> Result<Factory, ProgramError> {
+        // Factory account must be the one escrow belongs to
+        if *factory_info.key != escrow.factory {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let factory = Factory::unpack(&factory_info.data.borrow())?;
+
+        if factory.paused {
+            return Err(EscrowError::FactoryPaused.into());
+        }
+
+        Ok(factory)
+    }
+
+    /// SHA-1 hashes stay readable in the existing escrows, but aren't accepted anymore
+    fn check_hash_algorithm(hash: &DataHash) -> ProgramResult {
+        if hash.algorithm() == HashAlgorithm::Sha1 {
+            return Err(EscrowError::UnsupportedHashAlgorithm.into());
+        }
+        Ok(())
+    }
 
-        // Trusted handler not recognized
-        Err(EscrowError::UnauthorizedSigner.into())
+    /// Payouts of the escrow with the dispute window wait until the stored final
+    /// results can't be disputed anymore
+    fn check_dispute_window(escrow: &Escrow, clock: &Clock) -> ProgramResult {
+        if escrow.dispute_window != 0
+            && (escrow.results_stored_at == 0 || clock.unix_timestamp < escrow.dispute_window_end())
+        {
+            return Err(EscrowError::DisputeWindowOpen.into());
+        }
+        Ok(())
     }
 
     fn get_escrow_with_state_check(
         escrow_info: &AccountInfo,
         clock: &Clock,
-        trusted_handler_info: &AccountInfo,
+        signer_info: &AccountInfo,
+        allowed_roles: &[Role],
+        denied: EscrowError,
+        allowed_states: Vec<EscrowState>,
+    ) -> Result<Box<Escrow>, ProgramError> {
+        let escrow = Self::get_escrow_with_expiration_check(escrow_info, clock, allowed_states)?;
+
+        Self::check_role(&escrow, signer_info, allowed_roles, denied)?;
+
+        Ok(escrow)
+    }
+
+    fn get_escrow_with_expiration_check(
+        escrow_info: &AccountInfo,
+        clock: &Clock,
         allowed_states: Vec<EscrowState>,
     ) -> Result<Box<Escrow>, ProgramError> {
         let escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
@@ -88,6 +436,12 @@ impl Processor {
             return Err(ProgramError::UninitializedAccount);
         }
 
+        // Legacy layout has no room for the fee remainders and the later fields,
+        // the escrow must be migrated before anything is paid out
+        if escrow_info.data_len() == Escrow::LEN_V0 {
+            return Err(EscrowError::MigrationRequired.into());
+        }
+
         // Check escrow account expiration
         if escrow.expires < clock.unix_timestamp {
             return Err(EscrowError::EscrowExpired.into());
@@ -98,262 +452,3326 @@ impl Processor {
             return Err(EscrowError::WrongState.into());
         }
 
-        Self::check_trusted_handler(&escrow, trusted_handler_info)?;
-
         Ok(escrow)
     }
 
-    /// Issue a spl_token `Transfer` instruction.
-    #[allow(clippy::too_many_arguments)]
-    pub fn token_transfer<'a>(
-        escrow_account_key: &Pubkey,
-        token_program: AccountInfo<'a>,
-        source: AccountInfo<'a>,
-        destination: AccountInfo<'a>,
-        authority: AccountInfo<'a>,
-        bump_seed: u8,
+    /// Reads the escrow funded by the signer and adds the amount to the funded amount
+    fn get_escrow_with_fund_check(
+        escrow_info: &AccountInfo,
+        clock: &Clock,
+        launcher_info: &AccountInfo,
+        token_account_info: &AccountInfo,
         amount: u64,
-    ) -> ProgramResult {
-        let authority_signature_seeds = [&escrow_account_key.to_bytes()[..32], &[bump_seed]];
-        let signers = &[&authority_signature_seeds[..]];
+    ) -> Result<Box<Escrow>, ProgramError> {
+        let mut escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
 
-        let ix = spl_token::instruction::transfer(
-            token_program.key,
-            source.key,
-            destination.key,
-            authority.key,
-            &[],
-            amount,
-        )?;
+        // Check if escrow account exists and is initialized
+        if !escrow.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
 
-        invoke_signed(
-            &ix,
-            &[source, destination, authority, token_program],
-            signers,
-        )
-    }
+        // Check escrow account expiration
+        if escrow.expires < clock.unix_timestamp {
+            return Err(EscrowError::EscrowExpired.into());
+        }
 
-    /// Processes `FactoryInitialize` instruction.
-    pub fn process_factory_initialize(
-        _program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        version: u8,
-    ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let factory_info = next_account_info(account_info_iter)?;
+        // Escrow can be funded until all payouts are done
+        if escrow.state != EscrowState::Launched
+            && escrow.state != EscrowState::Pending
+            && escrow.state != EscrowState::Partial
+        {
+            return Err(EscrowError::WrongState.into());
+        }
 
-        let factory = Factory::unpack_unchecked(&factory_info.data.borrow())?;
+        // Only launcher is allowed to fund the escrow
+        Self::check_role(
+            &escrow,
+            launcher_info,
+            &[Role::Launcher],
+            EscrowError::UnauthorizedFund,
+        )?;
 
-        // Only new unitialized accounts are supported
-        if factory.is_initialized() {
-            return Err(ProgramError::AccountAlreadyInitialized);
+        if *token_account_info.key != escrow.token_account {
+            return Err(ProgramError::InvalidInstructionData);
         }
 
-        let factory = Factory { version };
+        escrow.funded_amount = escrow
+            .funded_amount
+            .checked_add(amount)
+            .ok_or(ProgramError::InvalidInstructionData)?;
 
-        Factory::pack(factory, &mut factory_info.data.borrow_mut())?;
-        Ok(())
+        Ok(escrow)
     }
 
-    /// Processes `Initialize` instruction.
-    pub fn process_initialize(
+    #[allow(clippy::too_many_arguments)]
+    fn check_payout_accounts(
         program_id: &Pubkey,
-        accounts: &[AccountInfo],
-        duration: u64,
+        escrow_info: &AccountInfo,
+        escrow: &Escrow,
+        token_account_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        reputation_oracle_token_account_info: &AccountInfo,
+        recording_oracle_token_account_info: &AccountInfo,
+        token_program_info: &AccountInfo,
     ) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let escrow_info = next_account_info(account_info_iter)?;
-        let factory_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
-
-        let token_mint_info = next_account_info(account_info_iter)?;
-        let token_account_info = next_account_info(account_info_iter)?;
-        let launcher_info = next_account_info(account_info_iter)?;
-        let canceler_info = next_account_info(account_info_iter)?;
-        let canceler_token_account_info = next_account_info(account_info_iter)?;
+        if *token_account_info.key != escrow.token_account
+            || *token_program_info.key != spl_token::id()
+            || *reputation_oracle_token_account_info.key
+                != escrow
+                    .reputation_oracle_token_account
+                    .ok_or(EscrowError::OracleNotInitialized)?
+            || *recording_oracle_token_account_info.key
+                != escrow
+                    .recording_oracle_token_account
+                    .ok_or(EscrowError::OracleNotInitialized)?
+            || *authority_info.key
+                != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(())
+    }
 
-        let escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
+    /// Calculates reputation and recording oracle fees of a single payout and
+    /// returns them with the amount left for the recipient. Fee fractions are
+    /// carried over to the next payout and rounded up with the last one, so that
+    /// the fees add up exactly to the stakes of the total amount.
+    fn calculate_oracle_fees(
+        escrow: &mut Escrow,
+        amount: u64,
+        last: bool,
+    ) -> Result<(u64, u64, u64), ProgramError> {
+        let reputation_oracle_fee_amount = Self::calculate_fee(
+            amount,
+            amount,
+            escrow.reputation_oracle_stake,
+            &mut escrow.reputation_oracle_fee_remainder,
+            last,
+        )?;
+        let recording_oracle_fee_amount = Self::calculate_fee(
+            amount,
+            amount - reputation_oracle_fee_amount,
+            escrow.recording_oracle_stake,
+            &mut escrow.recording_oracle_fee_remainder,
+            last,
+        )?;
+        let recipient_amount = amount
+            .checked_sub(reputation_oracle_fee_amount)
+            .and_then(|amount| amount.checked_sub(recording_oracle_fee_amount))
+            .ok_or(EscrowError::FeeOverflow)?;
+        Ok((
+            reputation_oracle_fee_amount,
+            recording_oracle_fee_amount,
+            recipient_amount,
+        ))
+    }
 
-        let factory = Factory::unpack(&factory_info.data.borrow())?;
+    /// Calculates the oracle fee of the payout, updating the accrued remainder.
+    /// The fee never takes more than `available`, the rest stays accrued.
+    fn calculate_fee(
+        amount: u64,
+        available: u64,
+        stake: u16,
+        remainder: &mut u64,
+        last: bool,
+    ) -> Result<u64, ProgramError> {
+        let basis_points = STAKE_BASIS_POINTS as u128;
+        let accrued = (amount as u128)
+            .checked_mul(stake as u128)
+            .and_then(|accrued| accrued.checked_add(*remainder as u128))
+            .ok_or(EscrowError::FeeOverflow)?;
 
-        // Only new unitialized accounts are supported
-        if escrow.is_initialized() {
-            return Err(ProgramError::AccountAlreadyInitialized);
+        let mut fee = accrued / basis_points;
+        if last && accrued % basis_points != 0 {
+            fee += 1;
         }
+        let fee = fee.min(available as u128);
 
-        // Escrow has to belong to initialized Factory
-        if !factory.is_initialized() {
-            return Err(EscrowError::FactoryNotInitialized.into());
-        }
+        *remainder = accrued
+            .saturating_sub(fee * basis_points)
+            .try_into()
+            .map_err(|_| EscrowError::FeeOverflow)?;
+        fee.try_into().map_err(|_| EscrowError::FeeOverflow.into())
+    }
+
+    /// Checks if the payout totals reach the stored amounts
+    fn is_last_payout(escrow: &Escrow, sent_amount: u64, sent_recipients: u64) -> bool {
+        sent_recipients == escrow.total_recipients && sent_amount == escrow.total_amount
+    }
+
+    /// Checks that the payouts fit into the funded and stored amounts and returns
+    /// the new sent amount and the new number of sent recipients
+    fn check_payout_totals(
+        escrow: &Escrow,
+        amount: u64,
+        recipients: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        // Check funded balance
+        let sent_amount = escrow
+            .sent_amount
+            .checked_add(amount)
+            .ok_or(EscrowError::TooManyPayouts)?;
+        if sent_amount > escrow.funded_amount {
+            return Err(EscrowError::NotEnoughBalance.into());
+        }
+
+        // Check if not too many payouts
+        let sent_recipients = escrow
+            .sent_recipients
+            .checked_add(recipients)
+            .ok_or(EscrowError::TooManyPayouts)?;
+        if (sent_amount > escrow.total_amount) || (sent_recipients > escrow.total_recipients) {
+            return Err(EscrowError::TooManyPayouts.into());
+        }
+
+        Ok((sent_amount, sent_recipients))
+    }
+
+    /// Accrues deferred oracle fees in the escrow until `SettleFees`
+    fn accrue_oracle_fees(
+        escrow: &mut Escrow,
+        reputation_oracle_fee_amount: u64,
+        recording_oracle_fee_amount: u64,
+    ) -> ProgramResult {
+        escrow.reputation_oracle_fee_accrued = escrow
+            .reputation_oracle_fee_accrued
+            .checked_add(reputation_oracle_fee_amount)
+            .ok_or(EscrowError::FeeOverflow)?;
+        escrow.recording_oracle_fee_accrued = escrow
+            .recording_oracle_fee_accrued
+            .checked_add(recording_oracle_fee_amount)
+            .ok_or(EscrowError::FeeOverflow)?;
+        Ok(())
+    }
+
+    /// Sends a single payout to the recipient and the oracle fees, unless the
+    /// fees are deferred
+    #[allow(clippy::too_many_arguments)]
+    fn send_payout<'a>(
+        escrow_info: &AccountInfo<'a>,
+        escrow: &mut Escrow,
+        token_program_info: &AccountInfo<'a>,
+        token_account_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        recipient_token_account_info: &AccountInfo<'a>,
+        reputation_oracle_token_account_info: &AccountInfo<'a>,
+        recording_oracle_token_account_info: &AccountInfo<'a>,
+        native_accounts: Option<&NativeAccounts<'a>>,
+        amount: u64,
+        last: bool,
+    ) -> Result<u64, ProgramError> {
+        // Calculate fees
+        let (reputation_oracle_fee_amount, recording_oracle_fee_amount, recipient_amount) =
+            Self::calculate_oracle_fees(escrow, amount, last)?;
+
+        // Send tokens
+        if recipient_amount != 0 {
+            Self::send_tokens(
+                escrow_info,
+                escrow,
+                token_program_info,
+                token_account_info,
+                authority_info,
+                recipient_token_account_info,
+                native_accounts,
+                recipient_amount,
+            )?;
+        }
+        Self::send_oracle_fees(
+            escrow_info,
+            escrow,
+            token_program_info,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            reputation_oracle_fee_amount,
+            recording_oracle_fee_amount,
+        )?;
+
+        EscrowEvent::PayoutMade {
+            escrow: *escrow_info.key,
+            recipient: *recipient_token_account_info.key,
+            amount: recipient_amount,
+            reputation_oracle_fee: reputation_oracle_fee_amount,
+            recording_oracle_fee: recording_oracle_fee_amount,
+        }
+        .emit();
+        Ok(recipient_amount)
+    }
+
+    /// Sends oracle fees of the payout, deferred fees are accrued in the escrow instead
+    #[allow(clippy::too_many_arguments)]
+    fn send_oracle_fees<'a>(
+        escrow_info: &AccountInfo<'a>,
+        escrow: &mut Escrow,
+        token_program_info: &AccountInfo<'a>,
+        token_account_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        reputation_oracle_token_account_info: &AccountInfo<'a>,
+        recording_oracle_token_account_info: &AccountInfo<'a>,
+        reputation_oracle_fee_amount: u64,
+        recording_oracle_fee_amount: u64,
+    ) -> ProgramResult {
+        if escrow.defer_fees {
+            return Self::accrue_oracle_fees(
+                escrow,
+                reputation_oracle_fee_amount,
+                recording_oracle_fee_amount,
+            );
+        }
+
+        if reputation_oracle_fee_amount != 0 {
+            Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                reputation_oracle_token_account_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                reputation_oracle_fee_amount,
+            )?;
+        }
+        if recording_oracle_fee_amount != 0 {
+            Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                recording_oracle_token_account_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                recording_oracle_fee_amount,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Sends tokens from the escrow token account, tokens of the native SOL escrow
+    /// sent to a system account are unwrapped
+    #[allow(clippy::too_many_arguments)]
+    fn send_tokens<'a>(
+        escrow_info: &AccountInfo<'a>,
+        escrow: &Escrow,
+        token_program_info: &AccountInfo<'a>,
+        token_account_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        recipient_info: &AccountInfo<'a>,
+        native_accounts: Option<&NativeAccounts<'a>>,
+        amount: u64,
+    ) -> ProgramResult {
+        match native_accounts {
+            Some(native_accounts) if Self::is_system_account(recipient_info) => {
+                Self::unwrap_to_authority(
+                    escrow_info,
+                    escrow,
+                    token_program_info,
+                    token_account_info,
+                    authority_info,
+                    native_accounts,
+                    amount,
+                )?;
+                Self::authority_transfer(
+                    escrow_info.key,
+                    native_accounts.system_program_info.clone(),
+                    authority_info.clone(),
+                    recipient_info.clone(),
+                    escrow.bump_seed,
+                    amount,
+                )
+            }
+            _ => Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                recipient_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                amount,
+            ),
+        }
+    }
+
+    /// Moves tokens of the native SOL escrow to the wrap account and closes it, so
+    /// that the escrow authority holds the SOL. The wrap account rent is taken from
+    /// the authority reserve and returned when the account is closed.
+    fn unwrap_to_authority<'a>(
+        escrow_info: &AccountInfo<'a>,
+        escrow: &Escrow,
+        token_program_info: &AccountInfo<'a>,
+        token_account_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        native_accounts: &NativeAccounts<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        Self::create_wrap_account(
+            escrow_info.key,
+            token_program_info.clone(),
+            authority_info.clone(),
+            authority_info.clone(),
+            native_accounts,
+            escrow.bump_seed,
+            0,
+        )?;
+        Self::token_transfer(
+            escrow_info.key,
+            token_program_info.clone(),
+            token_account_info.clone(),
+            native_accounts.wrap_account_info.clone(),
+            authority_info.clone(),
+            escrow.bump_seed,
+            amount,
+        )?;
+        Self::token_close_account(
+            escrow_info.key,
+            token_program_info.clone(),
+            native_accounts.wrap_account_info.clone(),
+            authority_info.clone(),
+            authority_info.clone(),
+            escrow.bump_seed,
+        )
+    }
+
+    /// Creates the receipt program account, fails with `duplicate_error` if it already exists.
+    /// Lamports sent to the receipt address in advance don't make it a receipt.
+    #[allow(clippy::too_many_arguments)]
+    fn create_payout_receipt<'a>(
+        program_id: &Pubkey,
+        payer_info: &AccountInfo<'a>,
+        receipt_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        seeds: &[&[u8]],
+        receipt: PayoutReceipt,
+        duplicate_error: EscrowError,
+    ) -> ProgramResult {
+        let (receipt_key, bump_seed) = Pubkey::find_program_address(seeds, program_id);
+        if *receipt_info.key != receipt_key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if receipt_info.owner == program_id {
+            return Err(duplicate_error.into());
+        }
+
+        let bump_seed = [bump_seed];
+        let mut signature_seeds = seeds.to_vec();
+        signature_seeds.push(&bump_seed);
+        Self::create_program_account(
+            program_id,
+            payer_info.clone(),
+            receipt_info.clone(),
+            system_program_info.clone(),
+            rent,
+            PayoutReceipt::LEN,
+            &signature_seeds,
+        )?;
+
+        PayoutReceipt::pack(receipt, &mut receipt_info.data.borrow_mut())
+    }
+
+    /// Records the scored job in the reputation of the worker owning the payment
+    /// recipient, the reputation account is created with the first job. The
+    /// escrow is counted as a single job, further scored payouts of the escrow
+    /// to the same worker only add to the earnings.
+    #[allow(clippy::too_many_arguments)]
+    fn update_worker_reputation<'a>(
+        program_id: &Pubkey,
+        escrow_info: &AccountInfo<'a>,
+        escrow: &Escrow,
+        oracle_info: &AccountInfo<'a>,
+        recipient_info: &AccountInfo<'a>,
+        worker_reputation_info: &AccountInfo<'a>,
+        worker_job_info: &AccountInfo<'a>,
+        oracle_approval_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+        amount: u64,
+        earned: u64,
+        worker_score: u16,
+    ) -> ProgramResult {
+        if worker_score > MAX_WORKER_SCORE {
+            return Err(EscrowError::ScoreOutOfBounds.into());
+        }
+
+        // Anyone can launch an escrow naming themselves the reputation oracle,
+        // so only the oracles approved by the factory can score the workers
+        let (oracle_approval_key, _) =
+            Self::find_oracle_approval_address(program_id, &escrow.factory, oracle_info.key);
+        if *oracle_approval_info.key != oracle_approval_key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        if oracle_approval_info.owner != program_id {
+            return Err(EscrowError::OracleNotApproved.into());
+        }
+        let oracle_approval =
+            OracleApproval::unpack_unchecked(&oracle_approval_info.data.borrow())?;
+        if !oracle_approval.is_initialized() {
+            return Err(EscrowError::OracleNotApproved.into());
+        }
+        if amount < oracle_approval.min_scored_amount {
+            return Err(EscrowError::ScoredPayoutTooSmall.into());
+        }
+
+        // Native SOL is paid straight to the worker wallet
+        let worker = if Self::is_system_account(recipient_info) {
+            *recipient_info.key
+        } else {
+            TokenAccount::unpack(&recipient_info.data.borrow())?.owner
+        };
+
+        let (worker_reputation_key, bump_seed) =
+            Self::find_worker_reputation_address(program_id, &escrow.factory, &worker);
+        if *worker_reputation_info.key != worker_reputation_key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Lamports sent to the reputation address in advance don't block its creation
+        let mut worker_reputation = if worker_reputation_info.owner != program_id {
+            Self::create_program_account(
+                program_id,
+                oracle_info.clone(),
+                worker_reputation_info.clone(),
+                system_program_info.clone(),
+                rent,
+                WorkerReputation::LEN,
+                &[
+                    WORKER_REPUTATION_SEED,
+                    &escrow.factory.to_bytes()[..32],
+                    &worker.to_bytes()[..32],
+                    &[bump_seed],
+                ],
+            )?;
+            WorkerReputation {
+                factory: escrow.factory,
+                worker,
+                ..Default::default()
+            }
+        } else {
+            WorkerReputation::unpack(&worker_reputation_info.data.borrow())?
+        };
+
+        if worker_job_info.owner != program_id {
+            Self::create_payout_receipt(
+                program_id,
+                oracle_info,
+                worker_job_info,
+                system_program_info,
+                rent,
+                &[
+                    WORKER_JOB_SEED,
+                    &escrow_info.key.to_bytes()[..32],
+                    &worker.to_bytes()[..32],
+                ],
+                PayoutReceipt {
+                    escrow: *escrow_info.key,
+                    recipient: *recipient_info.key,
+                    amount,
+                },
+                EscrowError::DuplicatePayout,
+            )?;
+            worker_reputation.record_job(earned, worker_score);
+        } else if *worker_job_info.key
+            == Self::find_worker_job_address(program_id, escrow_info.key, &worker).0
+        {
+            worker_reputation.record_earnings(earned);
+        } else {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        WorkerReputation::pack(
+            worker_reputation,
+            &mut worker_reputation_info.data.borrow_mut(),
+        )?;
+
+        EscrowEvent::WorkerReputationUpdated {
+            escrow: *escrow_info.key,
+            worker,
+            jobs_completed: worker_reputation.jobs_completed,
+            total_earned: worker_reputation.total_earned,
+            score: worker_reputation.score,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Records sent payouts and moves escrow to the `Partial` or `Paid` state
+    fn update_payout_state(escrow: &mut Escrow, sent_amount: u64, sent_recipients: u64) {
+        escrow.sent_amount = sent_amount;
+        escrow.sent_recipients = sent_recipients;
+
+        if Self::is_last_payout(escrow, sent_amount, sent_recipients) {
+            escrow.state = EscrowState::Paid;
+        } else {
+            escrow.state = EscrowState::Partial;
+        }
+    }
+
+    /// Stops the vesting schedule of the cancelled escrow, returns the unvested
+    /// amount released back to the escrow
+    fn stop_vesting_schedule(
+        program_id: &Pubkey,
+        escrow_info: &AccountInfo,
+        escrow: &Escrow,
+        vesting_schedule_info: &AccountInfo,
+    ) -> Result<u64, ProgramError> {
+        if vesting_schedule_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut vesting_schedule = VestingSchedule::unpack(&vesting_schedule_info.data.borrow())?;
+        if vesting_schedule.escrow != *escrow_info.key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let released = vesting_schedule.stop_vesting(escrow.vesting_stopped_at);
+        if released != 0 {
+            VestingSchedule::pack(
+                vesting_schedule,
+                &mut vesting_schedule_info.data.borrow_mut(),
+            )?;
+
+            EscrowEvent::VestingWithdrawn {
+                escrow: *escrow_info.key,
+                recipient: vesting_schedule.recipient,
+                amount: 0,
+                released,
+            }
+            .emit();
+        }
+
+        Ok(released)
+    }
+
+    /// Returns the remaining oracle bonds to the oracle token accounts
+    fn release_bonds<'a>(
+        escrow_info: &AccountInfo,
+        escrow: &mut Escrow,
+        token_program: AccountInfo<'a>,
+        bond_accounts: &BondAccounts<'a>,
+    ) -> ProgramResult {
+        let reputation_oracle_amount = escrow.reputation_oracle_bond;
+        let recording_oracle_amount = escrow.recording_oracle_bond;
+        if reputation_oracle_amount == 0 && recording_oracle_amount == 0 {
+            return Ok(());
+        }
+
+        if reputation_oracle_amount != 0 {
+            Self::bond_vault_transfer(
+                escrow_info.key,
+                token_program.clone(),
+                bond_accounts.bond_vault_info.clone(),
+                bond_accounts.reputation_oracle_token_account_info.clone(),
+                bond_accounts.bond_vault_bump_seed,
+                reputation_oracle_amount,
+            )?;
+        }
+        if recording_oracle_amount != 0 {
+            Self::bond_vault_transfer(
+                escrow_info.key,
+                token_program,
+                bond_accounts.bond_vault_info.clone(),
+                bond_accounts.recording_oracle_token_account_info.clone(),
+                bond_accounts.bond_vault_bump_seed,
+                recording_oracle_amount,
+            )?;
+        }
+
+        escrow.reputation_oracle_bond = 0;
+        escrow.recording_oracle_bond = 0;
+
+        EscrowEvent::BondsReleased {
+            escrow: *escrow_info.key,
+            reputation_oracle_amount,
+            recording_oracle_amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Issue a spl_token `Transfer` instruction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn token_transfer<'a>(
+        escrow_account_key: &Pubkey,
+        token_program: AccountInfo<'a>,
+        source: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        bump_seed: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        let authority_signature_seeds = [&escrow_account_key.to_bytes()[..32], &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            source.key,
+            destination.key,
+            authority.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[source, destination, authority, token_program],
+            signers,
+        )
+    }
+
+    /// Issue a spl_token `CloseAccount` instruction.
+    pub fn token_close_account<'a>(
+        escrow_account_key: &Pubkey,
+        token_program: AccountInfo<'a>,
+        account: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        let authority_signature_seeds = [&escrow_account_key.to_bytes()[..32], &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::close_account(
+            token_program.key,
+            account.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?;
+
+        invoke_signed(
+            &ix,
+            &[account, destination, authority, token_program],
+            signers,
+        )
+    }
+
+    /// Issue a spl_token `SetAuthority` instruction handing the token account
+    /// over to the new owner.
+    pub fn token_set_owner<'a>(
+        escrow_account_key: &Pubkey,
+        token_program: AccountInfo<'a>,
+        account: AccountInfo<'a>,
+        new_owner: &Pubkey,
+        authority: AccountInfo<'a>,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        let authority_signature_seeds = [&escrow_account_key.to_bytes()[..32], &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = spl_token::instruction::set_authority(
+            token_program.key,
+            account.key,
+            Some(new_owner),
+            spl_token::instruction::AuthorityType::AccountOwner,
+            authority.key,
+            &[],
+        )?;
+
+        invoke_signed(&ix, &[account, authority, token_program], signers)
+    }
+
+    /// Issue a spl_token `Transfer` instruction from the bond vault, the vault
+    /// is its own owner.
+    pub fn bond_vault_transfer<'a>(
+        escrow_account_key: &Pubkey,
+        token_program: AccountInfo<'a>,
+        bond_vault: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        bump_seed: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        let bond_vault_signature_seeds = [
+            BOND_VAULT_SEED,
+            &escrow_account_key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+        let signers = &[&bond_vault_signature_seeds[..]];
+
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            bond_vault.key,
+            destination.key,
+            bond_vault.key,
+            &[],
+            amount,
+        )?;
+
+        invoke_signed(&ix, &[bond_vault, destination, token_program], signers)
+    }
+
+    /// Issue a spl_token `CloseAccount` instruction for the bond vault.
+    pub fn bond_vault_close<'a>(
+        escrow_account_key: &Pubkey,
+        token_program: AccountInfo<'a>,
+        bond_vault: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        bump_seed: u8,
+    ) -> ProgramResult {
+        let bond_vault_signature_seeds = [
+            BOND_VAULT_SEED,
+            &escrow_account_key.to_bytes()[..32],
+            &[bump_seed],
+        ];
+        let signers = &[&bond_vault_signature_seeds[..]];
+
+        let ix = spl_token::instruction::close_account(
+            token_program.key,
+            bond_vault.key,
+            destination.key,
+            bond_vault.key,
+            &[],
+        )?;
+
+        invoke_signed(&ix, &[bond_vault, destination, token_program], signers)
+    }
+
+    /// Issue a system `Transfer` instruction from the escrow authority.
+    pub fn authority_transfer<'a>(
+        escrow_account_key: &Pubkey,
+        system_program: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        destination: AccountInfo<'a>,
+        bump_seed: u8,
+        lamports: u64,
+    ) -> ProgramResult {
+        let authority_signature_seeds = [&escrow_account_key.to_bytes()[..32], &[bump_seed]];
+        let signers = &[&authority_signature_seeds[..]];
+
+        let ix = system_instruction::transfer(authority.key, destination.key, lamports);
+
+        invoke_signed(&ix, &[authority, destination, system_program], signers)
+    }
+
+    /// Creates the wrap account, a native token account owned by the escrow
+    /// authority. The payer funds the rent and `amount` lamports to wrap, the
+    /// account may already hold some lamports.
+    fn create_wrap_account<'a>(
+        escrow_account_key: &Pubkey,
+        token_program: AccountInfo<'a>,
+        payer: AccountInfo<'a>,
+        authority: AccountInfo<'a>,
+        native_accounts: &NativeAccounts<'a>,
+        bump_seed: u8,
+        amount: u64,
+    ) -> ProgramResult {
+        let authority_signature_seeds = [&escrow_account_key.to_bytes()[..32], &[bump_seed]];
+        let wrap_signature_seeds = [
+            WRAP_SEED,
+            &escrow_account_key.to_bytes()[..32],
+            &[native_accounts.wrap_bump_seed],
+        ];
+        let signers = &[&authority_signature_seeds[..], &wrap_signature_seeds[..]];
+
+        Self::create_token_account(
+            token_program,
+            payer,
+            native_accounts.wrap_account_info.clone(),
+            native_accounts.native_mint_info.clone(),
+            authority,
+            native_accounts.rent_info.clone(),
+            &native_accounts.rent,
+            native_accounts.system_program_info.clone(),
+            signers,
+            amount,
+        )
+    }
+
+    /// Creates the token account at the program address. The payer funds the
+    /// rent and `amount` lamports, the account may already hold some lamports,
+    /// so it is allocated and assigned rather than created.
+    #[allow(clippy::too_many_arguments)]
+    fn create_token_account<'a>(
+        token_program: AccountInfo<'a>,
+        payer: AccountInfo<'a>,
+        account: AccountInfo<'a>,
+        mint: AccountInfo<'a>,
+        owner: AccountInfo<'a>,
+        rent_info: AccountInfo<'a>,
+        rent: &Rent,
+        system_program: AccountInfo<'a>,
+        signers: &[&[&[u8]]],
+        amount: u64,
+    ) -> ProgramResult {
+        let lamports = rent
+            .minimum_balance(TokenAccount::LEN)
+            .checked_add(amount)
+            .ok_or(ProgramError::InvalidInstructionData)?
+            .saturating_sub(account.lamports());
+        if lamports != 0 {
+            let ix = system_instruction::transfer(payer.key, account.key, lamports);
+            invoke_signed(
+                &ix,
+                &[payer, account.clone(), system_program.clone()],
+                signers,
+            )?;
+        }
+
+        let ix = system_instruction::allocate(account.key, TokenAccount::LEN as u64);
+        invoke_signed(&ix, &[account.clone(), system_program.clone()], signers)?;
+        let ix = system_instruction::assign(account.key, token_program.key);
+        invoke_signed(&ix, &[account.clone(), system_program], signers)?;
+
+        let ix = spl_token::instruction::initialize_account(
+            token_program.key,
+            account.key,
+            mint.key,
+            owner.key,
+        )?;
+        invoke(&ix, &[account, mint, owner, rent_info, token_program])
+    }
+
+    /// Issue a spl_token `Transfer` instruction signed by the oracle, locking
+    /// its bond in the bond vault.
+    fn lock_bond<'a>(
+        token_program: AccountInfo<'a>,
+        oracle_token_account: AccountInfo<'a>,
+        bond_vault: AccountInfo<'a>,
+        oracle: AccountInfo<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        let ix = spl_token::instruction::transfer(
+            token_program.key,
+            oracle_token_account.key,
+            bond_vault.key,
+            oracle.key,
+            &[],
+            amount,
+        )?;
+
+        invoke(
+            &ix,
+            &[oracle_token_account, bond_vault, oracle, token_program],
+        )
+    }
+
+    /// Creates the program account at the program derived address. The account
+    /// may already hold some lamports, so the payer only tops up the rent and
+    /// the account is allocated and assigned rather than created.
+    pub fn create_program_account<'a>(
+        program_id: &Pubkey,
+        payer: AccountInfo<'a>,
+        new_account: AccountInfo<'a>,
+        system_program: AccountInfo<'a>,
+        rent: &Rent,
+        space: usize,
+        signature_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        if *system_program.key != system_program::id() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let lamports = rent
+            .minimum_balance(space)
+            .saturating_sub(new_account.lamports());
+        if lamports != 0 {
+            let ix = system_instruction::transfer(payer.key, new_account.key, lamports);
+            invoke(&ix, &[payer, new_account.clone(), system_program.clone()])?;
+        }
+
+        let ix = system_instruction::allocate(new_account.key, space as u64);
+        invoke_signed(
+            &ix,
+            &[new_account.clone(), system_program.clone()],
+            &[signature_seeds],
+        )?;
+        let ix = system_instruction::assign(new_account.key, program_id);
+        invoke_signed(&ix, &[new_account, system_program], &[signature_seeds])
+    }
+
+    /// Processes `FactoryInitialize` instruction.
+    pub fn process_factory_initialize(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        version: u8,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let factory_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        if !admin_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let factory = Factory::unpack_unchecked(&factory_info.data.borrow())?;
+
+        // Only new unitialized accounts are supported
+        if factory.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        let factory = Factory {
+            version,
+            escrow_count: 0,
+            admin: *admin_info.key,
+            token_mint: COption::None,
+            max_duration: 0,
+            max_oracle_stake: STAKE_BASIS_POINTS,
+            paused: false,
+        };
+
+        Factory::pack(factory, &mut factory_info.data.borrow_mut())?;
+
+        EscrowEvent::FactoryInitialized {
+            factory: *factory_info.key,
+            version,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Processes `FactorySetAdmin` instruction.
+    pub fn process_factory_set_admin(accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let factory_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let new_admin_info = next_account_info(account_info_iter)?;
+
+        let mut factory = Self::get_factory_with_admin_check(factory_info, admin_info)?;
+
+        factory.admin = *new_admin_info.key;
+
+        Factory::pack(factory, &mut factory_info.data.borrow_mut())?;
+
+        EscrowEvent::FactoryAdminSet {
+            factory: *factory_info.key,
+            admin: factory.admin,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Processes `FactorySetVersion` instruction.
+    pub fn process_factory_set_version(accounts: &[AccountInfo], version: u8) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let factory_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut factory = Self::get_factory_with_admin_check(factory_info, admin_info)?;
+
+        // Version can only be bumped
+        if version <= factory.version {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        factory.version = version;
+
+        Factory::pack(factory, &mut factory_info.data.borrow_mut())?;
+
+        EscrowEvent::FactoryVersionSet {
+            factory: *factory_info.key,
+            version,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Processes `FactorySetPolicy` instruction.
+    pub fn process_factory_set_policy(
+        accounts: &[AccountInfo],
+        token_mint: COption<Pubkey>,
+        max_duration: u64,
+        max_oracle_stake: u16,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let factory_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut factory = Self::get_factory_with_admin_check(factory_info, admin_info)?;
+
+        // Check stake value validity
+        if max_oracle_stake == 0 || max_oracle_stake > STAKE_BASIS_POINTS {
+            return Err(EscrowError::StakeOutOfBounds.into());
+        }
+
+        factory.token_mint = token_mint;
+        factory.max_duration = max_duration;
+        factory.max_oracle_stake = max_oracle_stake;
+
+        Factory::pack(factory, &mut factory_info.data.borrow_mut())?;
+
+        EscrowEvent::FactoryPolicySet {
+            factory: *factory_info.key,
+            token_mint,
+            max_duration,
+            max_oracle_stake,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Processes `FactorySetPaused` instruction.
+    pub fn process_factory_set_paused(accounts: &[AccountInfo], paused: bool) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let factory_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+
+        let mut factory = Self::get_factory_with_admin_check(factory_info, admin_info)?;
+
+        factory.paused = paused;
+
+        Factory::pack(factory, &mut factory_info.data.borrow_mut())?;
+
+        EscrowEvent::FactoryPaused {
+            factory: *factory_info.key,
+            paused,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Processes `FactoryApproveOracle` instruction.
+    pub fn process_factory_approve_oracle(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        min_scored_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let factory_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let oracle_approval_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if factory_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Self::get_factory_with_admin_check(factory_info, admin_info)?;
+
+        let (oracle_approval_key, bump_seed) =
+            Self::find_oracle_approval_address(program_id, factory_info.key, oracle_info.key);
+        if *oracle_approval_info.key != oracle_approval_key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        if min_scored_amount == 0 {
+            if oracle_approval_info.owner != program_id {
+                return Err(EscrowError::OracleNotApproved.into());
+            }
+
+            // Zero revoked approval data and return its lamports to the administrator
+            oracle_approval_info
+                .data
+                .borrow_mut()
+                .iter_mut()
+                .for_each(|byte| *byte = 0);
+
+            let admin_lamports = admin_info
+                .lamports()
+                .checked_add(oracle_approval_info.lamports())
+                .ok_or(ProgramError::InvalidAccountData)?;
+            **admin_info.lamports.borrow_mut() = admin_lamports;
+            **oracle_approval_info.lamports.borrow_mut() = 0;
+        } else {
+            if oracle_approval_info.owner != program_id {
+                Self::create_program_account(
+                    program_id,
+                    admin_info.clone(),
+                    oracle_approval_info.clone(),
+                    system_program_info.clone(),
+                    rent,
+                    OracleApproval::LEN,
+                    &[
+                        ORACLE_APPROVAL_SEED,
+                        &factory_info.key.to_bytes()[..32],
+                        &oracle_info.key.to_bytes()[..32],
+                        &[bump_seed],
+                    ],
+                )?;
+            }
+
+            OracleApproval::pack(
+                OracleApproval {
+                    factory: *factory_info.key,
+                    oracle: *oracle_info.key,
+                    min_scored_amount,
+                },
+                &mut oracle_approval_info.data.borrow_mut(),
+            )?;
+        }
+
+        EscrowEvent::OracleApproved {
+            factory: *factory_info.key,
+            oracle: *oracle_info.key,
+            min_scored_amount,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Processes `Initialize` instruction.
+    pub fn process_initialize(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        duration: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let token_mint_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let launcher_info = next_account_info(account_info_iter)?;
+        let canceler_info = next_account_info(account_info_iter)?;
+        let canceler_token_account_info = next_account_info(account_info_iter)?;
+        let factory_entry_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
+
+        let mut factory = Factory::unpack_unchecked(&factory_info.data.borrow())?;
+
+        // Only new unitialized accounts are supported
+        if escrow.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        // Escrow has to belong to initialized Factory
+        if !factory.is_initialized() {
+            return Err(EscrowError::FactoryNotInitialized.into());
+        }
+
+        // Check duration validity
+        if duration == 0 {
+            return Err(EscrowError::EscrowExpired.into());
+        }
+        if factory.max_duration != 0 && duration > factory.max_duration {
+            return Err(EscrowError::DurationOutOfBounds.into());
+        }
+
+        // Check token mint is allowed by the factory policy
+        if let COption::Some(token_mint) = factory.token_mint {
+            if *token_mint_info.key != token_mint {
+                return Err(EscrowError::WrongTokenMint.into());
+            }
+        }
+
+        // Calculate authority key and bump seed
+        let (authority_key, bump_seed) =
+            Self::find_authority_bump_seed(program_id, escrow_info.key);
+
+        // Token account should be owned by the contract authority
+        let token_account = Box::new(TokenAccount::unpack_unchecked(
+            &token_account_info.data.borrow(),
+        )?);
+        if token_account.owner != authority_key {
+            return Err(EscrowError::TokenAccountAuthority.into());
+        }
+
+        // Check token account mints
+        if token_account.mint != *token_mint_info.key {
+            return Err(EscrowError::WrongTokenMint.into());
+        }
+
+        // Native SOL escrow can return funds to the canceler wallet
+        let native = *token_mint_info.key == spl_token::native_mint::id();
+        if !(native && Self::is_system_account(canceler_token_account_info)) {
+            let canceler_token_account = Box::new(TokenAccount::unpack_unchecked(
+                &canceler_token_account_info.data.borrow(),
+            )?);
+            if canceler_token_account.mint != *token_mint_info.key {
+                return Err(EscrowError::WrongTokenMint.into());
+            }
+        }
+
+        // Authority of the native SOL escrow keeps the reserve paying for the wrap
+        // account rent
+        if native {
+            let authority_info = next_account_info(account_info_iter)?;
+            if *authority_info.key != authority_key {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            let reserve = rent
+                .minimum_balance(TokenAccount::LEN)
+                .saturating_sub(authority_info.lamports());
+            if reserve != 0 {
+                let ix = system_instruction::transfer(payer_info.key, authority_info.key, reserve);
+                invoke(
+                    &ix,
+                    &[
+                        payer_info.clone(),
+                        authority_info.clone(),
+                        system_program_info.clone(),
+                    ],
+                )?;
+            }
+        }
+
+        Self::register_escrow(
+            program_id,
+            escrow_info.key,
+            factory_info,
+            &mut factory,
+            factory_entry_info,
+            payer_info,
+            system_program_info,
+            rent,
+        )?;
+
+        let escrow = Box::new(Escrow {
+            state: EscrowState::Launched,
+            factory: *factory_info.key,
+            expires: clock.unix_timestamp + duration as i64,
+            created_at: clock.unix_timestamp,
+            bump_seed,
+            token_mint: *token_mint_info.key,
+            token_account: *token_account_info.key,
+            launcher: *launcher_info.key,
+            canceler: *canceler_info.key,
+            canceler_token_account: *canceler_token_account_info.key,
+            ..Default::default()
+        });
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::EscrowCreated {
+            escrow: *escrow_info.key,
+            factory: escrow.factory,
+            token_mint: escrow.token_mint,
+            launcher: escrow.launcher,
+            canceler: escrow.canceler,
+            expires: escrow.expires,
+        }
+        .emit();
+        Ok(())
+    }
+
+    /// Registers the escrow in the factory registry under the next free index,
+    /// creating the factory entry account
+    #[allow(clippy::too_many_arguments)]
+    fn register_escrow<'a>(
+        program_id: &Pubkey,
+        escrow_key: &Pubkey,
+        factory_info: &AccountInfo<'a>,
+        factory: &mut Factory,
+        factory_entry_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        rent: &Rent,
+    ) -> ProgramResult {
+        // Factory entry must be the next free slot of the factory registry
+        let index = factory.escrow_count;
+        let (factory_entry_key, factory_entry_bump_seed) =
+            Self::find_factory_entry_address(program_id, factory_info.key, index);
+        if *factory_entry_info.key != factory_entry_key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Self::create_program_account(
+            program_id,
+            payer_info.clone(),
+            factory_entry_info.clone(),
+            system_program_info.clone(),
+            rent,
+            FactoryEntry::LEN,
+            &[
+                FACTORY_ENTRY_SEED,
+                &factory_info.key.to_bytes()[..32],
+                &index.to_le_bytes(),
+                &[factory_entry_bump_seed],
+            ],
+        )?;
+
+        let factory_entry = FactoryEntry {
+            escrow: *escrow_key,
+        };
+        FactoryEntry::pack(factory_entry, &mut factory_entry_info.data.borrow_mut())?;
+
+        factory.escrow_count = index
+            .checked_add(1)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        Factory::pack(*factory, &mut factory_info.data.borrow_mut())
+    }
+
+    /// Processes `Setup` instruction.
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_setup(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reputation_oracle_stake: u16,
+        recording_oracle_stake: u16,
+        manifest_url: &DataUrl,
+        manifest_hash: &DataHash,
+        defer_fees: bool,
+        dispute_window: u64,
+        reputation_oracle_bond: u64,
+        recording_oracle_bond: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let reputation_oracle_info = next_account_info(account_info_iter)?;
+        let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let recording_oracle_info = next_account_info(account_info_iter)?;
+        let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::Launcher, Role::TrustedHandler],
+            EscrowError::UnauthorizedSetup,
+            vec![EscrowState::Launched],
+        )?;
+
+        let factory = Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        Self::check_hash_algorithm(manifest_hash)?;
+
+        // Dispute window must end before the escrow expires
+        if dispute_window > (escrow.expires - clock.unix_timestamp) as u64 {
+            return Err(EscrowError::DurationOutOfBounds.into());
+        }
+
+        // Check stake value validity
+        let total_stake: u16 = reputation_oracle_stake
+            .checked_add(recording_oracle_stake)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        if total_stake == 0
+            || total_stake > STAKE_BASIS_POINTS
+            || total_stake > factory.max_oracle_stake
+        {
+            return Err(EscrowError::StakeOutOfBounds.into());
+        }
+
+        // Check token account mints
+        let reputation_oracle_token_account =
+            TokenAccount::unpack_unchecked(&reputation_oracle_token_account_info.data.borrow())?;
+        if reputation_oracle_token_account.mint != escrow.token_mint {
+            return Err(EscrowError::WrongTokenMint.into());
+        }
+        let recording_oracle_token_account =
+            TokenAccount::unpack_unchecked(&recording_oracle_token_account_info.data.borrow())?;
+        if recording_oracle_token_account.mint != escrow.token_mint {
+            return Err(EscrowError::WrongTokenMint.into());
+        }
+
+        let bonded = reputation_oracle_bond != 0 || recording_oracle_bond != 0;
+        if bonded {
+            let bond_vault_info = next_account_info(account_info_iter)?;
+            let token_mint_info = next_account_info(account_info_iter)?;
+            let rent_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+            let token_program_info = next_account_info(account_info_iter)?;
+
+            let (bond_vault_key, bond_vault_bump_seed) =
+                Self::find_bond_vault_address(program_id, escrow_info.key);
+            if *bond_vault_info.key != bond_vault_key
+                || *token_mint_info.key != escrow.token_mint
+                || *system_program_info.key != system_program::id()
+                || *token_program_info.key != spl_token::id()
+            {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+
+            // Oracles agree to lock their bonds
+            if (reputation_oracle_bond != 0 && !reputation_oracle_info.is_signer)
+                || (recording_oracle_bond != 0 && !recording_oracle_info.is_signer)
+            {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+
+            let bond_vault_signature_seeds = [
+                BOND_VAULT_SEED,
+                &escrow_info.key.to_bytes()[..32],
+                &[bond_vault_bump_seed],
+            ];
+            Self::create_token_account(
+                token_program_info.clone(),
+                trusted_handler_info.clone(),
+                bond_vault_info.clone(),
+                token_mint_info.clone(),
+                bond_vault_info.clone(),
+                rent_info.clone(),
+                &Rent::from_account_info(rent_info)?,
+                system_program_info.clone(),
+                &[&bond_vault_signature_seeds[..]],
+                0,
+            )?;
+
+            if reputation_oracle_bond != 0 {
+                Self::lock_bond(
+                    token_program_info.clone(),
+                    reputation_oracle_token_account_info.clone(),
+                    bond_vault_info.clone(),
+                    reputation_oracle_info.clone(),
+                    reputation_oracle_bond,
+                )?;
+            }
+            if recording_oracle_bond != 0 {
+                Self::lock_bond(
+                    token_program_info.clone(),
+                    recording_oracle_token_account_info.clone(),
+                    bond_vault_info.clone(),
+                    recording_oracle_info.clone(),
+                    recording_oracle_bond,
+                )?;
+            }
+        }
+
+        // Update escrow fields with the new values
+        escrow.reputation_oracle = COption::Some(*reputation_oracle_info.key);
+        escrow.reputation_oracle_token_account =
+            COption::Some(*reputation_oracle_token_account_info.key);
+        escrow.reputation_oracle_stake = reputation_oracle_stake;
+
+        escrow.recording_oracle = COption::Some(*recording_oracle_info.key);
+        escrow.recording_oracle_token_account =
+            COption::Some(*recording_oracle_token_account_info.key);
+        escrow.recording_oracle_stake = recording_oracle_stake;
+
+        escrow.manifest_url = *manifest_url;
+        escrow.manifest_hash = *manifest_hash;
+        escrow.defer_fees = defer_fees;
+        escrow.dispute_window = dispute_window;
+        escrow.bonded = bonded;
+        escrow.reputation_oracle_bond = reputation_oracle_bond;
+        escrow.recording_oracle_bond = recording_oracle_bond;
+
+        escrow.state = EscrowState::Pending;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::EscrowSetup {
+            escrow: *escrow_info.key,
+            reputation_oracle: *reputation_oracle_info.key,
+            reputation_oracle_stake,
+            recording_oracle: *recording_oracle_info.key,
+            recording_oracle_stake,
+            defer_fees,
+        }
+        .emit();
+
+        if bonded {
+            EscrowEvent::BondsLocked {
+                escrow: *escrow_info.key,
+                reputation_oracle_bond,
+                recording_oracle_bond,
+            }
+            .emit();
+        }
+        Ok(())
+    }
+
+    /// Processes `StoreResults` instruction.
+    pub fn process_store_results(
+        accounts: &[AccountInfo],
+        final_results_url: &DataUrl,
+        final_results_hash: &DataHash,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::RecordingOracle, Role::Launcher],
+            EscrowError::UnauthorizedStoreResults,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        Self::check_hash_algorithm(final_results_hash)?;
+
+        // Save final results url and hash, new results restart the dispute window
+        escrow.final_results_url = *final_results_url;
+        escrow.final_results_hash = *final_results_hash;
+        escrow.results_stored_at = clock.unix_timestamp;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::ResultsStored {
+            escrow: *escrow_info.key,
+            results_hash: *final_results_hash,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `StoreFinalAmounts` instruction.
+    pub fn process_store_amounts(
+        accounts: &[AccountInfo],
+        total_amount: u64,
+        total_recipients: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::ReputationOracle],
+            EscrowError::UnauthorizedStoreAmounts,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        // Total amount can't exceed deposited tokens
+        if total_amount > escrow.funded_amount {
+            return Err(EscrowError::NotEnoughBalance.into());
+        }
+
+        // Save final results url and hash
+        escrow.total_amount = total_amount;
+        escrow.total_recipients = total_recipients;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::AmountsStored {
+            escrow: *escrow_info.key,
+            total_amount,
+            total_recipients,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Payout` instruction.
+    pub fn process_payout(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+        payout_id: Option<&PayoutId>,
+        worker_score: Option<u16>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let recipient_token_account_info = next_account_info(account_info_iter)?;
+        let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::ReputationOracle],
+            EscrowError::UnauthorizedPayout,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        Self::check_dispute_window(&escrow, clock)?;
+
+        // Check all accounts validity
+        Self::check_payout_accounts(
+            program_id,
+            escrow_info,
+            &escrow,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            token_program_info,
+        )?;
+
+        let (sent_amount, sent_recipients) = Self::check_payout_totals(&escrow, amount, 1)?;
+
+        // Record the payout ID so the payout can't be repeated
+        if let Some(payout_id) = payout_id {
+            let payout_receipt_info = next_account_info(account_info_iter)?;
+            let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+
+            Self::create_payout_receipt(
+                program_id,
+                trusted_handler_info,
+                payout_receipt_info,
+                system_program_info,
+                rent,
+                &[
+                    PAYOUT_RECEIPT_SEED,
+                    &escrow_info.key.to_bytes()[..32],
+                    payout_id,
+                ],
+                PayoutReceipt {
+                    escrow: *escrow_info.key,
+                    recipient: *recipient_token_account_info.key,
+                    amount,
+                },
+                EscrowError::DuplicatePayout,
+            )?;
+        }
+
+        let native_accounts =
+            Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+
+        let last = Self::is_last_payout(&escrow, sent_amount, sent_recipients);
+        let recipient_amount = Self::send_payout(
+            escrow_info,
+            &mut escrow,
+            token_program_info,
+            token_account_info,
+            authority_info,
+            recipient_token_account_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            native_accounts.as_ref(),
+            amount,
+            last,
+        )?;
+
+        if let Some(worker_score) = worker_score {
+            let worker_reputation_info = next_account_info(account_info_iter)?;
+            let worker_job_info = next_account_info(account_info_iter)?;
+            let oracle_approval_info = next_account_info(account_info_iter)?;
+            let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+
+            Self::update_worker_reputation(
+                program_id,
+                escrow_info,
+                &escrow,
+                trusted_handler_info,
+                recipient_token_account_info,
+                worker_reputation_info,
+                worker_job_info,
+                oracle_approval_info,
+                system_program_info,
+                rent,
+                amount,
+                recipient_amount,
+                worker_score,
+            )?;
+        }
+
+        Self::update_payout_state(&mut escrow, sent_amount, sent_recipients);
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes `BatchPayout` instruction.
+    pub fn process_batch_payout(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amounts: &[u64],
+        payout_ids: &[PayoutId],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::ReputationOracle],
+            EscrowError::UnauthorizedPayout,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        Self::check_dispute_window(&escrow, clock)?;
+
+        // Check all accounts validity
+        Self::check_payout_accounts(
+            program_id,
+            escrow_info,
+            &escrow,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            token_program_info,
+        )?;
+
+        if amounts.is_empty() || !(payout_ids.is_empty() || payout_ids.len() == amounts.len()) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let batch_amount = amounts
+            .iter()
+            .try_fold(0u64, |total, amount| total.checked_add(*amount))
+            .ok_or(EscrowError::TooManyPayouts)?;
+        let (sent_amount, sent_recipients) =
+            Self::check_payout_totals(&escrow, batch_amount, amounts.len() as u64)?;
+
+        let recipient_token_account_infos = amounts
+            .iter()
+            .map(|_| next_account_info(account_info_iter))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Record the payout IDs so the payouts can't be repeated
+        if !payout_ids.is_empty() {
+            let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+
+            for ((payout_id, amount), recipient_token_account_info) in payout_ids
+                .iter()
+                .zip(amounts)
+                .zip(recipient_token_account_infos.iter())
+            {
+                let payout_receipt_info = next_account_info(account_info_iter)?;
+                Self::create_payout_receipt(
+                    program_id,
+                    trusted_handler_info,
+                    payout_receipt_info,
+                    system_program_info,
+                    rent,
+                    &[
+                        PAYOUT_RECEIPT_SEED,
+                        &escrow_info.key.to_bytes()[..32],
+                        payout_id,
+                    ],
+                    PayoutReceipt {
+                        escrow: *escrow_info.key,
+                        recipient: *recipient_token_account_info.key,
+                        amount: *amount,
+                    },
+                    EscrowError::DuplicatePayout,
+                )?;
+            }
+        }
+
+        let native_accounts =
+            Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+
+        // Send tokens to recipients, collecting fees. Native SOL payouts to system
+        // accounts are unwrapped at once, since the wrap account can't be created
+        // twice in a transaction.
+        let last_batch = Self::is_last_payout(&escrow, sent_amount, sent_recipients);
+        let mut reputation_oracle_fee_total: u64 = 0;
+        let mut recording_oracle_fee_total: u64 = 0;
+        let mut unwrapped_payouts = vec![];
+        for (index, (amount, recipient_token_account_info)) in amounts
+            .iter()
+            .zip(recipient_token_account_infos)
+            .enumerate()
+        {
+            let last = last_batch && index + 1 == amounts.len();
+            let (reputation_oracle_fee_amount, recording_oracle_fee_amount, recipient_amount) =
+                Self::calculate_oracle_fees(&mut escrow, *amount, last)?;
+
+            if recipient_amount != 0 {
+                if native_accounts.is_some()
+                    && Self::is_system_account(recipient_token_account_info)
+                {
+                    unwrapped_payouts.push((recipient_token_account_info, recipient_amount));
+                } else {
+                    Self::token_transfer(
+                        escrow_info.key,
+                        token_program_info.clone(),
+                        token_account_info.clone(),
+                        recipient_token_account_info.clone(),
+                        authority_info.clone(),
+                        escrow.bump_seed,
+                        recipient_amount,
+                    )?;
+                }
+            }
+
+            EscrowEvent::PayoutMade {
+                escrow: *escrow_info.key,
+                recipient: *recipient_token_account_info.key,
+                amount: recipient_amount,
+                reputation_oracle_fee: reputation_oracle_fee_amount,
+                recording_oracle_fee: recording_oracle_fee_amount,
+            }
+            .emit();
+
+            reputation_oracle_fee_total = reputation_oracle_fee_total
+                .checked_add(reputation_oracle_fee_amount)
+                .ok_or(EscrowError::FeeOverflow)?;
+            recording_oracle_fee_total = recording_oracle_fee_total
+                .checked_add(recording_oracle_fee_amount)
+                .ok_or(EscrowError::FeeOverflow)?;
+        }
+
+        if let Some(native_accounts) = native_accounts.as_ref() {
+            let unwrapped_amount: u64 = unwrapped_payouts.iter().map(|(_, amount)| amount).sum();
+            if unwrapped_amount != 0 {
+                Self::unwrap_to_authority(
+                    escrow_info,
+                    &escrow,
+                    token_program_info,
+                    token_account_info,
+                    authority_info,
+                    native_accounts,
+                    unwrapped_amount,
+                )?;
+            }
+            for (recipient_info, amount) in unwrapped_payouts {
+                Self::authority_transfer(
+                    escrow_info.key,
+                    native_accounts.system_program_info.clone(),
+                    authority_info.clone(),
+                    recipient_info.clone(),
+                    escrow.bump_seed,
+                    amount,
+                )?;
+            }
+        }
+
+        if escrow.defer_fees {
+            Self::accrue_oracle_fees(
+                &mut escrow,
+                reputation_oracle_fee_total,
+                recording_oracle_fee_total,
+            )?;
+            reputation_oracle_fee_total = 0;
+            recording_oracle_fee_total = 0;
+        }
+
+        // Send fees once for the whole batch
+        if reputation_oracle_fee_total != 0 {
+            Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                reputation_oracle_token_account_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                reputation_oracle_fee_total,
+            )?;
+        }
+        if recording_oracle_fee_total != 0 {
+            Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                recording_oracle_token_account_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                recording_oracle_fee_total,
+            )?;
+        }
+
+        Self::update_payout_state(&mut escrow, sent_amount, sent_recipients);
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes `StorePayoutsRoot` instruction.
+    pub fn process_store_payouts_root(
+        accounts: &[AccountInfo],
+        payouts_root: &MerkleHash,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::ReputationOracle],
+            EscrowError::UnauthorizedPayout,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        // Replacing the root would make already claimed leaves claimable again
+        if escrow.payouts_root != MerkleHash::default() {
+            return Err(EscrowError::WrongState.into());
+        }
+        if *payouts_root == MerkleHash::default() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        escrow.payouts_root = *payouts_root;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::PayoutsRootStored {
+            escrow: *escrow_info.key,
+            payouts_root: *payouts_root,
+            total_amount: escrow.total_amount,
+            total_recipients: escrow.total_recipients,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Claim` instruction.
+    pub fn process_claim(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        index: u64,
+        amount: u64,
+        proof: &[MerkleHash],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let claimant_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let recipient_token_account_info = next_account_info(account_info_iter)?;
+        let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+        let claim_receipt_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_expiration_check(
+            escrow_info,
+            clock,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        Self::check_dispute_window(&escrow, clock)?;
+
+        // Check all accounts validity
+        Self::check_payout_accounts(
+            program_id,
+            escrow_info,
+            &escrow,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            token_program_info,
+        )?;
+
+        // Check the leaf belongs to the stored payouts tree
+        let leaf = merkle::leaf_hash(index, recipient_token_account_info.key, amount);
+        if escrow.payouts_root == MerkleHash::default()
+            || !merkle::verify_proof(proof, &escrow.payouts_root, leaf)
+        {
+            return Err(EscrowError::InvalidMerkleProof.into());
+        }
+
+        let (sent_amount, sent_recipients) = Self::check_payout_totals(&escrow, amount, 1)?;
+
+        // Record the claim so the leaf can't be claimed again
+        Self::create_payout_receipt(
+            program_id,
+            claimant_info,
+            claim_receipt_info,
+            system_program_info,
+            rent,
+            &[
+                CLAIM_RECEIPT_SEED,
+                &escrow_info.key.to_bytes()[..32],
+                &index.to_le_bytes(),
+            ],
+            PayoutReceipt {
+                escrow: *escrow_info.key,
+                recipient: *recipient_token_account_info.key,
+                amount,
+            },
+            EscrowError::AlreadyClaimed,
+        )?;
+
+        let native_accounts =
+            Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+
+        let last = Self::is_last_payout(&escrow, sent_amount, sent_recipients);
+        Self::send_payout(
+            escrow_info,
+            &mut escrow,
+            token_program_info,
+            token_account_info,
+            authority_info,
+            recipient_token_account_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            native_accounts.as_ref(),
+            amount,
+            last,
+        )?;
+
+        Self::update_payout_state(&mut escrow, sent_amount, sent_recipients);
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes `Cancel` instruction.
+    pub fn process_cancel(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let canceler_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Escrow::unpack_unchecked(&escrow_info.data.borrow())?;
+
+        // Check if escrow account exists and is initialized
+        if !escrow.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        // Check escrow state, paid escrow can be cancelled while its payouts vest
+        if escrow.state == EscrowState::Complete
+            || (escrow.state == EscrowState::Paid && escrow.vesting_amount == 0)
+        {
+            return Err(EscrowError::WrongState.into());
+        }
+
+        Self::check_role(
+            &escrow,
+            trusted_handler_info,
+            &[Role::Canceler, Role::Launcher],
+            EscrowError::UnauthorizedCancel,
+        )?;
+
+        // Check all accounts validity
+        if *token_account_info.key != escrow.token_account
+            || *canceler_token_account_info.key != escrow.canceler_token_account
+            || *token_program_info.key != spl_token::id()
+            || *authority_info.key
+                != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let native_accounts =
+            Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+
+        // Vesting payouts stop vesting at the first cancellation, unvested tokens
+        // of the given schedules are released without waiting for the recipients
+        let stops_vesting = escrow.vesting_amount != 0 && escrow.state != EscrowState::Cancelled;
+        if escrow.vesting_amount != 0 {
+            let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+            if stops_vesting {
+                escrow.vesting_stopped_at = clock.unix_timestamp;
+            }
+
+            let mut released = 0u64;
+            for vesting_schedule_info in account_info_iter {
+                released = released
+                    .checked_add(Self::stop_vesting_schedule(
+                        program_id,
+                        escrow_info,
+                        &escrow,
+                        vesting_schedule_info,
+                    )?)
+                    .ok_or(ProgramError::InvalidAccountData)?;
+            }
+
+            // Released tokens were never paid
+            escrow.vesting_amount = escrow
+                .vesting_amount
+                .checked_sub(released)
+                .ok_or(ProgramError::InvalidAccountData)?;
+            escrow.sent_amount = escrow.sent_amount.saturating_sub(released);
+        }
+
+        // Check account balance, deferred fees stay for the oracles to settle and
+        // vesting payouts for the recipients to withdraw
+        let token_account = TokenAccount::unpack_unchecked(&token_account_info.data.borrow())?;
+        let amount = token_account
+            .amount
+            .saturating_sub(escrow.unsettled_fees())
+            .saturating_sub(escrow.vesting_amount);
+        if amount == 0 && !stops_vesting {
+            return Err(EscrowError::NotEnoughBalance.into());
+        }
+
+        // Call token contract to do transfer
+        if amount != 0 {
+            Self::send_tokens(
+                escrow_info,
+                &escrow,
+                token_program_info,
+                token_account_info,
+                authority_info,
+                canceler_token_account_info,
+                native_accounts.as_ref(),
+                amount,
+            )?;
+        }
+
+        escrow.state = EscrowState::Cancelled;
+
+        Escrow::pack(escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::Cancelled {
+            escrow: *escrow_info.key,
+            amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Complete` instruction.
+    pub fn process_complete(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::ReputationOracle, Role::Launcher, Role::TrustedHandler],
+            EscrowError::UnauthorizedComplete,
+            vec![EscrowState::Paid],
+        )?;
+
+        if escrow.unsettled_fees() != 0 {
+            return Err(EscrowError::FeesNotSettled.into());
+        }
+
+        if escrow.vesting_amount != 0 {
+            return Err(EscrowError::VestingInProgress.into());
+        }
+
+        // Oracles get their bonds back
+        if let Some(bond_accounts) =
+            Self::next_bond_accounts(program_id, escrow_info, &escrow, account_info_iter)?
+        {
+            let token_program_info = next_account_info(account_info_iter)?;
+            if *token_program_info.key != spl_token::id() {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            Self::release_bonds(
+                escrow_info,
+                &mut escrow,
+                token_program_info.clone(),
+                &bond_accounts,
+            )?;
+        }
+
+        escrow.state = EscrowState::Complete;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::Completed {
+            escrow: *escrow_info.key,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Close` instruction.
+    pub fn process_close(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let canceler_token_account_info = next_account_info(account_info_iter)?;
+        let receiver_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Escrow::unpack_unchecked(&escrow_info.data.borrow())?;
+
+        // Check if escrow account exists and is initialized
+        if !escrow.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        // Only escrows in the final state can be closed
+        if escrow.state != EscrowState::Complete && escrow.state != EscrowState::Cancelled {
+            return Err(EscrowError::WrongState.into());
+        }
+
+        Self::check_role(
+            &escrow,
+            trusted_handler_info,
+            &[Role::Canceler, Role::Launcher, Role::TrustedHandler],
+            EscrowError::UnauthorizedClose,
+        )?;
+
+        // Closing would hand the deferred fees and the vesting payouts over to the canceler
+        if escrow.unsettled_fees() != 0 {
+            return Err(EscrowError::FeesNotSettled.into());
+        }
+        if escrow.vesting_amount != 0 {
+            return Err(EscrowError::VestingInProgress.into());
+        }
+
+        // Check all accounts validity
+        if *token_account_info.key != escrow.token_account
+            || *canceler_token_account_info.key != escrow.canceler_token_account
+            || *token_program_info.key != spl_token::id()
+            || *authority_info.key
+                != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let native_accounts =
+            Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+        let bond_accounts =
+            Self::next_bond_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+
+        // Bonds of the cancelled escrow are still locked, return them and close the vault
+        if let Some(bond_accounts) = bond_accounts {
+            Self::release_bonds(
+                escrow_info,
+                &mut escrow,
+                token_program_info.clone(),
+                &bond_accounts,
+            )?;
+            Self::bond_vault_close(
+                escrow_info.key,
+                token_program_info.clone(),
+                bond_accounts.bond_vault_info,
+                receiver_info.clone(),
+                bond_accounts.bond_vault_bump_seed,
+            )?;
+        }
+
+        // Token account can only be closed when empty, return the rest to the canceler
+        let token_account = TokenAccount::unpack_unchecked(&token_account_info.data.borrow())?;
+        if token_account.amount != 0 {
+            Self::send_tokens(
+                escrow_info,
+                &escrow,
+                token_program_info,
+                token_account_info,
+                authority_info,
+                canceler_token_account_info,
+                native_accounts.as_ref(),
+                token_account.amount,
+            )?;
+        }
+
+        Self::token_close_account(
+            escrow_info.key,
+            token_program_info.clone(),
+            token_account_info.clone(),
+            receiver_info.clone(),
+            authority_info.clone(),
+            escrow.bump_seed,
+        )?;
+
+        // Authority reserve of the native SOL escrow goes to the receiver as well
+        if let Some(native_accounts) = native_accounts {
+            if authority_info.lamports() != 0 {
+                Self::authority_transfer(
+                    escrow_info.key,
+                    native_accounts.system_program_info,
+                    authority_info.clone(),
+                    receiver_info.clone(),
+                    escrow.bump_seed,
+                    authority_info.lamports(),
+                )?;
+            }
+        }
+
+        // Zero escrow data and move all its lamports to the receiver
+        escrow_info
+            .data
+            .borrow_mut()
+            .iter_mut()
+            .for_each(|byte| *byte = 0);
+
+        let receiver_lamports = receiver_info
+            .lamports()
+            .checked_add(escrow_info.lamports())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        **receiver_info.lamports.borrow_mut() = receiver_lamports;
+        **escrow_info.lamports.borrow_mut() = 0;
+
+        EscrowEvent::Closed {
+            escrow: *escrow_info.key,
+            receiver: *receiver_info.key,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Fund` instruction.
+    pub fn process_fund(accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let launcher_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let launcher_token_account_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let escrow = Self::get_escrow_with_fund_check(
+            escrow_info,
+            clock,
+            launcher_info,
+            token_account_info,
+            amount,
+        )?;
+
+        let ix = spl_token::instruction::transfer(
+            token_program_info.key,
+            launcher_token_account_info.key,
+            token_account_info.key,
+            launcher_info.key,
+            &[],
+            amount,
+        )?;
+        invoke(
+            &ix,
+            &[
+                launcher_token_account_info.clone(),
+                token_account_info.clone(),
+                launcher_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::Funded {
+            escrow: *escrow_info.key,
+            amount,
+            funded_amount: escrow.funded_amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `FundNative` instruction.
+    pub fn process_fund_native(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let launcher_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let escrow = Self::get_escrow_with_fund_check(
+            escrow_info,
+            clock,
+            launcher_info,
+            token_account_info,
+            amount,
+        )?;
+
+        if *authority_info.key != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
+            || *token_program_info.key != spl_token::id()
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let native_accounts =
+            Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?
+                .ok_or(EscrowError::WrongTokenMint)?;
+
+        // Wrap the launcher SOL and move it to the escrow token account, the wrap
+        // account rent goes back to the launcher
+        Self::create_wrap_account(
+            escrow_info.key,
+            token_program_info.clone(),
+            launcher_info.clone(),
+            authority_info.clone(),
+            &native_accounts,
+            escrow.bump_seed,
+            amount,
+        )?;
+        Self::token_transfer(
+            escrow_info.key,
+            token_program_info.clone(),
+            native_accounts.wrap_account_info.clone(),
+            token_account_info.clone(),
+            authority_info.clone(),
+            escrow.bump_seed,
+            amount,
+        )?;
+        Self::token_close_account(
+            escrow_info.key,
+            token_program_info.clone(),
+            native_accounts.wrap_account_info.clone(),
+            launcher_info.clone(),
+            authority_info.clone(),
+            escrow.bump_seed,
+        )?;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::Funded {
+            escrow: *escrow_info.key,
+            amount,
+            funded_amount: escrow.funded_amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Reclaim` instruction.
+    pub fn process_reclaim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let canceler_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
+
+        // Check if escrow account exists and is initialized
+        if !escrow.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        // Funds can be reclaimed by anyone only after expiration
+        if escrow.expires >= clock.unix_timestamp {
+            return Err(EscrowError::EscrowNotExpired.into());
+        }
+
+        // Check escrow state, finished escrows have nothing to reclaim
+        if escrow.state == EscrowState::Complete
+            || escrow.state == EscrowState::Paid
+            || escrow.state == EscrowState::Cancelled
+        {
+            return Err(EscrowError::WrongState.into());
+        }
+
+        // Check all accounts validity
+        if *token_account_info.key != escrow.token_account
+            || *canceler_token_account_info.key != escrow.canceler_token_account
+            || *token_program_info.key != spl_token::id()
+            || *authority_info.key
+                != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Escrow is cancelled even if it was never funded, deferred fees stay
+        // for the oracles to settle and vesting payouts for the recipients to withdraw,
+        // they are fully vested as the vesting ends before the expiration
+        let token_account = TokenAccount::unpack_unchecked(&token_account_info.data.borrow())?;
+        let amount = token_account
+            .amount
+            .saturating_sub(escrow.unsettled_fees())
+            .saturating_sub(escrow.vesting_amount);
+        if amount != 0 {
+            let native_accounts =
+                Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+            Self::send_tokens(
+                escrow_info,
+                &escrow,
+                token_program_info,
+                token_account_info,
+                authority_info,
+                canceler_token_account_info,
+                native_accounts.as_ref(),
+                amount,
+            )?;
+        }
+
+        if escrow.vesting_amount != 0 {
+            escrow.vesting_stopped_at = clock.unix_timestamp;
+        }
+        escrow.state = EscrowState::Cancelled;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::Cancelled {
+            escrow: *escrow_info.key,
+            amount,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `ExtendExpiration` instruction.
+    pub fn process_extend_expiration(
+        accounts: &[AccountInfo],
+        additional_seconds: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_expiration_check(
+            escrow_info,
+            clock,
+            vec![
+                EscrowState::Launched,
+                EscrowState::Pending,
+                EscrowState::Partial,
+                EscrowState::Disputed,
+            ],
+        )?;
+
+        // Only launcher and canceler are allowed to extend the escrow
+        Self::check_role(
+            &escrow,
+            trusted_handler_info,
+            &[Role::Launcher, Role::Canceler],
+            EscrowError::UnauthorizedExtend,
+        )?;
+
+        // Factory account must be the one escrow belongs to
+        if *factory_info.key != escrow.factory {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        let factory = Factory::unpack(&factory_info.data.borrow())?;
+
+        let additional_seconds: UnixTimestamp = additional_seconds
+            .try_into()
+            .map_err(|_| EscrowError::DurationOutOfBounds)?;
+        let expires = escrow
+            .expires
+            .checked_add(additional_seconds)
+            .ok_or(EscrowError::DurationOutOfBounds)?;
+
+        // Whole escrow lifetime is bounded the same way as the initial duration,
+        // escrows created before the creation time was stored can't be extended
+        // beyond the limit
+        if factory.max_duration != 0 {
+            if escrow.created_at == 0 {
+                return Err(EscrowError::DurationOutOfBounds.into());
+            }
+            let lifetime = (expires - escrow.created_at) as u64;
+            if lifetime > factory.max_duration {
+                return Err(EscrowError::DurationOutOfBounds.into());
+            }
+        }
+
+        escrow.expires = expires;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::ExpirationExtended {
+            escrow: *escrow_info.key,
+            expires,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `AddTrustedHandlers` instruction.
+    pub fn process_add_trusted_handlers(
+        accounts: &[AccountInfo],
+        handlers: &[Pubkey],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
-        // Check duration validity
-        if duration == 0 {
-            return Err(EscrowError::EscrowExpired.into());
-        }
+        let mut escrow = Self::get_escrow_with_expiration_check(
+            escrow_info,
+            clock,
+            vec![
+                EscrowState::Launched,
+                EscrowState::Pending,
+                EscrowState::Partial,
+                EscrowState::Paid,
+                EscrowState::Disputed,
+            ],
+        )?;
 
-        // Calculate authority key and bump seed
-        let (authority_key, bump_seed) =
-            Self::find_authority_bump_seed(program_id, escrow_info.key);
+        Self::check_role(
+            &escrow,
+            trusted_handler_info,
+            &[Role::Launcher, Role::Canceler],
+            EscrowError::UnauthorizedHandlersUpdate,
+        )?;
 
-        // Token account should be owned by the contract authority
-        let token_account = Box::new(TokenAccount::unpack_unchecked(
-            &token_account_info.data.borrow(),
-        )?);
-        if token_account.owner != authority_key {
-            return Err(EscrowError::TokenAccountAuthority.into());
+        for handler in handlers {
+            if *handler == Pubkey::default() {
+                return Err(ProgramError::InvalidInstructionData);
+            }
+            if escrow.trusted_handlers.contains(handler) {
+                continue;
+            }
+            let free_slot = escrow
+                .trusted_handlers
+                .iter_mut()
+                .find(|slot| **slot == Pubkey::default())
+                .ok_or(EscrowError::TooManyTrustedHandlers)?;
+            *free_slot = *handler;
         }
 
-        // Check token account mints
-        if token_account.mint != *token_mint_info.key {
-            return Err(EscrowError::WrongTokenMint.into());
-        }
-        let canceler_token_account = Box::new(TokenAccount::unpack_unchecked(
-            &canceler_token_account_info.data.borrow(),
-        )?);
-        if canceler_token_account.mint != *token_mint_info.key {
-            return Err(EscrowError::WrongTokenMint.into());
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::TrustedHandlersAdded {
+            escrow: *escrow_info.key,
+            handlers: handlers.to_vec(),
         }
+        .emit();
 
-        let escrow = Box::new(Escrow {
-            state: EscrowState::Launched,
-            expires: clock.unix_timestamp + duration as i64,
-            bump_seed,
-            token_mint: *token_mint_info.key,
-            token_account: *token_account_info.key,
-            launcher: *launcher_info.key,
-            canceler: *canceler_info.key,
-            canceler_token_account: *canceler_token_account_info.key,
-            ..Default::default()
-        });
+        Ok(())
+    }
+
+    /// Processes `RemoveTrustedHandlers` instruction.
+    pub fn process_remove_trusted_handlers(
+        accounts: &[AccountInfo],
+        handlers: &[Pubkey],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut escrow = Self::get_escrow_with_expiration_check(
+            escrow_info,
+            clock,
+            vec![
+                EscrowState::Launched,
+                EscrowState::Pending,
+                EscrowState::Partial,
+                EscrowState::Paid,
+                EscrowState::Disputed,
+            ],
+        )?;
+
+        Self::check_role(
+            &escrow,
+            trusted_handler_info,
+            &[Role::Launcher, Role::Canceler],
+            EscrowError::UnauthorizedHandlersUpdate,
+        )?;
+
+        for slot in escrow.trusted_handlers.iter_mut() {
+            if handlers.contains(slot) {
+                *slot = Pubkey::default();
+            }
+        }
 
         Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::TrustedHandlersRemoved {
+            escrow: *escrow_info.key,
+            handlers: handlers.to_vec(),
+        }
+        .emit();
+
         Ok(())
     }
 
-    /// Processes `Setup` instruction.
-    pub fn process_setup(
+    /// Processes `StoreIntermediateResults` instruction.
+    pub fn process_store_intermediate_results(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
-        reputation_oracle_stake: u8,
-        recording_oracle_stake: u8,
-        manifest_url: &DataUrl,
-        manifest_hash: &DataHash,
+        results_url: &DataUrl,
+        results_hash: &DataHash,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let escrow_info = next_account_info(account_info_iter)?;
         let trusted_handler_info = next_account_info(account_info_iter)?;
         let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let factory_info = next_account_info(account_info_iter)?;
+        let results_log_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_info = next_account_info(account_info_iter)?;
 
-        let reputation_oracle_info = next_account_info(account_info_iter)?;
+        let escrow = Self::get_escrow_with_state_check(
+            escrow_info,
+            clock,
+            trusted_handler_info,
+            &[Role::RecordingOracle, Role::Launcher],
+            EscrowError::UnauthorizedStoreResults,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        Self::check_hash_algorithm(results_hash)?;
+
+        let (results_log_key, bump_seed) =
+            Self::find_results_log_address(program_id, escrow_info.key);
+        if *results_log_info.key != results_log_key {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Log account is created with the first record, lamports sent to the log
+        // address in advance don't make it a log
+        let mut results_log = if results_log_info.owner != program_id {
+            Self::create_program_account(
+                program_id,
+                trusted_handler_info.clone(),
+                results_log_info.clone(),
+                system_program_info.clone(),
+                rent,
+                ResultsLog::ACCOUNT_LEN,
+                &[
+                    RESULTS_LOG_SEED,
+                    &escrow_info.key.to_bytes()[..32],
+                    &[bump_seed],
+                ],
+            )?;
+            ResultsLog {
+                escrow: *escrow_info.key,
+                count: 0,
+            }
+        } else {
+            let data = results_log_info.data.borrow();
+            if data.len() != ResultsLog::ACCOUNT_LEN {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let results_log = ResultsLog::unpack(&data[..ResultsLog::LEN])?;
+            if results_log.escrow != *escrow_info.key {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            results_log
+        };
+
+        if results_log.count as usize >= MAX_INTERMEDIATE_RESULTS {
+            return Err(EscrowError::TooManyIntermediateResults.into());
+        }
+
+        let result = IntermediateResult {
+            url: *results_url,
+            hash: *results_hash,
+            timestamp: clock.unix_timestamp,
+            submitter: *trusted_handler_info.key,
+        };
+        let mut data = results_log_info.data.borrow_mut();
+        ResultsLog::pack_result(results_log.count as usize, &result, &mut data)?;
+        results_log.count += 1;
+        ResultsLog::pack(results_log, &mut data[..ResultsLog::LEN])?;
+
+        EscrowEvent::IntermediateResultsStored {
+            escrow: *escrow_info.key,
+            results_hash: *results_hash,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `WriteUrlData` instruction.
+    pub fn process_write_url_data(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        offset: u32,
+        data: &[u8],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let url_data_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        if url_data_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !authority_info.is_signer {
+            return Err(EscrowError::UnauthorizedSigner.into());
+        }
+
+        let mut account_data = url_data_info.data.borrow_mut();
+        if account_data.len() < UrlData::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        // Accounts of other types are rejected by the account type check
+        let mut url_data = UrlData::unpack_unchecked(&account_data[..UrlData::LEN])?;
+
+        // The first write sets the authority, only a blank account can become URL data
+        if !url_data.is_initialized() {
+            if account_data.iter().any(|&byte| byte != 0) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            url_data.authority = *authority_info.key;
+        } else if url_data.authority != *authority_info.key {
+            return Err(EscrowError::UnauthorizedSigner.into());
+        }
+
+        let end = offset
+            .checked_add(data.len() as u32)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        let url = &mut account_data[UrlData::LEN..];
+        if end as usize > url.len() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        url[offset as usize..end as usize].copy_from_slice(data);
+        url_data.len = url_data.len.max(end);
+
+        UrlData::pack(url_data, &mut account_data[..UrlData::LEN])?;
+
+        EscrowEvent::UrlDataWritten {
+            url_data: *url_data_info.key,
+            offset,
+            len: data.len() as u32,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `Migrate` instruction.
+    pub fn process_migrate(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+
+        let version = Escrow::unpack_version(&escrow_info.data.borrow())?;
+        let mut escrow = Box::new(Escrow::unpack(&escrow_info.data.borrow())?);
+        if version == ESCROW_VERSION {
+            return Err(EscrowError::AlreadyMigrated.into());
+        }
+
+        // Versioned escrows have room for the latest layout and are rewritten in place
+        if escrow_info.data_len() == Escrow::LEN {
+            Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+            EscrowEvent::Migrated {
+                escrow: *escrow_info.key,
+                version: ESCROW_VERSION,
+            }
+            .emit();
+
+            return Ok(());
+        }
+
+        // Legacy escrow accounts can't be resized, the escrow is copied into the new account
+        let new_escrow_info = next_account_info(account_info_iter)?;
+        let launcher_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let receiver_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+        let factory_entry_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !launcher_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if *launcher_info.key != escrow.launcher {
+            return Err(EscrowError::UnauthorizedSigner.into());
+        }
+
+        // New escrow must be an uninitialized program account of the latest layout
+        if new_escrow_info.owner != program_id || new_escrow_info.data_len() != Escrow::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if Escrow::unpack_unchecked(&new_escrow_info.data.borrow())?.is_initialized() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        // Check all accounts validity
+        if *token_account_info.key != escrow.token_account
+            || *authority_info.key
+                != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
+            || *token_program_info.key != spl_token::id()
+            || *factory_info.key != escrow.factory
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut factory = Factory::unpack_unchecked(&factory_info.data.borrow())?;
+        if !factory.is_initialized() {
+            return Err(EscrowError::FactoryNotInitialized.into());
+        }
+
+        // Escrow authority is derived from the escrow address, hand the token
+        // account over to the authority of the new escrow
+        let (new_authority_key, new_bump_seed) =
+            Self::find_authority_bump_seed(program_id, new_escrow_info.key);
+        Self::token_set_owner(
+            escrow_info.key,
+            token_program_info.clone(),
+            token_account_info.clone(),
+            &new_authority_key,
+            authority_info.clone(),
+            escrow.bump_seed,
+        )?;
+
+        // Legacy escrows were funded with the plain token transfers, count the
+        // tokens already sent and the ones left in the token account as funded
+        let token_account = TokenAccount::unpack_unchecked(&token_account_info.data.borrow())?;
+        escrow.funded_amount = escrow
+            .sent_amount
+            .checked_add(token_account.amount)
+            .ok_or(ProgramError::InvalidAccountData)?;
+
+        escrow.bump_seed = new_bump_seed;
+        Escrow::pack(*escrow, &mut new_escrow_info.data.borrow_mut())?;
+
+        // Legacy escrows predate the factory registry, the new escrow is
+        // registered so it can be listed with the other escrows of the factory
+        Self::register_escrow(
+            program_id,
+            new_escrow_info.key,
+            factory_info,
+            &mut factory,
+            factory_entry_info,
+            launcher_info,
+            system_program_info,
+            rent,
+        )?;
+
+        // Zero legacy escrow data and move all its lamports to the receiver
+        escrow_info
+            .data
+            .borrow_mut()
+            .iter_mut()
+            .for_each(|byte| *byte = 0);
+
+        let receiver_lamports = receiver_info
+            .lamports()
+            .checked_add(escrow_info.lamports())
+            .ok_or(ProgramError::InvalidAccountData)?;
+        **receiver_info.lamports.borrow_mut() = receiver_lamports;
+        **escrow_info.lamports.borrow_mut() = 0;
+
+        EscrowEvent::Migrated {
+            escrow: *new_escrow_info.key,
+            version: ESCROW_VERSION,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `SettleFees` instruction.
+    pub fn process_settle_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let token_account_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
         let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
-        let recording_oracle_info = next_account_info(account_info_iter)?;
         let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
+
+        // Check if escrow account exists and is initialized
+        if !escrow.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        // Fees are earned, so they can be settled after expiration or cancellation
+        if escrow.state != EscrowState::Pending
+            && escrow.state != EscrowState::Partial
+            && escrow.state != EscrowState::Paid
+            && escrow.state != EscrowState::Cancelled
+            && escrow.state != EscrowState::Disputed
+        {
+            return Err(EscrowError::WrongState.into());
+        }
+
+        Self::check_role(
+            &escrow,
+            trusted_handler_info,
+            &[
+                Role::ReputationOracle,
+                Role::RecordingOracle,
+                Role::Launcher,
+                Role::TrustedHandler,
+            ],
+            EscrowError::UnauthorizedSettleFees,
+        )?;
+
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
+
+        // Check all accounts validity
+        Self::check_payout_accounts(
+            program_id,
+            escrow_info,
+            &escrow,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            token_program_info,
+        )?;
+
+        if escrow.reputation_oracle_fee_accrued != 0 {
+            Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                reputation_oracle_token_account_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                escrow.reputation_oracle_fee_accrued,
+            )?;
+        }
+        if escrow.recording_oracle_fee_accrued != 0 {
+            Self::token_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                token_account_info.clone(),
+                recording_oracle_token_account_info.clone(),
+                authority_info.clone(),
+                escrow.bump_seed,
+                escrow.recording_oracle_fee_accrued,
+            )?;
+        }
+
+        EscrowEvent::FeesSettled {
+            escrow: *escrow_info.key,
+            reputation_oracle_fee: escrow.reputation_oracle_fee_accrued,
+            recording_oracle_fee: escrow.recording_oracle_fee_accrued,
+        }
+        .emit();
+
+        escrow.reputation_oracle_fee_accrued = 0;
+        escrow.recording_oracle_fee_accrued = 0;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    /// Processes `Dispute` instruction.
+    pub fn process_dispute(accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let launcher_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
         let mut escrow = Self::get_escrow_with_state_check(
             escrow_info,
             clock,
-            trusted_handler_info,
-            vec![EscrowState::Launched],
+            launcher_info,
+            &[Role::Launcher],
+            EscrowError::UnauthorizedDispute,
+            vec![EscrowState::Pending, EscrowState::Partial],
+        )?;
+
+        // Only the stored results can be disputed and only during the dispute window
+        if escrow.dispute_window == 0
+            || escrow.results_stored_at == 0
+            || clock.unix_timestamp >= escrow.dispute_window_end()
+        {
+            return Err(EscrowError::DisputeWindowClosed.into());
+        }
+
+        escrow.state = EscrowState::Disputed;
+
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
+
+        EscrowEvent::Disputed {
+            escrow: *escrow_info.key,
+            results_hash: escrow.final_results_hash,
+        }
+        .emit();
+
+        Ok(())
+    }
+
+    /// Processes `ResolveDispute` instruction.
+    pub fn process_resolve_dispute(
+        accounts: &[AccountInfo],
+        accept_results: bool,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let escrow_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let factory_info = next_account_info(account_info_iter)?;
+
+        let mut escrow = Self::get_escrow_with_expiration_check(
+            escrow_info,
+            clock,
+            vec![EscrowState::Disputed],
         )?;
 
-        // Check stake value validity
-        let total_stake: u8 = reputation_oracle_stake
-            .checked_add(recording_oracle_stake)
-            .ok_or(ProgramError::InvalidInstructionData)?;
-        if total_stake == 0 || total_stake > 100 {
-            return Err(EscrowError::StakeOutOfBounds.into());
+        // Disputes are resolved by the administrator of the factory escrow belongs to
+        if *factory_info.key != escrow.factory {
+            return Err(ProgramError::InvalidInstructionData);
         }
+        Self::get_factory_with_admin_check(factory_info, admin_info)?;
 
-        // Check token account mints
-        let reputation_oracle_token_account =
-            TokenAccount::unpack_unchecked(&reputation_oracle_token_account_info.data.borrow())?;
-        if reputation_oracle_token_account.mint != escrow.token_mint {
-            return Err(EscrowError::WrongTokenMint.into());
-        }
-        let recording_oracle_token_account =
-            TokenAccount::unpack_unchecked(&recording_oracle_token_account_info.data.borrow())?;
-        if recording_oracle_token_account.mint != escrow.token_mint {
-            return Err(EscrowError::WrongTokenMint.into());
+        if accept_results {
+            // Accepted results are final, the dispute window is closed
+            escrow.results_stored_at = clock
+                .unix_timestamp
+                .saturating_sub(escrow.dispute_window as UnixTimestamp);
+        } else {
+            // Rejected results are dropped, payouts wait for the new ones
+            escrow.final_results_url = DataUrl::default();
+            escrow.final_results_hash = DataHash::default();
+            escrow.results_stored_at = 0;
+            // Launcher can now slash the oracle bonds
+            escrow.results_rejected = escrow.bonded;
         }
 
-        // Update escrow fields with the new values
-        escrow.reputation_oracle = COption::Some(*reputation_oracle_info.key);
-        escrow.reputation_oracle_token_account =
-            COption::Some(*reputation_oracle_token_account_info.key);
-        escrow.reputation_oracle_stake = reputation_oracle_stake;
-
-        escrow.recording_oracle = COption::Some(*recording_oracle_info.key);
-        escrow.recording_oracle_token_account =
-            COption::Some(*recording_oracle_token_account_info.key);
-        escrow.recording_oracle_stake = recording_oracle_stake;
+        escrow.state = if escrow.sent_recipients == 0 {
+            EscrowState::Pending
+        } else {
+            EscrowState::Partial
+        };
 
-        escrow.manifest_url = *manifest_url;
-        escrow.manifest_hash = *manifest_hash;
+        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
 
-        escrow.state = EscrowState::Pending;
+        EscrowEvent::DisputeResolved {
+            escrow: *escrow_info.key,
+            accept_results,
+        }
+        .emit();
 
-        Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
         Ok(())
     }
 
-    /// Processes `StoreResults` instruction.
-    pub fn process_store_results(
+    /// Processes `SlashBonds` instruction.
+    pub fn process_slash_bonds(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
-        final_results_url: &DataUrl,
-        final_results_hash: &DataHash,
+        reputation_oracle_amount: u64,
+        recording_oracle_amount: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let escrow_info = next_account_info(account_info_iter)?;
-        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let launcher_info = next_account_info(account_info_iter)?;
         let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let bond_vault_info = next_account_info(account_info_iter)?;
+        let receiver_token_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
 
         let mut escrow = Self::get_escrow_with_state_check(
             escrow_info,
             clock,
-            trusted_handler_info,
-            vec![EscrowState::Pending, EscrowState::Partial],
+            launcher_info,
+            &[Role::Launcher],
+            EscrowError::UnauthorizedSlash,
+            vec![
+                EscrowState::Pending,
+                EscrowState::Partial,
+                EscrowState::Paid,
+                EscrowState::Cancelled,
+            ],
         )?;
 
-        // Save final results url and hash
-        escrow.final_results_url = *final_results_url;
-        escrow.final_results_hash = *final_results_hash;
+        // Bonds are only at risk once the results are rejected by the factory administrator
+        if !escrow.results_rejected {
+            return Err(EscrowError::SlashNotAllowed.into());
+        }
+
+        let (bond_vault_key, bond_vault_bump_seed) =
+            Self::find_bond_vault_address(program_id, escrow_info.key);
+        if *bond_vault_info.key != bond_vault_key || *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        if reputation_oracle_amount > escrow.reputation_oracle_bond
+            || recording_oracle_amount > escrow.recording_oracle_bond
+        {
+            return Err(EscrowError::NotEnoughBalance.into());
+        }
+
+        let amount = reputation_oracle_amount
+            .checked_add(recording_oracle_amount)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+        if amount != 0 {
+            Self::bond_vault_transfer(
+                escrow_info.key,
+                token_program_info.clone(),
+                bond_vault_info.clone(),
+                receiver_token_account_info.clone(),
+                bond_vault_bump_seed,
+                amount,
+            )?;
+        }
+
+        escrow.reputation_oracle_bond -= reputation_oracle_amount;
+        escrow.recording_oracle_bond -= recording_oracle_amount;
+        escrow.results_rejected = false;
 
         Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
 
+        EscrowEvent::BondsSlashed {
+            escrow: *escrow_info.key,
+            receiver: *receiver_token_account_info.key,
+            reputation_oracle_amount,
+            recording_oracle_amount,
+        }
+        .emit();
+
         Ok(())
     }
 
-    /// Processes `StoreFinalAmounts` instruction.
-    pub fn process_store_amounts(
-        accounts: &[AccountInfo],
-        total_amount: u64,
-        total_recipients: u64,
-    ) -> ProgramResult {
+    /// Processes `ReleaseBonds` instruction.
+    pub fn process_release_bonds(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let escrow_info = next_account_info(account_info_iter)?;
-        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
         let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
 
-        let mut escrow = Self::get_escrow_with_state_check(
-            escrow_info,
-            clock,
-            trusted_handler_info,
-            vec![EscrowState::Pending, EscrowState::Partial],
+        let mut escrow = Box::new(Escrow::unpack(&escrow_info.data.borrow())?);
+
+        Self::check_role(
+            &escrow,
+            oracle_info,
+            &[Role::ReputationOracle, Role::RecordingOracle],
+            EscrowError::UnauthorizedRelease,
         )?;
 
-        // Save final results url and hash
-        escrow.total_amount = total_amount;
-        escrow.total_recipients = total_recipients;
+        // Bonds of the escrow running on are released by `Complete`, the ones the
+        // launcher can slash for the rejected results stay locked
+        if (escrow.state != EscrowState::Cancelled && escrow.expires >= clock.unix_timestamp)
+            || escrow.results_rejected
+        {
+            return Err(EscrowError::WrongState.into());
+        }
+
+        let bond_accounts =
+            Self::next_bond_accounts(program_id, escrow_info, &escrow, account_info_iter)?
+                .ok_or(EscrowError::WrongState)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        if *token_program_info.key != spl_token::id() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Self::release_bonds(
+            escrow_info,
+            &mut escrow,
+            token_program_info.clone(),
+            &bond_accounts,
+        )?;
 
         Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
 
         Ok(())
     }
 
-    /// Processes `Payout` instruction.
-    pub fn process_payout(
+    /// Processes `VestedPayout` instruction.
+    pub fn process_vested_payout(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount: u64,
+        start: UnixTimestamp,
+        cliff: UnixTimestamp,
+        end: UnixTimestamp,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let escrow_info = next_account_info(account_info_iter)?;
@@ -361,188 +3779,224 @@ impl Processor {
         let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
         let token_account_info = next_account_info(account_info_iter)?;
         let authority_info = next_account_info(account_info_iter)?;
-        let recipient_token_account_info = next_account_info(account_info_iter)?;
+        let recipient_info = next_account_info(account_info_iter)?;
         let reputation_oracle_token_account_info = next_account_info(account_info_iter)?;
         let recording_oracle_token_account_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
+        let factory_info = next_account_info(account_info_iter)?;
+        let vesting_schedule_info = next_account_info(account_info_iter)?;
+        let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+        let system_program_info = next_account_info(account_info_iter)?;
 
         let mut escrow = Self::get_escrow_with_state_check(
             escrow_info,
             clock,
             trusted_handler_info,
+            &[Role::ReputationOracle],
+            EscrowError::UnauthorizedPayout,
             vec![EscrowState::Pending, EscrowState::Partial],
         )?;
 
-        // Check all accounts validity
-        if *token_account_info.key != escrow.token_account
-            || *reputation_oracle_token_account_info.key
-                != escrow
-                    .reputation_oracle_token_account
-                    .ok_or(EscrowError::OracleNotInitialized)?
-            || *recording_oracle_token_account_info.key
-                != escrow
-                    .recording_oracle_token_account
-                    .ok_or(EscrowError::OracleNotInitialized)?
-            || *authority_info.key
-                != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
-        {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        Self::get_factory_with_pause_check(&escrow, factory_info)?;
 
-        // Check account balance
-        let token_account = TokenAccount::unpack_unchecked(&token_account_info.data.borrow())?;
-        if token_account.amount < amount {
-            return Err(EscrowError::NotEnoughBalance.into());
-        }
+        Self::check_dispute_window(&escrow, clock)?;
 
-        // Check if not too many payouts
-        if (escrow.sent_amount + amount > escrow.total_amount)
-            || (escrow.sent_recipients + 1 > escrow.total_recipients)
-        {
-            return Err(EscrowError::TooManyPayouts.into());
+        // Vesting must end before the escrow expires and can be reclaimed
+        if start > cliff || cliff > end || end > escrow.expires {
+            return Err(EscrowError::InvalidVestingSchedule.into());
         }
 
-        // Calculate fees
-        let reputation_oracle_fee_amount = amount
-            .checked_mul(escrow.reputation_oracle_stake as u64)
-            .unwrap_or(0)
-            .checked_div(100)
-            .unwrap_or(0);
-        let recording_oracle_fee_amount = amount
-            .checked_mul(escrow.recording_oracle_stake as u64)
-            .unwrap_or(0)
-            .checked_div(100)
-            .unwrap_or(0);
-        let recipient_amount = amount
-            .saturating_sub(reputation_oracle_fee_amount)
-            .saturating_sub(recording_oracle_fee_amount);
+        // Check all accounts validity
+        Self::check_payout_accounts(
+            program_id,
+            escrow_info,
+            &escrow,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            token_program_info,
+        )?;
 
-        // Send tokens
-        if recipient_amount != 0 {
-            Self::token_transfer(
-                escrow_info.key,
-                token_program_info.clone(),
-                token_account_info.clone(),
-                recipient_token_account_info.clone(),
-                authority_info.clone(),
-                escrow.bump_seed,
-                recipient_amount,
-            )?;
+        // Recipient must be able to receive the tokens when they vest
+        if !(Self::is_native(&escrow) && Self::is_system_account(recipient_info)) {
+            let recipient_token_account = TokenAccount::unpack(&recipient_info.data.borrow())?;
+            if recipient_token_account.mint != escrow.token_mint {
+                return Err(ProgramError::InvalidInstructionData);
+            }
         }
-        if reputation_oracle_fee_amount != 0 {
-            Self::token_transfer(
-                escrow_info.key,
-                token_program_info.clone(),
-                token_account_info.clone(),
-                reputation_oracle_token_account_info.clone(),
-                authority_info.clone(),
-                escrow.bump_seed,
-                reputation_oracle_fee_amount,
-            )?;
+
+        let (sent_amount, sent_recipients) = Self::check_payout_totals(&escrow, amount, 1)?;
+
+        let (vesting_schedule_key, bump_seed) =
+            Self::find_vesting_schedule_address(program_id, escrow_info.key, recipient_info.key);
+        if *vesting_schedule_info.key != vesting_schedule_key {
+            return Err(ProgramError::InvalidInstructionData);
         }
-        if recording_oracle_fee_amount != 0 {
-            Self::token_transfer(
-                escrow_info.key,
-                token_program_info.clone(),
-                token_account_info.clone(),
-                recording_oracle_token_account_info.clone(),
-                authority_info.clone(),
-                escrow.bump_seed,
-                recording_oracle_fee_amount,
-            )?;
+        // Lamports sent to the schedule address in advance don't make it a schedule
+        if vesting_schedule_info.owner == program_id {
+            return Err(EscrowError::VestingScheduleExists.into());
         }
+        Self::create_program_account(
+            program_id,
+            trusted_handler_info.clone(),
+            vesting_schedule_info.clone(),
+            system_program_info.clone(),
+            rent,
+            VestingSchedule::LEN,
+            &[
+                VESTING_SCHEDULE_SEED,
+                &escrow_info.key.to_bytes()[..32],
+                &recipient_info.key.to_bytes()[..32],
+                &[bump_seed],
+            ],
+        )?;
 
-        escrow.sent_amount += amount;
-        escrow.sent_recipients += 1;
+        // Oracles are paid right away, the recipient's tokens stay in the escrow
+        let last = Self::is_last_payout(&escrow, sent_amount, sent_recipients);
+        let (reputation_oracle_fee_amount, recording_oracle_fee_amount, recipient_amount) =
+            Self::calculate_oracle_fees(&mut escrow, amount, last)?;
+        Self::send_oracle_fees(
+            escrow_info,
+            &mut escrow,
+            token_program_info,
+            token_account_info,
+            authority_info,
+            reputation_oracle_token_account_info,
+            recording_oracle_token_account_info,
+            reputation_oracle_fee_amount,
+            recording_oracle_fee_amount,
+        )?;
 
-        if escrow.sent_recipients == escrow.total_recipients
-            && escrow.sent_amount == escrow.total_amount
-        {
-            escrow.state = EscrowState::Paid;
-        } else {
-            escrow.state = EscrowState::Partial;
-        }
+        VestingSchedule::pack(
+            VestingSchedule {
+                escrow: *escrow_info.key,
+                recipient: *recipient_info.key,
+                start,
+                cliff,
+                end,
+                total: recipient_amount,
+                withdrawn: 0,
+            },
+            &mut vesting_schedule_info.data.borrow_mut(),
+        )?;
+
+        // Vesting amount is a part of the sent amount and can't overflow
+        escrow.vesting_amount += recipient_amount;
+
+        Self::update_payout_state(&mut escrow, sent_amount, sent_recipients);
 
         Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
 
+        EscrowEvent::VestingCreated {
+            escrow: *escrow_info.key,
+            recipient: *recipient_info.key,
+            amount: recipient_amount,
+            reputation_oracle_fee: reputation_oracle_fee_amount,
+            recording_oracle_fee: recording_oracle_fee_amount,
+            start,
+            cliff,
+            end,
+        }
+        .emit();
+
         Ok(())
     }
 
-    /// Processes `Cancel` instruction.
-    pub fn process_cancel(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    /// Processes `Withdraw` instruction.
+    pub fn process_withdraw(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
         let escrow_info = next_account_info(account_info_iter)?;
-        let trusted_handler_info = next_account_info(account_info_iter)?;
+        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let vesting_schedule_info = next_account_info(account_info_iter)?;
         let token_account_info = next_account_info(account_info_iter)?;
         let authority_info = next_account_info(account_info_iter)?;
-        let canceler_token_account_info = next_account_info(account_info_iter)?;
+        let recipient_info = next_account_info(account_info_iter)?;
         let token_program_info = next_account_info(account_info_iter)?;
 
-        let mut escrow = Escrow::unpack_unchecked(&escrow_info.data.borrow())?;
+        let mut escrow = Box::new(Escrow::unpack_unchecked(&escrow_info.data.borrow())?);
 
         // Check if escrow account exists and is initialized
         if !escrow.is_initialized() {
             return Err(ProgramError::UninitializedAccount);
         }
 
-        // Check escrow state
-        if escrow.state == EscrowState::Complete || escrow.state == EscrowState::Paid {
+        // Vesting payouts are withdrawn from the paid out or cancelled escrow,
+        // the dispute of the later results doesn't stop the earlier payouts
+        if escrow.state != EscrowState::Partial
+            && escrow.state != EscrowState::Paid
+            && escrow.state != EscrowState::Disputed
+            && escrow.state != EscrowState::Cancelled
+        {
             return Err(EscrowError::WrongState.into());
         }
 
-        Self::check_trusted_handler(&escrow, trusted_handler_info)?;
-
         // Check all accounts validity
-        if *token_account_info.key != escrow.token_account
-            || *canceler_token_account_info.key != escrow.canceler_token_account
+        let mut vesting_schedule = VestingSchedule::unpack(&vesting_schedule_info.data.borrow())?;
+        let (vesting_schedule_key, _) =
+            Self::find_vesting_schedule_address(program_id, escrow_info.key, recipient_info.key);
+        if *vesting_schedule_info.key != vesting_schedule_key
+            || vesting_schedule.escrow != *escrow_info.key
+            || vesting_schedule.recipient != *recipient_info.key
+            || *token_account_info.key != escrow.token_account
+            || *token_program_info.key != spl_token::id()
             || *authority_info.key
                 != Self::authority_id(program_id, escrow_info.key, escrow.bump_seed)?
         {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        // Check account balance
-        let token_account = TokenAccount::unpack_unchecked(&token_account_info.data.borrow())?;
-        if token_account.amount == 0 {
+        // Payouts of the cancelled escrow stop vesting, the unvested remainder is
+        // released for the canceler
+        let released = if escrow.state == EscrowState::Cancelled {
+            vesting_schedule.stop_vesting(escrow.vesting_stopped_at)
+        } else {
+            0
+        };
+        let vested = vesting_schedule.vested_amount(clock.unix_timestamp);
+        let amount = vested.saturating_sub(vesting_schedule.withdrawn);
+        if amount == 0 && released == 0 {
             return Err(EscrowError::NotEnoughBalance.into());
         }
 
-        // Call token contract to do transfer
-        Self::token_transfer(
-            escrow_info.key,
-            token_program_info.clone(),
-            token_account_info.clone(),
-            canceler_token_account_info.clone(),
-            authority_info.clone(),
-            escrow.bump_seed,
-            token_account.amount,
-        )?;
-
-        escrow.state = EscrowState::Cancelled;
-
-        Escrow::pack(escrow, &mut escrow_info.data.borrow_mut())?;
-
-        Ok(())
-    }
-
-    /// Processes `Complete` instruction.
-    pub fn process_complete(accounts: &[AccountInfo]) -> ProgramResult {
-        let account_info_iter = &mut accounts.iter();
-        let escrow_info = next_account_info(account_info_iter)?;
-        let trusted_handler_info = next_account_info(account_info_iter)?;
-        let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        if amount != 0 {
+            let native_accounts =
+                Self::next_native_accounts(program_id, escrow_info, &escrow, account_info_iter)?;
+            Self::send_tokens(
+                escrow_info,
+                &escrow,
+                token_program_info,
+                token_account_info,
+                authority_info,
+                recipient_info,
+                native_accounts.as_ref(),
+                amount,
+            )?;
+        }
 
-        let mut escrow = Self::get_escrow_with_state_check(
-            escrow_info,
-            clock,
-            trusted_handler_info,
-            vec![EscrowState::Paid],
+        vesting_schedule.withdrawn = vested;
+        VestingSchedule::pack(
+            vesting_schedule,
+            &mut vesting_schedule_info.data.borrow_mut(),
         )?;
 
-        escrow.state = EscrowState::Complete;
+        // Released tokens were never paid
+        escrow.vesting_amount = escrow
+            .vesting_amount
+            .checked_sub(amount + released)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        escrow.sent_amount = escrow.sent_amount.saturating_sub(released);
 
         Escrow::pack(*escrow, &mut escrow_info.data.borrow_mut())?;
 
+        EscrowEvent::VestingWithdrawn {
+            escrow: *escrow_info.key,
+            recipient: *recipient_info.key,
+            amount,
+            released,
+        }
+        .emit();
+
         Ok(())
     }
 
@@ -564,14 +4018,23 @@ impl Processor {
                 recording_oracle_stake,
                 manifest_url,
                 manifest_hash,
+                defer_fees,
+                dispute_window,
+                reputation_oracle_bond,
+                recording_oracle_bond,
             } => {
                 msg!("Instruction: Setup");
                 Self::process_setup(
+                    program_id,
                     accounts,
                     reputation_oracle_stake,
                     recording_oracle_stake,
                     &manifest_url,
                     &manifest_hash,
+                    defer_fees,
+                    dispute_window,
+                    reputation_oracle_bond,
+                    recording_oracle_bond,
                 )
             }
             EscrowInstruction::StoreResults {
@@ -596,9 +4059,19 @@ impl Processor {
                     total_recipients,
                 )
             }
-            EscrowInstruction::Payout { amount } => {
+            EscrowInstruction::Payout {
+                amount,
+                payout_id,
+                worker_score,
+            } => {
                 msg!("Instruction: Payout");
-                Self::process_payout(program_id, accounts, amount)
+                Self::process_payout(
+                    program_id,
+                    accounts,
+                    amount,
+                    payout_id.as_ref(),
+                    worker_score,
+                )
             }
             EscrowInstruction::Cancel => {
                 msg!("Instruction: Payout");
@@ -606,7 +4079,144 @@ impl Processor {
             }
             EscrowInstruction::Complete => {
                 msg!("Instruction: Payout");
-                Self::process_complete(accounts)
+                Self::process_complete(program_id, accounts)
+            }
+            EscrowInstruction::FactorySetAdmin => {
+                msg!("Instruction: Set Factory Admin");
+                Self::process_factory_set_admin(accounts)
+            }
+            EscrowInstruction::FactorySetVersion { version } => {
+                msg!("Instruction: Set Factory Version");
+                Self::process_factory_set_version(accounts, version)
+            }
+            EscrowInstruction::FactorySetPolicy {
+                token_mint,
+                max_duration,
+                max_oracle_stake,
+            } => {
+                msg!("Instruction: Set Factory Policy");
+                Self::process_factory_set_policy(
+                    accounts,
+                    token_mint,
+                    max_duration,
+                    max_oracle_stake,
+                )
+            }
+            EscrowInstruction::FactorySetPaused { paused } => {
+                msg!("Instruction: Set Factory Paused");
+                Self::process_factory_set_paused(accounts, paused)
+            }
+            EscrowInstruction::Close => {
+                msg!("Instruction: Close");
+                Self::process_close(program_id, accounts)
+            }
+            EscrowInstruction::Fund { amount } => {
+                msg!("Instruction: Fund");
+                Self::process_fund(accounts, amount)
+            }
+            EscrowInstruction::BatchPayout {
+                amounts,
+                payout_ids,
+            } => {
+                msg!("Instruction: Batch Payout");
+                Self::process_batch_payout(program_id, accounts, &amounts, &payout_ids)
+            }
+            EscrowInstruction::StorePayoutsRoot { payouts_root } => {
+                msg!("Instruction: Store Payouts Root");
+                Self::process_store_payouts_root(accounts, &payouts_root)
+            }
+            EscrowInstruction::Claim {
+                index,
+                amount,
+                proof,
+            } => {
+                msg!("Instruction: Claim");
+                Self::process_claim(program_id, accounts, index, amount, &proof)
+            }
+            EscrowInstruction::Reclaim => {
+                msg!("Instruction: Reclaim");
+                Self::process_reclaim(program_id, accounts)
+            }
+            EscrowInstruction::ExtendExpiration { additional_seconds } => {
+                msg!("Instruction: Extend Expiration");
+                Self::process_extend_expiration(accounts, additional_seconds)
+            }
+            EscrowInstruction::AddTrustedHandlers { handlers } => {
+                msg!("Instruction: Add Trusted Handlers");
+                Self::process_add_trusted_handlers(accounts, &handlers)
+            }
+            EscrowInstruction::RemoveTrustedHandlers { handlers } => {
+                msg!("Instruction: Remove Trusted Handlers");
+                Self::process_remove_trusted_handlers(accounts, &handlers)
+            }
+            EscrowInstruction::StoreIntermediateResults {
+                results_url,
+                results_hash,
+            } => {
+                msg!("Instruction: Store Intermediate Results");
+                Self::process_store_intermediate_results(
+                    program_id,
+                    accounts,
+                    &results_url,
+                    &results_hash,
+                )
+            }
+            EscrowInstruction::WriteUrlData { offset, data } => {
+                msg!("Instruction: Write URL Data");
+                Self::process_write_url_data(program_id, accounts, offset, &data)
+            }
+            EscrowInstruction::Migrate => {
+                msg!("Instruction: Migrate");
+                Self::process_migrate(program_id, accounts)
+            }
+            EscrowInstruction::SettleFees => {
+                msg!("Instruction: Settle Fees");
+                Self::process_settle_fees(program_id, accounts)
+            }
+            EscrowInstruction::FundNative { amount } => {
+                msg!("Instruction: Fund Native");
+                Self::process_fund_native(program_id, accounts, amount)
+            }
+            EscrowInstruction::Dispute => {
+                msg!("Instruction: Dispute");
+                Self::process_dispute(accounts)
+            }
+            EscrowInstruction::ResolveDispute { accept_results } => {
+                msg!("Instruction: Resolve Dispute");
+                Self::process_resolve_dispute(accounts, accept_results)
+            }
+            EscrowInstruction::SlashBonds {
+                reputation_oracle_amount,
+                recording_oracle_amount,
+            } => {
+                msg!("Instruction: Slash Bonds");
+                Self::process_slash_bonds(
+                    program_id,
+                    accounts,
+                    reputation_oracle_amount,
+                    recording_oracle_amount,
+                )
+            }
+            EscrowInstruction::VestedPayout {
+                amount,
+                start,
+                cliff,
+                end,
+            } => {
+                msg!("Instruction: Vested Payout");
+                Self::process_vested_payout(program_id, accounts, amount, start, cliff, end)
+            }
+            EscrowInstruction::Withdraw => {
+                msg!("Instruction: Withdraw");
+                Self::process_withdraw(program_id, accounts)
+            }
+            EscrowInstruction::ReleaseBonds => {
+                msg!("Instruction: Release Bonds");
+                Self::process_release_bonds(program_id, accounts)
+            }
+            EscrowInstruction::FactoryApproveOracle { min_scored_amount } => {
+                msg!("Instruction: Approve Factory Oracle");
+                Self::process_factory_approve_oracle(program_id, accounts, min_scored_amount)
             }
         }
     }
@@ -628,6 +4238,46 @@ impl PrintProgramError for EscrowError {
             EscrowError::OracleNotInitialized => msg!("Error: oracle not initialized"),
             EscrowError::TooManyPayouts => msg!("Error: too many payouts"),
             EscrowError::FactoryNotInitialized => msg!("Factory isn't initialized"),
+            EscrowError::DurationOutOfBounds => msg!("Error: duration out of bounds"),
+            EscrowError::FactoryPaused => msg!("Error: factory paused"),
+            EscrowError::InvalidMerkleProof => msg!("Error: invalid Merkle proof"),
+            EscrowError::AlreadyClaimed => msg!("Error: already claimed"),
+            EscrowError::DuplicatePayout => msg!("Error: duplicate payout"),
+            EscrowError::EscrowNotExpired => msg!("Error: escrow not expired"),
+            EscrowError::TooManyTrustedHandlers => msg!("Error: too many trusted handlers"),
+            EscrowError::UnauthorizedSetup => msg!("Error: unauthorized setup"),
+            EscrowError::UnauthorizedStoreResults => msg!("Error: unauthorized results storing"),
+            EscrowError::UnauthorizedStoreAmounts => {
+                msg!("Error: unauthorized final amounts storing")
+            }
+            EscrowError::UnauthorizedPayout => msg!("Error: unauthorized payout"),
+            EscrowError::UnauthorizedCancel => msg!("Error: unauthorized cancel"),
+            EscrowError::UnauthorizedComplete => msg!("Error: unauthorized complete"),
+            EscrowError::UnauthorizedClose => msg!("Error: unauthorized close"),
+            EscrowError::UnauthorizedFund => msg!("Error: unauthorized fund"),
+            EscrowError::UnauthorizedExtend => msg!("Error: unauthorized expiration extension"),
+            EscrowError::UnauthorizedHandlersUpdate => {
+                msg!("Error: unauthorized trusted handlers update")
+            }
+            EscrowError::TooManyIntermediateResults => msg!("Error: too many intermediate results"),
+            EscrowError::UnsupportedHashAlgorithm => msg!("Error: unsupported hash algorithm"),
+            EscrowError::AlreadyMigrated => msg!("Error: escrow already migrated"),
+            EscrowError::FeeOverflow => msg!("Error: fee overflow"),
+            EscrowError::UnauthorizedSettleFees => msg!("Error: unauthorized fee settlement"),
+            EscrowError::FeesNotSettled => msg!("Error: fees not settled"),
+            EscrowError::DisputeWindowOpen => msg!("Error: dispute window open"),
+            EscrowError::DisputeWindowClosed => msg!("Error: dispute window closed"),
+            EscrowError::UnauthorizedDispute => msg!("Error: unauthorized dispute"),
+            EscrowError::UnauthorizedSlash => msg!("Error: unauthorized slash"),
+            EscrowError::SlashNotAllowed => msg!("Error: slash not allowed"),
+            EscrowError::ScoreOutOfBounds => msg!("Error: score out of bounds"),
+            EscrowError::InvalidVestingSchedule => msg!("Error: invalid vesting schedule"),
+            EscrowError::VestingInProgress => msg!("Error: vesting in progress"),
+            EscrowError::MigrationRequired => msg!("Error: migration required"),
+            EscrowError::UnauthorizedRelease => msg!("Error: unauthorized release"),
+            EscrowError::OracleNotApproved => msg!("Error: oracle not approved"),
+            EscrowError::ScoredPayoutTooSmall => msg!("Error: scored payout too small"),
+            EscrowError::VestingScheduleExists => msg!("Error: vesting schedule exists"),
         }
     }
 }
@@ -1,5 +1,6 @@
 //! State types
 
+use crate::{error::EscrowError, merkle::MerkleHash};
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use num_enum::TryFromPrimitive;
 use solana_program::{
@@ -9,20 +10,116 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
-use std::{fmt, mem, str::FromStr, string::ToString};
+use std::{fmt, str::FromStr, string::ToString};
 
 /// Size for the URL field
 pub const URL_LEN: usize = 256;
 
+/// Maximum size of the hash digest
+pub const MAX_DIGEST_LEN: usize = 32;
+
+/// Length prefix value of the URL stored in the separate account
+pub const URL_ACCOUNT_LEN: u16 = u16::MAX;
+
+/// Oracle stakes are expressed in basis points, this value stands for 100%
+pub const STAKE_BASIS_POINTS: u16 = 10_000;
+
+/// Worker scores are expressed in basis points, this value stands for the best score
+pub const MAX_WORKER_SCORE: u16 = 10_000;
+
+/// Maximum number of the additional trusted handlers of the escrow
+pub const MAX_TRUSTED_HANDLERS: usize = 8;
+
+/// Maximum number of the intermediate results stored in the results log,
+/// further results are rejected rather than overwriting the earlier ones
+pub const MAX_INTERMEDIATE_RESULTS: usize = 16;
+
+/// Latest layout version of the escrow account
+pub const ESCROW_VERSION: u8 = 7;
+
+/// Space reserved at the end of the escrow account for the fields of the later
+/// layout versions, so that versioned escrows can be migrated in place
+pub const ESCROW_RESERVED_LEN: usize = 128;
+
 /// Uninitialized Factory version
 pub const UNINITIALIZED_FACTORY_VERSION: u8 = 0;
 
+/// Seed prefix for the factory entry program addresses
+pub const FACTORY_ENTRY_SEED: &[u8] = b"factory_entry";
+
+/// Seed prefix for the claim receipt program addresses
+pub const CLAIM_RECEIPT_SEED: &[u8] = b"claim_receipt";
+
+/// Seed prefix for the payout receipt program addresses
+pub const PAYOUT_RECEIPT_SEED: &[u8] = b"payout_receipt";
+
+/// Seed prefix for the intermediate results log program addresses
+pub const RESULTS_LOG_SEED: &[u8] = b"results_log";
+
+/// Seed prefix for the temporary native token account wrapping SOL
+pub const WRAP_SEED: &[u8] = b"wrap";
+
+/// Seed prefix for the token account holding the oracle bonds
+pub const BOND_VAULT_SEED: &[u8] = b"bond_vault";
+
+/// Seed of the worker reputation account addresses
+pub const WORKER_REPUTATION_SEED: &[u8] = b"worker_reputation";
+
+/// Seed of the vesting schedule account addresses
+pub const VESTING_SCHEDULE_SEED: &[u8] = b"vesting_schedule";
+
+/// Seed of the oracle approval account addresses
+pub const ORACLE_APPROVAL_SEED: &[u8] = b"oracle_approval";
+
+/// Seed of the receipts recording the escrow job of the worker
+pub const WORKER_JOB_SEED: &[u8] = b"worker_job";
+
+/// Type of the program account, stored in the first byte of its data so that an
+/// account of one type is never taken for another.
+///
+/// Escrow accounts keep the layout version in the first byte instead, escrow
+/// versions are always below the first account type value.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum AccountType {
+    /// Account is not initialized yet, all of its data is zero
+    Uninitialized = 0,
+    /// [Factory](struct.Factory.html) account
+    Factory = 0x80,
+    /// [FactoryEntry](struct.FactoryEntry.html) account
+    FactoryEntry,
+    /// [UrlData](struct.UrlData.html) account
+    UrlData,
+    /// [PayoutReceipt](struct.PayoutReceipt.html) account
+    PayoutReceipt,
+    /// [WorkerReputation](struct.WorkerReputation.html) account
+    WorkerReputation,
+    /// [VestingSchedule](struct.VestingSchedule.html) account
+    VestingSchedule,
+    /// [ResultsLog](struct.ResultsLog.html) account
+    ResultsLog,
+    /// [OracleApproval](struct.OracleApproval.html) account
+    OracleApproval,
+}
+
 /// Factory account
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Factory {
     /// Factory's version
     pub version: u8,
+    /// Number of escrows registered in the factory, also the index of the next escrow
+    pub escrow_count: u64,
+    /// Factory administrator, the only account allowed to change the factory
+    pub admin: Pubkey,
+    /// Token mint escrows of this factory are restricted to, any mint if not set
+    pub token_mint: COption<Pubkey>,
+    /// Maximum escrow duration in seconds, unlimited if zero
+    pub max_duration: u64,
+    /// Maximum sum of the oracle stakes (in basis points)
+    pub max_oracle_stake: u16,
+    /// Emergency pause, setup, results and payouts of all the factory escrows are disabled
+    pub paused: bool,
 }
 
 impl Sealed for Factory {}
@@ -33,16 +130,101 @@ impl IsInitialized for Factory {
 }
 
 impl Pack for Factory {
-    const LEN: usize = mem::size_of::<Self>();
+    const LEN: usize = 89;
 
     /// Packs a [Factory](struct.Factory.html) into a byte buffer.
     fn pack_into_slice(&self, output: &mut [u8]) {
-        output[0] = self.version;
+        let output = array_mut_ref![output, 0, Factory::LEN];
+        let (
+            account_type_dst,
+            version_dst,
+            escrow_count_dst,
+            admin_dst,
+            token_mint_dst,
+            max_duration_dst,
+            max_oracle_stake_dst,
+            paused_dst,
+        ) = mut_array_refs![output, 1, 1, 8, 32, 36, 8, 2, 1];
+        account_type_dst[0] = AccountType::Factory as u8;
+        version_dst[0] = self.version;
+        escrow_count_dst.copy_from_slice(&self.escrow_count.to_le_bytes());
+        admin_dst.copy_from_slice(self.admin.as_ref());
+        pack_coption_key(&self.token_mint, token_mint_dst);
+        max_duration_dst.copy_from_slice(&self.max_duration.to_le_bytes());
+        max_oracle_stake_dst.copy_from_slice(&self.max_oracle_stake.to_le_bytes());
+        paused_dst[0] = self.paused as u8;
     }
 
     /// Unpacks a byte buffer into a [Factory](struct.Factory.html).
     fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
-        Ok(Factory { version: input[0] })
+        let input = array_ref![input, 0, Factory::LEN];
+        if !check_account_type(input, AccountType::Factory)? {
+            return Ok(Factory::default());
+        }
+        let (
+            _account_type_src,
+            version_src,
+            escrow_count_src,
+            admin_src,
+            token_mint_src,
+            max_duration_src,
+            max_oracle_stake_src,
+            paused_src,
+        ) = array_refs![input, 1, 1, 8, 32, 36, 8, 2, 1];
+        Ok(Factory {
+            version: version_src[0],
+            escrow_count: u64::from_le_bytes(*escrow_count_src),
+            admin: Pubkey::new_from_array(*admin_src),
+            token_mint: unpack_coption_key(token_mint_src)?,
+            max_duration: u64::from_le_bytes(*max_duration_src),
+            max_oracle_stake: u16::from_le_bytes(*max_oracle_stake_src),
+            paused: match paused_src[0] {
+                0 => false,
+                1 => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+        })
+    }
+}
+
+/// Factory registry entry, one per escrow created under the factory.
+///
+/// Entry accounts are program addresses derived from the factory address and
+/// the escrow index, so the escrows of a factory can be enumerated from
+/// `0` to `Factory::escrow_count`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FactoryEntry {
+    /// Escrow registered under this entry
+    pub escrow: Pubkey,
+}
+
+impl Sealed for FactoryEntry {}
+impl IsInitialized for FactoryEntry {
+    fn is_initialized(&self) -> bool {
+        self.escrow != Pubkey::default()
+    }
+}
+
+impl Pack for FactoryEntry {
+    const LEN: usize = 33;
+
+    /// Packs a [FactoryEntry](struct.FactoryEntry.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        output[0] = AccountType::FactoryEntry as u8;
+        output[1..33].copy_from_slice(self.escrow.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [FactoryEntry](struct.FactoryEntry.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, FactoryEntry::LEN];
+        if !check_account_type(input, AccountType::FactoryEntry)? {
+            return Ok(FactoryEntry::default());
+        }
+        let escrow = array_ref![input, 1, 32];
+        Ok(FactoryEntry {
+            escrow: Pubkey::new_from_array(*escrow),
+        })
     }
 }
 
@@ -64,6 +246,8 @@ pub enum EscrowState {
     Complete,
     /// Escrow is cancelled, money returned
     Cancelled,
+    /// Final results are disputed by the launcher, payouts are stopped
+    Disputed,
 }
 
 impl Default for EscrowState {
@@ -72,65 +256,180 @@ impl Default for EscrowState {
     }
 }
 
-/// Stores job manifest hash
-#[repr(transparent)]
+/// Algorithm of the [DataHash](struct.DataHash.html) digest, values are the multihash codes
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, TryFromPrimitive)]
+pub enum HashAlgorithm {
+    /// No hash stored, multihash identity with the empty digest
+    Empty = 0x00,
+    /// SHA-1, kept readable for existing escrows but not accepted for the new hashes
+    Sha1 = 0x11,
+    /// SHA-256
+    Sha256 = 0x12,
+    /// Keccak-256
+    Keccak256 = 0x1b,
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        HashAlgorithm::Empty
+    }
+}
+
+impl HashAlgorithm {
+    /// Size of the algorithm digest
+    pub fn digest_len(self) -> usize {
+        match self {
+            HashAlgorithm::Empty => 0,
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 | HashAlgorithm::Keccak256 => 32,
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashAlgorithm::Empty => write!(f, "none"),
+            HashAlgorithm::Sha1 => write!(f, "sha1"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+            HashAlgorithm::Keccak256 => write!(f, "keccak256"),
+        }
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = ProgramError;
+
+    fn from_str(s: &str) -> Result<Self, ProgramError> {
+        match s {
+            "none" => Ok(HashAlgorithm::Empty),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "keccak256" => Ok(HashAlgorithm::Keccak256),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+}
+
+/// Stores data hash, the digest of up to `MAX_DIGEST_LEN` bytes tagged with its algorithm
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
-pub struct DataHash([u8; 20]);
+pub struct DataHash {
+    algorithm: HashAlgorithm,
+    digest: [u8; MAX_DIGEST_LEN],
+}
 
 impl AsRef<[u8]> for DataHash {
+    /// Digest bytes
     fn as_ref(&self) -> &[u8] {
-        &self.0[..]
+        &self.digest[..self.algorithm.digest_len()]
     }
 }
 
 impl DataHash {
-    /// Create new from fixed size array
-    pub const fn new_from_array(data: [u8; 20]) -> Self {
-        Self(data)
-    }
-    /// Create new from slice
-    pub fn new_from_slice(data: &[u8]) -> Result<Self, ProgramError> {
-        if data.len() != mem::size_of::<DataHash>() {
+    /// Create new from the digest of the given algorithm
+    pub fn new(algorithm: HashAlgorithm, digest: &[u8]) -> Result<Self, ProgramError> {
+        if digest.len() != algorithm.digest_len() {
             return Err(ProgramError::InvalidInstructionData);
         }
-        let mut hash: Self = Default::default();
-        hash.0.copy_from_slice(data);
+        let mut hash = DataHash {
+            algorithm,
+            digest: [0; MAX_DIGEST_LEN],
+        };
+        hash.digest[..digest.len()].copy_from_slice(digest);
         Ok(hash)
     }
+
+    /// Digest algorithm
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
 }
 
 impl ToString for DataHash {
+    /// Returns the digest in `ALGORITHM:HEX` format, empty string if no hash is stored
     fn to_string(&self) -> String {
-        String::from_utf8(self.0.to_vec()).unwrap()
+        if self.algorithm == HashAlgorithm::Empty {
+            return String::new();
+        }
+        let digest: String = self
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("{}:{}", self.algorithm, digest)
+    }
+}
+
+impl Sealed for DataHash {}
+
+impl Pack for DataHash {
+    const LEN: usize = 2 + MAX_DIGEST_LEN;
+
+    /// Packs a [DataHash](struct.DataHash.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, DataHash::LEN];
+        let (algorithm_dst, digest_len_dst, digest_dst) =
+            mut_array_refs![output, 1, 1, MAX_DIGEST_LEN];
+        algorithm_dst[0] = self.algorithm as u8;
+        digest_len_dst[0] = self.algorithm.digest_len() as u8;
+        *digest_dst = self.digest;
+    }
+
+    /// Unpacks a byte buffer into a [DataHash](struct.DataHash.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, DataHash::LEN];
+        let (algorithm_src, digest_len_src, digest_src) = array_refs![input, 1, 1, MAX_DIGEST_LEN];
+        let algorithm = HashAlgorithm::try_from_primitive(algorithm_src[0])
+            .or(Err(ProgramError::InvalidAccountData))?;
+        if digest_len_src[0] as usize != algorithm.digest_len() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(DataHash {
+            algorithm,
+            digest: *digest_src,
+        })
     }
 }
 
-/// Stores data URL
-#[repr(transparent)]
+/// Stores data URL.
+///
+/// URLs up to `URL_LEN` bytes are stored inline, longer ones are kept in the
+/// [UrlData](struct.UrlData.html) account and only its address is stored.
 #[derive(Clone, Copy)]
-pub struct DataUrl([u8; URL_LEN]);
+pub struct DataUrl {
+    len: u16,
+    data: [u8; URL_LEN],
+}
 
 impl Default for DataUrl {
     fn default() -> Self {
-        DataUrl([0; URL_LEN])
+        DataUrl {
+            len: 0,
+            data: [0; URL_LEN],
+        }
     }
 }
 
 impl fmt::Debug for DataUrl {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0[..].fmt(f)
+        self.to_string().fmt(f)
     }
 }
 
 impl PartialEq for DataUrl {
     fn eq(&self, other: &Self) -> bool {
-        self.0.iter().zip(other.0.iter()).all(|(a, b)| a == b)
+        self.len == other.len && self.as_ref() == other.as_ref()
     }
 }
 
 impl AsRef<[u8]> for DataUrl {
+    /// URL bytes, or the address bytes if the URL is stored in the separate account
     fn as_ref(&self) -> &[u8] {
-        &self.0[..]
+        match self.len {
+            URL_ACCOUNT_LEN => &self.data[..32],
+            len => &self.data[..len as usize],
+        }
     }
 }
 
@@ -138,28 +437,583 @@ impl FromStr for DataUrl {
     type Err = ProgramError;
 
     fn from_str(s: &str) -> Result<Self, ProgramError> {
-        let bytes = s.as_bytes();
-        let length = bytes.len();
-        if length > mem::size_of::<DataUrl>() {
-            return Err(ProgramError::InvalidInstructionData);
-        }
-
-        let mut result: Self = Default::default();
-        result.0[..length].copy_from_slice(bytes);
-        Ok(result)
+        Self::new_from_slice(s.as_bytes())
     }
 }
 
 impl ToString for DataUrl {
+    /// Returns the URL, or the address of the account the URL is stored in
     fn to_string(&self) -> String {
-        String::from_utf8(self.0.to_vec()).unwrap()
+        match self.account() {
+            Some(account) => account.to_string(),
+            None => String::from_utf8_lossy(self.as_ref()).into_owned(),
+        }
     }
 }
 
 impl DataUrl {
-    /// Create new from fixed size array
+    /// Create new from fixed size array, all the bytes are used
     pub const fn new_from_array(data: [u8; URL_LEN]) -> Self {
-        Self(data)
+        Self {
+            len: URL_LEN as u16,
+            data,
+        }
+    }
+
+    /// Create new from slice of up to `URL_LEN` bytes
+    pub fn new_from_slice(data: &[u8]) -> Result<Self, ProgramError> {
+        let length = data.len();
+        if length > URL_LEN {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut result = DataUrl {
+            len: length as u16,
+            data: [0; URL_LEN],
+        };
+        result.data[..length].copy_from_slice(data);
+        Ok(result)
+    }
+
+    /// Create new referencing the [UrlData](struct.UrlData.html) account
+    pub fn new_from_account(account: &Pubkey) -> Self {
+        let mut result = DataUrl {
+            len: URL_ACCOUNT_LEN,
+            data: [0; URL_LEN],
+        };
+        result.data[..32].copy_from_slice(account.as_ref());
+        result
+    }
+
+    /// Address of the account the URL is stored in, `None` for inline URLs
+    pub fn account(&self) -> Option<Pubkey> {
+        match self.len {
+            URL_ACCOUNT_LEN => Some(Pubkey::new_from_array(*array_ref![self.data, 0, 32])),
+            _ => None,
+        }
+    }
+}
+
+impl Sealed for DataUrl {}
+
+impl Pack for DataUrl {
+    const LEN: usize = 2 + URL_LEN;
+
+    /// Packs a [DataUrl](struct.DataUrl.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, DataUrl::LEN];
+        let (len_dst, data_dst) = mut_array_refs![output, 2, URL_LEN];
+        *len_dst = self.len.to_le_bytes();
+        *data_dst = [0; URL_LEN];
+        data_dst[..self.as_ref().len()].copy_from_slice(self.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [DataUrl](struct.DataUrl.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, DataUrl::LEN];
+        let (len_src, data_src) = array_refs![input, 2, URL_LEN];
+        let len = u16::from_le_bytes(*len_src);
+        if len != URL_ACCOUNT_LEN && len as usize > URL_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(DataUrl {
+            len,
+            data: *data_src,
+        })
+    }
+}
+
+/// Data account holding the URL longer than `URL_LEN`.
+///
+/// The account is created by the client with the size of the header plus
+/// the URL length, the URL is written with `WriteUrlData` instructions signed
+/// by the authority set on the first write.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UrlData {
+    /// The only account allowed to write the URL
+    pub authority: Pubkey,
+    /// Number of the written URL bytes
+    pub len: u32,
+}
+
+impl Sealed for UrlData {}
+impl IsInitialized for UrlData {
+    fn is_initialized(&self) -> bool {
+        self.authority != Pubkey::default()
+    }
+}
+
+impl Pack for UrlData {
+    const LEN: usize = 37;
+
+    /// Packs a [UrlData](struct.UrlData.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, UrlData::LEN];
+        let (account_type_dst, authority_dst, len_dst) = mut_array_refs![output, 1, 32, 4];
+        account_type_dst[0] = AccountType::UrlData as u8;
+        authority_dst.copy_from_slice(self.authority.as_ref());
+        *len_dst = self.len.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [UrlData](struct.UrlData.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, UrlData::LEN];
+        if !check_account_type(input, AccountType::UrlData)? {
+            return Ok(UrlData::default());
+        }
+        let (_account_type_src, authority_src, len_src) = array_refs![input, 1, 32, 4];
+        Ok(UrlData {
+            authority: Pubkey::new_from_array(*authority_src),
+            len: u32::from_le_bytes(*len_src),
+        })
+    }
+}
+
+impl UrlData {
+    /// Unpacks the written URL bytes from the URL data account
+    pub fn unpack_url(data: &[u8]) -> Result<&[u8], ProgramError> {
+        let header = data
+            .get(..UrlData::LEN)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let url_data = UrlData::unpack(header)?;
+        data.get(UrlData::LEN..UrlData::LEN + url_data.len as usize)
+            .ok_or(ProgramError::InvalidAccountData)
+    }
+}
+
+/// Receipt of a sent payout.
+///
+/// Receipt accounts are program addresses derived from the escrow address and
+/// either the payouts tree leaf index or the payout ID, so every leaf can be
+/// claimed and every payout ID can be paid only once. Receipts derived from the
+/// escrow address and the worker wallet record the first scored payout of the
+/// worker, so the escrow is counted as a single job.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PayoutReceipt {
+    /// Escrow the payout was sent from
+    pub escrow: Pubkey,
+    /// Token account which received the payout
+    pub recipient: Pubkey,
+    /// Paid amount of tokens, including oracle fees
+    pub amount: u64,
+}
+
+impl Sealed for PayoutReceipt {}
+impl IsInitialized for PayoutReceipt {
+    fn is_initialized(&self) -> bool {
+        self.escrow != Pubkey::default()
+    }
+}
+
+impl Pack for PayoutReceipt {
+    const LEN: usize = 73;
+
+    /// Packs a [PayoutReceipt](struct.PayoutReceipt.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, PayoutReceipt::LEN];
+        let (account_type_dst, escrow_dst, recipient_dst, amount_dst) =
+            mut_array_refs![output, 1, 32, 32, 8];
+        account_type_dst[0] = AccountType::PayoutReceipt as u8;
+        escrow_dst.copy_from_slice(self.escrow.as_ref());
+        recipient_dst.copy_from_slice(self.recipient.as_ref());
+        *amount_dst = self.amount.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [PayoutReceipt](struct.PayoutReceipt.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, PayoutReceipt::LEN];
+        if !check_account_type(input, AccountType::PayoutReceipt)? {
+            return Ok(PayoutReceipt::default());
+        }
+        let (_account_type_src, escrow_src, recipient_src, amount_src) =
+            array_refs![input, 1, 32, 32, 8];
+        Ok(PayoutReceipt {
+            escrow: Pubkey::new_from_array(*escrow_src),
+            recipient: Pubkey::new_from_array(*recipient_src),
+            amount: u64::from_le_bytes(*amount_src),
+        })
+    }
+}
+
+/// Reputation of the worker paid by the escrows of the factory.
+///
+/// Reputation accounts are program addresses derived from the factory address
+/// and the worker wallet, they are updated by the scored payouts of the oracles
+/// approved by the factory.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WorkerReputation {
+    /// Factory the reputation belongs to
+    pub factory: Pubkey,
+    /// Worker wallet
+    pub worker: Pubkey,
+    /// Number of the escrows the worker received scored payouts from
+    pub jobs_completed: u64,
+    /// Total amount of tokens received, excluding oracle fees
+    pub total_earned: u64,
+    /// Average score of the jobs in basis points
+    pub score: u16,
+}
+
+impl Sealed for WorkerReputation {}
+impl IsInitialized for WorkerReputation {
+    fn is_initialized(&self) -> bool {
+        self.factory != Pubkey::default()
+    }
+}
+
+impl Pack for WorkerReputation {
+    const LEN: usize = 83;
+
+    /// Packs a [WorkerReputation](struct.WorkerReputation.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, WorkerReputation::LEN];
+        let (
+            account_type_dst,
+            factory_dst,
+            worker_dst,
+            jobs_completed_dst,
+            total_earned_dst,
+            score_dst,
+        ) = mut_array_refs![output, 1, 32, 32, 8, 8, 2];
+        account_type_dst[0] = AccountType::WorkerReputation as u8;
+        factory_dst.copy_from_slice(self.factory.as_ref());
+        worker_dst.copy_from_slice(self.worker.as_ref());
+        *jobs_completed_dst = self.jobs_completed.to_le_bytes();
+        *total_earned_dst = self.total_earned.to_le_bytes();
+        *score_dst = self.score.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [WorkerReputation](struct.WorkerReputation.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, WorkerReputation::LEN];
+        if !check_account_type(input, AccountType::WorkerReputation)? {
+            return Ok(WorkerReputation::default());
+        }
+        let (
+            _account_type_src,
+            factory_src,
+            worker_src,
+            jobs_completed_src,
+            total_earned_src,
+            score_src,
+        ) = array_refs![input, 1, 32, 32, 8, 8, 2];
+        Ok(WorkerReputation {
+            factory: Pubkey::new_from_array(*factory_src),
+            worker: Pubkey::new_from_array(*worker_src),
+            jobs_completed: u64::from_le_bytes(*jobs_completed_src),
+            total_earned: u64::from_le_bytes(*total_earned_src),
+            score: u16::from_le_bytes(*score_src),
+        })
+    }
+}
+
+impl WorkerReputation {
+    /// Records the completed job, the score is averaged over all the jobs
+    pub fn record_job(&mut self, earned: u64, score: u16) {
+        let jobs_completed = self.jobs_completed.saturating_add(1);
+        let total_score = (self.score as u128) * (self.jobs_completed as u128) + score as u128;
+        self.score = (total_score / jobs_completed as u128) as u16;
+        self.jobs_completed = jobs_completed;
+        self.record_earnings(earned);
+    }
+
+    /// Records the further payout of the job already counted
+    pub fn record_earnings(&mut self, earned: u64) {
+        self.total_earned = self.total_earned.saturating_add(earned);
+    }
+}
+
+/// Approval of the reputation oracle to score the workers of the factory.
+///
+/// Approval accounts are program addresses derived from the factory address and
+/// the oracle, they are created and closed by the factory administrator.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OracleApproval {
+    /// Factory approving the oracle
+    pub factory: Pubkey,
+    /// Approved reputation oracle
+    pub oracle: Pubkey,
+    /// Minimum payout amount the oracle can score, including oracle fees
+    pub min_scored_amount: u64,
+}
+
+impl Sealed for OracleApproval {}
+impl IsInitialized for OracleApproval {
+    fn is_initialized(&self) -> bool {
+        self.factory != Pubkey::default()
+    }
+}
+
+impl Pack for OracleApproval {
+    const LEN: usize = 73;
+
+    /// Packs an [OracleApproval](struct.OracleApproval.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, OracleApproval::LEN];
+        let (account_type_dst, factory_dst, oracle_dst, min_scored_amount_dst) =
+            mut_array_refs![output, 1, 32, 32, 8];
+        account_type_dst[0] = AccountType::OracleApproval as u8;
+        factory_dst.copy_from_slice(self.factory.as_ref());
+        oracle_dst.copy_from_slice(self.oracle.as_ref());
+        *min_scored_amount_dst = self.min_scored_amount.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into an [OracleApproval](struct.OracleApproval.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, OracleApproval::LEN];
+        if !check_account_type(input, AccountType::OracleApproval)? {
+            return Ok(OracleApproval::default());
+        }
+        let (_account_type_src, factory_src, oracle_src, min_scored_amount_src) =
+            array_refs![input, 1, 32, 32, 8];
+        Ok(OracleApproval {
+            factory: Pubkey::new_from_array(*factory_src),
+            oracle: Pubkey::new_from_array(*oracle_src),
+            min_scored_amount: u64::from_le_bytes(*min_scored_amount_src),
+        })
+    }
+}
+
+/// Vesting schedule of the payout.
+///
+/// Schedule accounts are program addresses derived from the escrow address and
+/// the recipient, the payout vests linearly from the start to the end and
+/// nothing can be withdrawn before the cliff.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VestingSchedule {
+    /// Escrow the payout is vested from
+    pub escrow: Pubkey,
+    /// Token account or, for the native SOL escrow, system account receiving the payout
+    pub recipient: Pubkey,
+    /// Vesting start timestamp
+    pub start: UnixTimestamp,
+    /// Nothing is vested before the cliff timestamp
+    pub cliff: UnixTimestamp,
+    /// Whole payout is vested at the end timestamp
+    pub end: UnixTimestamp,
+    /// Vested amount of tokens, excluding oracle fees
+    pub total: u64,
+    /// Amount of tokens already withdrawn
+    pub withdrawn: u64,
+}
+
+impl Sealed for VestingSchedule {}
+impl IsInitialized for VestingSchedule {
+    fn is_initialized(&self) -> bool {
+        self.escrow != Pubkey::default()
+    }
+}
+
+impl Pack for VestingSchedule {
+    const LEN: usize = 105;
+
+    /// Packs a [VestingSchedule](struct.VestingSchedule.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, VestingSchedule::LEN];
+        let (
+            account_type_dst,
+            escrow_dst,
+            recipient_dst,
+            start_dst,
+            cliff_dst,
+            end_dst,
+            total_dst,
+            withdrawn_dst,
+        ) = mut_array_refs![output, 1, 32, 32, 8, 8, 8, 8, 8];
+        account_type_dst[0] = AccountType::VestingSchedule as u8;
+        escrow_dst.copy_from_slice(self.escrow.as_ref());
+        recipient_dst.copy_from_slice(self.recipient.as_ref());
+        *start_dst = self.start.to_le_bytes();
+        *cliff_dst = self.cliff.to_le_bytes();
+        *end_dst = self.end.to_le_bytes();
+        *total_dst = self.total.to_le_bytes();
+        *withdrawn_dst = self.withdrawn.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [VestingSchedule](struct.VestingSchedule.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, VestingSchedule::LEN];
+        if !check_account_type(input, AccountType::VestingSchedule)? {
+            return Ok(VestingSchedule::default());
+        }
+        let (
+            _account_type_src,
+            escrow_src,
+            recipient_src,
+            start_src,
+            cliff_src,
+            end_src,
+            total_src,
+            withdrawn_src,
+        ) = array_refs![input, 1, 32, 32, 8, 8, 8, 8, 8];
+        Ok(VestingSchedule {
+            escrow: Pubkey::new_from_array(*escrow_src),
+            recipient: Pubkey::new_from_array(*recipient_src),
+            start: UnixTimestamp::from_le_bytes(*start_src),
+            cliff: UnixTimestamp::from_le_bytes(*cliff_src),
+            end: UnixTimestamp::from_le_bytes(*end_src),
+            total: u64::from_le_bytes(*total_src),
+            withdrawn: u64::from_le_bytes(*withdrawn_src),
+        })
+    }
+}
+
+impl VestingSchedule {
+    /// Returns the amount vested at the given timestamp
+    pub fn vested_amount(&self, timestamp: UnixTimestamp) -> u64 {
+        if timestamp < self.cliff {
+            0
+        } else if timestamp >= self.end {
+            self.total
+        } else {
+            // Cliff isn't before the start, so the vesting period isn't empty here
+            (self.total as u128 * (timestamp - self.start) as u128
+                / (self.end - self.start) as u128) as u64
+        }
+    }
+
+    /// Ends vesting at the given timestamp, the amount vested so far stays in
+    /// the schedule and the unvested remainder is returned
+    pub fn stop_vesting(&mut self, timestamp: UnixTimestamp) -> u64 {
+        if timestamp >= self.end {
+            return 0;
+        }
+        let vested = self.vested_amount(timestamp);
+        let unvested = self.total - vested;
+        self.total = vested;
+        self.end = timestamp.max(self.start);
+        self.cliff = self.end;
+        unvested
+    }
+}
+
+/// Intermediate results log header.
+///
+/// Log accounts are program addresses derived from the escrow address. The
+/// header is followed by `MAX_INTERMEDIATE_RESULTS` slots for the
+/// [IntermediateResult](struct.IntermediateResult.html) records, first
+/// `count` of them are in use. Records are never overwritten, so the log keeps
+/// the whole history of the escrow up to `MAX_INTERMEDIATE_RESULTS` records.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResultsLog {
+    /// Escrow the results belong to
+    pub escrow: Pubkey,
+    /// Number of the stored results
+    pub count: u64,
+}
+
+impl Sealed for ResultsLog {}
+impl IsInitialized for ResultsLog {
+    fn is_initialized(&self) -> bool {
+        self.escrow != Pubkey::default()
+    }
+}
+
+impl Pack for ResultsLog {
+    const LEN: usize = 41;
+
+    /// Packs a [ResultsLog](struct.ResultsLog.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, ResultsLog::LEN];
+        let (account_type_dst, escrow_dst, count_dst) = mut_array_refs![output, 1, 32, 8];
+        account_type_dst[0] = AccountType::ResultsLog as u8;
+        escrow_dst.copy_from_slice(self.escrow.as_ref());
+        *count_dst = self.count.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [ResultsLog](struct.ResultsLog.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, ResultsLog::LEN];
+        if !check_account_type(input, AccountType::ResultsLog)? {
+            return Ok(ResultsLog::default());
+        }
+        let (_account_type_src, escrow_src, count_src) = array_refs![input, 1, 32, 8];
+        Ok(ResultsLog {
+            escrow: Pubkey::new_from_array(*escrow_src),
+            count: u64::from_le_bytes(*count_src),
+        })
+    }
+}
+
+impl ResultsLog {
+    /// Size of the log account, the header and all the record slots
+    pub const ACCOUNT_LEN: usize =
+        ResultsLog::LEN + IntermediateResult::LEN * MAX_INTERMEDIATE_RESULTS;
+
+    /// Unpacks the stored records from the log account data
+    pub fn unpack_results(&self, data: &[u8]) -> Result<Vec<IntermediateResult>, ProgramError> {
+        if self.count as usize > MAX_INTERMEDIATE_RESULTS || data.len() < Self::ACCOUNT_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        data[ResultsLog::LEN..]
+            .chunks(IntermediateResult::LEN)
+            .take(self.count as usize)
+            .map(IntermediateResult::unpack_from_slice)
+            .collect()
+    }
+
+    /// Packs the record into the log account data slot with the given index
+    pub fn pack_result(
+        index: usize,
+        result: &IntermediateResult,
+        data: &mut [u8],
+    ) -> Result<(), ProgramError> {
+        if index >= MAX_INTERMEDIATE_RESULTS || data.len() < Self::ACCOUNT_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let offset = ResultsLog::LEN + IntermediateResult::LEN * index;
+        result.pack_into_slice(&mut data[offset..offset + IntermediateResult::LEN]);
+        Ok(())
+    }
+}
+
+/// Intermediate results record of the results log
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct IntermediateResult {
+    /// Results URL
+    pub url: DataUrl,
+    /// Results hash
+    pub hash: DataHash,
+    /// Time the results were stored
+    pub timestamp: UnixTimestamp,
+    /// Account which stored the results
+    pub submitter: Pubkey,
+}
+
+impl Sealed for IntermediateResult {}
+
+impl Pack for IntermediateResult {
+    const LEN: usize = DataUrl::LEN + DataHash::LEN + 40;
+
+    /// Packs a [IntermediateResult](struct.IntermediateResult.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, IntermediateResult::LEN];
+        let (url_dst, hash_dst, timestamp_dst, submitter_dst) =
+            mut_array_refs![output, DataUrl::LEN, DataHash::LEN, 8, 32];
+        self.url.pack_into_slice(url_dst);
+        self.hash.pack_into_slice(hash_dst);
+        *timestamp_dst = self.timestamp.to_le_bytes();
+        submitter_dst.copy_from_slice(self.submitter.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [IntermediateResult](struct.IntermediateResult.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, IntermediateResult::LEN];
+        let (url_src, hash_src, timestamp_src, submitter_src) =
+            array_refs![input, DataUrl::LEN, DataHash::LEN, 8, 32];
+        Ok(IntermediateResult {
+            url: DataUrl::unpack_from_slice(url_src)?,
+            hash: DataHash::unpack_from_slice(hash_src)?,
+            timestamp: UnixTimestamp::from_le_bytes(*timestamp_src),
+            submitter: Pubkey::new_from_array(*submitter_src),
+        })
     }
 }
 
@@ -167,7 +1021,7 @@ impl DataUrl {
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Escrow {
-    /// Current state of escrow entity: Uninitialized, Launched, Pending, Partial, Paid, Complete, Cancelled
+    /// Current state of escrow entity: Uninitialized, Launched, Pending, Partial, Paid, Complete, Cancelled, Disputed
     pub state: EscrowState,
     /// Factory account this Escrow belongs to
     pub factory: Pubkey,
@@ -183,14 +1037,14 @@ pub struct Escrow {
     pub reputation_oracle: COption<Pubkey>,
     /// Account for the reputation oracle to receive fee
     pub reputation_oracle_token_account: COption<Pubkey>,
-    /// Reputation oracle fee (in percents)
-    pub reputation_oracle_stake: u8,
+    /// Reputation oracle fee (in basis points)
+    pub reputation_oracle_stake: u16,
     /// Pubkey of the recording oracle
     pub recording_oracle: COption<Pubkey>,
     /// Account for the recording oracle to receive fee
     pub recording_oracle_token_account: COption<Pubkey>,
-    /// Recording oracle fee (in percents)
-    pub recording_oracle_stake: u8,
+    /// Recording oracle fee (in basis points)
+    pub recording_oracle_stake: u16,
     /// Launcher pubkey
     pub launcher: Pubkey,
     /// Canceler pubkey
@@ -213,6 +1067,42 @@ pub struct Escrow {
     pub final_results_url: DataUrl,
     /// Job results hash
     pub final_results_hash: DataHash,
+    /// Amount in tokens deposited with the `Fund` instruction
+    pub funded_amount: u64,
+    /// Merkle root of the claimable payouts, zeroes if not stored
+    pub payouts_root: MerkleHash,
+    /// Additional trusted handlers, unused entries are zeroes
+    pub trusted_handlers: [Pubkey; MAX_TRUSTED_HANDLERS],
+    /// Reputation oracle fee accrued but not paid yet (in 1/STAKE_BASIS_POINTS token units)
+    pub reputation_oracle_fee_remainder: u64,
+    /// Recording oracle fee accrued but not paid yet (in 1/STAKE_BASIS_POINTS token units)
+    pub recording_oracle_fee_remainder: u64,
+    /// Oracle fees are accrued during payouts and sent with `SettleFees`
+    pub defer_fees: bool,
+    /// Reputation oracle fee accrued by the payouts and not settled yet
+    pub reputation_oracle_fee_accrued: u64,
+    /// Recording oracle fee accrued by the payouts and not settled yet
+    pub recording_oracle_fee_accrued: u64,
+    /// Seconds after storing the final results during which the launcher can
+    /// dispute them and payouts are stopped, no dispute window if zero
+    pub dispute_window: u64,
+    /// Final results storing timestamp, the dispute window starts at it
+    pub results_stored_at: UnixTimestamp,
+    /// Oracles posted bonds at setup, the bond vault exists until the escrow is closed
+    pub bonded: bool,
+    /// Reputation oracle bond locked in the bond vault
+    pub reputation_oracle_bond: u64,
+    /// Recording oracle bond locked in the bond vault
+    pub recording_oracle_bond: u64,
+    /// Final results were rejected by the dispute resolution, the launcher can
+    /// slash the oracle bonds
+    pub results_rejected: bool,
+    /// Vesting payouts not withdrawn yet, the tokens stay in the escrow token account
+    pub vesting_amount: u64,
+    /// Cancellation timestamp of the escrow with vesting payouts, they stop vesting at it
+    pub vesting_stopped_at: UnixTimestamp,
+    /// Escrow creation timestamp, zero for escrows created before version 7
+    pub created_at: UnixTimestamp,
 }
 
 impl Sealed for Escrow {}
@@ -222,12 +1112,43 @@ impl IsInitialized for Escrow {
     }
 }
 
-impl Pack for Escrow {
-    const LEN: usize = 420 + URL_LEN + URL_LEN;
+impl Escrow {
+    /// Size of the legacy layout of the escrows created before the version
+    /// byte, with the stakes in percent, inline URLs and SHA-1 hashes
+    pub const LEN_V0: usize = 380 + URL_LEN + 20 + URL_LEN + 20;
 
-    /// Packs a [EscrowInfo](struct.EscrowInfo.html) into a byte buffer.
-    fn pack_into_slice(&self, output: &mut [u8]) {
-        let output = array_mut_ref![output, 0, Escrow::LEN];
+    /// Size of the escrow fields following the version byte
+    const FIELDS_LEN: usize = 422
+        + DataUrl::LEN
+        + DataHash::LEN
+        + DataUrl::LEN
+        + DataHash::LEN
+        + 32 * MAX_TRUSTED_HANDLERS;
+
+    /// Returns the layout version of the packed escrow, legacy escrows are told
+    /// apart by the account size. Uninitialized accounts have zero version.
+    pub fn unpack_version(input: &[u8]) -> Result<u8, ProgramError> {
+        match input.len() {
+            Escrow::LEN_V0 => Ok(0),
+            Escrow::LEN if input[0] <= ESCROW_VERSION => Ok(input[0]),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Returns the deferred oracle fees waiting for `SettleFees`
+    pub fn unsettled_fees(&self) -> u64 {
+        self.reputation_oracle_fee_accrued
+            .saturating_add(self.recording_oracle_fee_accrued)
+    }
+
+    /// Returns the end of the dispute window of the stored final results
+    pub fn dispute_window_end(&self) -> UnixTimestamp {
+        self.results_stored_at
+            .saturating_add(self.dispute_window as UnixTimestamp)
+    }
+
+    /// Packs the escrow fields following the version byte
+    fn pack_fields(&self, output: &mut [u8; Escrow::FIELDS_LEN]) {
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             expires_dst,
@@ -253,9 +1174,37 @@ impl Pack for Escrow {
             manifest_hash_dst,
             final_results_url_dst,
             final_results_hash_dst,
+            funded_amount_dst,
+            payouts_root_dst,
+            trusted_handlers_dst,
         ) = mut_array_refs![
-            output, 8, 1, 32, 32, 36, 36, 1, 36, 36, 1, 32, 32, 32, 8, 8, 8, 8, 1, 32, URL_LEN, 20,
-            URL_LEN, 20
+            output,
+            8,
+            1,
+            32,
+            32,
+            36,
+            36,
+            2,
+            36,
+            36,
+            2,
+            32,
+            32,
+            32,
+            8,
+            8,
+            8,
+            8,
+            1,
+            32,
+            DataUrl::LEN,
+            DataHash::LEN,
+            DataUrl::LEN,
+            DataHash::LEN,
+            8,
+            32,
+            32 * MAX_TRUSTED_HANDLERS
         ];
         expires_dst.copy_from_slice(&self.expires.to_le_bytes());
         bump_seed_dst[0] = self.bump_seed;
@@ -266,13 +1215,13 @@ impl Pack for Escrow {
             &self.reputation_oracle_token_account,
             reputation_oracle_token_account_dst,
         );
-        reputation_oracle_stake_dst[0] = self.reputation_oracle_stake;
+        reputation_oracle_stake_dst.copy_from_slice(&self.reputation_oracle_stake.to_le_bytes());
         pack_coption_key(&self.recording_oracle, recording_oracle_dst);
         pack_coption_key(
             &self.recording_oracle_token_account,
             recording_oracle_token_account_dst,
         );
-        recording_oracle_stake_dst[0] = self.recording_oracle_stake;
+        recording_oracle_stake_dst.copy_from_slice(&self.recording_oracle_stake.to_le_bytes());
         launcher_dst.copy_from_slice(self.launcher.as_ref());
         canceler_dst.copy_from_slice(self.canceler.as_ref());
         canceler_token_account_dst.copy_from_slice(self.canceler_token_account.as_ref());
@@ -282,15 +1231,136 @@ impl Pack for Escrow {
         sent_recipients_dst.copy_from_slice(&self.sent_recipients.to_le_bytes());
         state_dst[0] = self.state as u8;
         factory_acc.copy_from_slice(self.factory.as_ref());
-        manifest_url_dst.copy_from_slice(self.manifest_url.as_ref());
-        manifest_hash_dst.copy_from_slice(self.manifest_hash.as_ref());
-        final_results_url_dst.copy_from_slice(self.final_results_url.as_ref());
-        final_results_hash_dst.copy_from_slice(self.final_results_hash.as_ref());
+        self.manifest_url.pack_into_slice(manifest_url_dst);
+        self.manifest_hash.pack_into_slice(manifest_hash_dst);
+        self.final_results_url
+            .pack_into_slice(final_results_url_dst);
+        self.final_results_hash
+            .pack_into_slice(final_results_hash_dst);
+        funded_amount_dst.copy_from_slice(&self.funded_amount.to_le_bytes());
+        payouts_root_dst.copy_from_slice(&self.payouts_root);
+        for (handler, handler_dst) in self
+            .trusted_handlers
+            .iter()
+            .zip(trusted_handlers_dst.chunks_mut(32))
+        {
+            handler_dst.copy_from_slice(handler.as_ref());
+        }
     }
 
-    /// Unpacks a byte buffer into a [EscrowInfo](struct.EscrowInfo.html).
-    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![input, 0, Escrow::LEN];
+    /// Unpacks the escrow fields following the version byte
+    fn unpack_fields(input: &[u8; Escrow::FIELDS_LEN]) -> Result<Self, ProgramError> {
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            expires_src,
+            bump_seed_src,
+            token_mint_src,
+            token_account_src,
+            reputation_oracle_src,
+            reputation_oracle_token_account_src,
+            reputation_oracle_stake_src,
+            recording_oracle_src,
+            recording_oracle_token_account_src,
+            recording_oracle_stake_src,
+            launcher_src,
+            canceler_src,
+            canceler_token_account_src,
+            total_amount_src,
+            total_recipients_src,
+            sent_amount_src,
+            sent_recipients_src,
+            state_src,
+            factory_acc,
+            manifest_url_src,
+            manifest_hash_src,
+            final_results_url_src,
+            final_results_hash_src,
+            funded_amount_src,
+            payouts_root_src,
+            trusted_handlers_src,
+        ) = array_refs![
+            input,
+            8,
+            1,
+            32,
+            32,
+            36,
+            36,
+            2,
+            36,
+            36,
+            2,
+            32,
+            32,
+            32,
+            8,
+            8,
+            8,
+            8,
+            1,
+            32,
+            DataUrl::LEN,
+            DataHash::LEN,
+            DataUrl::LEN,
+            DataHash::LEN,
+            8,
+            32,
+            32 * MAX_TRUSTED_HANDLERS
+        ];
+        let mut trusted_handlers = [Pubkey::default(); MAX_TRUSTED_HANDLERS];
+        for (handler, handler_src) in trusted_handlers
+            .iter_mut()
+            .zip(trusted_handlers_src.chunks(32))
+        {
+            *handler = Pubkey::new_from_array(*array_ref![handler_src, 0, 32]);
+        }
+        Ok(Escrow {
+            expires: UnixTimestamp::from_le_bytes(*expires_src),
+
+            bump_seed: bump_seed_src[0],
+
+            token_mint: Pubkey::new_from_array(*token_mint_src),
+            token_account: Pubkey::new_from_array(*token_account_src),
+
+            reputation_oracle: unpack_coption_key(reputation_oracle_src)?,
+            reputation_oracle_token_account: unpack_coption_key(
+                reputation_oracle_token_account_src,
+            )?,
+            reputation_oracle_stake: u16::from_le_bytes(*reputation_oracle_stake_src),
+
+            recording_oracle: unpack_coption_key(recording_oracle_src)?,
+            recording_oracle_token_account: unpack_coption_key(recording_oracle_token_account_src)?,
+            recording_oracle_stake: u16::from_le_bytes(*recording_oracle_stake_src),
+
+            launcher: Pubkey::new_from_array(*launcher_src),
+            canceler: Pubkey::new_from_array(*canceler_src),
+            canceler_token_account: Pubkey::new_from_array(*canceler_token_account_src),
+            total_amount: u64::from_le_bytes(*total_amount_src),
+            total_recipients: u64::from_le_bytes(*total_recipients_src),
+            sent_amount: u64::from_le_bytes(*sent_amount_src),
+            sent_recipients: u64::from_le_bytes(*sent_recipients_src),
+            state: EscrowState::try_from_primitive(state_src[0])
+                .or(Err(ProgramError::InvalidAccountData))?,
+
+            factory: Pubkey::new_from_array(*factory_acc),
+
+            manifest_url: DataUrl::unpack_from_slice(manifest_url_src)?,
+            manifest_hash: DataHash::unpack_from_slice(manifest_hash_src)?,
+
+            final_results_url: DataUrl::unpack_from_slice(final_results_url_src)?,
+            final_results_hash: DataHash::unpack_from_slice(final_results_hash_src)?,
+
+            funded_amount: u64::from_le_bytes(*funded_amount_src),
+            payouts_root: *payouts_root_src,
+            trusted_handlers,
+
+            // Later fields are stored after the escrow fields
+            ..Default::default()
+        })
+    }
+
+    /// Unpacks the escrow from the legacy layout
+    fn unpack_v0(input: &[u8; Escrow::LEN_V0]) -> Result<Self, ProgramError> {
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             expires_src,
@@ -332,11 +1402,11 @@ impl Pack for Escrow {
             reputation_oracle_token_account: unpack_coption_key(
                 reputation_oracle_token_account_src,
             )?,
-            reputation_oracle_stake: reputation_oracle_stake_src[0],
+            reputation_oracle_stake: unpack_legacy_stake(reputation_oracle_stake_src)?,
 
             recording_oracle: unpack_coption_key(recording_oracle_src)?,
             recording_oracle_token_account: unpack_coption_key(recording_oracle_token_account_src)?,
-            recording_oracle_stake: recording_oracle_stake_src[0],
+            recording_oracle_stake: unpack_legacy_stake(recording_oracle_stake_src)?,
 
             launcher: Pubkey::new_from_array(*launcher_src),
             canceler: Pubkey::new_from_array(*canceler_src),
@@ -350,16 +1420,246 @@ impl Pack for Escrow {
 
             factory: Pubkey::new_from_array(*factory_acc),
 
-            manifest_url: DataUrl::new_from_array(*manifest_url_src),
-            manifest_hash: DataHash::new_from_array(*manifest_hash_src),
+            manifest_url: unpack_legacy_url(manifest_url_src)?,
+            manifest_hash: unpack_legacy_hash(manifest_hash_src)?,
 
-            final_results_url: DataUrl::new_from_array(*final_results_url_src),
-            final_results_hash: DataHash::new_from_array(*final_results_hash_src),
+            final_results_url: unpack_legacy_url(final_results_url_src)?,
+            final_results_hash: unpack_legacy_hash(final_results_hash_src)?,
+
+            // Funding, payouts root, trusted handlers and the later fields
+            // aren't stored in the legacy layout
+            ..Default::default()
         })
     }
 }
 
+impl Pack for Escrow {
+    const LEN: usize = 1 + Escrow::FIELDS_LEN + ESCROW_RESERVED_LEN;
+
+    /// Packs a [EscrowInfo](struct.EscrowInfo.html) into a byte buffer.
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, Escrow::LEN];
+        let (
+            version_dst,
+            fields_dst,
+            reputation_oracle_fee_remainder_dst,
+            recording_oracle_fee_remainder_dst,
+            defer_fees_dst,
+            reputation_oracle_fee_accrued_dst,
+            recording_oracle_fee_accrued_dst,
+            dispute_window_dst,
+            results_stored_at_dst,
+            bonded_dst,
+            reputation_oracle_bond_dst,
+            recording_oracle_bond_dst,
+            results_rejected_dst,
+            vesting_amount_dst,
+            vesting_stopped_at_dst,
+            created_at_dst,
+            reserved_dst,
+        ) = mut_array_refs![
+            output,
+            1,
+            Escrow::FIELDS_LEN,
+            8,
+            8,
+            1,
+            8,
+            8,
+            8,
+            8,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            8,
+            ESCROW_RESERVED_LEN - 91
+        ];
+        version_dst[0] = ESCROW_VERSION;
+        self.pack_fields(fields_dst);
+        reputation_oracle_fee_remainder_dst
+            .copy_from_slice(&self.reputation_oracle_fee_remainder.to_le_bytes());
+        recording_oracle_fee_remainder_dst
+            .copy_from_slice(&self.recording_oracle_fee_remainder.to_le_bytes());
+        defer_fees_dst[0] = self.defer_fees as u8;
+        reputation_oracle_fee_accrued_dst
+            .copy_from_slice(&self.reputation_oracle_fee_accrued.to_le_bytes());
+        recording_oracle_fee_accrued_dst
+            .copy_from_slice(&self.recording_oracle_fee_accrued.to_le_bytes());
+        dispute_window_dst.copy_from_slice(&self.dispute_window.to_le_bytes());
+        results_stored_at_dst.copy_from_slice(&self.results_stored_at.to_le_bytes());
+        bonded_dst[0] = self.bonded as u8;
+        reputation_oracle_bond_dst.copy_from_slice(&self.reputation_oracle_bond.to_le_bytes());
+        recording_oracle_bond_dst.copy_from_slice(&self.recording_oracle_bond.to_le_bytes());
+        results_rejected_dst[0] = self.results_rejected as u8;
+        vesting_amount_dst.copy_from_slice(&self.vesting_amount.to_le_bytes());
+        vesting_stopped_at_dst.copy_from_slice(&self.vesting_stopped_at.to_le_bytes());
+        created_at_dst.copy_from_slice(&self.created_at.to_le_bytes());
+        *reserved_dst = [0; ESCROW_RESERVED_LEN - 91];
+    }
+
+    /// Unpacks a byte buffer into a [EscrowInfo](struct.EscrowInfo.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let version = Escrow::unpack_version(input)?;
+        if input.len() == Escrow::LEN_V0 {
+            return Escrow::unpack_v0(array_ref![input, 0, Escrow::LEN_V0]);
+        }
+
+        let input = array_ref![input, 0, Escrow::LEN];
+        let (
+            _version_src,
+            fields_src,
+            reputation_oracle_fee_remainder_src,
+            recording_oracle_fee_remainder_src,
+            defer_fees_src,
+            reputation_oracle_fee_accrued_src,
+            recording_oracle_fee_accrued_src,
+            dispute_window_src,
+            results_stored_at_src,
+            bonded_src,
+            reputation_oracle_bond_src,
+            recording_oracle_bond_src,
+            results_rejected_src,
+            vesting_amount_src,
+            vesting_stopped_at_src,
+            created_at_src,
+            _reserved_src,
+        ) = array_refs![
+            input,
+            1,
+            Escrow::FIELDS_LEN,
+            8,
+            8,
+            1,
+            8,
+            8,
+            8,
+            8,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            8,
+            ESCROW_RESERVED_LEN - 91
+        ];
+        let mut escrow = Escrow::unpack_fields(fields_src)?;
+
+        // Fee remainders are stored since version 2
+        if version >= 2 {
+            escrow.reputation_oracle_fee_remainder =
+                u64::from_le_bytes(*reputation_oracle_fee_remainder_src);
+            escrow.recording_oracle_fee_remainder =
+                u64::from_le_bytes(*recording_oracle_fee_remainder_src);
+        }
+
+        // Deferred fee settlement is supported since version 3
+        if version >= 3 {
+            escrow.defer_fees = match defer_fees_src {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            };
+            escrow.reputation_oracle_fee_accrued =
+                u64::from_le_bytes(*reputation_oracle_fee_accrued_src);
+            escrow.recording_oracle_fee_accrued =
+                u64::from_le_bytes(*recording_oracle_fee_accrued_src);
+        }
+
+        // Results dispute window is supported since version 4
+        if version >= 4 {
+            escrow.dispute_window = u64::from_le_bytes(*dispute_window_src);
+            escrow.results_stored_at = UnixTimestamp::from_le_bytes(*results_stored_at_src);
+        }
+
+        // Oracle bonds are supported since version 5
+        if version >= 5 {
+            escrow.bonded = match bonded_src {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            };
+            escrow.reputation_oracle_bond = u64::from_le_bytes(*reputation_oracle_bond_src);
+            escrow.recording_oracle_bond = u64::from_le_bytes(*recording_oracle_bond_src);
+            escrow.results_rejected = match results_rejected_src {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            };
+        }
+
+        // Vesting payouts are supported since version 6
+        if version >= 6 {
+            escrow.vesting_amount = u64::from_le_bytes(*vesting_amount_src);
+            escrow.vesting_stopped_at = UnixTimestamp::from_le_bytes(*vesting_stopped_at_src);
+        }
+
+        // Creation time is stored since version 7
+        if version >= 7 {
+            escrow.created_at = UnixTimestamp::from_le_bytes(*created_at_src);
+        }
+
+        Ok(escrow)
+    }
+
+    /// Packs the escrow in the latest layout, legacy accounts are read only
+    /// since their layout can't hold the escrow fields and must be migrated first
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        match dst.len() {
+            Escrow::LEN_V0 => Err(EscrowError::MigrationRequired.into()),
+            Escrow::LEN => {
+                src.pack_into_slice(dst);
+                Ok(())
+            }
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    /// Unpacks the escrow of any supported layout version
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        Escrow::unpack_from_slice(input)
+    }
+}
+
 // Helpers
+/// Checks the account type in the first byte of the packed account, returns
+/// false for the uninitialized account with all the data zero
+fn check_account_type(input: &[u8], account_type: AccountType) -> Result<bool, ProgramError> {
+    match input.split_first() {
+        Some((&tag, _)) if tag == account_type as u8 => Ok(true),
+        Some((0, rest)) if rest.iter().all(|&byte| byte == 0) => Ok(false),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Unpacks the legacy oracle stake stored in percent
+fn unpack_legacy_stake(src: &[u8; 1]) -> Result<u16, ProgramError> {
+    match src[0] {
+        stake @ 0..=100 => Ok(stake as u16 * (STAKE_BASIS_POINTS / 100)),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+/// Unpacks the legacy URL stored inline and padded with zeroes
+fn unpack_legacy_url(src: &[u8; URL_LEN]) -> Result<DataUrl, ProgramError> {
+    let len = src
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |pos| pos + 1);
+    DataUrl::new_from_slice(&src[..len])
+}
+
+/// Unpacks the legacy SHA-1 digest, all zeroes if no hash is stored
+fn unpack_legacy_hash(src: &[u8; 20]) -> Result<DataHash, ProgramError> {
+    if src.iter().all(|&byte| byte == 0) {
+        return Ok(DataHash::default());
+    }
+    DataHash::new(HashAlgorithm::Sha1, src)
+}
+
 fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
     let (tag, body) = mut_array_refs![dst, 4, 32];
     match src {
@@ -372,6 +1672,7 @@ fn pack_coption_key(src: &COption<Pubkey>, dst: &mut [u8; 36]) {
         }
     }
 }
+
 fn unpack_coption_key(src: &[u8; 36]) -> Result<COption<Pubkey>, ProgramError> {
     let (tag, body) = array_refs![src, 4, 32];
     match *tag {
@@ -397,10 +1698,10 @@ mod test {
             token_account: Pubkey::new_from_array([2; 32]),
             reputation_oracle: COption::Some(Pubkey::new_from_array([3; 32])),
             reputation_oracle_token_account: COption::Some(Pubkey::new_from_array([4; 32])),
-            reputation_oracle_stake: 5,
+            reputation_oracle_stake: 550,
             recording_oracle: COption::None,
             recording_oracle_token_account: COption::Some(Pubkey::new_from_array([6; 32])),
-            recording_oracle_stake: 10,
+            recording_oracle_stake: 1025,
             launcher: Pubkey::new_from_array([7; 32]),
             canceler: Pubkey::new_from_array([8; 32]),
             canceler_token_account: Pubkey::new_from_array([9; 32]),
@@ -409,13 +1710,555 @@ mod test {
             sent_amount: 2000000,
             sent_recipients: 100000,
             manifest_url: DataUrl::new_from_array([10; URL_LEN]),
-            manifest_hash: DataHash::new_from_array([11; 20]),
+            manifest_hash: DataHash::new(HashAlgorithm::Sha256, &[11; 32]).unwrap(),
             final_results_url: DataUrl::new_from_array([12; URL_LEN]),
-            final_results_hash: DataHash::new_from_array([13; 20]),
+            final_results_hash: DataHash::new(HashAlgorithm::Keccak256, &[13; 32]).unwrap(),
+            funded_amount: 30000000,
+            payouts_root: [14; 32],
+            trusted_handlers: [Pubkey::new_from_array([15; 32]); MAX_TRUSTED_HANDLERS],
+            reputation_oracle_fee_remainder: 1500,
+            recording_oracle_fee_remainder: 2500,
+            defer_fees: true,
+            reputation_oracle_fee_accrued: 16,
+            recording_oracle_fee_accrued: 17,
+            dispute_window: 86400,
+            results_stored_at: 1606402000,
+            bonded: true,
+            reputation_oracle_bond: 18,
+            recording_oracle_bond: 19,
+            results_rejected: true,
+            vesting_amount: 20,
+            vesting_stopped_at: 1606402100,
+            created_at: 1606400000,
         };
         let mut packed_obj: [u8; Escrow::LEN] = [0; Escrow::LEN];
         Escrow::pack(obj, &mut packed_obj).unwrap();
         let unpacked_obj = Escrow::unpack(&packed_obj).unwrap();
         assert_eq!(unpacked_obj, obj);
+        assert_eq!(Escrow::unpack_version(&packed_obj).unwrap(), ESCROW_VERSION);
+
+        // Creation time isn't stored before version 7
+        let obj_v6 = Escrow {
+            created_at: 0,
+            ..obj
+        };
+        let mut packed_obj_v6 = packed_obj;
+        packed_obj_v6[0] = 6;
+        let unpacked_obj = Escrow::unpack(&packed_obj_v6).unwrap();
+        assert_eq!(unpacked_obj, obj_v6);
+        assert_eq!(Escrow::unpack_version(&packed_obj_v6).unwrap(), 6);
+
+        // Vesting payouts aren't stored before version 6
+        let obj_v5 = Escrow {
+            vesting_amount: 0,
+            vesting_stopped_at: 0,
+            ..obj_v6
+        };
+        let mut packed_obj_v5 = packed_obj;
+        packed_obj_v5[0] = 5;
+        let unpacked_obj = Escrow::unpack(&packed_obj_v5).unwrap();
+        assert_eq!(unpacked_obj, obj_v5);
+        assert_eq!(Escrow::unpack_version(&packed_obj_v5).unwrap(), 5);
+
+        // Oracle bonds aren't stored before version 5
+        let obj_v4 = Escrow {
+            bonded: false,
+            reputation_oracle_bond: 0,
+            recording_oracle_bond: 0,
+            results_rejected: false,
+            ..obj_v5
+        };
+        let mut packed_obj_v4 = packed_obj;
+        packed_obj_v4[0] = 4;
+        let unpacked_obj = Escrow::unpack(&packed_obj_v4).unwrap();
+        assert_eq!(unpacked_obj, obj_v4);
+        assert_eq!(Escrow::unpack_version(&packed_obj_v4).unwrap(), 4);
+
+        // Dispute window isn't stored before version 4
+        let obj_v3 = Escrow {
+            dispute_window: 0,
+            results_stored_at: 0,
+            ..obj_v4
+        };
+        let mut packed_obj_v3 = packed_obj;
+        packed_obj_v3[0] = 3;
+        let unpacked_obj = Escrow::unpack(&packed_obj_v3).unwrap();
+        assert_eq!(unpacked_obj, obj_v3);
+        assert_eq!(Escrow::unpack_version(&packed_obj_v3).unwrap(), 3);
+
+        // Deferred fees aren't stored before version 3
+        let obj_v2 = Escrow {
+            defer_fees: false,
+            reputation_oracle_fee_accrued: 0,
+            recording_oracle_fee_accrued: 0,
+            ..obj_v3
+        };
+        let mut packed_obj_v2 = packed_obj;
+        packed_obj_v2[0] = 2;
+        let unpacked_obj = Escrow::unpack(&packed_obj_v2).unwrap();
+        assert_eq!(unpacked_obj, obj_v2);
+        assert_eq!(Escrow::unpack_version(&packed_obj_v2).unwrap(), 2);
+
+        // Fee remainders aren't stored before version 2
+        let obj_v1 = Escrow {
+            reputation_oracle_fee_remainder: 0,
+            recording_oracle_fee_remainder: 0,
+            ..obj_v2
+        };
+        let mut packed_obj_v1 = packed_obj;
+        packed_obj_v1[0] = 1;
+        let unpacked_obj = Escrow::unpack(&packed_obj_v1).unwrap();
+        assert_eq!(unpacked_obj, obj_v1);
+        assert_eq!(Escrow::unpack_version(&packed_obj_v1).unwrap(), 1);
+
+        // Legacy accounts are readable but must be migrated to be written
+        let mut packed_obj_v0: [u8; Escrow::LEN_V0] = [0; Escrow::LEN_V0];
+        assert_eq!(
+            Escrow::pack(obj, &mut packed_obj_v0).unwrap_err(),
+            EscrowError::MigrationRequired.into()
+        );
+
+        // Unknown versions and sizes
+        packed_obj[0] = ESCROW_VERSION + 1;
+        assert!(Escrow::unpack(&packed_obj).is_err());
+        assert_eq!(
+            Escrow::unpack_unchecked(&[0; Escrow::LEN]).unwrap(),
+            Escrow::default()
+        );
+        assert!(Escrow::unpack(&packed_obj[..Escrow::LEN - 1]).is_err());
+        assert!(Escrow::pack(obj, &mut packed_obj[..Escrow::LEN - 1]).is_err());
+    }
+
+    #[test]
+    fn test_legacy_escrow_unpacking() {
+        let mut packed_obj = [0; Escrow::LEN_V0];
+        {
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (
+                expires_dst,
+                bump_seed_dst,
+                token_mint_dst,
+                token_account_dst,
+                reputation_oracle_dst,
+                reputation_oracle_token_account_dst,
+                reputation_oracle_stake_dst,
+                recording_oracle_dst,
+                recording_oracle_token_account_dst,
+                recording_oracle_stake_dst,
+                launcher_dst,
+                canceler_dst,
+                canceler_token_account_dst,
+                total_amount_dst,
+                total_recipients_dst,
+                sent_amount_dst,
+                sent_recipients_dst,
+                state_dst,
+                factory_dst,
+                manifest_url_dst,
+                _manifest_hash_dst,
+                _final_results_url_dst,
+                _final_results_hash_dst,
+            ) = mut_array_refs![
+                &mut packed_obj,
+                8,
+                1,
+                32,
+                32,
+                36,
+                36,
+                1,
+                36,
+                36,
+                1,
+                32,
+                32,
+                32,
+                8,
+                8,
+                8,
+                8,
+                1,
+                32,
+                URL_LEN,
+                20,
+                URL_LEN,
+                20
+            ];
+            *expires_dst = 1606402240i64.to_le_bytes();
+            bump_seed_dst[0] = 250;
+            *token_mint_dst = [1; 32];
+            *token_account_dst = [2; 32];
+            pack_coption_key(
+                &COption::Some(Pubkey::new_from_array([3; 32])),
+                reputation_oracle_dst,
+            );
+            pack_coption_key(
+                &COption::Some(Pubkey::new_from_array([4; 32])),
+                reputation_oracle_token_account_dst,
+            );
+            reputation_oracle_stake_dst[0] = 5;
+            pack_coption_key(
+                &COption::Some(Pubkey::new_from_array([5; 32])),
+                recording_oracle_dst,
+            );
+            pack_coption_key(
+                &COption::Some(Pubkey::new_from_array([6; 32])),
+                recording_oracle_token_account_dst,
+            );
+            recording_oracle_stake_dst[0] = 10;
+            *launcher_dst = [7; 32];
+            *canceler_dst = [8; 32];
+            *canceler_token_account_dst = [9; 32];
+            *total_amount_dst = 20000000u64.to_le_bytes();
+            *total_recipients_dst = 1000000u64.to_le_bytes();
+            *sent_amount_dst = 2000000u64.to_le_bytes();
+            *sent_recipients_dst = 100000u64.to_le_bytes();
+            state_dst[0] = EscrowState::Partial as u8;
+            *factory_dst = [10; 32];
+            manifest_url_dst[..17].copy_from_slice(b"http://result.com");
+        }
+
+        let escrow = Escrow::unpack(&packed_obj).unwrap();
+        assert_eq!(Escrow::unpack_version(&packed_obj).unwrap(), 0);
+        assert_eq!(
+            escrow,
+            Escrow {
+                expires: 1606402240,
+                bump_seed: 250,
+                token_mint: Pubkey::new_from_array([1; 32]),
+                token_account: Pubkey::new_from_array([2; 32]),
+                reputation_oracle: COption::Some(Pubkey::new_from_array([3; 32])),
+                reputation_oracle_token_account: COption::Some(Pubkey::new_from_array([4; 32])),
+                reputation_oracle_stake: 500,
+                recording_oracle: COption::Some(Pubkey::new_from_array([5; 32])),
+                recording_oracle_token_account: COption::Some(Pubkey::new_from_array([6; 32])),
+                recording_oracle_stake: 1000,
+                launcher: Pubkey::new_from_array([7; 32]),
+                canceler: Pubkey::new_from_array([8; 32]),
+                canceler_token_account: Pubkey::new_from_array([9; 32]),
+                total_amount: 20000000,
+                total_recipients: 1000000,
+                sent_amount: 2000000,
+                sent_recipients: 100000,
+                state: EscrowState::Partial,
+                factory: Pubkey::new_from_array([10; 32]),
+                manifest_url: DataUrl::from_str("http://result.com").unwrap(),
+                ..Default::default()
+            }
+        );
+
+        // Migrated escrow keeps all the legacy fields
+        let mut migrated_obj = [0; Escrow::LEN];
+        Escrow::pack(escrow, &mut migrated_obj).unwrap();
+        assert_eq!(Escrow::unpack(&migrated_obj).unwrap(), escrow);
+
+        // Stakes above 100% are invalid
+        packed_obj[145] = 101;
+        assert!(Escrow::unpack(&packed_obj).is_err());
+    }
+
+    #[test]
+    fn test_url_packing() {
+        let url = DataUrl::from_str("http://result.com").unwrap();
+        assert_eq!(url.to_string(), "http://result.com");
+        assert_eq!(url.account(), None);
+        let mut packed_url: [u8; DataUrl::LEN] = [0; DataUrl::LEN];
+        url.pack_into_slice(&mut packed_url);
+        assert_eq!(DataUrl::unpack_from_slice(&packed_url).unwrap(), url);
+
+        let account = Pubkey::new_from_array([1; 32]);
+        let url = DataUrl::new_from_account(&account);
+        assert_eq!(url.account(), Some(account));
+        url.pack_into_slice(&mut packed_url);
+        assert_eq!(DataUrl::unpack_from_slice(&packed_url).unwrap(), url);
+
+        assert!(DataUrl::from_str(&"a".repeat(URL_LEN + 1)).is_err());
+
+        let obj = UrlData {
+            authority: Pubkey::new_from_array([2; 32]),
+            len: 1000,
+        };
+        let mut packed_obj: [u8; UrlData::LEN] = [0; UrlData::LEN];
+        UrlData::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = UrlData::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+    }
+
+    #[test]
+    fn test_account_types() {
+        // Escrow versions never clash with the account types
+        assert!(ESCROW_VERSION < AccountType::Factory as u8);
+
+        // Accounts of other types starting with the same keys aren't URL data
+        let schedule = VestingSchedule {
+            escrow: Pubkey::new_from_array([1; 32]),
+            recipient: Pubkey::new_from_array([2; 32]),
+            ..Default::default()
+        };
+        let mut packed_schedule: [u8; VestingSchedule::LEN] = [0; VestingSchedule::LEN];
+        VestingSchedule::pack(schedule, &mut packed_schedule).unwrap();
+        assert_eq!(
+            UrlData::unpack_unchecked(&packed_schedule[..UrlData::LEN]),
+            Err(ProgramError::InvalidAccountData)
+        );
+        assert_eq!(
+            UrlData::unpack_url(&packed_schedule),
+            Err(ProgramError::InvalidAccountData)
+        );
+        let mut packed_log: [u8; ResultsLog::LEN] = [0; ResultsLog::LEN];
+        ResultsLog::pack(
+            ResultsLog {
+                escrow: schedule.escrow,
+                count: 1,
+            },
+            &mut packed_log,
+        )
+        .unwrap();
+        assert_eq!(
+            WorkerReputation::unpack_unchecked(&[&packed_log[..], &[0; 42]].concat()),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        // Blank accounts are uninitialized, other data is rejected
+        assert_eq!(
+            UrlData::unpack_unchecked(&[0; UrlData::LEN]).unwrap(),
+            UrlData::default()
+        );
+        let mut packed_obj = [0; UrlData::LEN];
+        packed_obj[1] = 1;
+        assert_eq!(
+            UrlData::unpack_unchecked(&packed_obj),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn test_hash_packing() {
+        let hash = DataHash::new(HashAlgorithm::Sha256, &[1; 32]).unwrap();
+        let mut packed_hash: [u8; DataHash::LEN] = [0; DataHash::LEN];
+        hash.pack_into_slice(&mut packed_hash);
+        assert_eq!(packed_hash[..2], [0x12, 32]);
+        assert_eq!(DataHash::unpack_from_slice(&packed_hash).unwrap(), hash);
+        assert_eq!(hash.to_string(), format!("sha256:{}", "01".repeat(32)));
+
+        // Existing SHA-1 hashes stay readable
+        let hash = DataHash::new(HashAlgorithm::Sha1, &[2; 20]).unwrap();
+        hash.pack_into_slice(&mut packed_hash);
+        assert_eq!(DataHash::unpack_from_slice(&packed_hash).unwrap(), hash);
+        assert_eq!(hash.as_ref(), &[2; 20]);
+
+        assert_eq!(DataHash::default().to_string(), "");
+        assert!(DataHash::new(HashAlgorithm::Keccak256, &[3; 20]).is_err());
+
+        // Digest size not matching the algorithm
+        packed_hash[1] = 32;
+        assert!(DataHash::unpack_from_slice(&packed_hash).is_err());
+    }
+
+    #[test]
+    fn test_legacy_hash_unpacking() {
+        // Escrows of the legacy layout store the raw SHA-1 digests
+        let mut packed_obj = [0; Escrow::LEN_V0];
+        packed_obj[347] = EscrowState::Complete as u8;
+        packed_obj[636..656].copy_from_slice(&[0xab; 20]);
+        packed_obj[912..932].copy_from_slice(&[0xcd; 20]);
+        let escrow = Escrow::unpack(&packed_obj).unwrap();
+        assert_eq!(
+            escrow.manifest_hash,
+            DataHash::new(HashAlgorithm::Sha1, &[0xab; 20]).unwrap()
+        );
+        assert_eq!(
+            escrow.final_results_hash.to_string(),
+            format!("sha1:{}", "cd".repeat(20))
+        );
+
+        // And keep them after the migration
+        let mut migrated_obj = [0; Escrow::LEN];
+        Escrow::pack(escrow, &mut migrated_obj).unwrap();
+        let migrated_escrow = Escrow::unpack(&migrated_obj).unwrap();
+        assert_eq!(migrated_escrow.manifest_hash, escrow.manifest_hash);
+        assert_eq!(
+            migrated_escrow.final_results_hash,
+            escrow.final_results_hash
+        );
+
+        // Zero digest means no hash is stored
+        packed_obj[912..932].copy_from_slice(&[0; 20]);
+        let escrow = Escrow::unpack(&packed_obj).unwrap();
+        assert_eq!(escrow.final_results_hash, DataHash::default());
+    }
+
+    #[test]
+    fn test_factory_packing() {
+        let obj = Factory {
+            version: 1,
+            escrow_count: 1000,
+            admin: Pubkey::new_from_array([2; 32]),
+            token_mint: COption::Some(Pubkey::new_from_array([3; 32])),
+            max_duration: 2592000,
+            max_oracle_stake: 2000,
+            paused: true,
+        };
+        let mut packed_obj: [u8; Factory::LEN] = [0; Factory::LEN];
+        Factory::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = Factory::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+
+        let obj = FactoryEntry {
+            escrow: Pubkey::new_from_array([1; 32]),
+        };
+        let mut packed_obj: [u8; FactoryEntry::LEN] = [0; FactoryEntry::LEN];
+        FactoryEntry::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = FactoryEntry::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+    }
+
+    #[test]
+    fn test_payout_receipt_packing() {
+        let obj = PayoutReceipt {
+            escrow: Pubkey::new_from_array([1; 32]),
+            recipient: Pubkey::new_from_array([2; 32]),
+            amount: 30000000,
+        };
+        let mut packed_obj: [u8; PayoutReceipt::LEN] = [0; PayoutReceipt::LEN];
+        PayoutReceipt::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = PayoutReceipt::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+    }
+
+    #[test]
+    fn test_worker_reputation() {
+        let mut obj = WorkerReputation {
+            factory: Pubkey::new_from_array([1; 32]),
+            worker: Pubkey::new_from_array([2; 32]),
+            ..Default::default()
+        };
+        obj.record_job(900, 8000);
+        obj.record_job(100, 9000);
+        obj.record_job(0, 10000);
+        assert_eq!(obj.jobs_completed, 3);
+        assert_eq!(obj.total_earned, 1000);
+        assert_eq!(obj.score, 9000);
+
+        // Further payouts of the job don't change the score
+        obj.record_earnings(500);
+        assert_eq!(obj.jobs_completed, 3);
+        assert_eq!(obj.total_earned, 1500);
+        assert_eq!(obj.score, 9000);
+
+        let mut packed_obj: [u8; WorkerReputation::LEN] = [0; WorkerReputation::LEN];
+        WorkerReputation::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = WorkerReputation::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+    }
+
+    #[test]
+    fn test_oracle_approval_packing() {
+        let obj = OracleApproval {
+            factory: Pubkey::new_from_array([1; 32]),
+            oracle: Pubkey::new_from_array([2; 32]),
+            min_scored_amount: 1000000,
+        };
+        let mut packed_obj: [u8; OracleApproval::LEN] = [0; OracleApproval::LEN];
+        OracleApproval::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = OracleApproval::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+
+        // Receipt of the same size is not taken for the approval
+        let mut packed_receipt: [u8; PayoutReceipt::LEN] = [0; PayoutReceipt::LEN];
+        PayoutReceipt::pack(
+            PayoutReceipt {
+                escrow: obj.factory,
+                recipient: obj.oracle,
+                amount: obj.min_scored_amount,
+            },
+            &mut packed_receipt,
+        )
+        .unwrap();
+        assert!(OracleApproval::unpack(&packed_receipt).is_err());
+    }
+
+    #[test]
+    fn test_vesting_schedule() {
+        let obj = VestingSchedule {
+            escrow: Pubkey::new_from_array([1; 32]),
+            recipient: Pubkey::new_from_array([2; 32]),
+            start: 1000,
+            cliff: 1250,
+            end: 2000,
+            total: 3000,
+            withdrawn: 500,
+        };
+        assert_eq!(obj.vested_amount(0), 0);
+        assert_eq!(obj.vested_amount(1249), 0);
+        assert_eq!(obj.vested_amount(1250), 750);
+        assert_eq!(obj.vested_amount(1999), 2997);
+        assert_eq!(obj.vested_amount(2000), 3000);
+        assert_eq!(obj.vested_amount(UnixTimestamp::MAX), 3000);
+
+        // Whole payout vests at once without the vesting period
+        let cliff_only = VestingSchedule {
+            start: 2000,
+            cliff: 2000,
+            ..obj
+        };
+        assert_eq!(cliff_only.vested_amount(1999), 0);
+        assert_eq!(cliff_only.vested_amount(2000), 3000);
+
+        // Stopped vesting keeps the vested amount and only releases the rest once
+        let mut stopped = obj;
+        assert_eq!(stopped.stop_vesting(1500), 1500);
+        assert_eq!(stopped.total, 1500);
+        assert_eq!(stopped.vested_amount(1500), 1500);
+        assert_eq!(stopped.stop_vesting(1500), 0);
+        assert_eq!(stopped.vested_amount(2000), 1500);
+
+        let mut stopped = obj;
+        assert_eq!(stopped.stop_vesting(1100), 3000);
+        assert_eq!(stopped.vested_amount(UnixTimestamp::MAX), 0);
+        assert_eq!(stopped.stop_vesting(1100), 0);
+
+        let mut stopped = obj;
+        assert_eq!(stopped.stop_vesting(2000), 0);
+        assert_eq!(stopped, obj);
+
+        let mut packed_obj: [u8; VestingSchedule::LEN] = [0; VestingSchedule::LEN];
+        VestingSchedule::pack(obj, &mut packed_obj).unwrap();
+        let unpacked_obj = VestingSchedule::unpack(&packed_obj).unwrap();
+        assert_eq!(unpacked_obj, obj);
+    }
+
+    #[test]
+    fn test_results_log_packing() {
+        let log = ResultsLog {
+            escrow: Pubkey::new_from_array([1; 32]),
+            count: 2,
+        };
+        let results = vec![
+            IntermediateResult {
+                url: DataUrl::new_from_array([2; URL_LEN]),
+                hash: DataHash::new(HashAlgorithm::Sha256, &[3; 32]).unwrap(),
+                timestamp: 1614556800,
+                submitter: Pubkey::new_from_array([4; 32]),
+            },
+            IntermediateResult {
+                url: DataUrl::new_from_array([5; URL_LEN]),
+                hash: DataHash::new(HashAlgorithm::Sha1, &[6; 20]).unwrap(),
+                timestamp: 1614643200,
+                submitter: Pubkey::new_from_array([7; 32]),
+            },
+        ];
+        let mut data = vec![0; ResultsLog::ACCOUNT_LEN];
+        log.pack_into_slice(&mut data);
+        for (index, result) in results.iter().enumerate() {
+            ResultsLog::pack_result(index, result, &mut data).unwrap();
+        }
+
+        let unpacked_log = ResultsLog::unpack_from_slice(&data).unwrap();
+        assert_eq!(unpacked_log, log);
+        assert_eq!(unpacked_log.unpack_results(&data).unwrap(), results);
+
+        // No free slots
+        assert!(ResultsLog::pack_result(MAX_INTERMEDIATE_RESULTS, &results[0], &mut data).is_err());
     }
 }
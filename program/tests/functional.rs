@@ -1,20 +1,84 @@
 #![cfg(feature = "test-bpf")]
 
+use hmt_escrow::error::EscrowError;
+use hmt_escrow::event::EscrowEvent;
+use hmt_escrow::merkle::{self, MerkleHash};
+use hmt_escrow::processor::Processor;
 use hmt_escrow::state::DataHash;
 use hmt_escrow::state::DataUrl;
+use hmt_escrow::state::HashAlgorithm;
+use hmt_escrow::state::STAKE_BASIS_POINTS;
 use hmt_escrow::*;
-use solana_program::{hash::Hash, program_pack::Pack, pubkey::Pubkey, system_instruction};
+use solana_program::{
+    hash::Hash,
+    instruction::{Instruction, InstructionError},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+};
 use solana_program_test::*;
 use solana_sdk::{
+    account::Account,
     signature::{Keypair, Signer},
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::Mutex,
+    thread::{self, ThreadId},
 };
-use std::str::FromStr;
 const DECIMALS: u8 = 9;
 
 const DEFAULT_FACTORY_VERSION: u8 = 1;
 
+/// Program log lines of the processed transactions with the bank threads
+/// processing them, so that the lines of the parallel tests don't mix up
+static PROGRAM_LOGS: Mutex<Vec<(ThreadId, String)>> = Mutex::new(Vec::new());
+
+/// Collects the runtime program logs
+struct ProgramLogger;
+
+impl log::Log for ProgramLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let line = record.args().to_string();
+        if line.starts_with("Program ") {
+            PROGRAM_LOGS
+                .lock()
+                .unwrap()
+                .push((thread::current().id(), line));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static PROGRAM_LOGGER: ProgramLogger = ProgramLogger;
+
+/// Decodes the escrow events from the program logs of the transactions
+/// processed so far
+fn program_events() -> Vec<EscrowEvent> {
+    let mut thread_logs: HashMap<ThreadId, Vec<String>> = HashMap::new();
+    for (thread, line) in PROGRAM_LOGS.lock().unwrap().iter() {
+        thread_logs.entry(*thread).or_default().push(line.clone());
+    }
+    thread_logs
+        .values()
+        .flat_map(|logs| EscrowEvent::decode_logs(&id(), logs).unwrap())
+        .collect()
+}
+
 fn program_test() -> ProgramTest {
+    // Program logs are collected by the logger set before the program test one
+    let _ = log::set_logger(&PROGRAM_LOGGER);
+
     let mut pc = ProgramTest::new(
         "hmt_escrow",
         id(),
@@ -115,6 +179,13 @@ async fn create_escrow(
     let rent = banks_client.get_rent().await.unwrap();
     let account_rent = rent.minimum_balance(state::Escrow::LEN);
 
+    let factory = get_factory(banks_client, &factory_account.pubkey()).await;
+    let (factory_entry, _) = processor::Processor::find_factory_entry_address(
+        &id(),
+        &factory_account.pubkey(),
+        factory.escrow_count,
+    );
+
     let mut transaction = Transaction::new_with_payer(
         &[
             system_instruction::create_account(
@@ -133,7 +204,10 @@ async fn create_escrow(
                 &launcher,
                 &canceler,
                 &canceler_token.pubkey(),
+                &factory_entry,
+                &payer.pubkey(),
                 *duration,
+                None,
             )
             .unwrap(),
         ],
@@ -143,6 +217,26 @@ async fn create_escrow(
     banks_client.process_transaction(transaction).await.unwrap();
 }
 
+async fn get_factory(banks_client: &mut BanksClient, factory: &Pubkey) -> state::Factory {
+    let factory_account = banks_client
+        .get_account(*factory)
+        .await
+        .expect("get_account")
+        .expect("cannot read factory account data");
+
+    state::Factory::unpack(factory_account.data.as_slice()).unwrap()
+}
+
+async fn get_escrow(banks_client: &mut BanksClient, escrow: &Pubkey) -> state::Escrow {
+    let escrow_account = banks_client
+        .get_account(*escrow)
+        .await
+        .expect("get_account")
+        .expect("cannot read escrow account data");
+
+    state::Escrow::unpack(escrow_account.data.as_slice()).unwrap()
+}
+
 async fn create_factory(
     banks_client: &mut BanksClient,
     payer: &Keypair,
@@ -162,7 +256,13 @@ async fn create_factory(
                 state::Factory::LEN as u64,
                 &id(),
             ),
-            instruction::factory_initialize(&id(), &factory_account.pubkey(), version).unwrap(),
+            instruction::factory_initialize(
+                &id(),
+                &factory_account.pubkey(),
+                &payer.pubkey(),
+                version,
+            )
+            .unwrap(),
         ],
         Some(&payer.pubkey()),
     );
@@ -171,6 +271,74 @@ async fn create_factory(
     banks_client.process_transaction(transaction).await.unwrap();
 }
 
+async fn set_factory_policy(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    factory: &Pubkey,
+    admin: &Keypair,
+    token_mint: COption<Pubkey>,
+    max_duration: u64,
+    max_oracle_stake: u16,
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::factory_set_policy(
+            &id(),
+            factory,
+            &admin.pubkey(),
+            token_mint,
+            max_duration,
+            max_oracle_stake,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, admin], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
+
+async fn set_factory_paused(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    factory: &Pubkey,
+    admin: &Keypair,
+    paused: bool,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::factory_set_paused(&id(), factory, &admin.pubkey(), paused).unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, admin], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn approve_oracle(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    factory: &Pubkey,
+    admin: &Keypair,
+    oracle: &Pubkey,
+    min_scored_amount: u64,
+) -> Result<(), TransportError> {
+    let (oracle_approval, _) = Processor::find_oracle_approval_address(&id(), factory, oracle);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::factory_approve_oracle(
+            &id(),
+            factory,
+            &admin.pubkey(),
+            &oracle_approval,
+            oracle,
+            min_scored_amount,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, admin], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
+
 async fn setup_escrow(
     banks_client: &mut BanksClient,
     payer: &Keypair,
@@ -179,12 +347,15 @@ async fn setup_escrow(
     trust_handler: &Keypair,
     reputation_oracle: &Keypair,
     reputation_oracle_token: &Keypair,
-    reputation_oracle_stake: &u8,
+    reputation_oracle_stake: &u16,
     recording_oracle: &Keypair,
     recording_oracle_token: &Keypair,
-    recording_oracle_stake: &u8,
+    recording_oracle_stake: &u16,
     manifest_url: &DataUrl,
     manifest_hash: &DataHash,
+    defer_fees: bool,
+    dispute_window: u64,
+    factory: &Pubkey,
 ) {
     let mut transaction = Transaction::new_with_payer(
         &[instruction::setup(
@@ -199,6 +370,12 @@ async fn setup_escrow(
             *recording_oracle_stake,
             manifest_url,
             manifest_hash,
+            defer_fees,
+            dispute_window,
+            0,
+            0,
+            factory,
+            None,
         )
         .unwrap()],
         Some(&payer.pubkey()),
@@ -215,6 +392,7 @@ async fn store_results(
     trust_handler: &Keypair,
     final_results_url: &DataUrl,
     final_results_hash: &DataHash,
+    factory: &Pubkey,
 ) {
     let mut transaction = Transaction::new_with_payer(
         &[instruction::store_results(
@@ -223,6 +401,7 @@ async fn store_results(
             &trust_handler.pubkey(),
             &final_results_url,
             final_results_hash,
+            factory,
         )
         .unwrap()],
         Some(&payer.pubkey()),
@@ -239,6 +418,7 @@ async fn store_final_amounts(
     trust_handler: &Keypair,
     total_amount: &f64,
     total_recipients: &u64,
+    factory: &Pubkey,
 ) {
     let mut transaction = Transaction::new_with_payer(
         &[instruction::store_amounts(
@@ -247,6 +427,7 @@ async fn store_final_amounts(
             &trust_handler.pubkey(),
             spl_token::ui_amount_to_amount(*total_amount, DECIMALS),
             *total_recipients,
+            factory,
         )
         .unwrap()],
         Some(&payer.pubkey()),
@@ -267,7 +448,14 @@ async fn payout(
     reputation_oracle_token_account: &Keypair,
     recording_oracle_token_account: &Keypair,
     amount: &f64,
-) {
+    factory: &Pubkey,
+    payout_id: Option<&instruction::PayoutId>,
+) -> Result<(), TransportError> {
+    let payout_receipt = payout_id.map(|payout_id| {
+        let (payout_receipt, _) =
+            Processor::find_payout_receipt_address(&id(), &escrow_account.pubkey(), payout_id);
+        (payout_id, payout_receipt)
+    });
     let mut transaction = Transaction::new_with_payer(
         &[instruction::payout(
             &id(),
@@ -280,15 +468,21 @@ async fn payout(
             &recording_oracle_token_account.pubkey(),
             &spl_token::id(),
             spl_token::ui_amount_to_amount(*amount, DECIMALS),
+            factory,
+            payout_receipt
+                .as_ref()
+                .map(|(payout_id, payout_receipt)| (*payout_id, payout_receipt)),
+            None,
+            None,
         )
         .unwrap()],
         Some(&payer.pubkey()),
     );
     transaction.sign(&[payer, trust_handler], *recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
+    banks_client.process_transaction(transaction).await
 }
 
-async fn cancel(
+async fn batch_payout(
     banks_client: &mut BanksClient,
     payer: &Keypair,
     recent_blockhash: &Hash,
@@ -296,17 +490,34 @@ async fn cancel(
     trust_handler: &Keypair,
     escrow_token_account: &Keypair,
     escrow_authority: &Pubkey,
-    canceler_token_account: &Keypair,
+    reputation_oracle_token_account: &Keypair,
+    recording_oracle_token_account: &Keypair,
+    factory: &Pubkey,
+    payouts: &[(Pubkey, f64)],
 ) {
+    let payouts: Vec<(Pubkey, u64)> = payouts
+        .iter()
+        .map(|(recipient, amount)| {
+            (
+                *recipient,
+                spl_token::ui_amount_to_amount(*amount, DECIMALS),
+            )
+        })
+        .collect();
     let mut transaction = Transaction::new_with_payer(
-        &[instruction::cancel(
+        &[instruction::batch_payout(
             &id(),
             &escrow_account.pubkey(),
             &trust_handler.pubkey(),
             &escrow_token_account.pubkey(),
             &escrow_authority,
-            &canceler_token_account.pubkey(),
+            &reputation_oracle_token_account.pubkey(),
+            &recording_oracle_token_account.pubkey(),
             &spl_token::id(),
+            factory,
+            &payouts,
+            &[],
+            None,
         )
         .unwrap()],
         Some(&payer.pubkey()),
@@ -315,115 +526,387 @@ async fn cancel(
     banks_client.process_transaction(transaction).await.unwrap();
 }
 
-async fn complete(
+async fn store_payouts_root(
     banks_client: &mut BanksClient,
     payer: &Keypair,
     recent_blockhash: &Hash,
     escrow_account: &Keypair,
     trust_handler: &Keypair,
+    factory: &Pubkey,
+    payouts_root: &MerkleHash,
 ) {
     let mut transaction = Transaction::new_with_payer(
-        &[
-            instruction::complete(&id(), &escrow_account.pubkey(), &trust_handler.pubkey())
-                .unwrap(),
-        ],
+        &[instruction::store_payouts_root(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            factory,
+            payouts_root,
+        )
+        .unwrap()],
         Some(&payer.pubkey()),
     );
     transaction.sign(&[payer, trust_handler], *recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 }
 
-async fn mint_to_escrow(
+async fn claim(
     banks_client: &mut BanksClient,
     payer: &Keypair,
     recent_blockhash: &Hash,
-    mint_pubkey: &Keypair,
-    account_pubkey: &Keypair,
-    owner_pubkey: &Keypair,
-    amount: f64,
+    escrow: &EscrowAccount,
+    claimant: &Keypair,
+    recipient_token_account: &Pubkey,
+    index: u64,
+    amount: u64,
+    proof: &[MerkleHash],
+) -> Result<(), TransportError> {
+    let (claim_receipt, _) =
+        Processor::find_claim_receipt_address(&id(), &escrow.escrow.pubkey(), index);
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::claim(
+            &id(),
+            &escrow.escrow.pubkey(),
+            &claimant.pubkey(),
+            &escrow.escrow_token_account.pubkey(),
+            &escrow.escrow_authority,
+            recipient_token_account,
+            &escrow.reputation_oracle_token.pubkey(),
+            &escrow.recording_oracle_token.pubkey(),
+            &spl_token::id(),
+            &escrow.factory.pubkey(),
+            &claim_receipt,
+            index,
+            amount,
+            proof,
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, claimant], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
+
+async fn cancel(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    trust_handler: &Keypair,
+    escrow_token_account: &Keypair,
+    escrow_authority: &Pubkey,
+    canceler_token_account: &Keypair,
 ) {
     let mut transaction = Transaction::new_with_payer(
-        &[spl_token::instruction::mint_to(
+        &[instruction::cancel(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            &escrow_token_account.pubkey(),
+            &escrow_authority,
+            &canceler_token_account.pubkey(),
             &spl_token::id(),
-            &mint_pubkey.pubkey(),
-            &account_pubkey.pubkey(),
-            &owner_pubkey.pubkey(),
-            &[],
-            spl_token::ui_amount_to_amount(amount, DECIMALS),
+            None,
+            None,
         )
         .unwrap()],
         Some(&payer.pubkey()),
     );
-    transaction.sign(&[payer, owner_pubkey], *recent_blockhash);
+    transaction.sign(&[payer, trust_handler], *recent_blockhash);
     banks_client.process_transaction(transaction).await.unwrap();
 }
 
-struct EscrowAccount {
-    pub escrow: Keypair,
-    pub factory: Keypair,
-    pub token_mint: Keypair,
-    pub escrow_token_account: Keypair,
-    pub launcher: Keypair,
-    pub canceler: Keypair,
-    pub canceler_token_account: Keypair,
-    pub duration: u64,
-    pub escrow_authority: Pubkey,
-    pub bump_seed: u8,
-    pub reputation_oracle: Keypair,
-    pub reputation_oracle_token: Keypair,
-    pub reputation_oracle_stake: u8,
-    pub recording_oracle: Keypair,
-    pub recording_oracle_token: Keypair,
-    pub recording_oracle_stake: u8,
-    pub manifest_url: DataUrl,
-    pub manifest_hash: DataHash,
-    pub final_results_url: DataUrl,
-    pub final_results_hash: DataHash,
-    pub total_amount: f64,
-    pub total_recipients: u64,
-    pub payout_amount: f64,
-    pub mint_authority: Keypair,
+async fn reclaim(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    escrow_token_account: &Keypair,
+    escrow_authority: &Pubkey,
+    canceler_token_account: &Keypair,
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::reclaim(
+            &id(),
+            &escrow_account.pubkey(),
+            &escrow_token_account.pubkey(),
+            &escrow_authority,
+            &canceler_token_account.pubkey(),
+            &spl_token::id(),
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
 }
 
-impl EscrowAccount {
-    pub fn new() -> Self {
-        let escrow = Keypair::new();
-        let factory = Keypair::new();
-        let token_mint = Keypair::new();
-        let escrow_token_account = Keypair::new();
-        let launcher = Keypair::new();
-        let canceler = Keypair::new();
-        let canceler_token_account = Keypair::new();
+async fn extend_expiration(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    trust_handler: &Keypair,
+    factory: &Pubkey,
+    additional_seconds: u64,
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::extend_expiration(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            factory,
+            additional_seconds,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, trust_handler], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
 
-        let reputation_oracle = Keypair::new();
-        let reputation_oracle_token = Keypair::new();
-        let recording_oracle = Keypair::new();
-        let recording_oracle_token = Keypair::new();
-        let mint_authority = Keypair::new();
+async fn add_trusted_handlers(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    trust_handler: &Keypair,
+    handlers: &[Pubkey],
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::add_trusted_handlers(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            handlers,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, trust_handler], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
 
-        let manifest_array_for_hash = [1, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 9, 8, 7, 6, 5, 4, 3, 9, 9];
-        let final_array_for_hash = [7, 3, 4, 3, 4, 5, 6, 7, 2, 1, 6, 6, 0, 2, 2, 2, 3, 2, 4, 7];
-        let manifest_hash = DataHash::new_from_array(manifest_array_for_hash);
-        let final_results_hash = DataHash::new_from_array(final_array_for_hash);
-        //find authority bumpseed
-        let (escrow_authority, bump_seed) =
-            hmt_escrow::processor::Processor::find_authority_bump_seed(&id(), &escrow.pubkey());
+async fn remove_trusted_handlers(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    trust_handler: &Keypair,
+    handlers: &[Pubkey],
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::remove_trusted_handlers(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            handlers,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, trust_handler], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
 
-        let manifest_url: DataUrl = match DataUrl::from_str("http://somemanifest.com") {
-            Ok(url) => url,
-            _ => Default::default(),
-        };
+async fn process_signed(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    instruction: Instruction,
+    signer: &Keypair,
+) -> Result<(), TransportError> {
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[payer, signer], *recent_blockhash);
+    banks_client.process_transaction(transaction).await
+}
 
-        let final_results_url: DataUrl = match DataUrl::from_str("http://result.com") {
-            Ok(url) => url,
-            _ => Default::default(),
-        };
-        Self {
-            escrow,
-            factory,
-            token_mint,
+fn assert_escrow_error(result: Result<(), TransportError>, error: EscrowError) {
+    let code = match ProgramError::from(error) {
+        ProgramError::Custom(code) => code,
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(code))
+    );
+}
+
+async fn close(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    trust_handler: &Keypair,
+    escrow_token_account: &Keypair,
+    escrow_authority: &Pubkey,
+    canceler_token_account: &Keypair,
+    receiver: &Pubkey,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::close(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            &escrow_token_account.pubkey(),
+            &escrow_authority,
+            &canceler_token_account.pubkey(),
+            receiver,
+            &spl_token::id(),
+            None,
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, trust_handler], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn fund(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    launcher: &Keypair,
+    launcher_token_account: &Keypair,
+    escrow_token_account: &Keypair,
+    amount: &f64,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::fund(
+            &id(),
+            &escrow_account.pubkey(),
+            &launcher.pubkey(),
+            &launcher_token_account.pubkey(),
+            &escrow_token_account.pubkey(),
+            &spl_token::id(),
+            spl_token::ui_amount_to_amount(*amount, DECIMALS),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, launcher], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn complete(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &Keypair,
+    trust_handler: &Keypair,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::complete(
+            &id(),
+            &escrow_account.pubkey(),
+            &trust_handler.pubkey(),
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, trust_handler], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn mint_to_escrow(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    mint_pubkey: &Keypair,
+    account_pubkey: &Keypair,
+    owner_pubkey: &Keypair,
+    amount: f64,
+) {
+    let mut transaction = Transaction::new_with_payer(
+        &[spl_token::instruction::mint_to(
+            &spl_token::id(),
+            &mint_pubkey.pubkey(),
+            &account_pubkey.pubkey(),
+            &owner_pubkey.pubkey(),
+            &[],
+            spl_token::ui_amount_to_amount(amount, DECIMALS),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, owner_pubkey], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+struct EscrowAccount {
+    pub escrow: Keypair,
+    pub factory: Keypair,
+    pub token_mint: Keypair,
+    pub escrow_token_account: Keypair,
+    pub launcher: Keypair,
+    pub launcher_token_account: Keypair,
+    pub canceler: Keypair,
+    pub canceler_token_account: Keypair,
+    pub duration: u64,
+    pub escrow_authority: Pubkey,
+    pub bump_seed: u8,
+    pub reputation_oracle: Keypair,
+    pub reputation_oracle_token: Keypair,
+    pub reputation_oracle_stake: u16,
+    pub recording_oracle: Keypair,
+    pub recording_oracle_token: Keypair,
+    pub recording_oracle_stake: u16,
+    pub manifest_url: DataUrl,
+    pub manifest_hash: DataHash,
+    pub defer_fees: bool,
+    pub dispute_window: u64,
+    pub final_results_url: DataUrl,
+    pub final_results_hash: DataHash,
+    pub total_amount: f64,
+    pub total_recipients: u64,
+    pub payout_amount: f64,
+    pub mint_authority: Keypair,
+}
+
+impl EscrowAccount {
+    pub fn new() -> Self {
+        let escrow = Keypair::new();
+        let factory = Keypair::new();
+        let token_mint = Keypair::new();
+        let escrow_token_account = Keypair::new();
+        let launcher = Keypair::new();
+        let launcher_token_account = Keypair::new();
+        let canceler = Keypair::new();
+        let canceler_token_account = Keypair::new();
+
+        let reputation_oracle = Keypair::new();
+        let reputation_oracle_token = Keypair::new();
+        let recording_oracle = Keypair::new();
+        let recording_oracle_token = Keypair::new();
+        let mint_authority = Keypair::new();
+
+        let manifest_hash = DataHash::new(HashAlgorithm::Sha256, &[1; 32]).unwrap();
+        let final_results_hash = DataHash::new(HashAlgorithm::Keccak256, &[7; 32]).unwrap();
+        //find authority bumpseed
+        let (escrow_authority, bump_seed) =
+            hmt_escrow::processor::Processor::find_authority_bump_seed(&id(), &escrow.pubkey());
+
+        let manifest_url: DataUrl = match DataUrl::from_str("http://somemanifest.com") {
+            Ok(url) => url,
+            _ => Default::default(),
+        };
+
+        let final_results_url: DataUrl = match DataUrl::from_str("http://result.com") {
+            Ok(url) => url,
+            _ => Default::default(),
+        };
+        Self {
+            escrow,
+            factory,
+            token_mint,
             escrow_token_account,
             launcher,
+            launcher_token_account,
             canceler,
             canceler_token_account,
             duration: 100000 as u64,
@@ -431,16 +914,18 @@ impl EscrowAccount {
             bump_seed,
             reputation_oracle,
             reputation_oracle_token,
-            reputation_oracle_stake: 10 as u8,
+            reputation_oracle_stake: 1000 as u16,
             recording_oracle,
             recording_oracle_token,
-            recording_oracle_stake: 15 as u8,
+            recording_oracle_stake: 1500 as u16,
             total_amount: 30.0 as f64,
             total_recipients: 1 as u64,
             payout_amount: 30.0 as f64,
             mint_authority,
             manifest_url,
             manifest_hash,
+            defer_fees: false,
+            dispute_window: 0,
             final_results_url,
             final_results_hash,
         }
@@ -547,6 +1032,9 @@ impl EscrowAccount {
             &self.recording_oracle_stake,
             &self.manifest_url,
             &self.manifest_hash,
+            self.defer_fees,
+            self.dispute_window,
+            &self.factory.pubkey(),
         )
         .await;
     }
@@ -565,6 +1053,7 @@ impl EscrowAccount {
             &self.launcher,
             &self.final_results_url,
             &self.final_results_hash,
+            &self.factory.pubkey(),
         )
         .await;
     }
@@ -580,9 +1069,10 @@ impl EscrowAccount {
             &payer,
             &recent_blockhash,
             &self.escrow,
-            &self.launcher,
+            &self.reputation_oracle,
             &self.total_amount,
             &self.total_recipients,
+            &self.factory.pubkey(),
         ).await;
     }
 
@@ -598,15 +1088,44 @@ impl EscrowAccount {
             payer,
             &recent_blockhash,
             &self.escrow,
-            &self.launcher,
+            &self.reputation_oracle,
             &self.escrow_token_account,
             &self.escrow_authority,
             recipient_token_account,
             &self.reputation_oracle_token,
             &self.recording_oracle_token,
             &self.payout_amount,
+            &self.factory.pubkey(),
+            None,
         )
-        .await;
+        .await
+        .unwrap();
+    }
+
+    pub async fn payout_escrow_with_id(
+        &self,
+        mut banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        recipient_token_account: &Keypair,
+        payout_id: &instruction::PayoutId,
+    ) -> Result<(), TransportError> {
+        payout(
+            &mut banks_client,
+            payer,
+            &recent_blockhash,
+            &self.escrow,
+            &self.reputation_oracle,
+            &self.escrow_token_account,
+            &self.escrow_authority,
+            recipient_token_account,
+            &self.reputation_oracle_token,
+            &self.recording_oracle_token,
+            &self.payout_amount,
+            &self.factory.pubkey(),
+            Some(payout_id),
+        )
+        .await
     }
 
     pub async fn cancel_escrow(
@@ -628,6 +1147,85 @@ impl EscrowAccount {
         .await;
     }
 
+    pub async fn reclaim_escrow(
+        &self,
+        mut banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+    ) -> Result<(), TransportError> {
+        reclaim(
+            &mut banks_client,
+            payer,
+            &recent_blockhash,
+            &self.escrow,
+            &self.escrow_token_account,
+            &self.escrow_authority,
+            &self.canceler_token_account,
+        )
+        .await
+    }
+
+    pub async fn close_escrow(
+        &self,
+        mut banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        receiver: &Pubkey,
+    ) {
+        close(
+            &mut banks_client,
+            payer,
+            &recent_blockhash,
+            &self.escrow,
+            &self.launcher,
+            &self.escrow_token_account,
+            &self.escrow_authority,
+            &self.canceler_token_account,
+            receiver,
+        )
+        .await;
+    }
+
+    pub async fn fund_escrow(
+        &self,
+        mut banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: &Hash,
+        amount: f64,
+    ) {
+        //Creating token account for launcher and minting tokens to deposit
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &self.launcher_token_account,
+            &self.token_mint.pubkey(),
+            &self.launcher.pubkey(),
+        )
+        .await;
+        mint_to_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &self.token_mint,
+            &self.launcher_token_account,
+            &self.mint_authority,
+            amount,
+        )
+        .await;
+        fund(
+            &mut banks_client,
+            payer,
+            &recent_blockhash,
+            &self.escrow,
+            &self.launcher,
+            &self.launcher_token_account,
+            &self.escrow_token_account,
+            &amount,
+        )
+        .await;
+    }
+
     pub async fn complete_escrow(
         &self,
         mut banks_client: &mut BanksClient,
@@ -639,7 +1237,7 @@ impl EscrowAccount {
             payer,
             &recent_blockhash,
             &self.escrow,
-            &self.launcher,
+            &self.reputation_oracle,
         )
         .await;
     }
@@ -709,21 +1307,416 @@ async fn test_hmt_escrow_initialize() {
 }
 
 #[::tokio::test]
-async fn test_hmt_escrow_setup() {
+async fn test_hmt_escrow_factory_registry() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
     let escrow_account = EscrowAccount::new();
     escrow_account
         .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
         .await;
 
-    escrow_account
-        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
-        .await;
+    // Register second escrow in the same factory
+    let escrow = Keypair::new();
+    let escrow_token_account = Keypair::new();
+    let (escrow_authority, _) =
+        hmt_escrow::processor::Processor::find_authority_bump_seed(&id(), &escrow.pubkey());
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &escrow_authority,
+    )
+    .await;
 
-    let setup_check = |escrow: state::Escrow| {
-        assert_eq!(escrow.state, state::EscrowState::Pending);
-        assert_eq!(escrow.bump_seed, escrow_account.bump_seed);
-        assert_eq!(escrow.token_mint, escrow_account.token_mint.pubkey());
+    // Lamports sent to the next factory entry in advance don't block the registration
+    let (next_factory_entry, _) = processor::Processor::find_factory_entry_address(
+        &id(),
+        &escrow_account.factory.pubkey(),
+        1,
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &next_factory_entry,
+            1_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    create_escrow(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow,
+        &escrow_account.factory,
+        &escrow_token_account,
+        &escrow_account.launcher.pubkey(),
+        &escrow_account.canceler.pubkey(),
+        &escrow_account.canceler_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &escrow_account.duration,
+    )
+    .await;
+
+    let factory = get_factory(&mut banks_client, &escrow_account.factory.pubkey()).await;
+    assert_eq!(factory.escrow_count, 2);
+
+    for (index, expected_escrow) in [escrow_account.escrow.pubkey(), escrow.pubkey()]
+        .iter()
+        .enumerate()
+    {
+        let (factory_entry, _) = processor::Processor::find_factory_entry_address(
+            &id(),
+            &escrow_account.factory.pubkey(),
+            index as u64,
+        );
+        let factory_entry = banks_client
+            .get_account(factory_entry)
+            .await
+            .expect("get_account")
+            .expect("cannot read factory entry data");
+        let factory_entry = state::FactoryEntry::unpack(factory_entry.data.as_slice()).unwrap();
+        assert_eq!(factory_entry.escrow, *expected_escrow);
+    }
+
+    let escrow_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.factory, escrow_account.factory.pubkey());
+    };
+    check_escrow_account_info(escrow_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_factory_admin() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let factory_account = Keypair::new();
+    create_factory(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &factory_account,
+        DEFAULT_FACTORY_VERSION,
+    )
+    .await;
+
+    let factory = get_factory(&mut banks_client, &factory_account.pubkey()).await;
+    assert_eq!(factory.admin, payer.pubkey());
+    assert_eq!(factory.token_mint, COption::None);
+
+    // Factory can't be initialized without the administrator signature
+    let unsigned_factory = Keypair::new();
+    let admin = Keypair::new();
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut initialize = instruction::factory_initialize(
+        &id(),
+        &unsigned_factory.pubkey(),
+        &admin.pubkey(),
+        DEFAULT_FACTORY_VERSION,
+    )
+    .unwrap();
+    initialize.accounts[1].is_signer = false;
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &unsigned_factory.pubkey(),
+                rent.minimum_balance(state::Factory::LEN),
+                state::Factory::LEN as u64,
+                &id(),
+            ),
+            initialize,
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &unsigned_factory], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(1, InstructionError::MissingRequiredSignature)
+    );
+    assert_eq!(factory.max_duration, 0);
+    assert_eq!(factory.max_oracle_stake, STAKE_BASIS_POINTS);
+
+    // Bump factory version
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::factory_set_version(
+            &id(),
+            &factory_account.pubkey(),
+            &payer.pubkey(),
+            DEFAULT_FACTORY_VERSION + 1,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Transfer administration
+    let new_admin = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::factory_set_admin(
+            &id(),
+            &factory_account.pubkey(),
+            &payer.pubkey(),
+            &new_admin.pubkey(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let factory = get_factory(&mut banks_client, &factory_account.pubkey()).await;
+    assert_eq!(factory.version, DEFAULT_FACTORY_VERSION + 1);
+    assert_eq!(factory.admin, new_admin.pubkey());
+
+    // Former administrator is not allowed to change the factory anymore
+    let token_mint = Pubkey::new_unique();
+    assert!(set_factory_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &factory_account.pubkey(),
+        &payer,
+        COption::Some(token_mint),
+        1000,
+        5000,
+    )
+    .await
+    .is_err());
+
+    set_factory_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &factory_account.pubkey(),
+        &new_admin,
+        COption::Some(token_mint),
+        1000,
+        5000,
+    )
+    .await
+    .unwrap();
+
+    let factory = get_factory(&mut banks_client, &factory_account.pubkey()).await;
+    assert_eq!(factory.token_mint, COption::Some(token_mint));
+    assert_eq!(factory.max_duration, 1000);
+    assert_eq!(factory.max_oracle_stake, 5000);
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_factory_policy() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    set_factory_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        COption::Some(escrow_account.token_mint.pubkey()),
+        escrow_account.duration,
+        escrow_account.reputation_oracle_stake + escrow_account.recording_oracle_stake - 1,
+    )
+    .await
+    .unwrap();
+
+    // Escrow duration over the factory maximum is rejected
+    let escrow = Keypair::new();
+    let escrow_token_account = Keypair::new();
+    let (escrow_authority, _) =
+        hmt_escrow::processor::Processor::find_authority_bump_seed(&id(), &escrow.pubkey());
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &escrow_authority,
+    )
+    .await;
+    let factory = get_factory(&mut banks_client, &escrow_account.factory.pubkey()).await;
+    let (factory_entry, _) = processor::Processor::find_factory_entry_address(
+        &id(),
+        &escrow_account.factory.pubkey(),
+        factory.escrow_count,
+    );
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &escrow.pubkey(),
+                rent.minimum_balance(state::Escrow::LEN),
+                state::Escrow::LEN as u64,
+                &id(),
+            ),
+            instruction::initialize(
+                &id(),
+                &escrow.pubkey(),
+                &escrow_account.factory.pubkey(),
+                &escrow_account.token_mint.pubkey(),
+                &escrow_token_account.pubkey(),
+                &escrow_account.launcher.pubkey(),
+                &escrow_account.canceler.pubkey(),
+                &escrow_account.canceler_token_account.pubkey(),
+                &factory_entry,
+                &payer.pubkey(),
+                escrow_account.duration + 1,
+                None,
+            )
+            .unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &escrow], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // Oracle stakes over the factory maximum are rejected
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.token_mint.pubkey(),
+        &escrow_account.reputation_oracle.pubkey(),
+    )
+    .await;
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.recording_oracle_token,
+        &escrow_account.token_mint.pubkey(),
+        &escrow_account.recording_oracle.pubkey(),
+    )
+    .await;
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::setup(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.reputation_oracle_token.pubkey(),
+            escrow_account.reputation_oracle_stake,
+            &escrow_account.recording_oracle.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            escrow_account.recording_oracle_stake,
+            &escrow_account.manifest_url,
+            &escrow_account.manifest_hash,
+            escrow_account.defer_fees,
+            escrow_account.dispute_window,
+            0,
+            0,
+            &escrow_account.factory.pubkey(),
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &escrow_account.launcher], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_factory_pause() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    set_factory_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        true,
+    )
+    .await;
+
+    let factory = get_factory(&mut banks_client, &escrow_account.factory.pubkey()).await;
+    assert!(factory.paused);
+
+    // Results can't be stored while the factory is paused
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::store_results(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.final_results_url,
+            &escrow_account.final_results_hash,
+            &escrow_account.factory.pubkey(),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &escrow_account.launcher], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // Cancel is still allowed so funds can be returned
+    mint_to_escrow(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.token_mint,
+        &escrow_account.escrow_token_account,
+        &escrow_account.mint_authority,
+        100.0,
+    )
+    .await;
+    escrow_account
+        .cancel_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let cancel_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    };
+    check_escrow_account_info(cancel_check, &escrow_account, &mut banks_client).await;
+
+    set_factory_paused(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        false,
+    )
+    .await;
+
+    let factory = get_factory(&mut banks_client, &escrow_account.factory.pubkey()).await;
+    assert!(!factory.paused);
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_setup() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let setup_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Pending);
+        assert_eq!(escrow.bump_seed, escrow_account.bump_seed);
+        assert_eq!(escrow.token_mint, escrow_account.token_mint.pubkey());
         assert_eq!(
             escrow.token_account,
             escrow_account.escrow_token_account.pubkey()
@@ -793,144 +1786,522 @@ async fn test_hmt_escrow_store_results() {
 }
 
 #[::tokio::test]
-async fn test_hmt_escrow_store_amounts() {
+async fn test_hmt_escrow_role_permissions() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
     let escrow_account = EscrowAccount::new();
     escrow_account
         .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
         .await;
-
     escrow_account
         .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
         .await;
     escrow_account
-        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
         .await;
 
-    let store_check = |escrow: state::Escrow| {
-        assert_eq!(escrow.state, state::EscrowState::Pending);
-        assert_eq!(
-            escrow.total_amount,
-            spl_token::ui_amount_to_amount(escrow_account.total_amount, DECIMALS)
-        );
-        assert_eq!(escrow.total_recipients, escrow_account.total_recipients);
+    let store_results = |signer: &Keypair| {
+        instruction::store_results(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &signer.pubkey(),
+            &escrow_account.final_results_url,
+            &escrow_account.final_results_hash,
+            &escrow_account.factory.pubkey(),
+        )
+        .unwrap()
+    };
+    let store_amounts = |signer: &Keypair| {
+        instruction::store_amounts(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &signer.pubkey(),
+            spl_token::ui_amount_to_amount(escrow_account.total_amount, DECIMALS),
+            escrow_account.total_recipients,
+            &escrow_account.factory.pubkey(),
+        )
+        .unwrap()
     };
 
-    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
-}
-
-#[::tokio::test]
-async fn test_hmt_escrow_payout() {
-    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
-    let escrow_account = EscrowAccount::new();
-    escrow_account
-        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
-        .await;
+    // Recording oracle can't cancel escrow
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::cancel(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.recording_oracle.pubkey(),
+                &escrow_account.escrow_token_account.pubkey(),
+                &escrow_account.escrow_authority,
+                &escrow_account.canceler_token_account.pubkey(),
+                &spl_token::id(),
+                None,
+                None,
+            )
+            .unwrap(),
+            &escrow_account.recording_oracle,
+        )
+        .await,
+        EscrowError::UnauthorizedCancel,
+    );
 
-    let recipient = Keypair::new();
-    let recipient_token_account = Keypair::new();
+    // Canceler can't store results
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_results(&escrow_account.canceler),
+            &escrow_account.canceler,
+        )
+        .await,
+        EscrowError::UnauthorizedStoreResults,
+    );
 
-    create_token_account(
-        &mut banks_client,
+    // Launcher can't store final amounts
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_amounts(&escrow_account.launcher),
+            &escrow_account.launcher,
+        )
+        .await,
+        EscrowError::UnauthorizedStoreAmounts,
+    );
+
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        store_results(&escrow_account.recording_oracle),
+        &escrow_account.recording_oracle,
+    )
+    .await
+    .unwrap();
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        store_amounts(&escrow_account.reputation_oracle),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .unwrap();
+
+    // Launcher can't pay out
+    let recipient_token_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
         &payer,
         &recent_blockhash,
         &recipient_token_account,
         &escrow_account.token_mint.pubkey(),
-        &recipient.pubkey(),
+        &Keypair::new().pubkey(),
     )
     .await;
+    assert_escrow_error(
+        payout(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &escrow_account.escrow,
+            &escrow_account.launcher,
+            &escrow_account.escrow_token_account,
+            &escrow_account.escrow_authority,
+            &recipient_token_account,
+            &escrow_account.reputation_oracle_token,
+            &escrow_account.recording_oracle_token,
+            &escrow_account.payout_amount,
+            &escrow_account.factory.pubkey(),
+            None,
+        )
+        .await,
+        EscrowError::UnauthorizedPayout,
+    );
+
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Pending);
+        assert_eq!(escrow.final_results_url, escrow_account.final_results_url);
+        assert_eq!(escrow.total_recipients, escrow_account.total_recipients);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
 
+#[::tokio::test]
+async fn test_hmt_escrow_intermediate_results() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
     escrow_account
         .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
         .await;
+
+    // Recording oracle pays for the results log, lamports sent to the log
+    // address in advance don't block its creation
+    let (results_log, _) =
+        Processor::find_results_log_address(&id(), &escrow_account.escrow.pubkey());
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(
+                &payer.pubkey(),
+                &escrow_account.recording_oracle.pubkey(),
+                1_000_000_000,
+            ),
+            system_instruction::transfer(&payer.pubkey(), &results_log, 1_000_000),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let results_urls = ["http://result.com/1", "http://result.com/2"];
+    let results_hash = |algorithm: HashAlgorithm, index: usize| {
+        DataHash::new(algorithm, &vec![index as u8; algorithm.digest_len()]).unwrap()
+    };
+    let store_intermediate_results = |signer: &Keypair, index: usize, hash: DataHash| {
+        instruction::store_intermediate_results(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &signer.pubkey(),
+            &DataUrl::from_str(results_urls[index]).unwrap(),
+            &hash,
+            &escrow_account.factory.pubkey(),
+            &results_log,
+        )
+        .unwrap()
+    };
+
+    for index in 0..results_urls.len() {
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_intermediate_results(
+                &escrow_account.recording_oracle,
+                index,
+                results_hash(HashAlgorithm::Sha256, index),
+            ),
+            &escrow_account.recording_oracle,
+        )
+        .await
+        .unwrap();
+    }
+
+    // SHA-1 isn't accepted for the new results
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_intermediate_results(
+                &escrow_account.recording_oracle,
+                0,
+                results_hash(HashAlgorithm::Sha1, 0),
+            ),
+            &escrow_account.recording_oracle,
+        )
+        .await,
+        EscrowError::UnsupportedHashAlgorithm,
+    );
+
+    // Canceler can't store results
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_intermediate_results(
+                &escrow_account.canceler,
+                0,
+                results_hash(HashAlgorithm::Sha256, 0),
+            ),
+            &escrow_account.canceler,
+        )
+        .await,
+        EscrowError::UnauthorizedStoreResults,
+    );
+
+    let results_log = banks_client
+        .get_account(results_log)
+        .await
+        .expect("get_account")
+        .expect("results log not found");
+    assert_eq!(results_log.data.len(), state::ResultsLog::ACCOUNT_LEN);
+    let log = state::ResultsLog::unpack_from_slice(&results_log.data).unwrap();
+    assert_eq!(log.escrow, escrow_account.escrow.pubkey());
+    assert_eq!(log.count, results_urls.len() as u64);
+    let results = log.unpack_results(&results_log.data).unwrap();
+    for (index, result) in results.iter().enumerate() {
+        assert_eq!(result.url, DataUrl::from_str(results_urls[index]).unwrap());
+        assert_eq!(result.hash, results_hash(HashAlgorithm::Sha256, index));
+        assert_eq!(result.submitter, escrow_account.recording_oracle.pubkey());
+    }
+
+    // Log holds a limited number of records, earlier ones are never overwritten
+    for index in results_urls.len()..state::MAX_INTERMEDIATE_RESULTS {
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_intermediate_results(
+                &escrow_account.recording_oracle,
+                0,
+                results_hash(HashAlgorithm::Sha256, index),
+            ),
+            &escrow_account.recording_oracle,
+        )
+        .await
+        .unwrap();
+    }
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            store_intermediate_results(
+                &escrow_account.recording_oracle,
+                1,
+                results_hash(HashAlgorithm::Sha256, 0),
+            ),
+            &escrow_account.recording_oracle,
+        )
+        .await,
+        EscrowError::TooManyIntermediateResults,
+    );
+
+    // Final results are left untouched
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.final_results_url, DataUrl::default());
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_long_url() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
     escrow_account
-        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
         .await;
-    escrow_account.store_amounts(&mut banks_client, &payer, &recent_blockhash).await;
 
-    let escrow_token_for_payout = 5000.0;
-    let escrow_token_for_payout_to_mint =
-        spl_token::ui_amount_to_amount(escrow_token_for_payout, DECIMALS);
+    let results_url = format!("https://result.com/{}", "a".repeat(1000));
+    let url_data = Keypair::new();
+    let rent = banks_client.get_rent().await.unwrap();
+    let url_data_len = state::UrlData::LEN + results_url.len();
+    let (first_part, second_part) = results_url.as_bytes().split_at(500);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &url_data.pubkey(),
+                rent.minimum_balance(url_data_len),
+                url_data_len as u64,
+                &id(),
+            ),
+            instruction::write_url_data(
+                &id(),
+                &url_data.pubkey(),
+                &escrow_account.launcher.pubkey(),
+                0,
+                first_part,
+            )
+            .unwrap(),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &url_data, &escrow_account.launcher],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
 
-    mint_to_escrow(
+    // Only the authority set by the first write can continue
+    let write_second_part = |authority: &Keypair| {
+        instruction::write_url_data(
+            &id(),
+            &url_data.pubkey(),
+            &authority.pubkey(),
+            first_part.len() as u32,
+            second_part,
+        )
+        .unwrap()
+    };
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            write_second_part(&escrow_account.canceler),
+            &escrow_account.canceler,
+        )
+        .await,
+        EscrowError::UnauthorizedSigner,
+    );
+    process_signed(
         &mut banks_client,
         &payer,
         &recent_blockhash,
-        &escrow_account.token_mint,
-        &escrow_account.escrow_token_account,
-        &escrow_account.mint_authority,
-        escrow_token_for_payout,
+        write_second_part(&escrow_account.launcher),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+
+    let url_data_account = banks_client
+        .get_account(url_data.pubkey())
+        .await
+        .expect("get_account")
+        .expect("URL data not found");
+    let url_data_state =
+        state::UrlData::unpack(&url_data_account.data[..state::UrlData::LEN]).unwrap();
+    assert_eq!(url_data_state.authority, escrow_account.launcher.pubkey());
+    assert_eq!(
+        state::UrlData::unpack_url(&url_data_account.data).unwrap(),
+        results_url.as_bytes()
+    );
+
+    // Other program accounts can't be written as URL data
+    let (factory_entry, _) = processor::Processor::find_factory_entry_address(
+        &id(),
+        &escrow_account.factory.pubkey(),
+        0,
+    );
+    let result = process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::write_url_data(
+            &id(),
+            &factory_entry,
+            &escrow_account.launcher.pubkey(),
+            0,
+            &[0; 32],
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_eq!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountData)
+    );
+
+    store_results(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.launcher,
+        &DataUrl::new_from_account(&url_data.pubkey()),
+        &escrow_account.final_results_hash,
+        &escrow_account.factory.pubkey(),
     )
     .await;
 
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.final_results_url.account(), Some(url_data.pubkey()));
+        assert_eq!(escrow.manifest_url, escrow_account.manifest_url);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_fund() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
     escrow_account
-        .payout_escrow(
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let escrow_token_for_payout = 5000.0;
+    let escrow_token_for_payout_to_mint =
+        spl_token::ui_amount_to_amount(escrow_token_for_payout, DECIMALS);
+
+    escrow_account
+        .fund_escrow(
             &mut banks_client,
             &payer,
             &recent_blockhash,
-            &recipient_token_account,
+            escrow_token_for_payout,
         )
         .await;
 
-    let stake_percent: f64 =
-        (escrow_account.reputation_oracle_stake + escrow_account.recording_oracle_stake).into();
-    let payout_amount_to_mint =
-        spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS);
-    let result_amount =
-        escrow_account.payout_amount - (escrow_account.payout_amount * (stake_percent / 100.0));
-    let result_amount_to_mint = spl_token::ui_amount_to_amount(result_amount, DECIMALS);
-
-    let amount_check = |token_account: spl_token::state::Account| {
-        assert_eq!(token_account.amount, result_amount_to_mint)
+    let fund_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.funded_amount, escrow_token_for_payout_to_mint);
     };
+    check_escrow_account_info(fund_check, &escrow_account, &mut banks_client).await;
 
-    check_token_account_info(amount_check, &recipient_token_account, &mut banks_client).await;
-
-    let store_check = |token_account: spl_token::state::Account| {
-        assert_eq!(
-            token_account.amount,
-            escrow_token_for_payout_to_mint - payout_amount_to_mint
-        );
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, escrow_token_for_payout_to_mint);
     };
-
     check_token_account_info(
-        store_check,
+        amount_check,
         &escrow_account.escrow_token_account,
         &mut banks_client,
     )
     .await;
 
-    let reputation_oracle_stake: f64 = escrow_account.reputation_oracle_stake.into();
-    let result_amount = escrow_account.payout_amount * (reputation_oracle_stake / 100.0);
-    let reputation_oracle_payout = spl_token::ui_amount_to_amount(result_amount, DECIMALS);
-
     let amount_check = |token_account: spl_token::state::Account| {
-        assert_eq!(token_account.amount, reputation_oracle_payout);
+        assert_eq!(token_account.amount, 0);
     };
     check_token_account_info(
         amount_check,
-        &escrow_account.reputation_oracle_token,
+        &escrow_account.launcher_token_account,
         &mut banks_client,
     )
     .await;
+}
 
-    let recording_oracle_stake: f64 = escrow_account.recording_oracle_stake.into();
-    let result_amount = escrow_account.payout_amount * (recording_oracle_stake / 100.0);
-    let recording_oracle_payout = spl_token::ui_amount_to_amount(result_amount, DECIMALS);
+#[::tokio::test]
+async fn test_hmt_escrow_store_amounts() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
 
-    let amount_check = |token_account: spl_token::state::Account| {
-        assert_eq!(token_account.amount, recording_oracle_payout);
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Pending);
+        assert_eq!(
+            escrow.total_amount,
+            spl_token::ui_amount_to_amount(escrow_account.total_amount, DECIMALS)
+        );
+        assert_eq!(escrow.total_recipients, escrow_account.total_recipients);
     };
-    check_token_account_info(
-        amount_check,
-        &escrow_account.recording_oracle_token,
-        &mut banks_client,
-    )
-    .await;
+
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
 }
 
 #[::tokio::test]
-async fn test_hmt_escrow_cancel() {
+async fn test_hmt_escrow_payout() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
     let escrow_account = EscrowAccount::new();
     escrow_account
@@ -956,61 +2327,121 @@ async fn test_hmt_escrow_cancel() {
     escrow_account
         .store_results(&mut banks_client, &payer, &recent_blockhash)
         .await;
-    escrow_account.store_amounts(&mut banks_client, &payer, &recent_blockhash).await;
 
     let escrow_token_for_payout = 5000.0;
     let escrow_token_for_payout_to_mint =
         spl_token::ui_amount_to_amount(escrow_token_for_payout, DECIMALS);
 
-    mint_to_escrow(
-        &mut banks_client,
-        &payer,
-        &recent_blockhash,
-        &escrow_account.token_mint,
-        &escrow_account.escrow_token_account,
-        &escrow_account.mint_authority,
-        escrow_token_for_payout,
-    )
-    .await;
-
     escrow_account
-        .cancel_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_token_for_payout,
+        )
         .await;
+    escrow_account.store_amounts(&mut banks_client, &payer, &recent_blockhash).await;
 
-    let initialize_check = |escrow: state::Escrow| {
-        assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    escrow_account
+        .payout_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &recipient_token_account,
+        )
+        .await;
+
+    let stake_basis_points: f64 =
+        (escrow_account.reputation_oracle_stake + escrow_account.recording_oracle_stake).into();
+    let payout_amount_to_mint =
+        spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS);
+    let result_amount = escrow_account.payout_amount
+        - (escrow_account.payout_amount * (stake_basis_points / STAKE_BASIS_POINTS as f64));
+    let result_amount_to_mint = spl_token::ui_amount_to_amount(result_amount, DECIMALS);
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, result_amount_to_mint)
     };
-    check_escrow_account_info(initialize_check, &escrow_account, &mut banks_client).await;
+
+    check_token_account_info(amount_check, &recipient_token_account, &mut banks_client).await;
 
     let store_check = |token_account: spl_token::state::Account| {
-        assert_eq!(token_account.amount, escrow_token_for_payout_to_mint);
+        assert_eq!(
+            token_account.amount,
+            escrow_token_for_payout_to_mint - payout_amount_to_mint
+        );
     };
+
     check_token_account_info(
         store_check,
-        &escrow_account.canceler_token_account,
+        &escrow_account.escrow_token_account,
+        &mut banks_client,
+    )
+    .await;
+
+    let reputation_oracle_stake: f64 = escrow_account.reputation_oracle_stake.into();
+    let result_amount =
+        escrow_account.payout_amount * (reputation_oracle_stake / STAKE_BASIS_POINTS as f64);
+    let reputation_oracle_payout = spl_token::ui_amount_to_amount(result_amount, DECIMALS);
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, reputation_oracle_payout);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.reputation_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+
+    let recording_oracle_stake: f64 = escrow_account.recording_oracle_stake.into();
+    let result_amount =
+        escrow_account.payout_amount * (recording_oracle_stake / STAKE_BASIS_POINTS as f64);
+    let recording_oracle_payout = spl_token::ui_amount_to_amount(result_amount, DECIMALS);
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, recording_oracle_payout);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.recording_oracle_token,
         &mut banks_client,
     )
     .await;
 }
 
 #[::tokio::test]
-async fn test_hmt_escrow_complete() {
+async fn test_hmt_escrow_events() {
     let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
     let escrow_account = EscrowAccount::new();
     escrow_account
         .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
         .await;
 
-    let recipient = Keypair::new();
-    let recipient_token_account = Keypair::new();
+    // Factory policy restricting the token mint
+    let factory = escrow_account.factory.pubkey();
+    let token_mint = escrow_account.token_mint.pubkey();
+    set_factory_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &factory,
+        &payer,
+        COption::Some(token_mint),
+        0,
+        STAKE_BASIS_POINTS,
+    )
+    .await
+    .unwrap();
 
+    let recipient_token_account = Keypair::new();
     create_token_account(
         &mut banks_client,
         &payer,
         &recent_blockhash,
         &recipient_token_account,
-        &escrow_account.token_mint.pubkey(),
-        &recipient.pubkey(),
+        &token_mint,
+        &Keypair::new().pubkey(),
     )
     .await;
 
@@ -1020,36 +2451,3804 @@ async fn test_hmt_escrow_complete() {
     escrow_account
         .store_results(&mut banks_client, &payer, &recent_blockhash)
         .await;
-    escrow_account.store_amounts(&mut banks_client, &payer, &recent_blockhash).await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .payout_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &recipient_token_account,
+        )
+        .await;
 
-    let escrow_token_for_payout = 5000.0;
+    // Events decoded from the transaction logs match the factory and the token balances
+    let events = program_events();
+    assert!(events.contains(&EscrowEvent::FactoryPolicySet {
+        factory,
+        token_mint: COption::Some(token_mint),
+        max_duration: 0,
+        max_oracle_stake: STAKE_BASIS_POINTS,
+    }));
+    let escrow = escrow_account.escrow.pubkey();
+    let payouts: Vec<&EscrowEvent> = events
+        .iter()
+        .filter(|event| match event {
+            EscrowEvent::PayoutMade {
+                escrow: payout_escrow,
+                ..
+            } => *payout_escrow == escrow,
+            _ => false,
+        })
+        .collect();
+    let amount = get_token_balance(&mut banks_client, &recipient_token_account.pubkey()).await;
+    let reputation_oracle_fee = get_token_balance(
+        &mut banks_client,
+        &escrow_account.reputation_oracle_token.pubkey(),
+    )
+    .await;
+    let recording_oracle_fee = get_token_balance(
+        &mut banks_client,
+        &escrow_account.recording_oracle_token.pubkey(),
+    )
+    .await;
+    assert_eq!(
+        payouts,
+        vec![&EscrowEvent::PayoutMade {
+            escrow,
+            recipient: recipient_token_account.pubkey(),
+            amount,
+            reputation_oracle_fee,
+            recording_oracle_fee,
+        }]
+    );
+}
 
-    mint_to_escrow(
+#[::tokio::test]
+async fn test_hmt_escrow_payout_idempotency() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.total_recipients = 2;
+    escrow_account.payout_amount = escrow_account.total_amount / 2.0;
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_account = Keypair::new();
+    let other_recipient_token_account = Keypair::new();
+    for token_account in &[&recipient_token_account, &other_recipient_token_account] {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    // Reputation oracle pays for the payout receipt
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let payout_ids = instruction::payout_ids(&[(
+        recipient_token_account.pubkey(),
+        spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS),
+    )]);
+
+    // Lamports sent to the payout receipt in advance don't block the payout
+    let (payout_receipt, _) = Processor::find_payout_receipt_address(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &payout_ids[0],
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &payout_receipt,
+            1_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    escrow_account
+        .payout_escrow_with_id(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &recipient_token_account,
+            &payout_ids[0],
+        )
+        .await
+        .unwrap();
+
+    // Reusing the payout ID, even for a different recipient
+    assert!(escrow_account
+        .payout_escrow_with_id(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &other_recipient_token_account,
+            &payout_ids[0],
+        )
+        .await
+        .is_err());
+
+    let payout_receipt = banks_client
+        .get_account(payout_receipt)
+        .await
+        .expect("get_account")
+        .expect("payout receipt not found");
+    let payout_receipt = state::PayoutReceipt::unpack(&payout_receipt.data).unwrap();
+    assert_eq!(payout_receipt.escrow, escrow_account.escrow.pubkey());
+    assert_eq!(payout_receipt.recipient, recipient_token_account.pubkey());
+
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Partial);
+        assert_eq!(escrow.sent_recipients, 1);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_batch_payout() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.total_recipients = 2;
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_accounts = [Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let amounts = [20.0, escrow_account.total_amount - 20.0];
+    let payouts: Vec<(Pubkey, f64)> = recipient_token_accounts
+        .iter()
+        .zip(amounts.iter())
+        .map(|(recipient_token_account, amount)| (recipient_token_account.pubkey(), *amount))
+        .collect();
+    batch_payout(
         &mut banks_client,
         &payer,
         &recent_blockhash,
-        &escrow_account.token_mint,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
         &escrow_account.escrow_token_account,
-        &escrow_account.mint_authority,
-        escrow_token_for_payout,
+        &escrow_account.escrow_authority,
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+        &escrow_account.factory.pubkey(),
+        &payouts,
+    )
+    .await;
+
+    let fee = |amount: u64, stake: u16| amount * stake as u64 / STAKE_BASIS_POINTS as u64;
+    let mut reputation_oracle_fees = 0;
+    let mut recording_oracle_fees = 0;
+    for (recipient_token_account, amount) in recipient_token_accounts.iter().zip(amounts.iter()) {
+        let amount = spl_token::ui_amount_to_amount(*amount, DECIMALS);
+        let reputation_oracle_fee = fee(amount, escrow_account.reputation_oracle_stake);
+        let recording_oracle_fee = fee(amount, escrow_account.recording_oracle_stake);
+        reputation_oracle_fees += reputation_oracle_fee;
+        recording_oracle_fees += recording_oracle_fee;
+
+        let recipient_amount = amount - reputation_oracle_fee - recording_oracle_fee;
+        let amount_check = |token_account: spl_token::state::Account| {
+            assert_eq!(token_account.amount, recipient_amount)
+        };
+        check_token_account_info(amount_check, recipient_token_account, &mut banks_client).await;
+    }
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, reputation_oracle_fees);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.reputation_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, recording_oracle_fees);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.recording_oracle_token,
+        &mut banks_client,
     )
     .await;
 
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Paid);
+        assert_eq!(escrow.sent_amount, escrow.total_amount);
+        assert_eq!(escrow.sent_recipients, 2);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_fee_remainders() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
     escrow_account
-        .payout_escrow(
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_accounts = [Keypair::new(), Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
             &mut banks_client,
             &payer,
             &recent_blockhash,
-            &recipient_token_account,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
         )
         .await;
+    }
 
     escrow_account
-        .complete_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
         .await;
 
-    let initialize_check = |escrow: state::Escrow| {
-        assert_eq!(escrow.state, state::EscrowState::Complete);
-    };
-    check_escrow_account_info(initialize_check, &escrow_account, &mut banks_client).await;
+    // Payouts too small for the fees to be whole token units
+    let amount = 9;
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::store_amounts(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            amount * recipient_token_accounts.len() as u64,
+            recipient_token_accounts.len() as u64,
+            &escrow_account.factory.pubkey(),
+        )
+        .unwrap(),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .unwrap();
+
+    for recipient_token_account in recipient_token_accounts.iter() {
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::payout(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.reputation_oracle.pubkey(),
+                &escrow_account.escrow_token_account.pubkey(),
+                &escrow_account.escrow_authority,
+                &recipient_token_account.pubkey(),
+                &escrow_account.reputation_oracle_token.pubkey(),
+                &escrow_account.recording_oracle_token.pubkey(),
+                &spl_token::id(),
+                amount,
+                &escrow_account.factory.pubkey(),
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+            &escrow_account.reputation_oracle,
+        )
+        .await
+        .unwrap();
+
+        if recipient_token_account.pubkey() == recipient_token_accounts[0].pubkey() {
+            let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+            assert_eq!(escrow.reputation_oracle_fee_remainder, 9000);
+            assert_eq!(escrow.recording_oracle_fee_remainder, 3500);
+        }
+    }
+
+    // Fees add up to the stakes of the total amount, rounded up with the last payout
+    for (recipient_token_account, recipient_amount) in
+        recipient_token_accounts.iter().zip([8, 7, 4].iter())
+    {
+        let amount_check = |token_account: spl_token::state::Account| {
+            assert_eq!(token_account.amount, *recipient_amount)
+        };
+        check_token_account_info(amount_check, recipient_token_account, &mut banks_client).await;
+    }
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, 3);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.reputation_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, 5);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.recording_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Paid);
+        assert_eq!(escrow.reputation_oracle_fee_remainder, 0);
+        assert_eq!(escrow.recording_oracle_fee_remainder, 0);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_deferred_fees() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.defer_fees = true;
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_account = Keypair::new();
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &recipient_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &Keypair::new().pubkey(),
+    )
+    .await;
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .payout_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &recipient_token_account,
+        )
+        .await;
+
+    // Fees stay in the escrow token account
+    let reputation_oracle_fee = spl_token::ui_amount_to_amount(3.0, DECIMALS);
+    let recording_oracle_fee = spl_token::ui_amount_to_amount(4.5, DECIMALS);
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Paid);
+        assert_eq!(escrow.reputation_oracle_fee_accrued, reputation_oracle_fee);
+        assert_eq!(escrow.recording_oracle_fee_accrued, recording_oracle_fee);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, 0);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.reputation_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(
+            token_account.amount,
+            reputation_oracle_fee + recording_oracle_fee
+        );
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.escrow_token_account,
+        &mut banks_client,
+    )
+    .await;
+
+    // Escrow can't be completed until the fees are settled
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::complete(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.launcher.pubkey(),
+                None,
+            )
+            .unwrap(),
+            &escrow_account.launcher,
+        )
+        .await,
+        EscrowError::FeesNotSettled,
+    );
+
+    let settle_fees = instruction::settle_fees(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &escrow_account.recording_oracle.pubkey(),
+        &escrow_account.escrow_token_account.pubkey(),
+        &escrow_account.escrow_authority,
+        &escrow_account.reputation_oracle_token.pubkey(),
+        &escrow_account.recording_oracle_token.pubkey(),
+        &spl_token::id(),
+        &escrow_account.factory.pubkey(),
+    )
+    .unwrap();
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            settle_fees.clone(),
+            &escrow_account.canceler,
+        )
+        .await,
+        EscrowError::UnauthorizedSettleFees,
+    );
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        settle_fees,
+        &escrow_account.recording_oracle,
+    )
+    .await
+    .unwrap();
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, reputation_oracle_fee);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.reputation_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, recording_oracle_fee);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.recording_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+
+    escrow_account
+        .complete_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let complete_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Complete);
+        assert_eq!(escrow.reputation_oracle_fee_accrued, 0);
+        assert_eq!(escrow.recording_oracle_fee_accrued, 0);
+    };
+    check_escrow_account_info(complete_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_claim() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.total_recipients = 2;
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_accounts = [Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let amounts = [
+        spl_token::ui_amount_to_amount(20.0, DECIMALS),
+        spl_token::ui_amount_to_amount(escrow_account.total_amount - 20.0, DECIMALS),
+    ];
+    let leaves: Vec<MerkleHash> = recipient_token_accounts
+        .iter()
+        .zip(amounts.iter())
+        .enumerate()
+        .map(|(index, (recipient_token_account, amount))| {
+            merkle::leaf_hash(index as u64, &recipient_token_account.pubkey(), *amount)
+        })
+        .collect();
+    let payouts_root = merkle::merkle_root(&leaves).unwrap();
+
+    store_payouts_root(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
+        &escrow_account.factory.pubkey(),
+        &payouts_root,
+    )
+    .await;
+
+    // Proof of the other leaf doesn't work
+    assert!(claim(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account,
+        &payer,
+        &recipient_token_accounts[0].pubkey(),
+        0,
+        amounts[0],
+        &merkle::merkle_proof(&leaves, 1).unwrap(),
+    )
+    .await
+    .is_err());
+
+    let claimant = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &claimant.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Lamports sent to the claim receipt in advance don't block the claim
+    let (claim_receipt, _) =
+        processor::Processor::find_claim_receipt_address(&id(), &escrow_account.escrow.pubkey(), 0);
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &claim_receipt,
+            1_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Escrow authority doesn't sign for any other token program
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::claim(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &claimant.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &recipient_token_accounts[0].pubkey(),
+            &escrow_account.reputation_oracle_token.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            &id(),
+            &escrow_account.factory.pubkey(),
+            &claim_receipt,
+            0,
+            amounts[0],
+            &merkle::merkle_proof(&leaves, 0).unwrap(),
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &claimant], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    for (index, recipient_token_account) in recipient_token_accounts.iter().enumerate() {
+        claim(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &escrow_account,
+            &payer,
+            &recipient_token_account.pubkey(),
+            index as u64,
+            amounts[index],
+            &merkle::merkle_proof(&leaves, index).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        // Claiming the same leaf again with another claimant
+        assert!(claim(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &escrow_account,
+            &claimant,
+            &recipient_token_account.pubkey(),
+            index as u64,
+            amounts[index],
+            &merkle::merkle_proof(&leaves, index).unwrap(),
+        )
+        .await
+        .is_err());
+
+        let reputation_oracle_fee = amounts[index] * escrow_account.reputation_oracle_stake as u64
+            / STAKE_BASIS_POINTS as u64;
+        let recording_oracle_fee = amounts[index] * escrow_account.recording_oracle_stake as u64
+            / STAKE_BASIS_POINTS as u64;
+        let recipient_amount = amounts[index] - reputation_oracle_fee - recording_oracle_fee;
+        let amount_check = |token_account: spl_token::state::Account| {
+            assert_eq!(token_account.amount, recipient_amount)
+        };
+        check_token_account_info(amount_check, recipient_token_account, &mut banks_client).await;
+    }
+
+    let store_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Paid);
+        assert_eq!(escrow.payouts_root, payouts_root);
+        assert_eq!(escrow.sent_amount, escrow.total_amount);
+        assert_eq!(escrow.sent_recipients, 2);
+    };
+    check_escrow_account_info(store_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_cancel() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient = Keypair::new();
+    let recipient_token_account = Keypair::new();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &recipient_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &recipient.pubkey(),
+    )
+    .await;
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let escrow_token_for_payout = 5000.0;
+    let escrow_token_for_payout_to_mint =
+        spl_token::ui_amount_to_amount(escrow_token_for_payout, DECIMALS);
+
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_token_for_payout,
+        )
+        .await;
+    escrow_account.store_amounts(&mut banks_client, &payer, &recent_blockhash).await;
+
+    escrow_account
+        .cancel_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let initialize_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    };
+    check_escrow_account_info(initialize_check, &escrow_account, &mut banks_client).await;
+
+    let store_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, escrow_token_for_payout_to_mint);
+    };
+    check_token_account_info(
+        store_check,
+        &escrow_account.canceler_token_account,
+        &mut banks_client,
+    )
+    .await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_reclaim() {
+    let mut context = program_test().start_with_context().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.duration = 10;
+    escrow_account
+        .initialize_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+    escrow_account
+        .setup_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+
+    let escrow_token_for_payout = 5000.0;
+    let escrow_token_for_payout_to_mint =
+        spl_token::ui_amount_to_amount(escrow_token_for_payout, DECIMALS);
+
+    escrow_account
+        .fund_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+            escrow_token_for_payout,
+        )
+        .await;
+
+    // Funds can't be reclaimed before expiration
+    assert!(escrow_account
+        .reclaim_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await
+        .is_err());
+
+    // Move the clock past the escrow expiration
+    context.warp_to_slot(1_000).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    // Anyone can reclaim the funds of the expired escrow
+    let payer = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &context.payer.pubkey(),
+            &payer.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(&[&context.payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // Escrow authority doesn't sign for any other token program
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::reclaim(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &escrow_account.canceler_token_account.pubkey(),
+            &id(),
+            None,
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        context
+            .banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidInstructionData)
+    );
+
+    escrow_account
+        .reclaim_escrow(&mut context.banks_client, &payer, &recent_blockhash)
+        .await
+        .unwrap();
+
+    let reclaim_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    };
+    check_escrow_account_info(reclaim_check, &escrow_account, &mut context.banks_client).await;
+
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, escrow_token_for_payout_to_mint);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.canceler_token_account,
+        &mut context.banks_client,
+    )
+    .await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_extend_expiration() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    set_factory_policy(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        COption::None,
+        escrow_account.duration * 2,
+        STAKE_BASIS_POINTS,
+    )
+    .await
+    .unwrap();
+
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    let expires = escrow.expires;
+
+    extend_expiration(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.launcher,
+        &escrow_account.factory.pubkey(),
+        escrow_account.duration / 2,
+    )
+    .await
+    .unwrap();
+
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(
+        escrow.expires,
+        expires + (escrow_account.duration / 2) as i64
+    );
+
+    // Time left can't exceed the factory maximum duration
+    assert!(extend_expiration(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.canceler,
+        &escrow_account.factory.pubkey(),
+        escrow_account.duration,
+    )
+    .await
+    .is_err());
+
+    // Only launcher and canceler are allowed to extend the escrow
+    assert!(extend_expiration(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &payer,
+        &escrow_account.factory.pubkey(),
+        escrow_account.duration / 4,
+    )
+    .await
+    .is_err());
+
+    extend_expiration(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.canceler,
+        &escrow_account.factory.pubkey(),
+        escrow_account.duration / 4,
+    )
+    .await
+    .unwrap();
+
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(
+        escrow.expires,
+        expires + (escrow_account.duration / 2 + escrow_account.duration / 4) as i64
+    );
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_extend_expiration_lifetime() {
+    let mut context = program_test().start_with_context().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.duration = 1000;
+    escrow_account
+        .initialize_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &context.last_blockhash,
+        )
+        .await;
+
+    set_factory_policy(
+        &mut context.banks_client,
+        &context.payer,
+        &context.last_blockhash,
+        &escrow_account.factory.pubkey(),
+        &context.payer,
+        COption::None,
+        escrow_account.duration * 2,
+        STAKE_BASIS_POINTS,
+    )
+    .await
+    .unwrap();
+
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(
+        escrow.created_at,
+        escrow.expires - escrow_account.duration as i64
+    );
+
+    // Time passed since the creation counts towards the maximum duration
+    context.warp_to_slot(1_000).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+    extend_expiration(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.launcher,
+        &escrow_account.factory.pubkey(),
+        escrow_account.duration,
+    )
+    .await
+    .unwrap();
+
+    let result = extend_expiration(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.launcher,
+        &escrow_account.factory.pubkey(),
+        1,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::DurationOutOfBounds);
+
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(
+        escrow.expires,
+        escrow.created_at + escrow_account.duration as i64 * 2
+    );
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_trusted_handlers() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let handler = Keypair::new();
+    let cancel_transaction = |payer: &Keypair| {
+        let mut transaction = Transaction::new_with_payer(
+            &[instruction::cancel(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &handler.pubkey(),
+                &escrow_account.escrow_token_account.pubkey(),
+                &escrow_account.escrow_authority,
+                &escrow_account.canceler_token_account.pubkey(),
+                &spl_token::id(),
+                None,
+                None,
+            )
+            .unwrap()],
+            Some(&payer.pubkey()),
+        );
+        transaction.sign(&[payer, &handler], recent_blockhash);
+        transaction
+    };
+
+    escrow_account
+        .fund_escrow(&mut banks_client, &payer, &recent_blockhash, 1000.0)
+        .await;
+
+    // Unknown handler is not allowed to cancel the escrow
+    assert!(banks_client
+        .process_transaction(cancel_transaction(&payer))
+        .await
+        .is_err());
+
+    // Only launcher and canceler are allowed to change the handlers
+    assert!(add_trusted_handlers(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &handler,
+        &[handler.pubkey()],
+    )
+    .await
+    .is_err());
+
+    add_trusted_handlers(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.launcher,
+        &[handler.pubkey()],
+    )
+    .await
+    .unwrap();
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert!(escrow.trusted_handlers.contains(&handler.pubkey()));
+
+    // List is bounded
+    let handlers: Vec<Pubkey> = (0..state::MAX_TRUSTED_HANDLERS)
+        .map(|_| Pubkey::new_unique())
+        .collect();
+    assert!(add_trusted_handlers(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.canceler,
+        &handlers,
+    )
+    .await
+    .is_err());
+
+    remove_trusted_handlers(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.canceler,
+        &[handler.pubkey()],
+    )
+    .await
+    .unwrap();
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert!(!escrow.trusted_handlers.contains(&handler.pubkey()));
+
+    // Added back by the canceler this time
+    add_trusted_handlers(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.canceler,
+        &[handler.pubkey()],
+    )
+    .await
+    .unwrap();
+
+    // Another fee payer, so the transaction differs from the rejected one
+    let other_payer = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &other_payer.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Handlers are not allowed to cancel the escrow
+    let result = banks_client
+        .process_transaction(cancel_transaction(&other_payer))
+        .await;
+    assert_escrow_error(result, EscrowError::UnauthorizedCancel);
+
+    escrow_account
+        .cancel_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    // Handler closes the cancelled escrow on behalf of the launcher
+    let receiver = Pubkey::new_unique();
+    close(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &handler,
+        &escrow_account.escrow_token_account,
+        &escrow_account.escrow_authority,
+        &escrow_account.canceler_token_account,
+        &receiver,
+    )
+    .await;
+    assert!(banks_client.get_account(receiver).await.unwrap().is_some());
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_complete() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient = Keypair::new();
+    let recipient_token_account = Keypair::new();
+
+    create_token_account(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &recipient_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &recipient.pubkey(),
+    )
+    .await;
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let escrow_token_for_payout = 5000.0;
+
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_token_for_payout,
+        )
+        .await;
+    escrow_account.store_amounts(&mut banks_client, &payer, &recent_blockhash).await;
+
+    escrow_account
+        .payout_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &recipient_token_account,
+        )
+        .await;
+
+    escrow_account
+        .complete_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let initialize_check = |escrow: state::Escrow| {
+        assert_eq!(escrow.state, state::EscrowState::Complete);
+    };
+    check_escrow_account_info(initialize_check, &escrow_account, &mut banks_client).await;
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_close() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    mint_to_escrow(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.token_mint,
+        &escrow_account.escrow_token_account,
+        &escrow_account.mint_authority,
+        100.0,
+    )
+    .await;
+    escrow_account
+        .cancel_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let escrow_lamports = banks_client
+        .get_account(escrow_account.escrow.pubkey())
+        .await
+        .expect("get_account")
+        .expect("cannot read escrow account data")
+        .lamports;
+    let token_account_lamports = banks_client
+        .get_account(escrow_account.escrow_token_account.pubkey())
+        .await
+        .expect("get_account")
+        .expect("cannot read escrow token account data")
+        .lamports;
+
+    let receiver = Pubkey::new_unique();
+    escrow_account
+        .close_escrow(&mut banks_client, &payer, &recent_blockhash, &receiver)
+        .await;
+
+    // Both accounts are gone and their rent is reclaimed by the receiver
+    assert!(banks_client
+        .get_account(escrow_account.escrow.pubkey())
+        .await
+        .expect("get_account")
+        .is_none());
+    assert!(banks_client
+        .get_account(escrow_account.escrow_token_account.pubkey())
+        .await
+        .expect("get_account")
+        .is_none());
+    let receiver_lamports = banks_client
+        .get_account(receiver)
+        .await
+        .expect("get_account")
+        .expect("cannot read receiver account data")
+        .lamports;
+    assert_eq!(receiver_lamports, escrow_lamports + token_account_lamports);
+}
+
+/// Packs the escrow in the legacy layout of the escrows created before the
+/// version byte
+fn legacy_escrow_data(escrow: &state::Escrow) -> Vec<u8> {
+    fn push_coption_key(data: &mut Vec<u8>, key: &COption<Pubkey>) {
+        match key {
+            COption::Some(key) => {
+                data.extend_from_slice(&[1, 0, 0, 0]);
+                data.extend_from_slice(key.as_ref());
+            }
+            COption::None => data.extend_from_slice(&[0; 36]),
+        }
+    }
+    fn push_url(data: &mut Vec<u8>, url: &DataUrl) {
+        let mut url_data = [0; state::URL_LEN];
+        url_data[..url.as_ref().len()].copy_from_slice(url.as_ref());
+        data.extend_from_slice(&url_data);
+    }
+    fn push_hash(data: &mut Vec<u8>, hash: &DataHash) {
+        let mut digest = [0; 20];
+        digest[..hash.as_ref().len()].copy_from_slice(hash.as_ref());
+        data.extend_from_slice(&digest);
+    }
+
+    let mut data = vec![];
+    data.extend_from_slice(&escrow.expires.to_le_bytes());
+    data.push(escrow.bump_seed);
+    data.extend_from_slice(escrow.token_mint.as_ref());
+    data.extend_from_slice(escrow.token_account.as_ref());
+    push_coption_key(&mut data, &escrow.reputation_oracle);
+    push_coption_key(&mut data, &escrow.reputation_oracle_token_account);
+    data.push((escrow.reputation_oracle_stake / 100) as u8);
+    push_coption_key(&mut data, &escrow.recording_oracle);
+    push_coption_key(&mut data, &escrow.recording_oracle_token_account);
+    data.push((escrow.recording_oracle_stake / 100) as u8);
+    data.extend_from_slice(escrow.launcher.as_ref());
+    data.extend_from_slice(escrow.canceler.as_ref());
+    data.extend_from_slice(escrow.canceler_token_account.as_ref());
+    data.extend_from_slice(&escrow.total_amount.to_le_bytes());
+    data.extend_from_slice(&escrow.total_recipients.to_le_bytes());
+    data.extend_from_slice(&escrow.sent_amount.to_le_bytes());
+    data.extend_from_slice(&escrow.sent_recipients.to_le_bytes());
+    data.push(escrow.state as u8);
+    data.extend_from_slice(escrow.factory.as_ref());
+    push_url(&mut data, &escrow.manifest_url);
+    push_hash(&mut data, &escrow.manifest_hash);
+    push_url(&mut data, &escrow.final_results_url);
+    push_hash(&mut data, &escrow.final_results_hash);
+    assert_eq!(data.len(), state::Escrow::LEN_V0);
+    data
+}
+
+/// Legacy escrow launched and set up before the version byte, the escrow
+/// account is added at the start of the test and the token accounts are created
+/// with `create_legacy_escrow_accounts`
+fn legacy_escrow(escrow_account: &EscrowAccount) -> state::Escrow {
+    state::Escrow {
+        expires: i64::MAX / 2,
+        bump_seed: escrow_account.bump_seed,
+        token_mint: escrow_account.token_mint.pubkey(),
+        token_account: escrow_account.escrow_token_account.pubkey(),
+        reputation_oracle: COption::Some(escrow_account.reputation_oracle.pubkey()),
+        reputation_oracle_token_account: COption::Some(
+            escrow_account.reputation_oracle_token.pubkey(),
+        ),
+        reputation_oracle_stake: escrow_account.reputation_oracle_stake,
+        recording_oracle: COption::Some(escrow_account.recording_oracle.pubkey()),
+        recording_oracle_token_account: COption::Some(
+            escrow_account.recording_oracle_token.pubkey(),
+        ),
+        recording_oracle_stake: escrow_account.recording_oracle_stake,
+        launcher: escrow_account.launcher.pubkey(),
+        canceler: escrow_account.canceler.pubkey(),
+        canceler_token_account: escrow_account.canceler_token_account.pubkey(),
+        state: state::EscrowState::Pending,
+        factory: escrow_account.factory.pubkey(),
+        manifest_url: escrow_account.manifest_url,
+        manifest_hash: DataHash::new(HashAlgorithm::Sha1, &[1; 20]).unwrap(),
+        ..Default::default()
+    }
+}
+
+async fn create_legacy_escrow_accounts(
+    mut banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &EscrowAccount,
+    amount: f64,
+) {
+    create_mint(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.token_mint,
+        &escrow_account.mint_authority.pubkey(),
+    )
+    .await;
+    for (token_account, owner) in &[
+        (
+            &escrow_account.escrow_token_account,
+            escrow_account.escrow_authority,
+        ),
+        (
+            &escrow_account.canceler_token_account,
+            escrow_account.canceler.pubkey(),
+        ),
+        (
+            &escrow_account.reputation_oracle_token,
+            escrow_account.reputation_oracle.pubkey(),
+        ),
+        (
+            &escrow_account.recording_oracle_token,
+            escrow_account.recording_oracle.pubkey(),
+        ),
+    ] {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            token_account,
+            &escrow_account.token_mint.pubkey(),
+            owner,
+        )
+        .await;
+    }
+    create_factory(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory,
+        DEFAULT_FACTORY_VERSION,
+    )
+    .await;
+
+    // Legacy escrows were funded with the plain token transfers
+    mint_to_escrow(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.token_mint,
+        &escrow_account.escrow_token_account,
+        &escrow_account.mint_authority,
+        amount,
+    )
+    .await;
+
+    // Launcher pays for the factory entry of the migrated escrow
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+}
+
+async fn migrate_legacy_escrow(
+    banks_client: &mut BanksClient,
+    payer: &Keypair,
+    recent_blockhash: &Hash,
+    escrow_account: &EscrowAccount,
+    new_escrow: &Keypair,
+    launcher: &Keypair,
+) -> Result<(), TransportError> {
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::create_account(
+            &payer.pubkey(),
+            &new_escrow.pubkey(),
+            rent.minimum_balance(state::Escrow::LEN),
+            state::Escrow::LEN as u64,
+            &id(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[payer, new_escrow], *recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let factory = get_factory(banks_client, &escrow_account.factory.pubkey()).await;
+    let (factory_entry, _) = Processor::find_factory_entry_address(
+        &id(),
+        &escrow_account.factory.pubkey(),
+        factory.escrow_count,
+    );
+    process_signed(
+        banks_client,
+        payer,
+        recent_blockhash,
+        instruction::migrate_legacy(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &new_escrow.pubkey(),
+            &launcher.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &launcher.pubkey(),
+            &escrow_account.factory.pubkey(),
+            &factory_entry,
+        )
+        .unwrap(),
+        launcher,
+    )
+    .await
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_migrate() {
+    // Legacy escrow, stored without the layout version byte
+    let escrow_account = EscrowAccount::new();
+    let legacy_escrow = legacy_escrow(&escrow_account);
+
+    // Escrow of the first versioned layout, without the fee remainders
+    let versioned_escrow = Pubkey::new_unique();
+    let mut versioned_escrow_data = vec![0; state::Escrow::LEN];
+    state::Escrow::pack(legacy_escrow, &mut versioned_escrow_data).unwrap();
+    versioned_escrow_data[0] = 1;
+
+    let mut program_test = program_test();
+    program_test.add_account(
+        escrow_account.escrow.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            data: legacy_escrow_data(&legacy_escrow),
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    program_test.add_account(
+        versioned_escrow,
+        Account {
+            lamports: 1_000_000_000,
+            data: versioned_escrow_data,
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_legacy_escrow_accounts(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account,
+        escrow_account.total_amount,
+    )
+    .await;
+
+    // Legacy escrow is readable in its own layout
+    assert_eq!(
+        get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await,
+        legacy_escrow
+    );
+
+    // But must be migrated before any change
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::extend_expiration(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.launcher.pubkey(),
+                &escrow_account.factory.pubkey(),
+                1000,
+            )
+            .unwrap(),
+            &escrow_account.launcher,
+        )
+        .await,
+        EscrowError::MigrationRequired,
+    );
+
+    // Only the launcher can migrate the legacy escrow
+    assert_escrow_error(
+        migrate_legacy_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &escrow_account,
+            &Keypair::new(),
+            &escrow_account.canceler,
+        )
+        .await,
+        EscrowError::UnauthorizedSigner,
+    );
+
+    // Legacy escrow is copied into the new account of the latest layout
+    let legacy_lamports = banks_client
+        .get_balance(escrow_account.escrow.pubkey())
+        .await
+        .unwrap();
+    let launcher_lamports = banks_client
+        .get_balance(escrow_account.launcher.pubkey())
+        .await
+        .unwrap();
+    let new_escrow = Keypair::new();
+    migrate_legacy_escrow(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account,
+        &new_escrow,
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    let new_escrow_data = banks_client
+        .get_account(new_escrow.pubkey())
+        .await
+        .expect("get_account")
+        .expect("cannot read escrow account data")
+        .data;
+    assert_eq!(
+        state::Escrow::unpack_version(&new_escrow_data).unwrap(),
+        state::ESCROW_VERSION
+    );
+    let (new_authority, new_bump_seed) =
+        Processor::find_authority_bump_seed(&id(), &new_escrow.pubkey());
+    assert_eq!(
+        state::Escrow::unpack(&new_escrow_data).unwrap(),
+        state::Escrow {
+            bump_seed: new_bump_seed,
+            funded_amount: spl_token::ui_amount_to_amount(escrow_account.total_amount, DECIMALS),
+            ..legacy_escrow
+        }
+    );
+
+    // Token account belongs to the new escrow and the legacy account is closed
+    let token_account = banks_client
+        .get_account(escrow_account.escrow_token_account.pubkey())
+        .await
+        .expect("get_account")
+        .expect("cannot read token account data");
+    assert_eq!(
+        spl_token::state::Account::unpack(&token_account.data)
+            .unwrap()
+            .owner,
+        new_authority
+    );
+    assert!(banks_client
+        .get_account(escrow_account.escrow.pubkey())
+        .await
+        .expect("get_account")
+        .is_none());
+    let rent = banks_client.get_rent().await.unwrap();
+    assert_eq!(
+        banks_client
+            .get_balance(escrow_account.launcher.pubkey())
+            .await
+            .unwrap(),
+        launcher_lamports + legacy_lamports - rent.minimum_balance(state::FactoryEntry::LEN)
+    );
+
+    // Migrated escrow is registered in its factory like the new ones
+    let factory = get_factory(&mut banks_client, &escrow_account.factory.pubkey()).await;
+    assert_eq!(factory.escrow_count, 1);
+    let (factory_entry, _) =
+        Processor::find_factory_entry_address(&id(), &escrow_account.factory.pubkey(), 0);
+    let factory_entry = banks_client
+        .get_account(factory_entry)
+        .await
+        .expect("get_account")
+        .expect("cannot read factory entry data");
+    assert_eq!(
+        state::FactoryEntry::unpack(factory_entry.data.as_slice())
+            .unwrap()
+            .escrow,
+        new_escrow.pubkey()
+    );
+
+    // Migrated escrow can be changed
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::extend_expiration(
+            &id(),
+            &new_escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.factory.pubkey(),
+            1000,
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_escrow(&mut banks_client, &new_escrow.pubkey())
+            .await
+            .expires,
+        legacy_escrow.expires + 1000
+    );
+
+    // Older versioned escrows are rewritten in place
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::migrate(&id(), &versioned_escrow).unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    let versioned_escrow_data = banks_client
+        .get_account(versioned_escrow)
+        .await
+        .expect("get_account")
+        .expect("cannot read escrow account data")
+        .data;
+    assert_eq!(
+        state::Escrow::unpack_version(&versioned_escrow_data).unwrap(),
+        state::ESCROW_VERSION
+    );
+    assert_eq!(
+        state::Escrow::unpack(&versioned_escrow_data).unwrap(),
+        legacy_escrow
+    );
+
+    // New escrows are created in the latest layout
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    let escrow_data = banks_client
+        .get_account(escrow_account.escrow.pubkey())
+        .await
+        .expect("get_account")
+        .expect("cannot read escrow account data")
+        .data;
+    assert_eq!(
+        state::Escrow::unpack_version(&escrow_data).unwrap(),
+        state::ESCROW_VERSION
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::migrate(&id(), &escrow_account.escrow.pubkey()).unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_escrow_error(
+        banks_client.process_transaction(transaction).await,
+        EscrowError::AlreadyMigrated,
+    );
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_legacy_fee_remainders() {
+    let escrow_account = EscrowAccount::new();
+    let legacy_escrow = legacy_escrow(&escrow_account);
+    let mut program_test = program_test();
+    program_test.add_account(
+        escrow_account.escrow.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            data: legacy_escrow_data(&legacy_escrow),
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    create_legacy_escrow_accounts(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account,
+        escrow_account.total_amount,
+    )
+    .await;
+
+    let recipient_token_accounts = [Keypair::new(), Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    // Legacy layout can't keep the fee remainders, nothing is paid out before the migration
+    let amount = 9;
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::payout(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.reputation_oracle.pubkey(),
+                &escrow_account.escrow_token_account.pubkey(),
+                &escrow_account.escrow_authority,
+                &recipient_token_accounts[0].pubkey(),
+                &escrow_account.reputation_oracle_token.pubkey(),
+                &escrow_account.recording_oracle_token.pubkey(),
+                &spl_token::id(),
+                amount,
+                &escrow_account.factory.pubkey(),
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::MigrationRequired,
+    );
+
+    let escrow = Keypair::new();
+    migrate_legacy_escrow(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account,
+        &escrow,
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    let (escrow_authority, _) = Processor::find_authority_bump_seed(&id(), &escrow.pubkey());
+
+    store_results(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow,
+        &escrow_account.launcher,
+        &escrow_account.final_results_url,
+        &escrow_account.final_results_hash,
+        &escrow_account.factory.pubkey(),
+    )
+    .await;
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::store_amounts(
+            &id(),
+            &escrow.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            amount * recipient_token_accounts.len() as u64,
+            recipient_token_accounts.len() as u64,
+            &escrow_account.factory.pubkey(),
+        )
+        .unwrap(),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .unwrap();
+
+    // Payouts too small for the fees to be whole token units
+    for recipient_token_account in recipient_token_accounts.iter() {
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::payout(
+                &id(),
+                &escrow.pubkey(),
+                &escrow_account.reputation_oracle.pubkey(),
+                &escrow_account.escrow_token_account.pubkey(),
+                &escrow_authority,
+                &recipient_token_account.pubkey(),
+                &escrow_account.reputation_oracle_token.pubkey(),
+                &escrow_account.recording_oracle_token.pubkey(),
+                &spl_token::id(),
+                amount,
+                &escrow_account.factory.pubkey(),
+                None,
+                None,
+                None,
+            )
+            .unwrap(),
+            &escrow_account.reputation_oracle,
+        )
+        .await
+        .unwrap();
+    }
+
+    // Legacy stakes in percent reconcile exactly with the migrated escrow
+    let mut paid = 0;
+    for (recipient_token_account, recipient_amount) in
+        recipient_token_accounts.iter().zip([8, 7, 4].iter())
+    {
+        let balance = get_token_balance(&mut banks_client, &recipient_token_account.pubkey()).await;
+        assert_eq!(balance, *recipient_amount);
+        paid += balance;
+    }
+    for (oracle_token_account, oracle_amount) in [
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+    ]
+    .iter()
+    .zip([3, 5].iter())
+    {
+        let balance = get_token_balance(&mut banks_client, &oracle_token_account.pubkey()).await;
+        assert_eq!(balance, *oracle_amount);
+        paid += balance;
+    }
+    assert_eq!(paid, amount * recipient_token_accounts.len() as u64);
+
+    let escrow = get_escrow(&mut banks_client, &escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Paid);
+    assert_eq!(escrow.sent_amount, paid);
+    assert_eq!(escrow.reputation_oracle_fee_remainder, 0);
+    assert_eq!(escrow.recording_oracle_fee_remainder, 0);
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_native() {
+    let mut program_test = program_test();
+    let mut native_mint_data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint {
+        decimals: DECIMALS,
+        is_initialized: true,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut native_mint_data);
+    program_test.add_account(
+        spl_token::native_mint::id(),
+        Account {
+            lamports: 1_000_000_000,
+            data: native_mint_data,
+            owner: spl_token::id(),
+            ..Default::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let escrow_account = EscrowAccount::new();
+    let native_mint = spl_token::native_mint::id();
+    let (wrap_account, _) = Processor::find_wrap_address(&id(), &escrow_account.escrow.pubkey());
+    create_factory(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory,
+        DEFAULT_FACTORY_VERSION,
+    )
+    .await;
+    for (token_account, owner) in &[
+        (
+            &escrow_account.escrow_token_account,
+            escrow_account.escrow_authority,
+        ),
+        (
+            &escrow_account.reputation_oracle_token,
+            escrow_account.reputation_oracle.pubkey(),
+        ),
+        (
+            &escrow_account.recording_oracle_token,
+            escrow_account.recording_oracle.pubkey(),
+        ),
+    ] {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            token_account,
+            &native_mint,
+            owner,
+        )
+        .await;
+    }
+
+    // Canceler receives SOL to its wallet
+    let canceler = escrow_account.canceler.pubkey();
+    let rent = banks_client.get_rent().await.unwrap();
+    let (factory_entry, _) =
+        Processor::find_factory_entry_address(&id(), &escrow_account.factory.pubkey(), 0);
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::create_account(
+                &payer.pubkey(),
+                &escrow_account.escrow.pubkey(),
+                rent.minimum_balance(state::Escrow::LEN),
+                state::Escrow::LEN as u64,
+                &id(),
+            ),
+            instruction::initialize(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.factory.pubkey(),
+                &native_mint,
+                &escrow_account.escrow_token_account.pubkey(),
+                &escrow_account.launcher.pubkey(),
+                &canceler,
+                &canceler,
+                &factory_entry,
+                &payer.pubkey(),
+                escrow_account.duration,
+                Some(&escrow_account.escrow_authority),
+            )
+            .unwrap(),
+            system_instruction::transfer(
+                &payer.pubkey(),
+                &escrow_account.launcher.pubkey(),
+                spl_token::ui_amount_to_amount(100.0, DECIMALS),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &escrow_account.escrow], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let reserve = rent.minimum_balance(spl_token::state::Account::LEN);
+    assert_eq!(
+        banks_client
+            .get_balance(escrow_account.escrow_authority)
+            .await
+            .unwrap(),
+        reserve
+    );
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    // Launcher SOL is wrapped into the escrow token account
+    let amount = spl_token::ui_amount_to_amount(escrow_account.total_amount, DECIMALS);
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::fund_native(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &spl_token::id(),
+            &wrap_account,
+            amount,
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        banks_client
+            .get_balance(escrow_account.launcher.pubkey())
+            .await
+            .unwrap(),
+        spl_token::ui_amount_to_amount(70.0, DECIMALS)
+    );
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(token_account.amount, amount);
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.escrow_token_account,
+        &mut banks_client,
+    )
+    .await;
+
+    store_final_amounts(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
+        &escrow_account.total_amount,
+        &2,
+        &escrow_account.factory.pubkey(),
+    )
+    .await;
+
+    // Payout to the wallet is unwrapped, oracle fees stay wrapped
+    let recipient = Keypair::new().pubkey();
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::payout(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &recipient,
+            &escrow_account.reputation_oracle_token.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            &spl_token::id(),
+            spl_token::ui_amount_to_amount(10.0, DECIMALS),
+            &escrow_account.factory.pubkey(),
+            None,
+            Some(&wrap_account),
+            None,
+        )
+        .unwrap(),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        banks_client.get_balance(recipient).await.unwrap(),
+        spl_token::ui_amount_to_amount(7.5, DECIMALS)
+    );
+    let amount_check = |token_account: spl_token::state::Account| {
+        assert_eq!(
+            token_account.amount,
+            spl_token::ui_amount_to_amount(1.0, DECIMALS)
+        );
+    };
+    check_token_account_info(
+        amount_check,
+        &escrow_account.reputation_oracle_token,
+        &mut banks_client,
+    )
+    .await;
+    assert_eq!(
+        banks_client
+            .get_balance(escrow_account.escrow_authority)
+            .await
+            .unwrap(),
+        reserve
+    );
+
+    // The rest goes to the canceler wallet
+    let cancel = instruction::cancel(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &escrow_account.launcher.pubkey(),
+        &escrow_account.escrow_token_account.pubkey(),
+        &escrow_account.escrow_authority,
+        &canceler,
+        &spl_token::id(),
+        Some(&wrap_account),
+        None,
+    )
+    .unwrap();
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        cancel,
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        banks_client.get_balance(canceler).await.unwrap(),
+        spl_token::ui_amount_to_amount(20.0, DECIMALS)
+    );
+
+    // Closing returns the authority reserve
+    let receiver = Keypair::new().pubkey();
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::close(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &canceler,
+            &receiver,
+            &spl_token::id(),
+            Some(&wrap_account),
+            None,
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        banks_client
+            .get_balance(escrow_account.escrow_authority)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        banks_client.get_balance(receiver).await.unwrap(),
+        reserve
+            + rent.minimum_balance(state::Escrow::LEN)
+            + rent.minimum_balance(spl_token::state::Account::LEN)
+    );
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_dispute() {
+    let mut context = program_test().start_with_context().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.dispute_window = 1000;
+    escrow_account.total_recipients = 2;
+    escrow_account.payout_amount = escrow_account.total_amount / 2.0;
+    let recent_blockhash = context.last_blockhash;
+    escrow_account
+        .initialize_escrow(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_account = Keypair::new();
+    let other_recipient_token_account = Keypair::new();
+    for token_account in &[&recipient_token_account, &other_recipient_token_account] {
+        create_token_account(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+
+    // Payouts wait for the end of the dispute window
+    let result = payout(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
+        &escrow_account.escrow_token_account,
+        &escrow_account.escrow_authority,
+        &recipient_token_account,
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+        &escrow_account.payout_amount,
+        &escrow_account.factory.pubkey(),
+        None,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::DisputeWindowOpen);
+
+    // Only launcher can dispute the results
+    let dispute = instruction::dispute(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &escrow_account.reputation_oracle.pubkey(),
+    )
+    .unwrap();
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        dispute,
+        &escrow_account.reputation_oracle,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::UnauthorizedDispute);
+
+    let dispute = instruction::dispute(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &escrow_account.launcher.pubkey(),
+    )
+    .unwrap();
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        dispute.clone(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Disputed);
+
+    // Disputed escrow can't be paid out
+    let result = payout(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
+        &escrow_account.escrow_token_account,
+        &escrow_account.escrow_authority,
+        &other_recipient_token_account,
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+        &escrow_account.payout_amount,
+        &escrow_account.factory.pubkey(),
+        None,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::WrongState);
+
+    // Only factory administrator can resolve the dispute
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        instruction::resolve_dispute(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.factory.pubkey(),
+            false,
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::UnauthorizedSigner);
+
+    // Rejected results are dropped
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::resolve_dispute(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &context.payer.pubkey(),
+            &escrow_account.factory.pubkey(),
+            false,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(&[&context.payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Pending);
+    assert_eq!(escrow.final_results_hash, DataHash::default());
+    assert_eq!(escrow.results_stored_at, 0);
+
+    // Payouts wait for the new results
+    let result = payout(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
+        &escrow_account.escrow_token_account,
+        &escrow_account.escrow_authority,
+        &recipient_token_account,
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+        &1.0,
+        &escrow_account.factory.pubkey(),
+        None,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::DisputeWindowOpen);
+
+    // New results are disputed again and accepted this time
+    context.warp_to_slot(100).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+    escrow_account
+        .store_results(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        dispute.clone(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::resolve_dispute(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &context.payer.pubkey(),
+            &escrow_account.factory.pubkey(),
+            true,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(&[&context.payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Pending);
+    assert_eq!(escrow.final_results_hash, escrow_account.final_results_hash);
+
+    // Accepted results are paid out right away
+    escrow_account
+        .payout_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            &recipient_token_account,
+        )
+        .await;
+
+    // Results stored by the recording oracle restart the dispute window
+    let final_results_hash = DataHash::new(HashAlgorithm::Sha256, &[8; 32]).unwrap();
+    store_results(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.recording_oracle,
+        &escrow_account.final_results_url,
+        &final_results_hash,
+        &escrow_account.factory.pubkey(),
+    )
+    .await;
+    let result = payout(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.reputation_oracle,
+        &escrow_account.escrow_token_account,
+        &escrow_account.escrow_authority,
+        &other_recipient_token_account,
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+        &escrow_account.payout_amount,
+        &escrow_account.factory.pubkey(),
+        None,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::DisputeWindowOpen);
+
+    // Results can't be disputed after the dispute window
+    context.warp_to_slot(5_000).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        dispute,
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::DisputeWindowClosed);
+
+    escrow_account
+        .payout_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            &other_recipient_token_account,
+        )
+        .await;
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Paid);
+}
+
+async fn get_token_balance(banks_client: &mut BanksClient, token_account: &Pubkey) -> u64 {
+    let account = banks_client
+        .get_account(*token_account)
+        .await
+        .expect("get_account")
+        .expect("cannot read token account data");
+    spl_token::state::Account::unpack_from_slice(account.data.as_slice())
+        .unwrap()
+        .amount
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_bonds() {
+    let mut context = program_test().start_with_context().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.dispute_window = 1000;
+    let recent_blockhash = context.last_blockhash;
+    escrow_account
+        .initialize_escrow(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+
+    let (bond_vault, _) =
+        Processor::find_bond_vault_address(&id(), &escrow_account.escrow.pubkey());
+    let reputation_oracle_bond = spl_token::ui_amount_to_amount(2.0, DECIMALS);
+    let recording_oracle_bond = spl_token::ui_amount_to_amount(3.0, DECIMALS);
+    for (token_account, oracle) in &[
+        (
+            &escrow_account.reputation_oracle_token,
+            &escrow_account.reputation_oracle,
+        ),
+        (
+            &escrow_account.recording_oracle_token,
+            &escrow_account.recording_oracle,
+        ),
+    ] {
+        create_token_account(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            token_account,
+            &escrow_account.token_mint.pubkey(),
+            &oracle.pubkey(),
+        )
+        .await;
+        mint_to_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            &escrow_account.token_mint,
+            token_account,
+            &escrow_account.mint_authority,
+            10.0,
+        )
+        .await;
+    }
+
+    // Oracles sign the setup locking their bonds
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::setup(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.reputation_oracle_token.pubkey(),
+            escrow_account.reputation_oracle_stake,
+            &escrow_account.recording_oracle.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            escrow_account.recording_oracle_stake,
+            &escrow_account.manifest_url,
+            &escrow_account.manifest_hash,
+            escrow_account.defer_fees,
+            escrow_account.dispute_window,
+            reputation_oracle_bond,
+            recording_oracle_bond,
+            &escrow_account.factory.pubkey(),
+            Some((&bond_vault, &escrow_account.token_mint.pubkey())),
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &context.payer,
+            &escrow_account.launcher,
+            &escrow_account.reputation_oracle,
+            &escrow_account.recording_oracle,
+        ],
+        recent_blockhash,
+    );
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert!(escrow.bonded);
+    assert_eq!(escrow.reputation_oracle_bond, reputation_oracle_bond);
+    assert_eq!(escrow.recording_oracle_bond, recording_oracle_bond);
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bond_vault).await,
+        reputation_oracle_bond + recording_oracle_bond
+    );
+
+    escrow_account
+        .fund_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+
+    // Bonds can't be slashed before the results are rejected
+    let slash =
+        |reputation_oracle_amount: u64, recording_oracle_amount: u64, launcher: &Keypair| {
+            instruction::slash_bonds(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &launcher.pubkey(),
+                &bond_vault,
+                &escrow_account.launcher_token_account.pubkey(),
+                &spl_token::id(),
+                reputation_oracle_amount,
+                recording_oracle_amount,
+            )
+            .unwrap()
+        };
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        slash(0, recording_oracle_bond, &escrow_account.launcher),
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::SlashNotAllowed);
+
+    // Results are disputed and rejected by the factory administrator
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        instruction::dispute(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::resolve_dispute(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &context.payer.pubkey(),
+            &escrow_account.factory.pubkey(),
+            false,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(&[&context.payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert!(escrow.results_rejected);
+
+    // Only launcher can slash, and not more than the bond
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        slash(0, recording_oracle_bond, &escrow_account.reputation_oracle),
+        &escrow_account.reputation_oracle,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::UnauthorizedSlash);
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        slash(0, recording_oracle_bond + 1, &escrow_account.launcher),
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::NotEnoughBalance);
+
+    let launcher_balance = get_token_balance(
+        &mut context.banks_client,
+        &escrow_account.launcher_token_account.pubkey(),
+    )
+    .await;
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        slash(0, recording_oracle_bond, &escrow_account.launcher),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &escrow_account.launcher_token_account.pubkey()
+        )
+        .await,
+        launcher_balance + recording_oracle_bond
+    );
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.reputation_oracle_bond, reputation_oracle_bond);
+    assert_eq!(escrow.recording_oracle_bond, 0);
+    assert!(!escrow.results_rejected);
+
+    // Rejection allows a single slash
+    let result = process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        slash(1, 0, &escrow_account.launcher),
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::SlashNotAllowed);
+
+    // New results are paid out after the dispute window
+    context.warp_to_slot(100).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+    escrow_account
+        .store_results(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    context.warp_to_slot(5_000).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+    let recipient_token_account = Keypair::new();
+    create_token_account(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        &recipient_token_account,
+        &escrow_account.token_mint.pubkey(),
+        &Keypair::new().pubkey(),
+    )
+    .await;
+    escrow_account
+        .payout_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            &recipient_token_account,
+        )
+        .await;
+
+    // Complete returns the rest of the bonds to the oracles
+    let bond_accounts = (
+        &bond_vault,
+        &escrow_account.reputation_oracle_token.pubkey(),
+        &escrow_account.recording_oracle_token.pubkey(),
+    );
+    let reputation_oracle_balance = get_token_balance(
+        &mut context.banks_client,
+        &escrow_account.reputation_oracle_token.pubkey(),
+    )
+    .await;
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        instruction::complete(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            Some(bond_accounts),
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &escrow_account.reputation_oracle_token.pubkey()
+        )
+        .await,
+        reputation_oracle_balance + reputation_oracle_bond
+    );
+    assert_eq!(
+        get_token_balance(&mut context.banks_client, &bond_vault).await,
+        0
+    );
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Complete);
+    assert_eq!(escrow.reputation_oracle_bond, 0);
+
+    // Closing the escrow closes the bond vault as well
+    let receiver = Keypair::new().pubkey();
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        instruction::close(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &escrow_account.canceler_token_account.pubkey(),
+            &receiver,
+            &spl_token::id(),
+            None,
+            Some(bond_accounts),
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert!(context
+        .banks_client
+        .get_account(bond_vault)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_release_bonds() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let escrow_account = EscrowAccount::new();
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let (bond_vault, _) =
+        Processor::find_bond_vault_address(&id(), &escrow_account.escrow.pubkey());
+    let reputation_oracle_bond = spl_token::ui_amount_to_amount(2.0, DECIMALS);
+    let recording_oracle_bond = spl_token::ui_amount_to_amount(3.0, DECIMALS);
+    for (token_account, oracle) in &[
+        (
+            &escrow_account.reputation_oracle_token,
+            &escrow_account.reputation_oracle,
+        ),
+        (
+            &escrow_account.recording_oracle_token,
+            &escrow_account.recording_oracle,
+        ),
+    ] {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            token_account,
+            &escrow_account.token_mint.pubkey(),
+            &oracle.pubkey(),
+        )
+        .await;
+        mint_to_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &escrow_account.token_mint,
+            token_account,
+            &escrow_account.mint_authority,
+            10.0,
+        )
+        .await;
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::setup(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.reputation_oracle_token.pubkey(),
+            escrow_account.reputation_oracle_stake,
+            &escrow_account.recording_oracle.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            escrow_account.recording_oracle_stake,
+            &escrow_account.manifest_url,
+            &escrow_account.manifest_hash,
+            escrow_account.defer_fees,
+            escrow_account.dispute_window,
+            reputation_oracle_bond,
+            recording_oracle_bond,
+            &escrow_account.factory.pubkey(),
+            Some((&bond_vault, &escrow_account.token_mint.pubkey())),
+        )
+        .unwrap()],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[
+            &payer,
+            &escrow_account.launcher,
+            &escrow_account.reputation_oracle,
+            &escrow_account.recording_oracle,
+        ],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+
+    let release = |oracle: &Keypair| {
+        instruction::release_bonds(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &oracle.pubkey(),
+            &bond_vault,
+            &escrow_account.reputation_oracle_token.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            &spl_token::id(),
+        )
+        .unwrap()
+    };
+
+    // Bonds of the running escrow stay locked
+    let result = process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        release(&escrow_account.reputation_oracle),
+        &escrow_account.reputation_oracle,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::WrongState);
+
+    cancel(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.escrow,
+        &escrow_account.launcher,
+        &escrow_account.escrow_token_account,
+        &escrow_account.escrow_authority,
+        &escrow_account.canceler_token_account,
+    )
+    .await;
+
+    // Only oracles can release their bonds
+    let result = process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        release(&escrow_account.launcher),
+        &escrow_account.launcher,
+    )
+    .await;
+    assert_escrow_error(result, EscrowError::UnauthorizedRelease);
+
+    // Either oracle returns both bonds without the launcher closing the escrow
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        release(&escrow_account.recording_oracle),
+        &escrow_account.recording_oracle,
+    )
+    .await
+    .unwrap();
+    for token_account in &[
+        &escrow_account.reputation_oracle_token,
+        &escrow_account.recording_oracle_token,
+    ] {
+        assert_eq!(
+            get_token_balance(&mut banks_client, &token_account.pubkey()).await,
+            spl_token::ui_amount_to_amount(10.0, DECIMALS)
+        );
+    }
+    assert_eq!(get_token_balance(&mut banks_client, &bond_vault).await, 0);
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    assert_eq!(escrow.reputation_oracle_bond, 0);
+    assert_eq!(escrow.recording_oracle_bond, 0);
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_worker_reputation() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.total_recipients = 2;
+    escrow_account.payout_amount = escrow_account.total_amount / 2.0;
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    // Both recipient token accounts belong to the same worker
+    let worker = Keypair::new();
+    let recipient_token_accounts = [Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &worker.pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let (worker_reputation, _) = Processor::find_worker_reputation_address(
+        &id(),
+        &escrow_account.factory.pubkey(),
+        &worker.pubkey(),
+    );
+    let (worker_job, _) = Processor::find_worker_job_address(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &worker.pubkey(),
+    );
+    let (oracle_approval, _) = Processor::find_oracle_approval_address(
+        &id(),
+        &escrow_account.factory.pubkey(),
+        &escrow_account.reputation_oracle.pubkey(),
+    );
+
+    // Reputation oracle pays for the worker reputation account, lamports sent to
+    // its address in advance don't prevent the creation
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(
+                &payer.pubkey(),
+                &escrow_account.reputation_oracle.pubkey(),
+                1_000_000_000,
+            ),
+            system_instruction::transfer(&payer.pubkey(), &worker_reputation, 1_000),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let payout_amount = spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS);
+    let scored_payout = |recipient: &Pubkey, worker_reputation: &Pubkey, worker_score: u16| {
+        instruction::payout(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            recipient,
+            &escrow_account.reputation_oracle_token.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            &spl_token::id(),
+            payout_amount,
+            &escrow_account.factory.pubkey(),
+            None,
+            None,
+            Some((
+                worker_score,
+                worker_reputation,
+                &worker_job,
+                &oracle_approval,
+            )),
+        )
+        .unwrap()
+    };
+
+    // Escrow reputation oracle is not approved by the factory
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            scored_payout(
+                &recipient_token_accounts[0].pubkey(),
+                &worker_reputation,
+                7000,
+            ),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::OracleNotApproved,
+    );
+
+    // Only factory administrator approves the oracles
+    assert_escrow_error(
+        approve_oracle(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            &escrow_account.factory.pubkey(),
+            &escrow_account.reputation_oracle,
+            &escrow_account.reputation_oracle.pubkey(),
+            1,
+        )
+        .await,
+        EscrowError::UnauthorizedSigner,
+    );
+
+    // Payout below the approved minimum
+    approve_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        &escrow_account.reputation_oracle.pubkey(),
+        payout_amount + 1,
+    )
+    .await
+    .unwrap();
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            scored_payout(
+                &recipient_token_accounts[0].pubkey(),
+                &worker_reputation,
+                7500,
+            ),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::ScoredPayoutTooSmall,
+    );
+    approve_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        &escrow_account.reputation_oracle.pubkey(),
+        payout_amount,
+    )
+    .await
+    .unwrap();
+
+    // Score above 100%
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            scored_payout(
+                &recipient_token_accounts[0].pubkey(),
+                &worker_reputation,
+                state::MAX_WORKER_SCORE + 1,
+            ),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::ScoreOutOfBounds,
+    );
+
+    // Reputation account of another worker
+    assert!(process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        scored_payout(
+            &recipient_token_accounts[0].pubkey(),
+            &Pubkey::new_unique(),
+            8000,
+        ),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .is_err());
+
+    for (recipient_token_account, worker_score) in
+        recipient_token_accounts.iter().zip([8000, 10000].iter())
+    {
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            scored_payout(
+                &recipient_token_account.pubkey(),
+                &worker_reputation,
+                *worker_score,
+            ),
+            &escrow_account.reputation_oracle,
+        )
+        .await
+        .unwrap();
+    }
+
+    let mut total_earned = 0;
+    for recipient_token_account in recipient_token_accounts.iter() {
+        total_earned +=
+            get_token_balance(&mut banks_client, &recipient_token_account.pubkey()).await;
+    }
+
+    let worker_reputation = banks_client
+        .get_account(worker_reputation)
+        .await
+        .expect("get_account")
+        .expect("worker reputation not found");
+    let worker_reputation = state::WorkerReputation::unpack(&worker_reputation.data).unwrap();
+    assert_eq!(worker_reputation.factory, escrow_account.factory.pubkey());
+    assert_eq!(worker_reputation.worker, worker.pubkey());
+    assert_eq!(worker_reputation.total_earned, total_earned);
+
+    // Both payouts of the escrow count as a single job scored by the first one
+    assert_eq!(worker_reputation.jobs_completed, 1);
+    assert_eq!(worker_reputation.score, 8000);
+
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Paid);
+
+    // Revoked approval is closed
+    approve_oracle(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        &escrow_account.factory.pubkey(),
+        &payer,
+        &escrow_account.reputation_oracle.pubkey(),
+        0,
+    )
+    .await
+    .unwrap();
+    assert!(banks_client
+        .get_account(oracle_approval)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+async fn get_vesting_schedule(
+    banks_client: &mut BanksClient,
+    vesting_schedule: &Pubkey,
+) -> state::VestingSchedule {
+    let account = banks_client
+        .get_account(*vesting_schedule)
+        .await
+        .expect("get_account")
+        .expect("cannot read vesting schedule data");
+    state::VestingSchedule::unpack(account.data.as_slice()).unwrap()
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_vesting() {
+    let (mut banks_client, payer, recent_blockhash) = program_test().start().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.total_recipients = 2;
+    escrow_account.payout_amount = escrow_account.total_amount / 2.0;
+    escrow_account
+        .initialize_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_accounts = [Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut banks_client, &payer, &recent_blockhash)
+        .await;
+
+    // Reputation oracle pays for the vesting schedules
+    let mut transaction = Transaction::new_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let expires = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey())
+        .await
+        .expires;
+    let vesting_schedules: Vec<Pubkey> = recipient_token_accounts
+        .iter()
+        .map(|recipient_token_account| {
+            Processor::find_vesting_schedule_address(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &recipient_token_account.pubkey(),
+            )
+            .0
+        })
+        .collect();
+    let vested_payout = |index: usize, start: i64, cliff: i64, end: i64| {
+        instruction::vested_payout(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &recipient_token_accounts[index].pubkey(),
+            &escrow_account.reputation_oracle_token.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            &spl_token::id(),
+            spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS),
+            &escrow_account.factory.pubkey(),
+            &vesting_schedules[index],
+            start,
+            cliff,
+            end,
+        )
+        .unwrap()
+    };
+    let withdraw = |index: usize| {
+        instruction::withdraw(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &vesting_schedules[index],
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &recipient_token_accounts[index].pubkey(),
+            &spl_token::id(),
+            None,
+        )
+        .unwrap()
+    };
+    let cancel = instruction::cancel(
+        &id(),
+        &escrow_account.escrow.pubkey(),
+        &escrow_account.launcher.pubkey(),
+        &escrow_account.escrow_token_account.pubkey(),
+        &escrow_account.escrow_authority,
+        &escrow_account.canceler_token_account.pubkey(),
+        &spl_token::id(),
+        None,
+        Some(&[]),
+    )
+    .unwrap();
+
+    // Cliff before the start and vesting after the expiration
+    for (start, cliff, end) in [(10, 5, 20), (0, 0, expires + 1)].iter() {
+        assert_escrow_error(
+            process_signed(
+                &mut banks_client,
+                &payer,
+                &recent_blockhash,
+                vested_payout(0, *start, *cliff, *end),
+                &escrow_account.reputation_oracle,
+            )
+            .await,
+            EscrowError::InvalidVestingSchedule,
+        );
+    }
+
+    // First recipient is vested right away, the second one only at the expiration
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        vested_payout(0, 0, 0, 1),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .unwrap();
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            vested_payout(0, 0, 0, 2),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::VestingScheduleExists,
+    );
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        vested_payout(1, expires, expires, expires),
+        &escrow_account.reputation_oracle,
+    )
+    .await
+    .unwrap();
+
+    let vested_amounts = [
+        get_vesting_schedule(&mut banks_client, &vesting_schedules[0])
+            .await
+            .total,
+        get_vesting_schedule(&mut banks_client, &vesting_schedules[1])
+            .await
+            .total,
+    ];
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Paid);
+    assert_eq!(escrow.sent_amount, escrow.total_amount);
+    assert_eq!(escrow.vesting_amount, vested_amounts[0] + vested_amounts[1]);
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            &escrow_account.escrow_token_account.pubkey()
+        )
+        .await,
+        escrow.vesting_amount
+    );
+
+    // Escrow can't be completed before the vesting payouts are withdrawn
+    assert_escrow_error(
+        process_signed(
+            &mut banks_client,
+            &payer,
+            &recent_blockhash,
+            instruction::complete(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &escrow_account.reputation_oracle.pubkey(),
+                None,
+            )
+            .unwrap(),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::VestingInProgress,
+    );
+
+    // Anyone can withdraw the vested tokens for the recipient
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw(0)],
+        Some(&escrow_account.reputation_oracle.pubkey()),
+    );
+    transaction.sign(&[&escrow_account.reputation_oracle], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &recipient_token_accounts[0].pubkey()).await,
+        vested_amounts[0]
+    );
+    let mut transaction = Transaction::new_with_payer(
+        &[withdraw(1)],
+        Some(&escrow_account.reputation_oracle.pubkey()),
+    );
+    transaction.sign(&[&escrow_account.reputation_oracle], recent_blockhash);
+    assert_escrow_error(
+        banks_client.process_transaction(transaction).await,
+        EscrowError::NotEnoughBalance,
+    );
+
+    // Cancelling the paid escrow stops vesting, nothing is returned yet
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        cancel,
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            &escrow_account.canceler_token_account.pubkey()
+        )
+        .await,
+        0
+    );
+
+    // Unvested tokens are released back to the escrow and returned by cancelling again
+    let mut transaction = Transaction::new_with_payer(&[withdraw(1)], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        get_token_balance(&mut banks_client, &recipient_token_accounts[1].pubkey()).await,
+        0
+    );
+    let escrow = get_escrow(&mut banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    assert_eq!(escrow.vesting_amount, 0);
+    assert_eq!(escrow.sent_amount, escrow.total_amount - vested_amounts[1]);
+
+    process_signed(
+        &mut banks_client,
+        &payer,
+        &recent_blockhash,
+        instruction::cancel(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &escrow_account.canceler_token_account.pubkey(),
+            &spl_token::id(),
+            None,
+            None,
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(
+            &mut banks_client,
+            &escrow_account.canceler_token_account.pubkey()
+        )
+        .await,
+        vested_amounts[1]
+    );
+}
+
+#[::tokio::test]
+async fn test_hmt_escrow_vesting_dispute_and_cancel() {
+    let mut context = program_test().start_with_context().await;
+    let mut escrow_account = EscrowAccount::new();
+    escrow_account.total_recipients = 3;
+    escrow_account.payout_amount = escrow_account.total_amount / 3.0;
+    escrow_account.dispute_window = 1000;
+    let recent_blockhash = context.last_blockhash;
+    escrow_account
+        .initialize_escrow(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+
+    let recipient_token_accounts = [Keypair::new(), Keypair::new()];
+    for recipient_token_account in recipient_token_accounts.iter() {
+        create_token_account(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            recipient_token_account,
+            &escrow_account.token_mint.pubkey(),
+            &Keypair::new().pubkey(),
+        )
+        .await;
+    }
+
+    escrow_account
+        .setup_escrow(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .store_results(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    escrow_account
+        .fund_escrow(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            escrow_account.total_amount,
+        )
+        .await;
+    escrow_account
+        .store_amounts(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+
+    // Vesting payouts are sent after the dispute window
+    context.warp_to_slot(5_000).unwrap();
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+
+    let expires = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey())
+        .await
+        .expires;
+    let vesting_schedules: Vec<Pubkey> = recipient_token_accounts
+        .iter()
+        .map(|recipient_token_account| {
+            Processor::find_vesting_schedule_address(
+                &id(),
+                &escrow_account.escrow.pubkey(),
+                &recipient_token_account.pubkey(),
+            )
+            .0
+        })
+        .collect();
+
+    // Lamports sent to the schedule address in advance don't block the payout
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(
+                &context.payer.pubkey(),
+                &escrow_account.reputation_oracle.pubkey(),
+                1_000_000_000,
+            ),
+            system_instruction::transfer(&context.payer.pubkey(), &vesting_schedules[0], 1_000),
+        ],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(&[&context.payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let vested_payout = |index: usize, start: i64, cliff: i64, end: i64| {
+        instruction::vested_payout(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.reputation_oracle.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &recipient_token_accounts[index].pubkey(),
+            &escrow_account.reputation_oracle_token.pubkey(),
+            &escrow_account.recording_oracle_token.pubkey(),
+            &spl_token::id(),
+            spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS),
+            &escrow_account.factory.pubkey(),
+            &vesting_schedules[index],
+            start,
+            cliff,
+            end,
+        )
+        .unwrap()
+    };
+
+    // First recipient is vested right away, nothing of the second one vests
+    // before the cancellation
+    for (index, start, cliff, end) in [(0, 0, 0, 1), (1, expires - 1, expires - 1, expires)].iter()
+    {
+        process_signed(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            vested_payout(*index, *start, *cliff, *end),
+            &escrow_account.reputation_oracle,
+        )
+        .await
+        .unwrap();
+    }
+    let vested_amounts = [
+        get_vesting_schedule(&mut context.banks_client, &vesting_schedules[0])
+            .await
+            .total,
+        get_vesting_schedule(&mut context.banks_client, &vesting_schedules[1])
+            .await
+            .total,
+    ];
+
+    // Second schedule for the same recipient
+    assert_escrow_error(
+        process_signed(
+            &mut context.banks_client,
+            &context.payer,
+            &recent_blockhash,
+            vested_payout(0, 0, 0, 2),
+            &escrow_account.reputation_oracle,
+        )
+        .await,
+        EscrowError::VestingScheduleExists,
+    );
+
+    // Vested tokens are withdrawn while the new results are disputed
+    escrow_account
+        .store_results(&mut context.banks_client, &context.payer, &recent_blockhash)
+        .await;
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        instruction::dispute(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[instruction::withdraw(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &vesting_schedules[0],
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &recipient_token_accounts[0].pubkey(),
+            &spl_token::id(),
+            None,
+        )
+        .unwrap()],
+        Some(&context.payer.pubkey()),
+    );
+    transaction.sign(&[&context.payer], recent_blockhash);
+    context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &recipient_token_accounts[0].pubkey()
+        )
+        .await,
+        vested_amounts[0]
+    );
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Disputed);
+    assert_eq!(escrow.vesting_amount, vested_amounts[1]);
+
+    // Cancel returns the unvested tokens of the given schedules without any
+    // action of the recipients
+    let escrow_balance = get_token_balance(
+        &mut context.banks_client,
+        &escrow_account.escrow_token_account.pubkey(),
+    )
+    .await;
+    process_signed(
+        &mut context.banks_client,
+        &context.payer,
+        &recent_blockhash,
+        instruction::cancel(
+            &id(),
+            &escrow_account.escrow.pubkey(),
+            &escrow_account.launcher.pubkey(),
+            &escrow_account.escrow_token_account.pubkey(),
+            &escrow_account.escrow_authority,
+            &escrow_account.canceler_token_account.pubkey(),
+            &spl_token::id(),
+            None,
+            Some(&vesting_schedules),
+        )
+        .unwrap(),
+        &escrow_account.launcher,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_token_balance(
+            &mut context.banks_client,
+            &escrow_account.canceler_token_account.pubkey()
+        )
+        .await,
+        escrow_balance
+    );
+    assert_eq!(
+        get_vesting_schedule(&mut context.banks_client, &vesting_schedules[1])
+            .await
+            .total,
+        0
+    );
+    let escrow = get_escrow(&mut context.banks_client, &escrow_account.escrow.pubkey()).await;
+    assert_eq!(escrow.state, state::EscrowState::Cancelled);
+    assert_eq!(escrow.vesting_amount, 0);
+    assert_eq!(
+        escrow.sent_amount,
+        2 * spl_token::ui_amount_to_amount(escrow_account.payout_amount, DECIMALS)
+            - vested_amounts[1]
+    );
 }